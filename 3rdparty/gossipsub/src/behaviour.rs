@@ -47,6 +47,23 @@ use wasm_timer::{Instant, Interval};
 
 mod tests;
 
+/// Maximal number of received messages waiting for validation.
+const MAX_PENDING_VALIDATION: usize = 1024;
+
+/// Result of application-level validation of a received message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageAcceptance {
+    /// The message is valid, it will be forwarded to peers.
+    Accept,
+    /// The message is neither valid nor malicious (e.g. duplicate), it won't be forwarded.
+    Ignore,
+    /// The message is invalid, it won't be forwarded.
+    Reject,
+}
+
+/// Filter of outgoing messages, returns false if the message shouldn't be sent to the peer.
+pub type SendFilter = Box<dyn FnMut(&PeerId, &GossipsubMessage) -> bool + Send>;
+
 /// Network behaviour that handles the gossipsub protocol.
 pub struct Gossipsub {
     /// Configuration providing gossipsub performance parameters.
@@ -91,6 +108,15 @@ pub struct Gossipsub {
 
     // Allow to communicate inside a topic
     public_topics: HashSet<TopicHash>,
+
+    /// Topics which messages are forwarded only after validation by the application.
+    validated_topics: HashSet<TopicHash>,
+
+    /// Received messages waiting for validation, with their propagation sources.
+    pending_validation: LruCache<MessageId, (GossipsubMessage, PeerId)>,
+
+    /// Filter of outgoing messages.
+    send_filter: Option<SendFilter>,
 }
 
 impl Gossipsub {
@@ -124,6 +150,9 @@ impl Gossipsub {
             ),
             whitelisted_peers: HashSet::new(),
             public_topics: HashSet::new(),
+            validated_topics: HashSet::new(),
+            pending_validation: LruCache::new(MAX_PENDING_VALIDATION),
+            send_filter: None,
         };
         gossip.public_topics = gs_config
             .public_topics
@@ -351,6 +380,45 @@ impl Gossipsub {
         true
     }
 
+    /// Require application-level validation of messages received on this topic.
+    ///
+    /// Messages of validated topics are neither forwarded nor gossiped until
+    /// `report_message_validation_result()` is called with `MessageAcceptance::Accept`.
+    pub fn enable_validation(&mut self, topic: &Topic) {
+        let topic_hash = self.topic_hash(topic.clone());
+        self.validated_topics.insert(topic_hash);
+    }
+
+    /// Set the filter of outgoing messages.
+    ///
+    /// The filter is called for every message right before it's passed to the peer's handler.
+    /// Filtered out messages are dropped, control messages and subscriptions are always sent.
+    pub fn set_send_filter(&mut self, filter: SendFilter) {
+        self.send_filter = Some(filter);
+    }
+
+    /// Report the result of validation of a message received on a validated topic.
+    ///
+    /// Accepted messages are forwarded to mesh peers, ignored and rejected messages are dropped.
+    /// Returns the propagation source of the message, or None if the message is not waiting for
+    /// validation (e.g. has been already validated or evicted from the queue).
+    pub fn report_message_validation_result(
+        &mut self,
+        message_id: &MessageId,
+        acceptance: MessageAcceptance,
+    ) -> Option<PeerId> {
+        let (message, propagation_source) = self.pending_validation.pop(message_id)?;
+        debug!(
+            "Message validated: {:?}, result: {:?}",
+            message_id, acceptance
+        );
+        if acceptance == MessageAcceptance::Accept {
+            self.mcache.put(message.clone());
+            self.forward_msg(message, &propagation_source);
+        }
+        Some(propagation_source)
+    }
+
     /// Gossipsub JOIN(topic) - adds topic peers to mesh and sends them GRAFT messages.
     fn join(&mut self, topic_hash: &TopicHash) {
         debug!("Running JOIN for topic: {:?}", topic_hash);
@@ -587,6 +655,21 @@ impl Gossipsub {
             return;
         }
 
+        // hold the message until validation by the application
+        if msg
+            .topics
+            .iter()
+            .any(|t| self.validated_topics.contains(t) && self.mesh.contains_key(t))
+        {
+            debug!("Message waiting for validation: {:?}", msg_id);
+            self.pending_validation
+                .put(msg_id.clone(), (msg.clone(), propagation_source.clone()));
+            self.events.push_back(NetworkBehaviourAction::GenerateEvent(
+                GossipsubEvent::Message(propagation_source.clone(), msg_id, msg),
+            ));
+            return;
+        }
+
         // add to the memcache
         self.mcache.put(msg.clone());
 
@@ -971,6 +1054,41 @@ impl Gossipsub {
         debug!("Completed forwarding message");
     }
 
+    /// Helper function which applies the send filter to messages of `rpc`.
+    /// Returns None if nothing is left to send.
+    fn filter_messages(
+        &mut self,
+        peer_id: &PeerId,
+        rpc: Arc<GossipsubRpc>,
+    ) -> Option<Arc<GossipsubRpc>> {
+        let filter = match &mut self.send_filter {
+            Some(filter) => filter,
+            None => return Some(rpc),
+        };
+        let messages: Vec<GossipsubMessage> = rpc
+            .messages
+            .iter()
+            .filter(|m| filter(peer_id, m))
+            .cloned()
+            .collect();
+        if messages.len() == rpc.messages.len() {
+            return Some(rpc);
+        }
+        debug!(
+            "Dropped {} messages to peer {:?}",
+            rpc.messages.len() - messages.len(),
+            peer_id
+        );
+        if messages.is_empty() && rpc.subscriptions.is_empty() && rpc.control_msgs.is_empty() {
+            return None;
+        }
+        Some(Arc::new(GossipsubRpc {
+            messages,
+            subscriptions: rpc.subscriptions.clone(),
+            control_msgs: rpc.control_msgs.clone(),
+        }))
+    }
+
     /// Helper function to get a set of `n` random gossipsub peers for a `topic_hash`
     /// filtered by the function `f`.
     fn get_random_peers(
@@ -1184,29 +1302,46 @@ impl NetworkBehaviour for Gossipsub {
             Self::OutEvent,
         >,
     > {
-        if let Some(event) = self.events.pop_front() {
+        while let Some(event) = self.events.pop_front() {
             // clone send event reference if others references are present
             match event {
                 NetworkBehaviourAction::NotifyHandler {
                     peer_id,
                     handler,
                     event: send_event,
-                } => match Arc::try_unwrap(send_event) {
-                    Ok(event) => {
-                        return Poll::Ready(NetworkBehaviourAction::NotifyHandler {
-                            peer_id,
-                            event,
-                            handler,
-                        });
+                } => {
+                    let send_event = match self.filter_messages(&peer_id, send_event) {
+                        Some(send_event) => send_event,
+                        None => continue,
+                    };
+                    // report sent messages for traffic accounting
+                    for message in send_event.messages.iter().rev() {
+                        self.events
+                            .push_front(NetworkBehaviourAction::GenerateEvent(
+                                GossipsubEvent::MessageSent {
+                                    peer_id: peer_id.clone(),
+                                    topics: message.topics.clone(),
+                                    size: message.data.len(),
+                                },
+                            ));
                     }
-                    Err(event) => {
-                        return Poll::Ready(NetworkBehaviourAction::NotifyHandler {
-                            peer_id,
-                            event: (*event).clone(),
-                            handler,
-                        });
+                    match Arc::try_unwrap(send_event) {
+                        Ok(event) => {
+                            return Poll::Ready(NetworkBehaviourAction::NotifyHandler {
+                                peer_id,
+                                event,
+                                handler,
+                            });
+                        }
+                        Err(event) => {
+                            return Poll::Ready(NetworkBehaviourAction::NotifyHandler {
+                                peer_id,
+                                event: (*event).clone(),
+                                handler,
+                            });
+                        }
                     }
-                },
+                }
                 NetworkBehaviourAction::GenerateEvent(e) => {
                     return Poll::Ready(NetworkBehaviourAction::GenerateEvent(e));
                 }
@@ -1264,4 +1399,14 @@ pub enum GossipsubEvent {
         /// The topic it has subscribed from.
         topic: TopicHash,
     },
+
+    /// A message has been sent to a remote.
+    MessageSent {
+        /// Remote that the message has been sent to.
+        peer_id: PeerId,
+        /// The topics of the message.
+        topics: Vec<TopicHash>,
+        /// The size of the message data.
+        size: usize,
+    },
 }
@@ -845,4 +845,68 @@ mod tests {
             "Expected peer to be removed from mesh"
         );
     }
+
+    #[test]
+    /// Test that messages of validated topics are forwarded only after acceptance.
+    fn test_message_validation() {
+        let (mut gs, peers, topic_hashes) =
+            build_and_inject_nodes(20, vec![String::from("validated")], true);
+        gs.enable_validation(&Topic::new(String::from("validated")));
+        gs.events.clear();
+
+        let id = gs.config.message_id_fn;
+        let new_message = |n: u8| GossipsubMessage {
+            source: peers[0].clone(),
+            data: vec![n],
+            sequence_number: n as u64,
+            topics: topic_hashes.clone(),
+        };
+        let count_forwarded = |gs: &Gossipsub| {
+            gs.events
+                .iter()
+                .filter(|e| match e {
+                    NetworkBehaviourAction::NotifyHandler { event, .. } => {
+                        !event.messages.is_empty()
+                    }
+                    _ => false,
+                })
+                .count()
+        };
+
+        // Accepted message is forwarded and cached.
+        let message = new_message(1);
+        let msg_id = id(&message);
+        gs.handle_received_message(message, &peers[0]);
+        assert_eq!(count_forwarded(&gs), 0, "Message must wait for validation");
+        assert!(gs.mcache.get(&msg_id).is_none());
+        assert_eq!(
+            gs.report_message_validation_result(&msg_id, MessageAcceptance::Accept),
+            Some(peers[0].clone())
+        );
+        assert!(
+            count_forwarded(&gs) > 0,
+            "Accepted message must be forwarded"
+        );
+        assert!(gs.mcache.get(&msg_id).is_some());
+        assert_eq!(
+            gs.report_message_validation_result(&msg_id, MessageAcceptance::Accept),
+            None
+        );
+
+        // Rejected message is dropped.
+        gs.events.clear();
+        let message = new_message(2);
+        let msg_id = id(&message);
+        gs.handle_received_message(message, &peers[1]);
+        assert_eq!(
+            gs.report_message_validation_result(&msg_id, MessageAcceptance::Reject),
+            Some(peers[1].clone())
+        );
+        assert_eq!(
+            count_forwarded(&gs),
+            0,
+            "Rejected message must not be forwarded"
+        );
+        assert!(gs.mcache.get(&msg_id).is_none());
+    }
 }
@@ -22,15 +22,21 @@
 // SOFTWARE.
 
 use crate::crypto::ApiToken;
+use crate::tls::{parse_endpoint, tls_connector, ClientTlsConfig};
 use crate::InnerResponses;
 use crate::ResponseKind;
-use crate::{decode, encode, Request, Response};
+use crate::{decode, encode, unix_socket_path, Request, Response};
 use failure::bail;
 use failure::Error;
 use futures::prelude::*;
 use futures::SinkExt;
 use log::*;
+use std::io;
+use std::path::Path;
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::TcpStream;
+use tokio_rustls::webpki::DNSNameRef;
+use tokio_rustls::TlsConnector;
 use tokio_tungstenite::tungstenite::Error as WsError;
 use tokio_tungstenite::tungstenite::Message;
 use tokio_tungstenite::WebSocketStream;
@@ -46,30 +52,96 @@ fn handle_connection_error(_e: WsError) -> RetryPolicy<WsError> {
     RetryPolicy::WaitRetry(RECONNECT_TIMEOUT)
 }
 
+/// Plain TCP for ws://, TLS for wss://, or a Unix domain socket for unix:PATH.
+trait ClientIo: AsyncRead + AsyncWrite + Unpin + Send + Sync {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send + Sync> ClientIo for T {}
+type ClientStream = Box<dyn ClientIo>;
+
+#[cfg(unix)]
+async fn connect_unix(path: &Path) -> io::Result<ClientStream> {
+    let socket = tokio::net::UnixStream::connect(path).await?;
+    Ok(Box::new(socket))
+}
+
+#[cfg(not(unix))]
+async fn connect_unix(_path: &Path) -> io::Result<ClientStream> {
+    Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "Unix domain sockets are not supported",
+    ))
+}
+
+async fn connect(
+    endpoint: String,
+    connector: TlsConnector,
+    server_name: Option<String>,
+) -> Result<WebSocketStream<ClientStream>, WsError> {
+    if let Some(path) = unix_socket_path(&endpoint) {
+        let socket = connect_unix(path).await?;
+        // The host is not used by the server.
+        let (connection, _response) =
+            tokio_tungstenite::client_async("ws://localhost/", socket).await?;
+        return Ok(connection);
+    }
+    let invalid_input = |e| WsError::Io(io::Error::new(io::ErrorKind::InvalidInput, e));
+    let (tls, host, addr) = parse_endpoint(&endpoint).map_err(|e| invalid_input(e.to_string()))?;
+    let socket = TcpStream::connect(&addr).await?;
+    let stream: ClientStream = if tls {
+        let server_name = server_name.unwrap_or(host);
+        let domain = DNSNameRef::try_from_ascii_str(&server_name)
+            .map_err(|_| invalid_input(format!("Invalid server name: {}", server_name)))?;
+        Box::new(connector.connect(domain, socket).await?)
+    } else {
+        Box::new(socket)
+    };
+    let (connection, _response) = tokio_tungstenite::client_async(&endpoint, stream).await?;
+    Ok(connection)
+}
+
 pub struct WebSocketClient {
     /// Remote endpoint.
     endpoint: String,
     /// API Token.
     api_token: ApiToken,
-    connection: WebSocketStream<TcpStream>,
+    /// Used for wss:// endpoints.
+    connector: TlsConnector,
+    /// Name to verify the server certificate against.
+    server_name: Option<String>,
+    connection: WebSocketStream<ClientStream>,
     /// Pending notifications.
     pending_notifications: VecDeque<Response>,
 }
 
 impl WebSocketClient {
     pub async fn new(endpoint: String, api_token: ApiToken) -> Result<Self, Error> {
-        let connection = tokio_tungstenite::connect_async(&endpoint).await?.0;
+        Self::with_tls(endpoint, api_token, ClientTlsConfig::default()).await
+    }
+
+    /// Connects to ws://, wss:// or unix:PATH endpoint, using given certificates for wss://.
+    pub async fn with_tls(
+        endpoint: String,
+        api_token: ApiToken,
+        tls: ClientTlsConfig,
+    ) -> Result<Self, Error> {
+        let connector = tls_connector(&tls)?;
+        let server_name = tls.server_name;
+        let connection = connect(endpoint.clone(), connector.clone(), server_name.clone()).await?;
         let pending_notifications = VecDeque::new();
         Ok(Self {
             endpoint,
             api_token,
+            connector,
+            server_name,
             connection,
             pending_notifications,
         })
     }
 
+    /// Sends a request and waits for the response with the same id.
+    /// Notifications received meanwhile are queued.
     pub async fn request(&mut self, msg: Request) -> Result<Response, Error> {
         trace!("[{}] <= {:?}", self.endpoint, msg);
+        let id = msg.id;
         let msg = encode(&self.api_token, &msg);
         let msg = Message::Text(msg);
         loop {
@@ -84,16 +156,27 @@ impl WebSocketClient {
                 };
 
                 match response.kind {
-                    ResponseKind::NetworkResponse(_)
-                    | ResponseKind::WalletResponse(_)
-                    | ResponseKind::NodeResponse(_)
-                    | ResponseKind::Raw(_) => {
+                    ResponseKind::NetworkNotification(_)
+                    | ResponseKind::WalletNotification(_)
+                    | ResponseKind::StatusNotification(_)
+                    | ResponseKind::ChainNotification(_)
+                    | ResponseKind::Inner(InnerResponses::Reconnect) => {
+                        trace!("Received notification in response of request, pushing to pending list, notification = {:?}", response);
+                        self.pending_notifications.push_back(response);
+                    }
+                    _ if response.id == id => {
                         return Ok(response);
                     }
-                    _ => {
-                        trace!("Received notification in response of request, pushing to pending list, notification = {:?}", response);
+                    // Notifications of external APIs.
+                    _ if response.id == 0 => {
                         self.pending_notifications.push_back(response);
                     }
+                    _ => {
+                        debug!(
+                            "Dropped response to a previous request, id = {}",
+                            response.id
+                        );
+                    }
                 }
             }
         }
@@ -118,14 +201,19 @@ impl WebSocketClient {
             info!("Error on sending message to websocket, reconnecting");
             debug!("Websocket::send_raw error = {:?}", e);
             tokio::time::delay_for(RECONNECT_TIMEOUT).await;
-            let endpoit = self.endpoint.clone();
-            if let Ok(connection) = FutureRetry::new(
-                || tokio_tungstenite::connect_async(&endpoit),
+            // The closure must not borrow `self`, otherwise the future isn't `Send`.
+            let (endpoint, connector, server_name) = (
+                self.endpoint.clone(),
+                self.connector.clone(),
+                self.server_name.clone(),
+            );
+            if let Ok((connection, _attempts)) = FutureRetry::new(
+                || connect(endpoint.clone(), connector.clone(), server_name.clone()),
                 handle_connection_error,
             )
             .await
             {
-                self.connection = (connection.0).0;
+                self.connection = connection;
                 info!("Reconnected to websocket, trying to resend last request.");
                 let msg = SinkExt::send(&mut self.connection, msg).await?;
                 self.push_reconnect_notification();
@@ -152,14 +240,19 @@ impl WebSocketClient {
                 info!("Error on receiving message to websocket, reconnecting");
                 debug!("Websocket::receive error = {:?}", e);
                 tokio::time::delay_for(RECONNECT_TIMEOUT).await;
-                let endpoit = self.endpoint.clone();
-                if let Ok(connection) = FutureRetry::new(
-                    || tokio_tungstenite::connect_async(&endpoit),
+                // The closure must not borrow `self`, otherwise the future isn't `Send`.
+                let (endpoint, connector, server_name) = (
+                    self.endpoint.clone(),
+                    self.connector.clone(),
+                    self.server_name.clone(),
+                );
+                if let Ok((connection, _attempts)) = FutureRetry::new(
+                    || connect(endpoint.clone(), connector.clone(), server_name.clone()),
                     handle_connection_error,
                 )
                 .await
                 {
-                    self.connection = (connection.0).0;
+                    self.connection = connection;
                     info!("Reconnected to websocket, trying to receive again.");
                     self.push_reconnect_notification();
                     return Ok(None);
@@ -175,7 +268,11 @@ impl WebSocketClient {
 
     fn push_reconnect_notification(&mut self) {
         let kind = ResponseKind::Inner(InnerResponses::Reconnect);
-        let response = Response { id: 0, kind };
+        let response = Response {
+            id: 0,
+            kind,
+            seq: None,
+        };
         self.pending_notifications.push_back(response);
     }
 }
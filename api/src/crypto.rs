@@ -57,6 +57,10 @@ impl ApiToken {
         token2.copy_from_slice(&token);
         Ok(ApiToken(token2))
     }
+
+    pub fn to_base64(&self) -> String {
+        base64::encode(&self.0)
+    }
 }
 
 // Encrypts the plaintext with given 32-byte key
@@ -83,6 +87,21 @@ pub fn decrypt(key: &ApiToken, ciphertext: &[u8]) -> Vec<u8> {
     output
 }
 
+/// Decrypts the message, returns None if it wasn't encrypted with this key.
+pub(crate) fn try_decrypt(key: &ApiToken, ciphertext: &[u8]) -> Option<Vec<u8>> {
+    if ciphertext.len() < 16 {
+        return None;
+    }
+    let msg = decrypt(key, ciphertext);
+    // Check for {} brackets in decoded message.
+    const LEFT_BRACKET: u8 = 123;
+    const RIGHT_BRACKET: u8 = 125;
+    if msg.len() < 2 || msg[0] != LEFT_BRACKET || msg[msg.len() - 1] != RIGHT_BRACKET {
+        return None;
+    }
+    Some(msg)
+}
+
 // Load API Key from file, generate new key, if file is missing
 pub fn load_or_create_api_token(token_file: &Path) -> Result<ApiToken, KeyError> {
     if !token_file.exists() {
@@ -27,19 +27,33 @@
 mod client;
 mod crypto;
 mod error;
+pub mod metrics;
 pub mod network_api;
+#[cfg(feature = "grpc")]
+pub mod protos;
+pub mod schema;
 pub mod server;
+mod tls;
+mod tokens;
 
 pub use crate::client::WebSocketClient;
-use crate::crypto::{decrypt, encrypt};
+use crate::crypto::{encrypt, try_decrypt};
 pub use crate::crypto::{load_api_token, load_or_create_api_token, ApiToken};
 pub use crate::error::KeyError;
+use crate::server::journal::JournalKind;
+pub use crate::tls::{ClientTlsConfig, ServerTlsConfig};
+pub use crate::tokens::{
+    required_permission, ApiTokens, Permission, TokenInfo, TokenScope, TokenStore,
+    MASTER_TOKEN_NAME,
+};
 use failure::{bail, Error};
 use log::*;
 pub use network_api::*;
+use schemars::JsonSchema;
 use serde::de::DeserializeOwned;
 use serde::ser::Serialize;
 use serde_derive::{Deserialize, Serialize};
+use std::path::Path;
 pub use stegos_node::{ChainNotification, NodeRequest, NodeResponse, StatusNotification};
 pub use stegos_wallet::api::*;
 
@@ -48,27 +62,64 @@ pub type RequestId = u64;
 fn is_request_id_default(id: &RequestId) -> bool {
     *id == 0
 }
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(untagged)]
 pub enum RequestKind {
     NetworkRequest(NetworkRequest),
     WalletsRequest(WalletRequest),
     NodeRequest(NodeRequest),
+    /// Requests of external APIs, e.g. the vault, have their own schemas.
+    #[schemars(skip)]
     Raw(serde_json::Value),
 }
 
+/// Limits of the API server, see `server::limits::ApiLimits`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Limit {
+    Connections,
+    MessageSize,
+    ConnectionRate,
+    TokenRate,
+    Subscriptions,
+}
+
+impl Limit {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Limit::Connections => "connections",
+            Limit::MessageSize => "message_size",
+            Limit::ConnectionRate => "connection_rate",
+            Limit::TokenRate => "token_rate",
+            Limit::Subscriptions => "subscriptions",
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum InnerResponses {
     /// This notifications are imediately created after reconnected to server,
     /// and need to inform client that it should resubscribe.
     Reconnect,
+    /// The request has been rejected by limits of the server.
+    LimitExceeded {
+        limit: Limit,
+        error: String,
+    },
     InternalError {
         error: String,
     },
+    /// Notifications of the journaled subscription after `since_seq` are no longer
+    /// available, the subscription has been closed and must be renewed.
+    NotificationsLost {
+        subscription: JournalKind,
+        since_seq: u64,
+        first_seq: u64,
+    },
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct Request {
     #[serde(flatten)]
@@ -76,6 +127,11 @@ pub struct Request {
     #[serde(default)]
     #[serde(skip_serializing_if = "is_request_id_default")]
     pub id: u64,
+    /// Resume a chain or wallet subscription after this notification sequence id,
+    /// replaying missed notifications from the server journal.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub since_seq: Option<u64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -88,8 +144,9 @@ pub enum ResponseKind {
     NodeResponse(NodeResponse),
     StatusNotification(StatusNotification),
     ChainNotification(ChainNotification),
-    Raw(serde_json::Value),
+    // Before `Raw`, which matches anything.
     Inner(InnerResponses),
+    Raw(serde_json::Value),
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -100,6 +157,23 @@ pub struct Response {
     #[serde(default)]
     #[serde(skip_serializing_if = "is_request_id_default")]
     pub id: RequestId,
+    /// Sequence id of a journaled notification,
+    /// or the last sequence id in the journal for a subscribe response.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seq: Option<u64>,
+}
+
+/// Prefix of Unix domain socket endpoints, e.g. `unix:/run/stegos/stegosd.sock`.
+pub const UNIX_ENDPOINT_PREFIX: &str = "unix:";
+
+/// Returns the socket path of a `unix:PATH` endpoint.
+pub fn unix_socket_path(endpoint: &str) -> Option<&Path> {
+    if endpoint.starts_with(UNIX_ENDPOINT_PREFIX) {
+        Some(Path::new(&endpoint[UNIX_ENDPOINT_PREFIX.len()..]))
+    } else {
+        None
+    }
 }
 
 pub fn encode<T: Serialize>(api_token: &ApiToken, msg: &T) -> String {
@@ -117,14 +191,13 @@ pub fn decode<T: DeserializeOwned>(api_token: &ApiToken, msg: &str) -> Result<T,
             bail!("Failed to base64::decode");
         }
     };
-    let msg = decrypt(api_token, &msg);
-    // Check for {} brackets in decoded message.
-    const LEFT_BRACKET: u8 = 123;
-    const RIGHT_BRACKET: u8 = 125;
-    if msg.len() < 2 || msg[0] != LEFT_BRACKET || msg[msg.len() - 1] != RIGHT_BRACKET {
-        error!("Failed to decrypt message");
-        bail!("Failed to decrypt");
-    }
+    let msg = match try_decrypt(api_token, &msg) {
+        Some(msg) => msg,
+        None => {
+            error!("Failed to decrypt message");
+            bail!("Failed to decrypt");
+        }
+    };
     let msg: T = match serde_json::from_slice(&msg) {
         Ok(r) => r,
         Err(e) => {
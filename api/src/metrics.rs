@@ -0,0 +1,43 @@
+//! WebSocket API - Metrics.
+
+//
+// MIT License
+//
+// Copyright (c) 2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use lazy_static::lazy_static;
+use prometheus::*;
+
+lazy_static! {
+    pub static ref API_CONNECTIONS: IntGauge = register_int_gauge!(
+        "stegos_api_connections",
+        "The number of WebSocket API connections"
+    )
+    .unwrap();
+    pub static ref API_REQUESTS: IntCounter =
+        register_int_counter!("stegos_api_requests", "The number of API requests").unwrap();
+    pub static ref API_LIMIT_EXCEEDED: IntCounterVec = register_int_counter_vec!(
+        "stegos_api_limit_exceeded",
+        "The number of rejected API connections and requests per limit",
+        &["limit"]
+    )
+    .unwrap();
+}
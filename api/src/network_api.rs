@@ -2,13 +2,15 @@ use crate::server::api::*;
 use async_trait::async_trait;
 use failure::{bail, Error};
 use futures::channel::{mpsc, oneshot};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use stegos_crypto::pbc;
 use stegos_network::{
-    Network, NetworkResponse as NetworkServiceResponse, NodeInfo, UnicastMessage,
+    Network, NetworkResponse as NetworkServiceResponse, NodeInfo, PeerId, PeerScoreInfo,
+    PeerTraffic, TopicTraffic, UnicastMessage,
 };
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "type")]
 #[serde(rename_all = "snake_case")]
 pub enum NetworkRequest {
@@ -37,9 +39,16 @@ pub enum NetworkRequest {
         data: Vec<u8>,
     },
     ConnectedNodesRequest {},
+    PeerScores {},
+    ClearPeerScores {
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        peer_id: Option<String>,
+    },
+    TrafficStats {},
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "type")]
 #[serde(rename_all = "snake_case")]
 pub enum NetworkResponse {
@@ -68,12 +77,20 @@ pub enum NetworkResponse {
         total: usize,
         nodes: Vec<NodeInfo>,
     },
+    PeerScores {
+        peers: Vec<PeerScoreInfo>,
+    },
+    PeerScoresCleared,
+    TrafficStats {
+        topics: Vec<TopicTraffic>,
+        peers: Vec<PeerTraffic>,
+    },
     Error {
         error: String,
     },
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "type")]
 #[serde(rename_all = "snake_case")]
 pub enum NetworkNotification {
@@ -166,6 +183,25 @@ impl NetworkApi {
                 let rx = self.network.list_connected_nodes()?;
                 Ok(NetworkResult::Async(rx))
             }
+            NetworkRequest::PeerScores {} => {
+                let rx = self.network.peer_scores()?;
+                Ok(NetworkResult::Async(rx))
+            }
+            NetworkRequest::ClearPeerScores { peer_id } => {
+                let peer_id = match peer_id {
+                    Some(peer_id) => match PeerId::from_str(&peer_id) {
+                        Ok(peer_id) => Some(peer_id),
+                        Err(_) => bail!("Invalid peer_id: {}", peer_id),
+                    },
+                    None => None,
+                };
+                self.network.clear_peer_scores(peer_id)?;
+                Ok(NetworkResult::Immediate(NetworkResponse::PeerScoresCleared))
+            }
+            NetworkRequest::TrafficStats {} => {
+                let rx = self.network.traffic_stats()?;
+                Ok(NetworkResult::Async(rx))
+            }
         }
     }
 }
@@ -176,6 +212,7 @@ enum NetworkResult {
 }
 use super::{RequestKind, ResponseKind};
 use std::convert::{TryFrom, TryInto};
+use std::str::FromStr;
 impl TryFrom<RawRequest> for NetworkRequest {
     type Error = Error;
     fn try_from(request: RawRequest) -> Result<NetworkRequest, Self::Error> {
@@ -205,6 +242,12 @@ impl ApiHandler for NetworkApi {
                             nodes,
                         }
                     }
+                    NetworkServiceResponse::PeerScores { peers } => {
+                        NetworkResponse::PeerScores { peers }
+                    }
+                    NetworkServiceResponse::TrafficStats { topics, peers } => {
+                        NetworkResponse::TrafficStats { topics, peers }
+                    }
                 };
                 Ok(result.into())
             }
@@ -0,0 +1,91 @@
+//! WebSocket API - JSON Schema of requests.
+
+//
+// MIT License
+//
+// Copyright (c) 2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::{
+    ChainNotification, NetworkNotification, NetworkResponse, NodeResponse, Request,
+    StatusNotification, WalletNotification, WalletResponse,
+};
+use schemars::gen::SchemaGenerator;
+use schemars::schema::RootSchema;
+#[cfg(any(test, feature = "schema-snapshot"))]
+use std::path::Path;
+
+/// JSON Schema of the WebSocket API.
+///
+/// The root schema describes requests, responses and notifications are in `definitions`:
+/// `NodeResponse`, `WalletResponse`, `NetworkResponse`, `StatusNotification`,
+/// `ChainNotification`, `WalletNotification` and `NetworkNotification`.
+///
+/// The schema is generated from the types at runtime. A snapshot is kept in
+/// `api/schema/api.json` to generate client SDKs, it is written by the `schema` test:
+/// run `STEGOS_UPDATE_SCHEMA=1 cargo test --all schema` after changing the API.
+/// The same test of stegos-vault updates `api/schema/vault.json`.
+pub fn api_schema() -> RootSchema {
+    let mut gen = SchemaGenerator::default();
+    gen.subschema_for::<NodeResponse>();
+    gen.subschema_for::<WalletResponse>();
+    gen.subschema_for::<NetworkResponse>();
+    gen.subschema_for::<StatusNotification>();
+    gen.subschema_for::<ChainNotification>();
+    gen.subschema_for::<WalletNotification>();
+    gen.subschema_for::<NetworkNotification>();
+    let mut schema = gen.into_root_schema_for::<Request>();
+    schema.schema.metadata().title = Some("Stegos API request".to_string());
+    schema
+}
+
+/// Compares the schema with the snapshot, updates the snapshot if `STEGOS_UPDATE_SCHEMA` is set.
+#[cfg(any(test, feature = "schema-snapshot"))]
+pub fn check_snapshot(schema: &RootSchema, path: &Path) {
+    let schema = serde_json::to_string_pretty(schema).expect("serialized") + "\n";
+    if std::env::var("STEGOS_UPDATE_SCHEMA").is_ok() {
+        std::fs::write(path, &schema).expect("snapshot written");
+        eprintln!("Updated {:?}, please commit it", path);
+    }
+    let snapshot = std::fs::read_to_string(path).unwrap_or_else(|e| {
+        panic!(
+            "Failed to read {:?}: {}, run with STEGOS_UPDATE_SCHEMA=1 to create it",
+            path, e
+        )
+    });
+    assert!(
+        schema == snapshot,
+        "API schema differs from {:?}, run with STEGOS_UPDATE_SCHEMA=1 to update it",
+        path
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schema() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("schema")
+            .join("api.json");
+        check_snapshot(&api_schema(), &path);
+    }
+}
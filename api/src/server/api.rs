@@ -25,6 +25,7 @@ use failure::{bail, Error};
 use async_trait::async_trait;
 
 use crate::network_api::*;
+use crate::schema::api_schema;
 use crate::{Request, RequestKind, ResponseKind};
 use futures::stream::SelectAll;
 use futures::stream::StreamExt;
@@ -168,7 +169,7 @@ impl From<WalletResponse> for RawResponse {
         RawResponse(ResponseKind::WalletResponse(response))
     }
 }
-type Subscribtion = Box<dyn Stream<Item = RawResponse> + Unpin + Send>;
+pub(super) type Subscribtion = Box<dyn Stream<Item = RawResponse> + Unpin + Send>;
 // Todo: Later replace our requests with json-rpc core, and remove register/apihandler.
 #[async_trait]
 pub trait ApiHandler: Sync + Send {
@@ -225,6 +226,10 @@ impl<T: ApiHandler + Sync + Clone + 'static> ApiHandler for Option<T> {
 impl ApiHandler for Node {
     async fn process_request(&self, req: RawRequest) -> Result<RawResponse, Error> {
         let request: NodeRequest = req.try_into()?;
+        if let NodeRequest::ApiSchema {} = request {
+            let schema = serde_json::to_value(api_schema())?;
+            return Ok(NodeResponse::ApiSchema { schema }.into());
+        }
         let response = self.request(request).await?;
         Ok(response.into())
     }
@@ -0,0 +1,926 @@
+//! WebSocket API - gRPC.
+//!
+//! The `stegos.api.Api` service from `protos/api.proto`, available with the `grpc` feature.
+//! Blocks, transactions and outputs use the protobuf messages of `stegos_blockchain`.
+//! Calls require `authorization: Bearer <token>` metadata, where the token is the base64
+//! value from `api.token` or a named token, with the same scopes as the WebSocket API.
+//! A gRPC connection takes a connection slot of the shared limits while it has calls
+//! in progress, each streaming call counts as a subscription.
+
+//
+// Copyright (c) 2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use super::api::{ApiHandler, RawRequest};
+use super::limits::{self, Connection, Limiter, RateLimiter};
+use super::register::Register;
+use super::Context;
+use crate::protos::api as proto;
+use crate::tls::{tls_acceptor, ServerTlsConfig};
+use crate::tokens::ApiTokens;
+use crate::{InnerResponses, Limit, Request, RequestKind, ResponseKind};
+use failure::{bail, format_err, Error};
+use futures::prelude::*;
+use grpcio::{
+    CertificateRequestType, ChannelBuilder, Environment, Marshaller, Method, MethodType,
+    RpcContext, RpcStatus, RpcStatusCode, Server, ServerBuilder, ServerCredentials,
+    ServerCredentialsBuilder, ServerStreamingSink, ServiceBuilder, UnarySink, WriteFlags,
+};
+use log::*;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use stegos_blockchain::api::{Reachability, StatusInfo};
+use stegos_blockchain::protos::blockchain;
+use stegos_blockchain::{Transaction, TransactionStatus};
+use stegos_crypto::hash::Hash;
+use stegos_network::Network;
+use stegos_node::{ChainNotification, ExtendedMacroBlock, NodeRequest, NodeResponse};
+use stegos_serialization::traits::ProtoConvert;
+use stegos_wallet::api::{
+    AccountBalance, AccountNotification, AccountResponse, Balance, MessageInfo, PaymentInfo,
+    PublicPaymentInfo, StakeInfo, WalletControlRequest, WalletControlResponse, WalletNotification,
+    WalletRequest, WalletResponse,
+};
+use tokio::runtime::Handle;
+
+macro_rules! method {
+    ($ty:ident, $name:expr) => {
+        Method {
+            ty: MethodType::$ty,
+            name: $name,
+            req_mar: Marshaller {
+                ser: grpcio::pb_ser,
+                de: grpcio::pb_de,
+            },
+            resp_mar: Marshaller {
+                ser: grpcio::pb_ser,
+                de: grpcio::pb_de,
+            },
+        }
+    };
+}
+
+const METHOD_STATUS_INFO: Method<proto::StatusInfoRequest, proto::StatusInfo> =
+    method!(Unary, "/stegos.api.Api/StatusInfo");
+const METHOD_MACRO_BLOCK: Method<proto::MacroBlockRequest, proto::ExtendedMacroBlock> =
+    method!(Unary, "/stegos.api.Api/MacroBlock");
+const METHOD_MICRO_BLOCK: Method<proto::MicroBlockRequest, blockchain::MicroBlock> =
+    method!(Unary, "/stegos.api.Api/MicroBlock");
+const METHOD_OUTPUTS: Method<proto::OutputsRequest, proto::OutputsResponse> =
+    method!(Unary, "/stegos.api.Api/Outputs");
+const METHOD_BROADCAST_TRANSACTION: Method<
+    proto::BroadcastTransactionRequest,
+    proto::BroadcastTransactionResponse,
+> = method!(Unary, "/stegos.api.Api/BroadcastTransaction");
+const METHOD_SUBSCRIBE_CHAIN: Method<proto::SubscribeChainRequest, proto::ChainNotification> =
+    method!(ServerStreaming, "/stegos.api.Api/SubscribeChain");
+const METHOD_SUBSCRIBE_WALLET: Method<proto::SubscribeWalletRequest, proto::WalletNotification> =
+    method!(ServerStreaming, "/stegos.api.Api/SubscribeWallet");
+
+fn failed(code: RpcStatusCode, error: impl ToString) -> RpcStatus {
+    RpcStatus::new(code, Some(error.to_string()))
+}
+
+fn unexpected(response: impl std::fmt::Debug) -> RpcStatus {
+    failed(
+        RpcStatusCode::INTERNAL,
+        format!("Unexpected response: {:?}", response),
+    )
+}
+
+/// Converts error responses into gRPC errors.
+fn check(kind: ResponseKind) -> Result<ResponseKind, RpcStatus> {
+    match kind {
+        ResponseKind::Inner(InnerResponses::InternalError { error }) => {
+            Err(failed(RpcStatusCode::INTERNAL, error))
+        }
+        ResponseKind::Inner(InnerResponses::LimitExceeded { error, .. }) => {
+            Err(failed(RpcStatusCode::RESOURCE_EXHAUSTED, error))
+        }
+        ResponseKind::NodeResponse(NodeResponse::Error { error })
+        | ResponseKind::WalletResponse(WalletResponse::WalletControlResponse(
+            WalletControlResponse::Error { error },
+        ))
+        | ResponseKind::WalletResponse(WalletResponse::AccountResponse {
+            response: AccountResponse::Error { error },
+            ..
+        }) => Err(failed(RpcStatusCode::UNKNOWN, error)),
+        kind => Ok(kind),
+    }
+}
+
+fn node_response(kind: ResponseKind) -> Result<NodeResponse, RpcStatus> {
+    match check(kind)? {
+        ResponseKind::NodeResponse(response) => Ok(response),
+        kind => Err(unexpected(kind)),
+    }
+}
+
+fn status_info(status: &StatusInfo) -> proto::StatusInfo {
+    let mut proto = proto::StatusInfo::new();
+    proto.set_is_synchronized(status.is_synchronized);
+    proto.set_epoch(status.epoch);
+    proto.set_offset(status.offset);
+    proto.set_view_change(status.view_change);
+    proto.set_last_block_hash(status.last_block_hash.into_proto());
+    proto.set_last_macro_block_hash(status.last_macro_block_hash.into_proto());
+    proto.set_last_macro_block_timestamp(status.last_macro_block_timestamp.into());
+    proto.set_local_timestamp(status.local_timestamp.into());
+    let reachability = match status.reachability {
+        Reachability::Unknown => "unknown",
+        Reachability::Public => "public",
+        Reachability::Private => "private",
+    };
+    proto.set_reachability(reachability.to_string());
+    if let Some(advertised_address) = &status.advertised_address {
+        proto.set_advertised_address(advertised_address.clone());
+    }
+    proto
+}
+
+fn extended_macro_block(block: &ExtendedMacroBlock) -> proto::ExtendedMacroBlock {
+    let mut proto = proto::ExtendedMacroBlock::new();
+    proto.set_block(block.block.into_proto());
+    proto.set_epoch_info(block.epoch_info.into_proto());
+    if let Some(old_epoch_info) = &block.old_epoch_info {
+        proto.set_old_epoch_info(old_epoch_info.into_proto());
+    }
+    proto
+}
+
+fn transaction_status(status: &TransactionStatus) -> proto::TransactionStatus {
+    let mut proto = proto::TransactionStatus::new();
+    let (name, epoch, offset) = match status {
+        TransactionStatus::Created {} => ("created", None, None),
+        TransactionStatus::Accepted {} => ("accepted", None, None),
+        TransactionStatus::Rejected { error } => {
+            proto.set_error(error.clone());
+            ("rejected", None, None)
+        }
+        TransactionStatus::Prepared { epoch, offset } => ("prepared", Some(*epoch), Some(*offset)),
+        TransactionStatus::Rollback { epoch, offset } => ("rollback", Some(*epoch), Some(*offset)),
+        TransactionStatus::Committed { epoch } => ("committed", Some(*epoch), None),
+        TransactionStatus::Conflicted { epoch, offset } => ("conflicted", Some(*epoch), *offset),
+    };
+    proto.set_status(name.to_string());
+    if let Some(epoch) = epoch {
+        proto.set_epoch(epoch);
+    }
+    if let Some(offset) = offset {
+        proto.set_offset(offset);
+        proto.set_has_offset(true);
+    }
+    proto
+}
+
+fn chain_notification(notification: &ChainNotification) -> proto::ChainNotification {
+    let mut proto = proto::ChainNotification::new();
+    match notification {
+        ChainNotification::MicroBlockPrepared(block) => {
+            proto.set_micro_block_prepared(block.into_proto())
+        }
+        ChainNotification::MicroBlockReverted(reverted) => {
+            proto.set_micro_block_reverted(reverted.block.into_proto())
+        }
+        ChainNotification::MacroBlockCommitted(block) => {
+            proto.set_macro_block_committed(extended_macro_block(block))
+        }
+    }
+    proto
+}
+
+fn balance(balance: &Balance) -> proto::Balance {
+    let mut proto = proto::Balance::new();
+    proto.set_current(balance.current);
+    proto.set_available(balance.available);
+    proto
+}
+
+fn account_balance(account_balance: &AccountBalance) -> proto::AccountBalance {
+    let mut proto = proto::AccountBalance::new();
+    proto.set_payment(balance(&account_balance.payment));
+    proto.set_public_payment(balance(&account_balance.public_payment));
+    proto.set_stake(balance(&account_balance.stake));
+    proto.set_total(balance(&account_balance.total));
+    proto.set_is_final(account_balance.is_final);
+    proto.set_epoch(account_balance.epoch);
+    proto
+}
+
+fn payment_info(info: &PaymentInfo) -> proto::PaymentInfo {
+    let mut proto = proto::PaymentInfo::new();
+    proto.set_output_hash(info.output_hash.into_proto());
+    proto.set_amount(info.amount);
+    proto.set_data(info.data.into_proto());
+    if let Some(pending_timestamp) = info.pending_timestamp {
+        proto.set_pending_timestamp(pending_timestamp.into());
+    }
+    proto.set_recipient(info.recipient.into_proto());
+    if let Some(rvalue) = &info.rvalue {
+        proto.set_rvalue(rvalue.into_proto());
+    }
+    proto.set_is_change(info.is_change);
+    proto
+}
+
+fn public_payment_info(info: &PublicPaymentInfo) -> proto::PublicPaymentInfo {
+    let mut proto = proto::PublicPaymentInfo::new();
+    proto.set_output_hash(info.output_hash.into_proto());
+    proto.set_amount(info.amount);
+    if let Some(pending_timestamp) = info.pending_timestamp {
+        proto.set_pending_timestamp(pending_timestamp.into());
+    }
+    proto.set_recipient(info.recipient.into_proto());
+    proto
+}
+
+fn stake_info(info: &StakeInfo) -> proto::StakeInfo {
+    let mut proto = proto::StakeInfo::new();
+    proto.set_output_hash(info.output_hash.into_proto());
+    proto.set_account_pkey(info.account_pkey.into_proto());
+    if let Some(active_until_epoch) = info.active_until_epoch {
+        proto.set_active_until_epoch(active_until_epoch);
+        proto.set_has_active_until_epoch(true);
+    }
+    if let Some(is_active) = info.is_active {
+        proto.set_is_active(is_active);
+        proto.set_has_is_active(true);
+    }
+    proto.set_amount(info.amount);
+    proto
+}
+
+fn message_info(info: &MessageInfo) -> proto::MessageInfo {
+    let mut proto = proto::MessageInfo::new();
+    proto.set_message_hash(info.message_hash.into_proto());
+    proto.set_sender(info.sender.into_proto());
+    proto.set_timestamp(info.timestamp.into());
+    proto.set_text(info.text.clone());
+    proto
+}
+
+/// Returns `None` for internal notifications, which are not sent to clients.
+fn wallet_notification(notification: &WalletNotification) -> Option<proto::WalletNotification> {
+    let mut proto = proto::WalletNotification::new();
+    proto.set_account_id(notification.account_id.clone());
+    match &notification.notification {
+        AccountNotification::StatusChanged(status) => proto.set_status_changed(status_info(status)),
+        AccountNotification::UpstreamError(_) | AccountNotification::ChainConfigChanged(_) => {
+            return None
+        }
+        AccountNotification::Unsealed => proto.set_unsealed(proto::Unsealed::new()),
+        AccountNotification::Sealed => proto.set_sealed(proto::Sealed::new()),
+        AccountNotification::BalanceChanged(balance) => {
+            proto.set_balance_changed(account_balance(balance))
+        }
+        AccountNotification::TransactionStatus { tx_hash, status } => {
+            let mut transaction = proto::AccountTransactionStatus::new();
+            transaction.set_tx_hash(tx_hash.into_proto());
+            transaction.set_status(transaction_status(status));
+            proto.set_transaction_status(transaction)
+        }
+        AccountNotification::Received(info) => proto.set_received(payment_info(info)),
+        AccountNotification::ReceivedPublic(info) => {
+            proto.set_received_public(public_payment_info(info))
+        }
+        AccountNotification::Spent(info) => proto.set_spent(payment_info(info)),
+        AccountNotification::SpentPublic(info) => proto.set_spent_public(public_payment_info(info)),
+        AccountNotification::Staked(info) => proto.set_staked(stake_info(info)),
+        AccountNotification::Unstaked(info) => proto.set_unstaked(stake_info(info)),
+        AccountNotification::MessageReceived(info) => {
+            proto.set_message_received(message_info(info))
+        }
+    }
+    Some(proto)
+}
+
+/// Calls in progress and the request rate of a gRPC connection.
+struct Peer {
+    rate: RateLimiter,
+    calls: usize,
+    subscriptions: usize,
+    /// The connection slot, taken while there are calls in progress.
+    connection: Option<Connection>,
+}
+
+type Peers = Arc<Mutex<HashMap<String, Peer>>>;
+
+/// A call in progress, released when dropped.
+struct Call {
+    peers: Peers,
+    peer: String,
+    subscription: bool,
+}
+
+impl Drop for Call {
+    fn drop(&mut self) {
+        let mut peers = self.peers.lock().unwrap();
+        if let Some(peer) = peers.get_mut(&self.peer) {
+            peer.calls -= 1;
+            if self.subscription {
+                peer.subscriptions -= 1;
+            }
+            if peer.calls == 0 {
+                peer.connection = None;
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+struct ApiService {
+    ctx: Arc<Context>,
+    /// Calls are handled on the Tokio runtime, not on gRPC threads.
+    runtime: Handle,
+    /// Connections by peer address.
+    peers: Peers,
+}
+
+impl ApiService {
+    /// Authenticates the call and checks limits, returns the token name and the call,
+    /// which is counted until dropped.
+    fn open_call(&self, ctx: &RpcContext, subscription: bool) -> Result<(String, Call), RpcStatus> {
+        let token = self.authenticate(ctx)?;
+        let peer = ctx.peer();
+        let now = Instant::now();
+        let limiter = &self.ctx.limiter;
+        let mut peers = self.peers.lock().unwrap();
+        // Idle connections are forgotten once their rate is back to the initial one.
+        peers.retain(|_, peer| peer.calls > 0 || !peer.rate.is_full(now));
+        let entry = peers.entry(peer.clone()).or_insert_with(|| Peer {
+            rate: RateLimiter::new(limiter.limits.connection_requests_per_second, now),
+            calls: 0,
+            subscriptions: 0,
+            connection: None,
+        });
+        if entry.connection.is_none() {
+            entry.connection = Connection::open(limiter.clone());
+        }
+        let max_subscriptions = limiter.limits.max_subscriptions;
+        let limit = if entry.connection.is_none() {
+            Some(Limit::Connections)
+        } else if !entry.rate.check(now) {
+            Some(Limit::ConnectionRate)
+        } else if !limiter.check_token(&token, now) {
+            Some(Limit::TokenRate)
+        } else if subscription && max_subscriptions > 0 && entry.subscriptions >= max_subscriptions
+        {
+            Some(Limit::Subscriptions)
+        } else {
+            None
+        };
+        if let Some(limit) = limit {
+            if entry.calls == 0 {
+                entry.connection = None;
+            }
+            let error = limits::exceeded(limit);
+            debug!("[{}] {}: token={}", peer, error, token);
+            return Err(failed(RpcStatusCode::RESOURCE_EXHAUSTED, error));
+        }
+        entry.calls += 1;
+        if subscription {
+            entry.subscriptions += 1;
+        }
+        let call = Call {
+            peers: self.peers.clone(),
+            peer,
+            subscription,
+        };
+        Ok((token, call))
+    }
+
+    /// Authenticates the call by its metadata, returns the token name.
+    fn authenticate(&self, ctx: &RpcContext) -> Result<String, RpcStatus> {
+        let token = ctx
+            .request_headers()
+            .iter()
+            .find(|(key, _)| *key == "authorization")
+            .and_then(|(_, value)| std::str::from_utf8(value).ok())
+            .filter(|value| value.starts_with("Bearer "))
+            .and_then(|value| {
+                self.ctx
+                    .tokens
+                    .authenticate_bearer(&value["Bearer ".len()..])
+            });
+        let token = match token {
+            Some(token) => token,
+            None => {
+                debug!("[{}] Unauthorized gRPC call", ctx.peer());
+                return Err(failed(RpcStatusCode::UNAUTHENTICATED, "Invalid API token"));
+            }
+        };
+        Ok(token)
+    }
+
+    /// Processes a request, returns the response and the register with its notifications.
+    async fn process(
+        &self,
+        peer: String,
+        call: Result<(String, Call), RpcStatus>,
+        kind: RequestKind,
+    ) -> Result<(ResponseKind, Register, Call), RpcStatus> {
+        let (token, call) = call?;
+        self.ctx
+            .tokens
+            .authorize(&token, &peer, &kind)
+            .map_err(|e| failed(RpcStatusCode::PERMISSION_DENIED, e))?;
+        let mut register = self.ctx.register(peer);
+        let request = RawRequest(Request {
+            kind,
+            id: 0,
+            since_seq: None,
+        });
+        let response = register
+            .process_as(&token, request)
+            .await
+            .map_err(|e| failed(RpcStatusCode::INVALID_ARGUMENT, e))?;
+        Ok((check(response.0)?, register, call))
+    }
+
+    /// Handles a call with a single node response.
+    fn unary<Resp, F>(&self, ctx: RpcContext, kind: RequestKind, sink: UnarySink<Resp>, f: F)
+    where
+        Resp: Send + 'static,
+        F: FnOnce(NodeResponse) -> Result<Resp, RpcStatus> + Send + 'static,
+    {
+        let service = self.clone();
+        let peer = ctx.peer();
+        let call = self.open_call(&ctx, false);
+        self.runtime.spawn(async move {
+            let response = match service.process(peer.clone(), call, kind).await {
+                Ok((response, _register, _call)) => node_response(response).and_then(f),
+                Err(status) => Err(status),
+            };
+            let result = match response {
+                Ok(response) => sink.success(response).await,
+                Err(status) => sink.fail(status).await,
+            };
+            if let Err(e) = result {
+                debug!("[{}] Failed to send gRPC response: {}", peer, e);
+            }
+        });
+    }
+
+    /// Handles a subscription, sending notifications until the call is cancelled.
+    fn streaming<Resp, F>(
+        &self,
+        ctx: RpcContext,
+        kind: RequestKind,
+        mut sink: ServerStreamingSink<Resp>,
+        f: F,
+    ) where
+        Resp: Send + 'static,
+        F: Fn(ResponseKind) -> Option<Resp> + Send + 'static,
+    {
+        let service = self.clone();
+        let peer = ctx.peer();
+        let call = self.open_call(&ctx, true);
+        self.runtime.spawn(async move {
+            let result = match service.process(peer.clone(), call, kind).await {
+                Ok((_response, register, _call)) => {
+                    let mut notifications = register
+                        .notifications
+                        .filter_map(move |notification| future::ready(f(notification.0)))
+                        .map(|notification| {
+                            Ok::<_, grpcio::Error>((notification, WriteFlags::default()))
+                        });
+                    match sink.send_all(&mut notifications).await {
+                        Ok(()) => sink.close().await,
+                        Err(e) => Err(e),
+                    }
+                }
+                Err(status) => sink.fail(status).await,
+            };
+            if let Err(e) = result {
+                debug!("[{}] gRPC subscription closed: {}", peer, e);
+            }
+        });
+    }
+
+    fn status_info(
+        &self,
+        ctx: RpcContext,
+        _req: proto::StatusInfoRequest,
+        sink: UnarySink<proto::StatusInfo>,
+    ) {
+        let kind = RequestKind::NodeRequest(NodeRequest::StatusInfo {});
+        self.unary(ctx, kind, sink, |response| match response {
+            NodeResponse::StatusInfo(status) => Ok(status_info(&status)),
+            response => Err(unexpected(response)),
+        })
+    }
+
+    fn macro_block(
+        &self,
+        ctx: RpcContext,
+        req: proto::MacroBlockRequest,
+        sink: UnarySink<proto::ExtendedMacroBlock>,
+    ) {
+        let epoch = req.get_epoch();
+        let kind = RequestKind::NodeRequest(NodeRequest::MacroBlockInfo { epoch });
+        self.unary(ctx, kind, sink, |response| match response {
+            NodeResponse::MacroBlockInfo(block) => Ok(extended_macro_block(&block)),
+            response => Err(unexpected(response)),
+        })
+    }
+
+    fn micro_block(
+        &self,
+        ctx: RpcContext,
+        req: proto::MicroBlockRequest,
+        sink: UnarySink<blockchain::MicroBlock>,
+    ) {
+        let epoch = req.get_epoch();
+        let offset = req.get_offset();
+        let kind = RequestKind::NodeRequest(NodeRequest::MicroBlockInfo { epoch, offset });
+        self.unary(ctx, kind, sink, |response| match response {
+            NodeResponse::MicroBlockInfo(block) => Ok(block.into_proto()),
+            response => Err(unexpected(response)),
+        })
+    }
+
+    fn outputs(
+        &self,
+        ctx: RpcContext,
+        req: proto::OutputsRequest,
+        sink: UnarySink<proto::OutputsResponse>,
+    ) {
+        let utxos: Result<Vec<Hash>, Error> = req.utxos.iter().map(Hash::from_proto).collect();
+        let utxos = match utxos {
+            Ok(utxos) => utxos,
+            Err(e) => {
+                let status = failed(RpcStatusCode::INVALID_ARGUMENT, e);
+                ctx.spawn(sink.fail(status).map(|_| ()));
+                return;
+            }
+        };
+        let kind = RequestKind::NodeRequest(NodeRequest::OutputsList { utxos });
+        self.unary(ctx, kind, sink, |response| match response {
+            NodeResponse::OutputsList { utxos } => {
+                let mut proto = proto::OutputsResponse::new();
+                for output in &utxos {
+                    proto.utxos.push(output.into_proto());
+                }
+                Ok(proto)
+            }
+            response => Err(unexpected(response)),
+        })
+    }
+
+    fn broadcast_transaction(
+        &self,
+        ctx: RpcContext,
+        req: proto::BroadcastTransactionRequest,
+        sink: UnarySink<proto::BroadcastTransactionResponse>,
+    ) {
+        let data = match Transaction::from_proto(req.get_transaction()) {
+            Ok(data) => data,
+            Err(e) => {
+                let status = failed(RpcStatusCode::INVALID_ARGUMENT, e);
+                ctx.spawn(sink.fail(status).map(|_| ()));
+                return;
+            }
+        };
+        let kind = RequestKind::NodeRequest(NodeRequest::BroadcastTransaction { data });
+        self.unary(ctx, kind, sink, |response| match response {
+            NodeResponse::BroadcastTransaction { hash, status } => {
+                let mut proto = proto::BroadcastTransactionResponse::new();
+                proto.set_hash(hash.into_proto());
+                proto.set_status(transaction_status(&status));
+                Ok(proto)
+            }
+            response => Err(unexpected(response)),
+        })
+    }
+
+    fn subscribe_chain(
+        &self,
+        ctx: RpcContext,
+        req: proto::SubscribeChainRequest,
+        sink: ServerStreamingSink<proto::ChainNotification>,
+    ) {
+        let epoch = req.get_epoch();
+        let offset = req.get_offset();
+        let kind = RequestKind::NodeRequest(NodeRequest::SubscribeChain { epoch, offset });
+        self.streaming(ctx, kind, sink, |notification| match notification {
+            ResponseKind::ChainNotification(notification) => {
+                Some(chain_notification(&notification))
+            }
+            _ => None,
+        })
+    }
+
+    fn subscribe_wallet(
+        &self,
+        ctx: RpcContext,
+        _req: proto::SubscribeWalletRequest,
+        sink: ServerStreamingSink<proto::WalletNotification>,
+    ) {
+        let kind = RequestKind::WalletsRequest(WalletRequest::WalletControlRequest(
+            WalletControlRequest::SubscribeWalletUpdates {},
+        ));
+        self.streaming(ctx, kind, sink, |notification| match notification {
+            ResponseKind::WalletNotification(notification) => wallet_notification(&notification),
+            _ => None,
+        })
+    }
+}
+
+/// Loads the TLS material of the API, which is not reloaded on SIGHUP.
+fn server_credentials(tls: &ServerTlsConfig) -> Result<ServerCredentials, Error> {
+    // Checks certificates and keys the same way as other API servers.
+    tls_acceptor(tls)?;
+    let read = |path: &Path| {
+        std::fs::read(path).map_err(|e| format_err!("Failed to open {:?}: {}", path, e))
+    };
+    let mut builder =
+        ServerCredentialsBuilder::new().add_cert(read(&tls.certificate)?, read(&tls.private_key)?);
+    if let Some(client_ca) = &tls.client_ca {
+        builder = builder.root_cert(
+            read(client_ca)?,
+            CertificateRequestType::RequestAndRequireClientCertificateAndVerify,
+        );
+    }
+    Ok(builder.build())
+}
+
+/// Starts the gRPC server, which is stopped when the returned `Server` is dropped.
+/// TLS is required on non-loopback addresses. Must be called within the Tokio runtime.
+pub fn spawn_grpc_server(
+    endpoint: String,
+    tls: Option<ServerTlsConfig>,
+    tokens: ApiTokens,
+    apis: Vec<Box<dyn ApiHandler>>,
+    network: Option<Network>,
+    version: String,
+    chain_name: String,
+    limiter: Arc<Limiter>,
+) -> Result<Server, Error> {
+    let addr: SocketAddr = endpoint.parse()?;
+    let credentials = match tls {
+        Some(tls) => {
+            info!(target: "stegos_api", "Starting gRPC Server with TLS on {}", &addr);
+            Some(server_credentials(&tls)?)
+        }
+        None if addr.ip().is_loopback() => {
+            info!(target: "stegos_api", "Starting gRPC Server on {}", &addr);
+            None
+        }
+        None => bail!(
+            "gRPC Server requires TLS on non-loopback address {}, configure api_tls",
+            addr
+        ),
+    };
+    let max_message_size = limiter.limits.max_message_size;
+    let service = ApiService {
+        ctx: Arc::new(Context {
+            tokens,
+            apis,
+            network,
+            version,
+            chain_name,
+            journal: None,
+            limiter,
+        }),
+        runtime: Handle::current(),
+        peers: Arc::new(Mutex::new(HashMap::new())),
+    };
+
+    let service = ServiceBuilder::new()
+        .add_unary_handler(&METHOD_STATUS_INFO, {
+            let service = service.clone();
+            move |ctx, req, sink| service.status_info(ctx, req, sink)
+        })
+        .add_unary_handler(&METHOD_MACRO_BLOCK, {
+            let service = service.clone();
+            move |ctx, req, sink| service.macro_block(ctx, req, sink)
+        })
+        .add_unary_handler(&METHOD_MICRO_BLOCK, {
+            let service = service.clone();
+            move |ctx, req, sink| service.micro_block(ctx, req, sink)
+        })
+        .add_unary_handler(&METHOD_OUTPUTS, {
+            let service = service.clone();
+            move |ctx, req, sink| service.outputs(ctx, req, sink)
+        })
+        .add_unary_handler(&METHOD_BROADCAST_TRANSACTION, {
+            let service = service.clone();
+            move |ctx, req, sink| service.broadcast_transaction(ctx, req, sink)
+        })
+        .add_server_streaming_handler(&METHOD_SUBSCRIBE_CHAIN, {
+            let service = service.clone();
+            move |ctx, req, sink| service.subscribe_chain(ctx, req, sink)
+        })
+        .add_server_streaming_handler(&METHOD_SUBSCRIBE_WALLET, move |ctx, req, sink| {
+            service.subscribe_wallet(ctx, req, sink)
+        })
+        .build();
+
+    let env = Arc::new(Environment::new(1));
+    let mut builder = ServerBuilder::new(env.clone()).register_service(service);
+    builder = match credentials {
+        Some(credentials) => {
+            builder.bind_with_cred(addr.ip().to_string(), addr.port(), credentials)
+        }
+        None => builder.bind(addr.ip().to_string(), addr.port()),
+    };
+    if max_message_size > 0 {
+        let args = ChannelBuilder::new(env)
+            .max_receive_message_len(max_message_size as i32)
+            .build_args();
+        builder = builder.channel_args(args);
+    }
+    let mut server = builder.build()?;
+    server.start();
+    Ok(server)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::ApiToken;
+    use crate::server::api::RawResponse;
+    use crate::server::limits::ApiLimits;
+    use async_trait::async_trait;
+    use futures::channel::mpsc;
+    use grpcio::{CallOption, Client, MetadataBuilder};
+    use stegos_blockchain::{MicroBlock, Timestamp};
+    use stegos_crypto::{pbc, scc};
+
+    #[derive(Clone)]
+    struct TestNode;
+
+    #[async_trait]
+    impl ApiHandler for TestNode {
+        async fn process_request(&self, req: RawRequest) -> Result<RawResponse, Error> {
+            match req.0.kind {
+                RequestKind::NodeRequest(NodeRequest::StatusInfo {}) => {
+                    let status = StatusInfo {
+                        epoch: 1,
+                        reachability: Reachability::Public,
+                        ..Default::default()
+                    };
+                    Ok(NodeResponse::StatusInfo(status).into())
+                }
+                RequestKind::NodeRequest(NodeRequest::SubscribeChain { .. }) => {
+                    let (skey, pkey) = pbc::make_random_keys();
+                    let random = pbc::make_VRF(&skey, &Hash::digest("random"));
+                    let block = MicroBlock::empty(
+                        Hash::zero(),
+                        1,
+                        0,
+                        0,
+                        None,
+                        pkey,
+                        random,
+                        Vec::new(),
+                        Timestamp::now(),
+                    );
+                    // The stream ends after the notification.
+                    let (mut tx, rx) = mpsc::channel(1);
+                    tx.try_send(ChainNotification::MicroBlockPrepared(block))
+                        .unwrap();
+                    let response = NodeResponse::SubscribedChain {
+                        current_epoch: 1,
+                        current_offset: 0,
+                        rx: Some(rx),
+                    };
+                    Ok(response.into())
+                }
+                kind => bail!("Unsupported request: {:?}", kind),
+            }
+        }
+
+        fn cloned(&self) -> Box<dyn ApiHandler> {
+            Box::new(self.clone())
+        }
+    }
+
+    #[tokio::test]
+    async fn loopback() {
+        let api_token = ApiToken::new();
+        let apis: Vec<Box<dyn ApiHandler>> = vec![Box::new(TestNode)];
+        let server = spawn_grpc_server(
+            "127.0.0.1:0".to_string(),
+            None,
+            api_token.into(),
+            apis,
+            None,
+            "test".to_string(),
+            "dev".to_string(),
+            Arc::new(Limiter::new(ApiLimits::default())),
+        )
+        .unwrap();
+        let (_, port) = server.bind_addrs().next().unwrap();
+        let env = Arc::new(Environment::new(1));
+        let channel = ChannelBuilder::new(env).connect(&format!("127.0.0.1:{}", port));
+        let client = Client::new(channel);
+        let mut headers = MetadataBuilder::new();
+        let authorization = format!("Bearer {}", api_token.to_base64());
+        headers.add_str("authorization", &authorization).unwrap();
+        let headers = headers.build();
+        let options = || CallOption::default().headers(headers.clone());
+
+        // Calls without the token are rejected.
+        let request = proto::StatusInfoRequest::new();
+        let call = client.unary_call_async(&METHOD_STATUS_INFO, &request, CallOption::default());
+        match call.unwrap().await {
+            Err(grpcio::Error::RpcFailure(status)) => {
+                assert!(status.status == RpcStatusCode::UNAUTHENTICATED)
+            }
+            _ => panic!("The call must fail"),
+        }
+
+        let call = client.unary_call_async(&METHOD_STATUS_INFO, &request, options());
+        let status = call.unwrap().await.unwrap();
+        assert_eq!(status.get_epoch(), 1);
+        assert_eq!(status.get_reachability(), "public");
+
+        let request = proto::SubscribeChainRequest::new();
+        let notifications = client
+            .server_streaming(&METHOD_SUBSCRIBE_CHAIN, &request, options())
+            .unwrap();
+        let notifications: Vec<_> = notifications.try_collect().await.unwrap();
+        assert_eq!(notifications.len(), 1);
+        assert!(notifications[0].has_micro_block_prepared());
+    }
+
+    #[test]
+    fn transaction_statuses() {
+        let proto = transaction_status(&TransactionStatus::Prepared {
+            epoch: 1,
+            offset: 0,
+        });
+        assert_eq!(proto.get_status(), "prepared");
+        assert_eq!(proto.get_epoch(), 1);
+        assert!(proto.get_has_offset());
+
+        let proto = transaction_status(&TransactionStatus::Conflicted {
+            epoch: 2,
+            offset: None,
+        });
+        assert_eq!(proto.get_status(), "conflicted");
+        assert_eq!(proto.get_epoch(), 2);
+        assert!(!proto.get_has_offset());
+
+        let proto = transaction_status(&TransactionStatus::Rejected {
+            error: "x".to_string(),
+        });
+        assert_eq!(proto.get_status(), "rejected");
+        assert_eq!(proto.get_error(), "x");
+    }
+
+    #[test]
+    fn wallet_notifications() {
+        let notification = WalletNotification {
+            account_id: "1".to_string(),
+            notification: AccountNotification::Sealed,
+        };
+        let proto = wallet_notification(&notification).unwrap();
+        assert_eq!(proto.get_account_id(), "1");
+        assert!(proto.has_sealed());
+
+        let notification = WalletNotification {
+            account_id: "1".to_string(),
+            notification: AccountNotification::Staked(StakeInfo {
+                output_hash: Hash::digest("output"),
+                account_pkey: scc::make_random_keys().1,
+                active_until_epoch: Some(10),
+                is_active: None,
+                amount: 100,
+            }),
+        };
+        let proto = wallet_notification(&notification).unwrap();
+        let staked = proto.get_staked();
+        assert_eq!(staked.get_amount(), 100);
+        assert_eq!(staked.get_active_until_epoch(), 10);
+        assert!(staked.get_has_active_until_epoch() && !staked.get_has_is_active());
+
+        // Internal notifications can't be serialized.
+        let notification = WalletNotification {
+            account_id: "1".to_string(),
+            notification: AccountNotification::UpstreamError("x".to_string()),
+        };
+        assert!(wallet_notification(&notification).is_none());
+    }
+}
@@ -0,0 +1,398 @@
+//! WebSocket API - Notifications Journal.
+
+//
+// Copyright (c) 2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use super::open_private;
+use crate::{RequestKind, ResponseKind};
+use failure::{ensure, Error, Fail};
+use futures::task::{Context, Poll, Waker};
+use futures::Stream;
+use log::*;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use stegos_node::{ChainNotification, NodeRequest};
+use stegos_wallet::api::{WalletControlRequest, WalletRequest};
+
+/// Notifications which can be replayed from the journal.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JournalKind {
+    Chain,
+    Wallet,
+}
+
+impl JournalKind {
+    /// Returns the kind of notifications produced by a subscribe request.
+    pub fn of_request(kind: &RequestKind) -> Option<JournalKind> {
+        match kind {
+            RequestKind::NodeRequest(NodeRequest::SubscribeChain { .. }) => {
+                Some(JournalKind::Chain)
+            }
+            RequestKind::WalletsRequest(WalletRequest::WalletControlRequest(
+                WalletControlRequest::SubscribeWalletUpdates {},
+            )) => Some(JournalKind::Wallet),
+            _ => None,
+        }
+    }
+
+    /// Returns the kind of a notification, if it is journaled.
+    pub fn of_notification(kind: &ResponseKind) -> Option<JournalKind> {
+        match kind {
+            ResponseKind::ChainNotification(_) => Some(JournalKind::Chain),
+            ResponseKind::WalletNotification(_) => Some(JournalKind::Wallet),
+            _ => None,
+        }
+    }
+}
+
+/// Returns true if the chain notification is about a block before (epoch, offset).
+pub fn is_chain_history((epoch, offset): (u64, u32), notification: &ChainNotification) -> bool {
+    match notification {
+        ChainNotification::MicroBlockPrepared(block) => {
+            (block.header.epoch, block.header.offset) < (epoch, offset)
+        }
+        ChainNotification::MacroBlockCommitted(block) => block.block.header.epoch < epoch,
+        ChainNotification::MicroBlockReverted(_) => false,
+    }
+}
+
+/// Notifications after `since_seq` are no longer available for replay.
+#[derive(Debug, Clone, Fail)]
+#[fail(
+    display = "Notifications are no longer available for replay: since_seq={}, first_seq={}",
+    since_seq, first_seq
+)]
+pub struct JournalGap {
+    pub since_seq: u64,
+    /// The first sequence id available for replay.
+    pub first_seq: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Entry {
+    seq: u64,
+    kind: JournalKind,
+    notification: Value,
+}
+
+/// A stream of journaled notifications of one kind with their sequence ids.
+/// Notifications are read from the journal, so a slow reader doesn't use memory,
+/// but the stream ends with a gap when the reader falls behind the journal.
+pub struct JournalReceiver {
+    inner: Arc<Mutex<Inner>>,
+    kind: JournalKind,
+    /// Sequence id of the last read entry.
+    seq: u64,
+    terminated: bool,
+}
+
+impl Stream for JournalReceiver {
+    type Item = Result<(u64, Value), JournalGap>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        if self.terminated {
+            return Poll::Ready(None);
+        }
+        let inner = self.inner.clone();
+        let mut inner = inner.lock().unwrap();
+        match inner.next(self.kind, self.seq) {
+            Ok(Some((seq, notification))) => {
+                self.seq = seq;
+                Poll::Ready(Some(Ok((seq, notification))))
+            }
+            Ok(None) => {
+                // Skip entries of other kinds.
+                self.seq = inner.last_seq;
+                if !inner.wakers.iter().any(|w| w.will_wake(cx.waker())) {
+                    inner.wakers.push(cx.waker().clone());
+                }
+                Poll::Pending
+            }
+            Err(gap) => {
+                warn!(target: "stegos_api", "{}", gap);
+                self.terminated = true;
+                Poll::Ready(Some(Err(gap)))
+            }
+        }
+    }
+}
+
+struct Inner {
+    path: PathBuf,
+    file: File,
+    /// The maximal number of entries available for replay.
+    capacity: usize,
+    /// The last `capacity` entries.
+    entries: VecDeque<Entry>,
+    /// The number of lines in the file.
+    lines: usize,
+    last_seq: u64,
+    /// Readers waiting for new entries.
+    wakers: Vec<Waker>,
+}
+
+impl Inner {
+    /// Returns the first entry of the kind after `seq`.
+    fn next(&self, kind: JournalKind, seq: u64) -> Result<Option<(u64, Value)>, JournalGap> {
+        let first_seq = match self.entries.front() {
+            Some(first) => first.seq,
+            None => self.last_seq + 1,
+        };
+        // Entries have been evicted, or the journal has been recreated.
+        if seq + 1 < first_seq || seq > self.last_seq {
+            return Err(JournalGap {
+                since_seq: seq,
+                first_seq,
+            });
+        }
+        // Entries are ordered by seq.
+        let (mut low, mut high) = (0, self.entries.len());
+        while low < high {
+            let middle = (low + high) / 2;
+            if self.entries[middle].seq <= seq {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        let entry = self
+            .entries
+            .iter()
+            .skip(low)
+            .find(|entry| entry.kind == kind)
+            .map(|entry| (entry.seq, entry.notification.clone()));
+        Ok(entry)
+    }
+
+    /// Rewrites the file with entries available for replay.
+    fn compact(&mut self) -> Result<(), Error> {
+        let tmp_path = self.path.with_extension("tmp");
+        let mut tmp = open_private(
+            OpenOptions::new().write(true).create(true).truncate(true),
+            &tmp_path,
+        )?;
+        for entry in &self.entries {
+            writeln!(tmp, "{}", serde_json::to_string(entry)?)?;
+        }
+        tmp.sync_all()?;
+        fs::rename(&tmp_path, &self.path)?;
+        self.file = open_private(OpenOptions::new().append(true), &self.path)?;
+        self.lines = self.entries.len();
+        debug!(target: "stegos_api", "Compacted notifications journal: entries={}", self.lines);
+        Ok(())
+    }
+}
+
+/// Bounded on-disk buffer of notifications, used to resume subscriptions after reconnect.
+/// Subscribers read notifications from the buffer, see `JournalReceiver`.
+#[derive(Clone)]
+pub struct Journal {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl Journal {
+    /// Opens the journal, creating the file if it doesn't exist.
+    pub fn open(path: &Path, capacity: usize) -> Result<Journal, Error> {
+        ensure!(capacity > 0, "Journal capacity must be positive");
+        let mut entries = VecDeque::with_capacity(capacity + 1);
+        let mut lines = 0;
+        if path.exists() {
+            for line in BufReader::new(File::open(path)?).lines() {
+                let line = line?;
+                lines += 1;
+                match serde_json::from_str::<Entry>(&line) {
+                    Ok(entry) => {
+                        entries.push_back(entry);
+                        if entries.len() > capacity {
+                            entries.pop_front();
+                        }
+                    }
+                    // The last line can be truncated by a crash.
+                    Err(e) => warn!(target: "stegos_api", "Skipped journal entry: {}", e),
+                }
+            }
+        }
+        let last_seq = entries.back().map(|e| e.seq).unwrap_or(0);
+        let file = open_private(OpenOptions::new().create(true).append(true), path)?;
+        info!(target: "stegos_api", "Opened notifications journal: path={:?}, last_seq={}", path, last_seq);
+        let mut inner = Inner {
+            path: path.to_path_buf(),
+            file,
+            capacity,
+            entries,
+            lines,
+            last_seq,
+            wakers: Vec::new(),
+        };
+        if inner.lines > inner.entries.len() {
+            inner.compact()?;
+        }
+        Ok(Journal {
+            inner: Arc::new(Mutex::new(inner)),
+        })
+    }
+
+    /// Returns the sequence id of the last notification.
+    pub fn last_seq(&self) -> u64 {
+        self.inner.lock().unwrap().last_seq
+    }
+
+    /// Stores a notification and wakes up subscribers.
+    /// Returns the assigned sequence id.
+    pub fn append(&self, kind: JournalKind, notification: Value) -> Result<u64, Error> {
+        let mut inner = self.inner.lock().unwrap();
+        let seq = inner.last_seq + 1;
+        let entry = Entry {
+            seq,
+            kind,
+            notification,
+        };
+        writeln!(inner.file, "{}", serde_json::to_string(&entry)?)?;
+        inner.last_seq = seq;
+        inner.lines += 1;
+        inner.entries.push_back(entry);
+        if inner.entries.len() > inner.capacity {
+            inner.entries.pop_front();
+        }
+        for waker in inner.wakers.drain(..) {
+            waker.wake();
+        }
+        if inner.lines > 2 * inner.capacity {
+            inner.compact()?;
+        }
+        Ok(seq)
+    }
+
+    /// Subscribes to notifications of the given kind after `since_seq`.
+    /// Returns the sequence id of the last notification and the stream of notifications,
+    /// starting with the replayed ones, or a gap if some of them are no longer available.
+    pub fn subscribe(
+        &self,
+        kind: JournalKind,
+        since_seq: u64,
+    ) -> Result<(u64, JournalReceiver), JournalGap> {
+        let inner = self.inner.lock().unwrap();
+        inner.next(kind, since_seq)?;
+        let rx = JournalReceiver {
+            inner: self.inner.clone(),
+            kind,
+            seq: since_seq,
+            terminated: false,
+        };
+        Ok((inner.last_seq, rx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{FutureExt, StreamExt};
+    use serde_json::json;
+    use tempdir::TempDir;
+
+    fn drain(rx: &mut JournalReceiver) -> Vec<u64> {
+        let mut seqs = Vec::new();
+        while let Some(Some(Ok((seq, _)))) = rx.next().now_or_never() {
+            seqs.push(seq);
+        }
+        seqs
+    }
+
+    #[test]
+    fn replay() {
+        let dir = TempDir::new("journal").unwrap();
+        let path = dir.path().join("journal.jsonl");
+        let journal = Journal::open(&path, 4).unwrap();
+        assert_eq!(journal.last_seq(), 0);
+        for i in 0..3 {
+            journal
+                .append(JournalKind::Chain, json!({ "i": i }))
+                .unwrap();
+        }
+        journal.append(JournalKind::Wallet, json!({})).unwrap();
+
+        let (last_seq, mut rx) = journal.subscribe(JournalKind::Chain, 1).unwrap();
+        assert_eq!(last_seq, 4);
+        assert_eq!(drain(&mut rx), vec![2, 3]);
+        journal.append(JournalKind::Chain, json!({})).unwrap();
+        journal.append(JournalKind::Wallet, json!({})).unwrap();
+        assert_eq!(drain(&mut rx), vec![5]);
+
+        // Only the last `capacity` entries survive compaction and reopen.
+        for _ in 0..5 {
+            journal.append(JournalKind::Chain, json!({})).unwrap();
+        }
+        drop(journal);
+        drop(rx);
+        let journal = Journal::open(&path, 4).unwrap();
+        assert_eq!(journal.last_seq(), 11);
+        let (_, mut rx) = journal.subscribe(JournalKind::Chain, 7).unwrap();
+        assert_eq!(drain(&mut rx), vec![8, 9, 10, 11]);
+        let lines = fs::read_to_string(&path).unwrap().lines().count();
+        assert_eq!(lines, 4);
+        // Notifications are only accessible by the owner.
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        journal.append(JournalKind::Chain, json!({})).unwrap();
+        let (seq, notification) = futures::executor::block_on(rx.next()).unwrap().unwrap();
+        assert_eq!(seq, 12);
+        assert_eq!(notification, json!({}));
+    }
+
+    #[test]
+    fn gaps() {
+        let dir = TempDir::new("journal").unwrap();
+        let path = dir.path().join("journal.jsonl");
+        let journal = Journal::open(&path, 2).unwrap();
+        journal.subscribe(JournalKind::Wallet, 0).unwrap();
+        for _ in 0..3 {
+            journal.append(JournalKind::Wallet, json!({})).unwrap();
+        }
+        // The first notification has been evicted.
+        let gap = journal.subscribe(JournalKind::Wallet, 0).err().unwrap();
+        assert_eq!((gap.since_seq, gap.first_seq), (0, 2));
+        // The journal has been recreated.
+        assert!(journal.subscribe(JournalKind::Wallet, 4).is_err());
+
+        // A slow subscriber is closed after a gap.
+        let (_, mut rx) = journal.subscribe(JournalKind::Wallet, 1).unwrap();
+        for _ in 0..2 {
+            journal.append(JournalKind::Wallet, json!({})).unwrap();
+        }
+        match rx.next().now_or_never() {
+            Some(Some(Err(gap))) => assert_eq!((gap.since_seq, gap.first_seq), (1, 4)),
+            result => panic!("{:?}", result.map(|r| r.map(|r| r.is_ok()))),
+        }
+        assert!(rx.next().now_or_never().unwrap().is_none());
+    }
+}
@@ -0,0 +1,849 @@
+//! WebSocket API - JSON-RPC 2.0 over HTTP.
+//!
+//! Requests are sent as `POST /` with `Authorization: Bearer <token>`, where the token
+//! is the base64 value from `api.token` or a named token. The method is the `type` of
+//! the WebSocket request and params are the remaining fields, e.g.
+//! `{"jsonrpc": "2.0", "method": "balance_info", "params": {"account_id": "1"}, "id": 1}`.
+//! Error responses of the node, wallet and network APIs are returned as JSON-RPC errors.
+//!
+//! Subscriptions are sent as `POST /events`, the response is a stream of server-sent
+//! events: the JSON-RPC response first, then a `notification` for each update.
+//! The stream is closed when the token is revoked.
+//!
+//! The server uses the TLS configuration of the WebSocket API. Without TLS, it only
+//! listens on loopback addresses, since tokens are sent in clear text.
+
+//
+// MIT License
+//
+// Copyright (c) 2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use super::api::{ApiHandler, RawRequest, RawResponse};
+use super::limits::{self, Connection, Limiter, RateLimiter};
+use super::register::Register;
+use super::{open_connection, reloadable_tls_acceptor, Context};
+use crate::tls::ServerTlsConfig;
+use crate::tokens::ApiTokens;
+use crate::{
+    AccountResponse, Limit, NetworkResponse, NodeResponse, Request, RequestKind, ResponseKind,
+    WalletControlResponse, WalletResponse,
+};
+use failure::{bail, Error};
+use futures::select;
+use futures::stream::{self, Stream, StreamExt};
+use futures::FutureExt;
+use hyper::header::{AUTHORIZATION, CACHE_CONTROL, CONTENT_TYPE};
+use hyper::server::conn::Http;
+use hyper::service::service_fn;
+use hyper::{Body, Method, StatusCode};
+use log::*;
+use serde::ser::Serialize;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::task::{self, Poll};
+use std::time::{Duration, Instant};
+use stegos_network::Network;
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+use tokio_rustls::TlsAcceptor;
+
+/// How often event streams check that their token hasn't been revoked.
+const TOKEN_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+// JSON-RPC 2.0 error codes.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+const SERVER_ERROR: i64 = -32000;
+
+#[derive(Debug, Deserialize)]
+struct JsonRpcRequest {
+    jsonrpc: String,
+    method: String,
+    #[serde(default)]
+    params: Value,
+    /// Requests without id are notifications, which have no response.
+    #[serde(default)]
+    id: Option<Value>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonRpcError {
+    code: i64,
+    message: String,
+}
+
+impl JsonRpcError {
+    fn new(code: i64, message: impl ToString) -> Self {
+        JsonRpcError {
+            code,
+            message: message.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct JsonRpcResponse {
+    jsonrpc: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<JsonRpcError>,
+    id: Value,
+}
+
+impl JsonRpcResponse {
+    fn new(id: Value, result: Result<Value, JsonRpcError>) -> Self {
+        let (result, error) = match result {
+            Ok(result) => (Some(result), None),
+            Err(error) => (None, Some(error)),
+        };
+        JsonRpcResponse {
+            jsonrpc: "2.0",
+            result,
+            error,
+            id,
+        }
+    }
+}
+
+/// Converts JSON-RPC method and params to the request of the WebSocket API.
+fn to_request(method: &str, params: Value) -> Result<Request, JsonRpcError> {
+    let mut params = match params {
+        Value::Object(params) => params,
+        Value::Null => Map::new(),
+        _ => {
+            return Err(JsonRpcError::new(
+                INVALID_PARAMS,
+                "params must be an object",
+            ))
+        }
+    };
+    params.insert("type".to_string(), Value::String(method.to_string()));
+    let kind: RequestKind = serde_json::from_value(Value::Object(params))
+        .map_err(|e| JsonRpcError::new(INVALID_PARAMS, e))?;
+    Ok(Request {
+        kind,
+        id: 0,
+        since_seq: None,
+    })
+}
+
+fn status(code: StatusCode) -> hyper::Response<Body> {
+    hyper::Response::builder()
+        .status(code)
+        .body(Body::empty())
+        .expect("valid response")
+}
+
+fn json_response<T: Serialize>(value: &T) -> hyper::Response<Body> {
+    let body = serde_json::to_string(value).expect("serialized");
+    hyper::Response::builder()
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(body))
+        .expect("valid response")
+}
+
+fn sse_event<T: Serialize>(value: &T) -> String {
+    format!(
+        "data: {}\n\n",
+        serde_json::to_string(value).expect("serialized")
+    )
+}
+
+/// State of an HTTP connection, which can carry several requests.
+struct HttpConnection {
+    _connection: Connection,
+    /// Rate of requests on this connection.
+    rate: Mutex<RateLimiter>,
+    /// The number of subscriptions of open event streams.
+    subscriptions: AtomicUsize,
+}
+
+/// Releases the subscriptions of an event stream when it is closed.
+struct Subscriptions {
+    connection: Arc<HttpConnection>,
+    count: usize,
+}
+
+impl Drop for Subscriptions {
+    fn drop(&mut self) {
+        self.connection
+            .subscriptions
+            .fetch_sub(self.count, Ordering::SeqCst);
+    }
+}
+
+fn bearer_token(req: &hyper::Request<Body>) -> Option<&str> {
+    let value = req.headers().get(AUTHORIZATION)?.to_str().ok()?;
+    if value.starts_with("Bearer ") {
+        Some(&value["Bearer ".len()..])
+    } else {
+        None
+    }
+}
+
+/// Charges a request to the rates of the connection and the token,
+/// checks the number of subscriptions of the connection.
+fn check_limits(
+    ctx: &Context,
+    connection: &HttpConnection,
+    register: &Register,
+    token: &str,
+    request: &RawRequest,
+) -> Result<(), Limit> {
+    let now = Instant::now();
+    if !connection.rate.lock().unwrap().check(now) {
+        return Err(Limit::ConnectionRate);
+    }
+    if !ctx.limiter.check_token(token, now) {
+        return Err(Limit::TokenRate);
+    }
+    let max_subscriptions = ctx.limiter.limits.max_subscriptions;
+    if max_subscriptions > 0 && register.is_subscribe(request) {
+        let subscriptions =
+            connection.subscriptions.load(Ordering::SeqCst) + register.notifications.len();
+        if subscriptions >= max_subscriptions {
+            return Err(Limit::Subscriptions);
+        }
+    }
+    Ok(())
+}
+
+async fn call(
+    ctx: &Context,
+    connection: &HttpConnection,
+    register: &mut Register,
+    token: &str,
+    request: JsonRpcRequest,
+    events: bool,
+) -> Result<Value, JsonRpcError> {
+    if request.jsonrpc != "2.0" {
+        return Err(JsonRpcError::new(
+            INVALID_REQUEST,
+            "jsonrpc must be \"2.0\"",
+        ));
+    }
+    let request = RawRequest(to_request(&request.method, request.params)?);
+    if register.is_subscribe(&request) != events {
+        let message = if events {
+            "Only subscriptions are supported by /events"
+        } else {
+            "Subscriptions are only supported by /events"
+        };
+        return Err(JsonRpcError::new(INVALID_REQUEST, message));
+    }
+    if let Err(limit) = check_limits(ctx, connection, register, token, &request) {
+        let error = limits::exceeded(limit);
+        debug!("{}: token={}", error, token);
+        return Err(JsonRpcError::new(SERVER_ERROR, error));
+    }
+    let response = register
+        .try_process(token, request)
+        .await
+        .map_err(|e| JsonRpcError::new(SERVER_ERROR, e))?;
+    if let Some(error) = response_error(&response.0) {
+        return Err(JsonRpcError::new(SERVER_ERROR, error));
+    }
+    serde_json::to_value(&response.0).map_err(|e| JsonRpcError::new(INTERNAL_ERROR, e))
+}
+
+/// Returns the error of a failed request, which handlers return as a response.
+fn response_error(response: &ResponseKind) -> Option<&str> {
+    match response {
+        ResponseKind::NodeResponse(NodeResponse::Error { error })
+        | ResponseKind::NetworkResponse(NetworkResponse::Error { error })
+        | ResponseKind::WalletResponse(WalletResponse::WalletControlResponse(
+            WalletControlResponse::Error { error },
+        ))
+        | ResponseKind::WalletResponse(WalletResponse::AccountResponse {
+            response: AccountResponse::Error { error },
+            ..
+        }) => Some(error),
+        _ => None,
+    }
+}
+
+async fn process(
+    ctx: &Context,
+    connection: &HttpConnection,
+    register: &mut Register,
+    token: &str,
+    request: Value,
+    events: bool,
+) -> Option<JsonRpcResponse> {
+    let request: JsonRpcRequest = match serde_json::from_value(request) {
+        Ok(request) => request,
+        Err(e) => {
+            let error = JsonRpcError::new(INVALID_REQUEST, e);
+            return Some(JsonRpcResponse::new(Value::Null, Err(error)));
+        }
+    };
+    let id = request.id.clone();
+    let result = call(ctx, connection, register, token, request, events).await;
+    id.map(|id| JsonRpcResponse::new(id, result))
+}
+
+/// Returns notifications until the subscriptions end or the token is revoked.
+fn notifications(
+    register: Register,
+    subscriptions: Subscriptions,
+    tokens: ApiTokens,
+    token: String,
+) -> impl Stream<Item = RawResponse> {
+    stream::unfold(
+        (register, subscriptions),
+        move |(mut register, subscriptions)| {
+            let tokens = tokens.clone();
+            let token = token.clone();
+            async move {
+                loop {
+                    if !tokens.is_valid(&token) {
+                        debug!(
+                            "API token has been revoked, closing events: token={}",
+                            token
+                        );
+                        return None;
+                    }
+                    let mut check = tokio::time::delay_for(TOKEN_CHECK_INTERVAL).fuse();
+                    select! {
+                        notification = register.notifications.next() => {
+                            return notification
+                                .map(|notification| (notification, (register, subscriptions)));
+                        }
+                        _ = check => {}
+                    }
+                }
+            }
+        },
+    )
+}
+
+/// Makes a stream `Sync`, as required by `Body::wrap_stream`. It's only polled by one task.
+struct SyncStream<S>(Mutex<S>);
+
+impl<S: Stream + Unpin> Stream for SyncStream<S> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Option<Self::Item>> {
+        self.0.lock().unwrap().poll_next_unpin(cx)
+    }
+}
+
+/// Responds with the results of subscriptions, followed by notifications.
+fn event_stream(
+    notifications: impl Stream<Item = RawResponse> + Send + 'static,
+    batch: bool,
+    responses: Vec<JsonRpcResponse>,
+) -> hyper::Response<Body> {
+    let mut events = Vec::new();
+    if batch && !responses.is_empty() {
+        events.push(sse_event(&responses));
+    } else if let Some(response) = responses.first() {
+        events.push(sse_event(response));
+    }
+    let notifications = notifications.map(|notification| {
+        let notification = json!({
+            "jsonrpc": "2.0",
+            "method": "notification",
+            "params": notification.0,
+        });
+        sse_event(&notification)
+    });
+    let stream = stream::iter(events)
+        .chain(notifications)
+        .map(Ok::<_, Infallible>);
+    let stream = SyncStream(Mutex::new(Box::pin(stream)));
+    hyper::Response::builder()
+        .header(CONTENT_TYPE, "text/event-stream")
+        .header(CACHE_CONTROL, "no-cache")
+        .body(Body::wrap_stream(stream))
+        .expect("valid response")
+}
+
+async fn handle_rpc(
+    ctx: Arc<Context>,
+    connection: Arc<HttpConnection>,
+    peer: SocketAddr,
+    req: hyper::Request<Body>,
+    events: bool,
+) -> hyper::Response<Body> {
+    let token = match bearer_token(&req).and_then(|token| ctx.tokens.authenticate_bearer(token)) {
+        Some(token) => token,
+        None => {
+            debug!("[{}] Unauthorized HTTP request", peer);
+            return status(StatusCode::UNAUTHORIZED);
+        }
+    };
+    let max_body_size = ctx.limiter.limits.max_message_size;
+
+    let mut chunks = req.into_body();
+    let mut body = Vec::new();
+    while let Some(chunk) = chunks.next().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                debug!("[{}] Failed to read HTTP request: {}", peer, e);
+                return status(StatusCode::BAD_REQUEST);
+            }
+        };
+        if max_body_size > 0 && body.len() + chunk.len() > max_body_size {
+            limits::exceeded(Limit::MessageSize);
+            return status(StatusCode::PAYLOAD_TOO_LARGE);
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    let (batch, requests) = match serde_json::from_slice(&body) {
+        Ok(Value::Array(requests)) => (true, requests),
+        Ok(request) => (false, vec![request]),
+        Err(e) => {
+            let error = JsonRpcError::new(PARSE_ERROR, e);
+            return json_response(&JsonRpcResponse::new(Value::Null, Err(error)));
+        }
+    };
+    if requests.is_empty() {
+        let error = JsonRpcError::new(INVALID_REQUEST, "Empty batch");
+        return json_response(&JsonRpcResponse::new(Value::Null, Err(error)));
+    }
+
+    // Every request of a batch is charged to the limits.
+    let mut register = ctx.register(peer.to_string());
+    let mut responses = Vec::new();
+    for request in requests {
+        let response = process(&ctx, &connection, &mut register, &token, request, events).await;
+        if let Some(response) = response {
+            responses.push(response);
+        }
+    }
+    if events {
+        let count = register.notifications.len();
+        connection.subscriptions.fetch_add(count, Ordering::SeqCst);
+        let subscriptions = Subscriptions { connection, count };
+        let notifications = notifications(register, subscriptions, ctx.tokens.clone(), token);
+        event_stream(notifications, batch, responses)
+    } else if responses.is_empty() {
+        status(StatusCode::NO_CONTENT)
+    } else if batch {
+        json_response(&responses)
+    } else {
+        json_response(&responses[0])
+    }
+}
+
+async fn handle(
+    ctx: Arc<Context>,
+    connection: Arc<HttpConnection>,
+    peer: SocketAddr,
+    req: hyper::Request<Body>,
+) -> Result<hyper::Response<Body>, Infallible> {
+    trace!("[{}] {} {}", peer, req.method(), req.uri());
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+    let response = match (method, path.as_str()) {
+        (Method::POST, "/") => handle_rpc(ctx, connection, peer, req, false).await,
+        (Method::POST, "/events") => handle_rpc(ctx, connection, peer, req, true).await,
+        (_, "/") | (_, "/events") => status(StatusCode::METHOD_NOT_ALLOWED),
+        _ => status(StatusCode::NOT_FOUND),
+    };
+    Ok(response)
+}
+
+/// Starts JSON-RPC server, restricted by `limiter` shared with other API servers.
+#[allow(clippy::too_many_arguments)]
+pub async fn spawn_jsonrpc_server(
+    endpoint: String,
+    tls: Option<ServerTlsConfig>,
+    tokens: ApiTokens,
+    apis: Vec<Box<dyn ApiHandler>>,
+    network: Option<Network>,
+    version: String,
+    chain_name: String,
+    limiter: Arc<Limiter>,
+) -> Result<JoinHandle<()>, Error> {
+    let addr: SocketAddr = endpoint.parse()?;
+    let acceptor = match tls {
+        Some(tls) => {
+            info!(target: "stegos_api", "Starting JSON-RPC Server on https://{}", &addr);
+            Some(reloadable_tls_acceptor(tls)?)
+        }
+        None if addr.ip().is_loopback() => {
+            info!(target: "stegos_api", "Starting JSON-RPC Server on http://{}", &addr);
+            None
+        }
+        None => bail!(
+            "JSON-RPC Server requires TLS on non-loopback address {}, configure api_tls",
+            addr
+        ),
+    };
+    let ctx = Arc::new(Context {
+        tokens,
+        apis,
+        network,
+        version,
+        chain_name,
+        journal: None,
+        limiter,
+    });
+    let listener = TcpListener::bind(&addr).await?;
+    Ok(serve(listener, acceptor, ctx))
+}
+
+/// Serves JSON-RPC requests of connections accepted by `listener`.
+fn serve(
+    mut listener: TcpListener,
+    acceptor: Option<Arc<RwLock<TlsAcceptor>>>,
+    ctx: Arc<Context>,
+) -> JoinHandle<()> {
+    let http = Http::new();
+    tokio::spawn(async move {
+        while let Ok((stream, peer)) = listener.accept().await {
+            let ctx = ctx.clone();
+            let acceptor = match &acceptor {
+                Some(acceptor) => Some(acceptor.read().unwrap().clone()),
+                None => None,
+            };
+            let http = http.clone();
+            let limiter = ctx.limiter.clone();
+            let connection = match open_connection(&limiter, &peer.to_string()) {
+                Some(connection) => connection,
+                None => continue,
+            };
+            let rate = RateLimiter::new(
+                limiter.limits.connection_requests_per_second,
+                Instant::now(),
+            );
+            let connection = Arc::new(HttpConnection {
+                _connection: connection,
+                rate: Mutex::new(rate),
+                subscriptions: AtomicUsize::new(0),
+            });
+            tokio::spawn(async move {
+                let service =
+                    service_fn(move |req| handle(ctx.clone(), connection.clone(), peer, req));
+                let result = match acceptor {
+                    Some(acceptor) => match limiter.handshake(acceptor.accept(stream)).await {
+                        Some(Ok(stream)) => http.serve_connection(stream, service).await,
+                        Some(Err(e)) => {
+                            debug!("[{}] TLS handshake failed: {}", peer, e);
+                            return;
+                        }
+                        None => {
+                            debug!("[{}] TLS handshake timed out", peer);
+                            return;
+                        }
+                    },
+                    None => http.serve_connection(stream, service).await,
+                };
+                if let Err(e) = result {
+                    debug!("[{}] HTTP connection failed: {}", peer, e);
+                }
+            });
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::ApiToken;
+    use crate::network_api::NetworkRequest;
+    use crate::server::limits::ApiLimits;
+    use crate::{AccountNotification, ChainNotification, WalletControlRequest, WalletNotification};
+    use async_trait::async_trait;
+    use futures::channel::mpsc;
+    use hyper::Client;
+    use stegos_blockchain::api::StatusInfo;
+    use stegos_blockchain::{MicroBlock, Timestamp};
+    use stegos_crypto::hash::Hash;
+    use stegos_crypto::pbc;
+    use stegos_node::NodeRequest;
+    use stegos_wallet::api::{AccountRequest, WalletRequest};
+
+    #[derive(Clone)]
+    struct TestNode;
+
+    #[async_trait]
+    impl ApiHandler for TestNode {
+        async fn process_request(&self, req: RawRequest) -> Result<RawResponse, Error> {
+            match req.0.kind {
+                RequestKind::NodeRequest(NodeRequest::StatusInfo {}) => {
+                    let status = StatusInfo {
+                        epoch: 1,
+                        ..Default::default()
+                    };
+                    Ok(NodeResponse::StatusInfo(status).into())
+                }
+                RequestKind::NodeRequest(NodeRequest::PopMicroBlock {}) => {
+                    let error = "Not a leader".to_string();
+                    Ok(NodeResponse::Error { error }.into())
+                }
+                RequestKind::NodeRequest(NodeRequest::SubscribeChain { .. }) => {
+                    let (skey, pkey) = pbc::make_random_keys();
+                    let random = pbc::make_VRF(&skey, &Hash::digest("random"));
+                    let block = MicroBlock::empty(
+                        Hash::zero(),
+                        1,
+                        0,
+                        0,
+                        None,
+                        pkey,
+                        random,
+                        Vec::new(),
+                        Timestamp::now(),
+                    );
+                    // The stream ends after the notification.
+                    let (mut tx, rx) = mpsc::channel(1);
+                    tx.try_send(ChainNotification::MicroBlockPrepared(block))
+                        .unwrap();
+                    let response = NodeResponse::SubscribedChain {
+                        current_epoch: 1,
+                        current_offset: 0,
+                        rx: Some(rx),
+                    };
+                    Ok(response.into())
+                }
+                kind => bail!("Unsupported request: {:?}", kind),
+            }
+        }
+
+        fn cloned(&self) -> Box<dyn ApiHandler> {
+            Box::new(self.clone())
+        }
+    }
+
+    #[derive(Clone)]
+    struct TestWallet;
+
+    #[async_trait]
+    impl ApiHandler for TestWallet {
+        async fn process_request(&self, req: RawRequest) -> Result<RawResponse, Error> {
+            match req.0.kind {
+                RequestKind::WalletsRequest(WalletRequest::WalletControlRequest(
+                    WalletControlRequest::SubscribeWalletUpdates {},
+                )) => {
+                    // The stream ends after the notification.
+                    let (tx, rx) = mpsc::unbounded();
+                    let notification = WalletNotification {
+                        account_id: "1".to_string(),
+                        notification: AccountNotification::Sealed,
+                    };
+                    tx.unbounded_send(notification).unwrap();
+                    let response = WalletControlResponse::SubscribedWalletUpdates { rx: Some(rx) };
+                    Ok(WalletResponse::WalletControlResponse(response).into())
+                }
+                RequestKind::WalletsRequest(WalletRequest::AccountRequest {
+                    account_id, ..
+                }) => {
+                    let error = "Account is sealed".to_string();
+                    let response = WalletResponse::AccountResponse {
+                        account_id,
+                        response: AccountResponse::Error { error },
+                    };
+                    Ok(response.into())
+                }
+                kind => bail!("Unsupported request: {:?}", kind),
+            }
+        }
+
+        fn cloned(&self) -> Box<dyn ApiHandler> {
+            Box::new(self.clone())
+        }
+    }
+
+    /// Starts the server on a loopback port, returns its URL.
+    async fn start(api_token: ApiToken) -> String {
+        let ctx = Arc::new(Context {
+            tokens: api_token.into(),
+            apis: vec![Box::new(TestNode), Box::new(TestWallet)],
+            network: None,
+            version: "test".to_string(),
+            chain_name: "dev".to_string(),
+            journal: None,
+            limiter: Arc::new(Limiter::new(ApiLimits::default())),
+        });
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        serve(listener, None, ctx);
+        url
+    }
+
+    /// Posts the body, returns the status and the body of the response.
+    async fn post(url: &str, token: Option<&str>, body: Value) -> (StatusCode, String) {
+        let mut request = hyper::Request::post(url);
+        if let Some(token) = token {
+            request = request.header(AUTHORIZATION, format!("Bearer {}", token));
+        }
+        let request = request.body(Body::from(body.to_string())).unwrap();
+        let response = Client::new().request(request).await.unwrap();
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    /// Posts the body, returns the JSON response.
+    async fn call(url: &str, token: &str, body: Value) -> Value {
+        let (status, body) = post(url, Some(token), body).await;
+        assert_eq!(status, StatusCode::OK);
+        serde_json::from_str(&body).unwrap()
+    }
+
+    /// Posts the body to `/events`, returns all server-sent events until the stream is closed.
+    async fn post_events(url: &str, token: &str, body: Value) -> Vec<Value> {
+        let (status, body) = post(&format!("{}/events", url), Some(token), body).await;
+        assert_eq!(status, StatusCode::OK);
+        body.split_terminator("\n\n")
+            .map(|event| serde_json::from_str(&event["data: ".len()..]).unwrap())
+            .collect()
+    }
+
+    fn request(method: &str, params: Value, id: u64) -> Value {
+        json!({"jsonrpc": "2.0", "method": method, "params": params, "id": id})
+    }
+
+    #[tokio::test]
+    async fn unauthorized() {
+        let api_token = ApiToken::new();
+        let url = start(api_token).await;
+        let body = request("status_info", json!({}), 1);
+        let (status, _) = post(&url, None, body.clone()).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let other_token = ApiToken::new().to_base64();
+        let (status, _) = post(&url, Some(&other_token), body.clone()).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let response = call(&url, &api_token.to_base64(), body).await;
+        assert_eq!(response["result"]["epoch"], 1);
+    }
+
+    #[tokio::test]
+    async fn batches() {
+        let api_token = ApiToken::new();
+        let token = api_token.to_base64();
+        let url = start(api_token).await;
+
+        // Notifications have no responses.
+        let notification = json!({"jsonrpc": "2.0", "method": "status_info"});
+        let batch = json!([
+            request("status_info", json!({}), 1),
+            notification.clone(),
+            request("pop_micro_block", json!({}), 2),
+            request("balance_info", json!({"account_id": "1"}), 3),
+        ]);
+        let responses = call(&url, &token, batch).await;
+        let responses = responses.as_array().unwrap();
+        assert_eq!(responses.len(), 3);
+        assert_eq!(responses[0]["id"], 1);
+        assert_eq!(responses[0]["result"]["type"], "status_info");
+        // Errors of handlers are JSON-RPC errors.
+        assert_eq!(responses[1]["id"], 2);
+        assert_eq!(
+            responses[1]["error"],
+            json!({"code": SERVER_ERROR, "message": "Not a leader"})
+        );
+        assert!(responses[1].get("result").is_none());
+        assert_eq!(responses[2]["id"], 3);
+        assert_eq!(
+            responses[2]["error"],
+            json!({"code": SERVER_ERROR, "message": "Account is sealed"})
+        );
+
+        let (status, body) = post(&url, Some(&token), json!([notification])).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert!(body.is_empty());
+
+        let response = call(&url, &token, json!([])).await;
+        assert_eq!(response["id"], Value::Null);
+        assert_eq!(response["error"]["code"], INVALID_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn subscriptions() {
+        let api_token = ApiToken::new();
+        let token = api_token.to_base64();
+        let url = start(api_token).await;
+
+        // Subscriptions are only accepted by /events, other requests only by /.
+        let subscribe = request("subscribe_chain", json!({"epoch": 1, "offset": 0}), 1);
+        let response = call(&url, &token, subscribe.clone()).await;
+        assert_eq!(response["error"]["code"], INVALID_REQUEST);
+        let events = post_events(&url, &token, request("status_info", json!({}), 2)).await;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0]["id"], 2);
+        assert_eq!(events[0]["error"]["code"], INVALID_REQUEST);
+
+        let batch = json!([subscribe, request("subscribe_wallet_updates", json!({}), 3)]);
+        let mut events = post_events(&url, &token, batch).await;
+        assert_eq!(events.len(), 3);
+        let responses = events.remove(0);
+        assert_eq!(responses[0]["id"], 1);
+        assert_eq!(responses[0]["result"]["type"], "subscribed_chain");
+        assert_eq!(responses[1]["id"], 3);
+        assert_eq!(responses[1]["result"]["type"], "subscribed_wallet_updates");
+        for event in &events {
+            assert_eq!(event["method"], "notification");
+        }
+        let mut types: Vec<_> = events
+            .iter()
+            .map(|event| event["params"]["type"].as_str().unwrap())
+            .collect();
+        types.sort();
+        assert_eq!(types, vec!["micro_block_prepared", "sealed"]);
+        let wallet = events
+            .iter()
+            .find(|event| event["params"]["type"] == "sealed")
+            .unwrap();
+        assert_eq!(wallet["params"]["account_id"], "1");
+    }
+
+    #[test]
+    fn requests() {
+        match to_request("status_info", Value::Null).unwrap().kind {
+            RequestKind::NodeRequest(NodeRequest::StatusInfo {}) => {}
+            kind => panic!("{:?}", kind),
+        }
+        match to_request("chain_name", json!({})).unwrap().kind {
+            RequestKind::NetworkRequest(NetworkRequest::ChainName {}) => {}
+            kind => panic!("{:?}", kind),
+        }
+        let params = json!({"account_id": "1"});
+        match to_request("balance_info", params).unwrap().kind {
+            RequestKind::WalletsRequest(WalletRequest::AccountRequest {
+                account_id,
+                request: AccountRequest::BalanceInfo {},
+            }) => assert_eq!(account_id, "1"),
+            kind => panic!("{:?}", kind),
+        }
+        let error = to_request("status_info", json!([1])).unwrap_err();
+        assert_eq!(error.code, INVALID_PARAMS);
+
+        let response = JsonRpcResponse::new(json!(1), Err(JsonRpcError::new(SERVER_ERROR, "x")));
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            json!({"jsonrpc": "2.0", "error": {"code": -32000, "message": "x"}, "id": 1})
+        );
+    }
+}
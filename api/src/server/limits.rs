@@ -0,0 +1,220 @@
+//! WebSocket API - Limits.
+
+//
+// Copyright (c) 2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::metrics;
+use crate::Limit;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Limits of API servers, zero means unlimited.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApiLimits {
+    /// The maximal number of concurrent connections of all API servers.
+    pub max_connections: usize,
+    /// The maximal size of a WebSocket message or a JSON-RPC request, in bytes.
+    pub max_message_size: usize,
+    /// The maximal number of requests per second of a connection, counting batch elements.
+    pub connection_requests_per_second: u32,
+    /// The maximal number of requests per second of all connections using the same token.
+    pub token_requests_per_second: u32,
+    /// The maximal number of active subscriptions of a connection.
+    pub max_subscriptions: usize,
+    /// The maximal duration of TLS and WebSocket handshakes, in seconds.
+    pub handshake_timeout: u64,
+}
+
+impl Default for ApiLimits {
+    fn default() -> Self {
+        ApiLimits {
+            max_connections: 256,
+            max_message_size: 1024 * 1024,
+            connection_requests_per_second: 100,
+            token_requests_per_second: 500,
+            max_subscriptions: 16,
+            handshake_timeout: 10,
+        }
+    }
+}
+
+/// Token bucket, which allows bursts of up to `rate` requests.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    rate: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(rate: u32, now: Instant) -> Self {
+        RateLimiter {
+            rate: rate as f64,
+            tokens: rate as f64,
+            updated: now,
+        }
+    }
+
+    /// Takes a token from the bucket, returns false if the bucket is empty.
+    pub(crate) fn check(&mut self, now: Instant) -> bool {
+        if self.rate == 0.0 {
+            return true;
+        }
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+        self.updated = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns true if the bucket has been refilled, i.e. it is the same as a new one.
+    #[cfg(feature = "grpc")]
+    pub(crate) fn is_full(&self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens + elapsed * self.rate >= self.rate
+    }
+}
+
+/// Limits shared by all connections of API servers.
+pub struct Limiter {
+    pub(crate) limits: ApiLimits,
+    connections: AtomicUsize,
+    tokens: Mutex<HashMap<String, RateLimiter>>,
+}
+
+impl Limiter {
+    pub fn new(limits: ApiLimits) -> Self {
+        Limiter {
+            limits,
+            connections: AtomicUsize::new(0),
+            tokens: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Checks the rate of requests of all connections using the token.
+    pub(crate) fn check_token(&self, token: &str, now: Instant) -> bool {
+        let rate = self.limits.token_requests_per_second;
+        let mut tokens = self.tokens.lock().unwrap();
+        tokens
+            .entry(token.to_string())
+            .or_insert_with(|| RateLimiter::new(rate, now))
+            .check(now)
+    }
+
+    /// Runs a handshake, returns None if it takes longer than `handshake_timeout`.
+    pub(crate) async fn handshake<F: Future>(&self, handshake: F) -> Option<F::Output> {
+        match self.limits.handshake_timeout {
+            0 => Some(handshake.await),
+            timeout => tokio::time::timeout(Duration::from_secs(timeout), handshake)
+                .await
+                .ok(),
+        }
+    }
+}
+
+/// Counts a connection while alive.
+pub(crate) struct Connection {
+    limiter: Arc<Limiter>,
+}
+
+impl Connection {
+    /// Registers a new connection, returns None if there are too many connections.
+    pub(crate) fn open(limiter: Arc<Limiter>) -> Option<Connection> {
+        let connections = limiter.connections.fetch_add(1, Ordering::SeqCst) + 1;
+        let max_connections = limiter.limits.max_connections;
+        if max_connections > 0 && connections > max_connections {
+            limiter.connections.fetch_sub(1, Ordering::SeqCst);
+            return None;
+        }
+        metrics::API_CONNECTIONS.inc();
+        Some(Connection { limiter })
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.limiter.connections.fetch_sub(1, Ordering::SeqCst);
+        metrics::API_CONNECTIONS.dec();
+    }
+}
+
+/// Counts a violation of the limit and returns the error for the client.
+pub(crate) fn exceeded(limit: Limit) -> String {
+    metrics::API_LIMIT_EXCEEDED
+        .with_label_values(&[limit.as_str()])
+        .inc();
+    match limit {
+        Limit::Connections => "Too many connections".to_string(),
+        Limit::MessageSize => "Message is too big".to_string(),
+        Limit::ConnectionRate => "Too many requests on the connection".to_string(),
+        Limit::TokenRate => "Too many requests with the API token".to_string(),
+        Limit::Subscriptions => "Too many subscriptions on the connection".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn rate_limiter() {
+        let now = Instant::now();
+        let mut limiter = RateLimiter::new(2, now);
+        assert!(limiter.check(now));
+        assert!(limiter.check(now));
+        assert!(!limiter.check(now));
+        let now = now + Duration::from_millis(500);
+        assert!(limiter.check(now));
+        assert!(!limiter.check(now));
+        // Unused tokens don't accumulate beyond the rate.
+        let now = now + Duration::from_secs(10);
+        assert!(limiter.check(now));
+        assert!(limiter.check(now));
+        assert!(!limiter.check(now));
+
+        let mut unlimited = RateLimiter::new(0, now);
+        for _ in 0..1000 {
+            assert!(unlimited.check(now));
+        }
+    }
+
+    #[test]
+    fn connections() {
+        let limiter = Arc::new(Limiter::new(ApiLimits {
+            max_connections: 1,
+            ..Default::default()
+        }));
+        let connection = Connection::open(limiter.clone()).unwrap();
+        assert!(Connection::open(limiter.clone()).is_none());
+        drop(connection);
+        assert!(Connection::open(limiter).is_some());
+    }
+}
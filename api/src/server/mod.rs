@@ -23,22 +23,38 @@
 
 use crate::crypto::ApiToken;
 use crate::network_api::NetworkApi;
-use crate::{decode, encode, InnerResponses, Request, Response, ResponseKind};
+use crate::tls::{tls_acceptor, ServerTlsConfig};
+use crate::tokens::ApiTokens;
+use crate::{
+    decode, encode, metrics, unix_socket_path, InnerResponses, Limit, Request, RequestKind,
+    Response, ResponseKind,
+};
 use failure::{bail, Error};
 use futures::prelude::*;
 use futures::select;
+use futures::stream::SelectAll;
 use futures::SinkExt;
 use log::*;
 use stegos_network::Network;
+use stegos_node::{NodeRequest, NodeResponse};
+use stegos_wallet::api::{WalletControlRequest, WalletRequest};
 
 use std::pin::Pin;
 
 use api::clone_apis;
+use std::fs;
 use std::net::SocketAddr;
+use std::path::Path;
 
-use tokio::net::{TcpListener, TcpStream};
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
 use tokio_tungstenite::WebSocketStream;
-use tungstenite::protocol::Message;
+use tungstenite::protocol::frame::coding::CloseCode;
+use tungstenite::protocol::{CloseFrame, Message, WebSocketConfig};
+use tungstenite::Error as WsError;
 
 use tokio::task::JoinHandle;
 /// The number of values to fit in the output buffer.
@@ -47,72 +63,287 @@ const OUTPUT_BUFFER_SIZE: usize = 100;
 // const CONSOLE_TOPIC: &'static str = "console";
 
 /// A type definition for stream.
-type WsStream = sink::Buffer<WebSocketStream<TcpStream>, Message>;
+type WsStream<S> = sink::Buffer<WebSocketStream<S>, Message>;
+/// Notifications of a journaled subscription with their sequence ids.
+type JournalStream = Box<dyn Stream<Item = (Option<u64>, ResponseKind)> + Unpin + Send>;
 
 pub mod api;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod journal;
+pub mod jsonrpc;
+pub mod limits;
 pub mod register;
+pub mod webhooks;
 use api::*;
+use journal::{is_chain_history, Journal, JournalGap, JournalKind};
+use limits::{Connection, Limiter, RateLimiter};
 use register::Register;
 
-/// Handler of incoming connections.
-struct WebSocketHandler {
-    /// Remote address.
-    peer: SocketAddr,
-    /// API Token.
-    api_token: ApiToken,
-    /// Incoming stream.
-    connection: WsStream,
-
-    register: Register,
+/// Shared state of API servers.
+struct Context {
+    tokens: ApiTokens,
+    apis: Vec<Box<dyn ApiHandler>>,
+    network: Option<Network>,
+    version: String,
+    chain_name: String,
+    journal: Option<Journal>,
+    limiter: Arc<Limiter>,
 }
 
-impl WebSocketHandler {
-    fn new(
-        peer: SocketAddr,
-        api_token: ApiToken,
-        connection: WsStream,
-        apis: Vec<Box<dyn ApiHandler>>,
-        network: Option<Network>,
-        version: String,
-        chain_name: String,
-    ) -> Self {
-        let mut register = Register::new();
-        if let Some(network) = network {
-            let network_api = NetworkApi::new(network, version, chain_name);
+impl Context {
+    /// Creates a register with all APIs available to a client.
+    fn register(&self, peer: String) -> Register {
+        let mut register = Register::new(peer, self.tokens.clone());
+        if let Some(network) = &self.network {
+            let network_api = NetworkApi::new(
+                network.clone(),
+                self.version.clone(),
+                self.chain_name.clone(),
+            );
             register.add_api(Box::new(network_api));
         }
-        for api in apis {
+        for api in clone_apis(&self.apis) {
             register.add_api(api);
         }
+        register
+    }
+}
+
+/// Handler of incoming connections.
+struct WebSocketHandler<S> {
+    /// Remote address, or credentials of Unix domain socket peers.
+    peer: String,
+    ctx: Arc<Context>,
+    /// The name and the value of the token used by the client, set by the first message.
+    api_token: Option<(String, ApiToken)>,
+    /// Incoming stream.
+    connection: WsStream<S>,
+
+    register: Register,
+    /// Notifications of subscriptions resumed from the journal.
+    journal_notifications: SelectAll<JournalStream>,
+    /// Rate of requests on this connection.
+    rate: RateLimiter,
+}
 
+impl<S> WebSocketHandler<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send,
+{
+    fn new(peer: String, ctx: Arc<Context>, connection: WsStream<S>) -> Self {
+        let register = ctx.register(peer.clone());
+        let rate = RateLimiter::new(
+            ctx.limiter.limits.connection_requests_per_second,
+            Instant::now(),
+        );
         WebSocketHandler {
             peer,
-            api_token,
+            ctx,
+            api_token: None,
             connection,
             register,
+            journal_notifications: SelectAll::new(),
+            rate,
+        }
+    }
+
+    /// Checks request rates and the number of subscriptions.
+    fn check_limits(&mut self, token: &str, req: &RawRequest) -> Result<(), Limit> {
+        let now = Instant::now();
+        if !self.rate.check(now) {
+            return Err(Limit::ConnectionRate);
+        }
+        if !self.ctx.limiter.check_token(token, now) {
+            return Err(Limit::TokenRate);
+        }
+        let max_subscriptions = self.ctx.limiter.limits.max_subscriptions;
+        if max_subscriptions > 0 && self.register.is_subscribe(req) {
+            let subscriptions =
+                self.register.notifications.len() + self.journal_notifications.len();
+            if subscriptions >= max_subscriptions {
+                return Err(Limit::Subscriptions);
+            }
         }
+        Ok(())
+    }
+
+    /// Processes a request, subscriptions with `since_seq` are resumed from the journal.
+    async fn process(&mut self, token: &str, req: Request) -> Response {
+        let id = req.id;
+        let since_seq = req.since_seq;
+        let journal = match (&self.ctx.journal, JournalKind::of_request(&req.kind)) {
+            (Some(journal), Some(kind)) => Some((journal.clone(), kind)),
+            _ => None,
+        };
+        let req = RawRequest(req);
+        trace!("Request = {:?}", req);
+        metrics::API_REQUESTS.inc();
+        if let Err(limit) = self.check_limits(token, &req) {
+            let error = limits::exceeded(limit);
+            debug!("[{}] {}: token={}", self.peer, error, token);
+            let kind = ResponseKind::Inner(InnerResponses::LimitExceeded { limit, error });
+            return Response {
+                kind,
+                id,
+                seq: None,
+            };
+        }
+        let result = match &journal {
+            // Notifications are delivered from the journal.
+            Some(_) => self.register.try_process_detached(token, req).await,
+            None => self.register.try_process(token, req).await,
+        };
+        let (kind, seq) = match result {
+            Ok(mut response) => match journal {
+                Some((journal, kind)) => {
+                    match self.subscribe_journal(token, &journal, kind, since_seq, &mut response) {
+                        // Let clients know where to resume from.
+                        Ok(seq) => (response.0, seq),
+                        Err(gap) => {
+                            let kind = InnerResponses::NotificationsLost {
+                                subscription: kind,
+                                since_seq: gap.since_seq,
+                                first_seq: gap.first_seq,
+                            };
+                            (ResponseKind::Inner(kind), None)
+                        }
+                    }
+                }
+                None => (response.0, None),
+            },
+            Err(e) => {
+                let error = e.to_string();
+                (
+                    ResponseKind::Inner(InnerResponses::InternalError { error }),
+                    None,
+                )
+            }
+        };
+        Response { kind, id, seq }
     }
 
-    async fn send(sink: &mut WsStream, api_token: &ApiToken, msg: Response) -> Result<(), Error> {
+    /// Subscribes to journaled notifications, which are delivered with sequence ids.
+    /// A chain subscription without `since_seq` first receives blocks before the current
+    /// position from the node. Returns the last sequence id in the journal.
+    fn subscribe_journal(
+        &mut self,
+        token: &str,
+        journal: &Journal,
+        kind: JournalKind,
+        since_seq: Option<u64>,
+        response: &mut RawResponse,
+    ) -> Result<Option<u64>, JournalGap> {
+        // Failed requests have no notifications.
+        let history = match response.subscribe_to_stream() {
+            Ok(history) => history,
+            Err(_) => return Ok(None),
+        };
+        let mut position = match (&response.0, since_seq) {
+            (
+                ResponseKind::NodeResponse(NodeResponse::SubscribedChain {
+                    current_epoch,
+                    current_offset,
+                    ..
+                }),
+                None,
+            ) => Some((*current_epoch, *current_offset)),
+            _ => None,
+        };
+        let since_seq = since_seq.unwrap_or_else(|| journal.last_seq());
+        let (last_seq, rx) = journal.subscribe(kind, since_seq)?;
+        // Wallet notifications are limited to the accounts of the token.
+        let accounts = match kind {
+            JournalKind::Wallet => self.ctx.tokens.accounts(token),
+            JournalKind::Chain => None,
+        };
+        let journaled = rx.filter_map(move |item| {
+            let item = match item {
+                Ok((seq, notification)) => {
+                    if let Some(current) = position {
+                        // Skip blocks sent by the node, if the journal lags behind it.
+                        match serde_json::from_value(notification.clone()) {
+                            Ok(block) if is_chain_history(current, &block) => {
+                                return future::ready(None);
+                            }
+                            _ => position = None,
+                        }
+                    }
+                    if let Some(accounts) = &accounts {
+                        let account_id = notification
+                            .get("account_id")
+                            .and_then(serde_json::Value::as_str);
+                        if !account_id.map_or(false, |id| accounts.contains(id)) {
+                            return future::ready(None);
+                        }
+                    }
+                    (Some(seq), ResponseKind::Raw(notification))
+                }
+                Err(gap) => {
+                    let kind = InnerResponses::NotificationsLost {
+                        subscription: kind,
+                        since_seq: gap.since_seq,
+                        first_seq: gap.first_seq,
+                    };
+                    (None, ResponseKind::Inner(kind))
+                }
+            };
+            future::ready(Some(item))
+        });
+        let notifications: JournalStream = match position {
+            Some(current) => {
+                // Blocks after the current position are delivered from the journal.
+                let history = history
+                    .take_while(move |notification| {
+                        future::ready(match &notification.0 {
+                            ResponseKind::ChainNotification(block) => {
+                                is_chain_history(current, block)
+                            }
+                            _ => false,
+                        })
+                    })
+                    .map(|notification| (None, notification.0));
+                Box::new(history.chain(journaled))
+            }
+            None => Box::new(journaled),
+        };
+        self.journal_notifications.push(notifications);
+        Ok(Some(last_seq))
+    }
+
+    async fn send(
+        sink: &mut WsStream<S>,
+        api_token: &ApiToken,
+        msg: Response,
+    ) -> Result<(), Error> {
         let msg = encode(api_token, &msg);
         let msg = Message::Text(msg);
         Self::send_raw(sink, msg).await
     }
 
-    async fn send_raw(sink: &mut WsStream, msg: Message) -> Result<(), Error> {
+    async fn send_raw(sink: &mut WsStream<S>, msg: Message) -> Result<(), Error> {
         SinkExt::send(sink, msg).await.map_err(From::from)
     }
 
     async fn receive(
-        connection: &mut WsStream,
-        api_token: ApiToken,
-        peer: SocketAddr,
-    ) -> Result<Request, Error> {
+        connection: &mut WsStream<S>,
+        tokens: &ApiTokens,
+        api_token: Option<(String, ApiToken)>,
+        peer: &str,
+    ) -> Result<(String, ApiToken, Request), Error> {
         loop {
             let result = connection.next().await;
             match result {
                 Some(Ok(Message::Text(msg))) => {
-                    return decode(&api_token, &msg);
+                    let (name, api_token) = match api_token {
+                        Some(api_token) => api_token,
+                        None => match tokens.authenticate(&msg) {
+                            Some(api_token) => api_token,
+                            None => bail!("[{}] => Unknown API token", peer),
+                        },
+                    };
+                    let req = decode(&api_token, &msg)?;
+                    return Ok((name, api_token, req));
                 }
                 Some(Ok(Message::Ping(msg))) => {
                     trace!("[{}] => Ping(len={})", peer, msg.len());
@@ -127,6 +358,15 @@ impl WebSocketHandler {
                 Some(Ok(Message::Close(data))) => {
                     bail!("[{}] => Close(has_data={})", peer, data.is_some());
                 }
+                Some(Err(WsError::Capacity(e))) => {
+                    let reason = limits::exceeded(Limit::MessageSize).into();
+                    let frame = CloseFrame {
+                        code: CloseCode::Size,
+                        reason,
+                    };
+                    let _err = connection.send(Message::Close(Some(frame))).await;
+                    bail!("[{}] => Error({})", peer, e);
+                }
                 Some(Err(e)) => {
                     bail!("[{}] => Error({})", peer, e);
                 }
@@ -139,9 +379,10 @@ impl WebSocketHandler {
 
     async fn spawn(mut self) {
         loop {
-            let api_token = self.api_token;
-            let peer = self.peer;
-            let mut receive_orig = Self::receive(&mut self.connection, api_token, peer);
+            let api_token = self.api_token.clone();
+            let peer = self.peer.clone();
+            let mut receive_orig =
+                Self::receive(&mut self.connection, &self.ctx.tokens, api_token, &peer);
             let receive = unsafe { Pin::new_unchecked(&mut receive_orig) };
             let mut receive = receive.fuse();
             select! {
@@ -151,36 +392,49 @@ impl WebSocketHandler {
                     let notifiocation = if let Some(notification) = notification {
                         trace!("Forwarding notification = {:?}", notification);
                         let kind = notification.0;
-                        let response = Response { kind, id:0 };
-                        if let Err(e) = Self::send(&mut self.connection, &self.api_token, response).await {
+                        let response = Response { kind, id: 0, seq: None };
+                        // Notifications are only possible after a subscribe request.
+                        let (_, api_token) = self.api_token.as_ref().expect("authenticated");
+                        if let Err(e) = Self::send(&mut self.connection, api_token, response).await {
                             error!("Error during response send = {}", e);
                         }
                     } else {
                         trace!("Notifications stream ended.");
                     };
                 }
+                notification = self.journal_notifications.next() => {
+                    drop(receive);
+                    drop(receive_orig);
+                    if let Some((seq, kind)) = notification {
+                        trace!("Forwarding journaled notification: seq={:?}", seq);
+                        let response = Response { kind, id: 0, seq };
+                        let (_, api_token) = self.api_token.as_ref().expect("authenticated");
+                        if let Err(e) = Self::send(&mut self.connection, api_token, response).await {
+                            error!("Error during response send = {}", e);
+                        }
+                    }
+                }
                 req = receive => {
                     drop(receive);
                     drop(receive_orig);
-                    let req = match req {
+                    let (name, api_token, req) = match req {
                         Ok(res) => res,
                         Err(e) => {
                             trace!("{}", e);
                             return ();
                         }
                     };
-                    let id = req.id;
-                    let req = RawRequest(req);
-                    trace!("Request = {:?}", req);
+                    if self.api_token.is_none() {
+                        info!("[{}] Authenticated: token={}", peer, name);
+                        self.api_token = Some((name.clone(), api_token));
+                    }
+                    if !self.ctx.tokens.is_valid(&name) {
+                        info!("[{}] API token has been revoked: token={}", peer, name);
+                        return ();
+                    }
                     let block = async {
-                        let kind = match self.register.try_process("nothing", req).await {
-                            Ok(response) => response.0,
-                            Err(e) => {ResponseKind::Inner(InnerResponses::InternalError {
-                                error: e.to_string()
-                            })}
-                        };
-                        let response = Response { kind, id };
-                        Self::send(&mut self.connection, &self.api_token, response).await
+                        let response = self.process(&name, req).await;
+                        Self::send(&mut self.connection, &api_token, response).await
                     };
                     if let Err(e) = block.await {
                         warn!("Error during processing of request, error={}", e);
@@ -192,49 +446,338 @@ impl WebSocketHandler {
     }
 }
 
-pub async fn spawn_server(
-    endpoint: String,
-    api_token: ApiToken,
-    apis: Vec<Box<dyn ApiHandler>>,
-    network: Option<Network>,
-    version: String,
-    chain_name: String,
+/// Creates a TLS acceptor, which is replaced when certificates are reloaded on SIGHUP.
+pub(super) fn reloadable_tls_acceptor(
+    tls: ServerTlsConfig,
+) -> Result<Arc<RwLock<TlsAcceptor>>, Error> {
+    let acceptor = Arc::new(RwLock::new(tls_acceptor(&tls)?));
+    reload_on_sighup(tls, acceptor.clone())?;
+    Ok(acceptor)
+}
+
+/// Reloads TLS certificates on SIGHUP.
+#[cfg(unix)]
+fn reload_on_sighup(cfg: ServerTlsConfig, acceptor: Arc<RwLock<TlsAcceptor>>) -> Result<(), Error> {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut hangup = signal(SignalKind::hangup())?;
+    tokio::spawn(async move {
+        while let Some(()) = hangup.recv().await {
+            match tls_acceptor(&cfg) {
+                Ok(new_acceptor) => {
+                    *acceptor.write().unwrap() = new_acceptor;
+                    info!(target: "stegos_api", "Reloaded TLS certificates");
+                }
+                Err(e) => {
+                    error!(target: "stegos_api", "Failed to reload TLS certificates: {}", e);
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn reload_on_sighup(
+    _cfg: ServerTlsConfig,
+    _acceptor: Arc<RwLock<TlsAcceptor>>,
+) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Subscribes to chain notifications since the last block and to wallet notifications.
+/// Notifications of missing APIs, e.g. of the node on a light node, are skipped.
+async fn subscribe_events(register: &mut Register) {
+    // Requests of the server itself are not authorized.
+    let request = |kind| {
+        RawRequest(Request {
+            kind,
+            id: 0,
+            since_seq: None,
+        })
+    };
+
+    let status = request(RequestKind::NodeRequest(NodeRequest::StatusInfo {}));
+    match register.process(status, false).await {
+        Ok(RawResponse(ResponseKind::NodeResponse(NodeResponse::StatusInfo(status)))) => {
+            let subscribe_chain = request(RequestKind::NodeRequest(NodeRequest::SubscribeChain {
+                epoch: status.epoch,
+                offset: status.offset,
+            }));
+            if let Err(e) = register.process(subscribe_chain, true).await {
+                warn!(target: "stegos_api", "Failed to subscribe to chain notifications: {}", e);
+            }
+        }
+        Ok(response) => {
+            warn!(target: "stegos_api", "Failed to subscribe to chain notifications: response={:?}", response);
+        }
+        Err(e) => debug!(target: "stegos_api", "Chain notifications are not available: {}", e),
+    }
+    let subscribe_wallet = request(RequestKind::WalletsRequest(
+        WalletRequest::WalletControlRequest(WalletControlRequest::SubscribeWalletUpdates {}),
+    ));
+    if let Err(e) = register.process(subscribe_wallet, true).await {
+        debug!(target: "stegos_api", "Wallet notifications are not available: {}", e);
+    }
+}
+
+/// Feeds the journal with chain and wallet notifications.
+async fn feed_journal(ctx: Arc<Context>, journal: Journal) {
+    let mut register = ctx.register("journal".to_string());
+    subscribe_events(&mut register).await;
+
+    while let Some(RawResponse(notification)) = register.notifications.next().await {
+        let kind = match JournalKind::of_notification(&notification) {
+            Some(kind) => kind,
+            None => continue,
+        };
+        let result = serde_json::to_value(&notification)
+            .map_err(Error::from)
+            .and_then(|notification| journal.append(kind, notification));
+        if let Err(e) = result {
+            error!(target: "stegos_api", "Failed to write notifications journal: {}", e);
+        }
+    }
+}
+
+/// Opens a file which is only accessible by the owner, notifications can be private.
+fn open_private(options: &mut fs::OpenOptions, path: &Path) -> std::io::Result<fs::File> {
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(options, 0o600);
+    let file = options.open(path)?;
+    // The mode is only applied to new files.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(file)
+}
+
+/// Accepts TCP connections, wss:// if TLS is configured.
+async fn listen_tcp(
+    addr: SocketAddr,
+    tls: Option<ServerTlsConfig>,
+    ctx: Arc<Context>,
 ) -> Result<JoinHandle<()>, Error> {
-    let addr: SocketAddr = endpoint.parse()?;
-    info!(target: "stegos_api", "Starting API Server on {}", &addr);
+    let acceptor = match tls {
+        Some(tls) => {
+            info!(target: "stegos_api", "Starting API Server on wss://{}", &addr);
+            Some(reloadable_tls_acceptor(tls)?)
+        }
+        None => {
+            info!(target: "stegos_api", "Starting API Server on {}", &addr);
+            None
+        }
+    };
     let mut listener = TcpListener::bind(&addr).await?;
 
     Ok(tokio::spawn(async move {
         while let Ok((stream, addr)) = listener.accept().await {
-            tokio::spawn(handle_connection(
-                stream,
-                addr,
-                api_token.clone(),
-                clone_apis(&apis),
-                network.clone(),
-                version.clone(),
-                chain_name.clone(),
-            ));
+            let ctx = ctx.clone();
+            let acceptor = match &acceptor {
+                Some(acceptor) => Some(acceptor.read().unwrap().clone()),
+                None => None,
+            };
+            let peer = addr.to_string();
+            let connection = match open_connection(&ctx.limiter, &peer) {
+                Some(connection) => connection,
+                None => continue,
+            };
+            tokio::spawn(async move {
+                match acceptor {
+                    Some(acceptor) => match ctx.limiter.handshake(acceptor.accept(stream)).await {
+                        Some(Ok(stream)) => handle_connection(stream, peer, ctx, connection).await,
+                        Some(Err(e)) => debug!("[{}] TLS handshake failed: {}", peer, e),
+                        None => debug!("[{}] TLS handshake timed out", peer),
+                    },
+                    None => handle_connection(stream, peer, ctx, connection).await,
+                }
+            });
         }
     }))
 }
 
-async fn handle_connection(
-    raw_stream: TcpStream,
-    peer: SocketAddr,
-    api_token: ApiToken,
+/// Accepts connections on a Unix domain socket, available only to the owner.
+#[cfg(unix)]
+async fn listen_unix(path: &Path, ctx: Arc<Context>) -> Result<JoinHandle<()>, Error> {
+    use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+    use tokio::net::UnixListener;
+    info!(target: "stegos_api", "Starting API Server on unix:{}", path.display());
+    // Remove the socket left by the previous run.
+    if let Ok(metadata) = fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            bail!("Not a Unix domain socket: {:?}", path);
+        }
+        fs::remove_file(path)?;
+    }
+    let file_name = match path.file_name() {
+        Some(file_name) => file_name.to_string_lossy(),
+        None => bail!("Invalid socket path: {:?}", path),
+    };
+    // The socket is bound in a directory available only to the owner,
+    // so nobody can connect before the permissions are restricted.
+    let private_dir = path.with_file_name(format!(".{}.{}", file_name, std::process::id()));
+    if fs::symlink_metadata(&private_dir).is_ok() {
+        fs::remove_dir_all(&private_dir)?;
+    }
+    fs::DirBuilder::new().mode(0o700).create(&private_dir)?;
+    let private_path = private_dir.join(file_name.as_ref());
+    let bound = UnixListener::bind(&private_path).and_then(|listener| {
+        fs::set_permissions(&private_path, fs::Permissions::from_mode(0o600))?;
+        fs::rename(&private_path, path)?;
+        Ok(listener)
+    });
+    fs::remove_dir_all(&private_dir)?;
+    let mut listener = bound?;
+
+    Ok(tokio::spawn(async move {
+        while let Ok((stream, _addr)) = listener.accept().await {
+            let peer = match stream.peer_cred() {
+                Ok(cred) => format!("unix:uid={}", cred.uid),
+                Err(_) => "unix".to_string(),
+            };
+            if let Some(connection) = open_connection(&ctx.limiter, &peer) {
+                tokio::spawn(handle_connection(stream, peer, ctx.clone(), connection));
+            }
+        }
+    }))
+}
+
+#[cfg(not(unix))]
+async fn listen_unix(path: &Path, _ctx: Arc<Context>) -> Result<JoinHandle<()>, Error> {
+    bail!("Unix domain sockets are not supported: {:?}", path);
+}
+
+/// Starts WebSocket API server on `ip:port` and `unix:PATH` endpoints.
+/// TCP endpoints are wss:// if TLS is configured.
+/// Chain and wallet subscriptions can be resumed if the journal is set.
+/// Connections and requests are restricted by `limiter`, shared with other API servers.
+#[allow(clippy::too_many_arguments)]
+pub async fn spawn_server(
+    endpoints: Vec<String>,
+    tls: Option<ServerTlsConfig>,
+    tokens: ApiTokens,
     apis: Vec<Box<dyn ApiHandler>>,
     network: Option<Network>,
     version: String,
     chain_name: String,
-) {
-    let ws_stream = tokio_tungstenite::accept_async(raw_stream)
-        .await
-        .expect("Error during the websocket handshake occurred");
+    journal: Option<Journal>,
+    limiter: Arc<Limiter>,
+) -> Result<JoinHandle<()>, Error> {
+    let ctx = Arc::new(Context {
+        tokens,
+        apis,
+        network,
+        version,
+        chain_name,
+        journal,
+        limiter,
+    });
+    let mut listeners = Vec::new();
+    for endpoint in endpoints {
+        let listener = match unix_socket_path(&endpoint) {
+            Some(path) => listen_unix(path, ctx.clone()).await?,
+            None => listen_tcp(endpoint.parse()?, tls.clone(), ctx.clone()).await?,
+        };
+        listeners.push(listener);
+    }
+    if let Some(journal) = &ctx.journal {
+        tokio::spawn(feed_journal(ctx.clone(), journal.clone()));
+    }
+
+    Ok(tokio::spawn(async move {
+        future::join_all(listeners).await;
+    }))
+}
+
+/// Takes a connection slot before handshakes, the peer is disconnected if there are too many
+/// connections.
+pub(super) fn open_connection(limiter: &Arc<Limiter>, peer: &str) -> Option<Connection> {
+    let connection = Connection::open(limiter.clone());
+    if connection.is_none() {
+        let error = limits::exceeded(Limit::Connections);
+        warn!("[{}] Rejected: {}", peer, error);
+    }
+    connection
+}
+
+async fn handle_connection<S>(
+    raw_stream: S,
+    peer: String,
+    ctx: Arc<Context>,
+    _connection: Connection,
+) where
+    S: AsyncRead + AsyncWrite + Unpin + Send,
+{
+    let max_size = match ctx.limiter.limits.max_message_size {
+        0 => None,
+        max_size => Some(max_size),
+    };
+    let config = WebSocketConfig {
+        max_send_queue: None,
+        max_message_size: max_size,
+        max_frame_size: max_size,
+    };
+    let handshake = tokio_tungstenite::accept_async_with_config(raw_stream, Some(config));
+    let ws_stream = match ctx.limiter.handshake(handshake).await {
+        Some(Ok(ws_stream)) => ws_stream,
+        Some(Err(e)) => {
+            debug!("[{}] WebSocket handshake failed: {}", peer, e);
+            return;
+        }
+        None => {
+            debug!("[{}] WebSocket handshake timed out", peer);
+            return;
+        }
+    };
     debug!("[{}] Accepted", peer);
     let stream = ws_stream.buffer(OUTPUT_BUFFER_SIZE);
     info!("[{}] Connected", peer);
-    WebSocketHandler::new(peer, api_token, stream, apis, network, version, chain_name)
-        .spawn()
+    WebSocketHandler::new(peer, ctx, stream).spawn().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::ApiToken;
+    use crate::network_api::NetworkRequest;
+    use crate::WebSocketClient;
+    use limits::ApiLimits;
+    use tempdir::TempDir;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn unix_socket() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = TempDir::new("unix_socket").unwrap();
+        let path = dir.path().join("api.sock");
+        let endpoint = format!("unix:{}", path.display());
+        let api_token = ApiToken::new();
+        spawn_server(
+            vec![endpoint.clone()],
+            None,
+            api_token.into(),
+            Vec::new(),
+            None,
+            "test".to_string(),
+            "dev".to_string(),
+            None,
+            Arc::new(Limiter::new(ApiLimits::default())),
+        )
         .await
+        .unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        // The private directory has been removed.
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        let mut client = WebSocketClient::new(endpoint, api_token).await.unwrap();
+        let request = Request {
+            kind: RequestKind::NetworkRequest(NetworkRequest::VersionInfo {}),
+            id: 1,
+            since_seq: None,
+        };
+        let response = client.request(request).await.unwrap();
+        assert_eq!(response.id, 1);
+    }
 }
@@ -20,22 +20,40 @@
 // LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
-use super::api::{ApiHandler, RawRequest, RawResponse};
+use super::api::{ApiHandler, RawRequest, RawResponse, Subscribtion};
+use crate::tokens::ApiTokens;
+use crate::ResponseKind;
 use failure::{bail, Error};
-use futures::stream::SelectAll;
-use futures::Stream;
+use futures::future;
+use futures::stream::{SelectAll, StreamExt};
 use log::{debug, trace};
 use std::collections::HashSet;
+use stegos_wallet::api::AccountId;
+
+/// Returns true if the notification is about one of the accounts, or isn't about an account.
+pub(super) fn allowed_notification(accounts: &HashSet<AccountId>, kind: &ResponseKind) -> bool {
+    match kind {
+        ResponseKind::WalletNotification(notification) => {
+            accounts.contains(&notification.account_id)
+        }
+        _ => true,
+    }
+}
 
 pub struct Register {
+    /// Remote address, used for the audit log.
+    peer: String,
+    tokens: ApiTokens,
     methods: Vec<Box<dyn ApiHandler>>,
     registred_notifications: HashSet<String>,
-    pub notifications: SelectAll<Box<dyn Stream<Item = RawResponse> + Unpin + Send>>,
+    pub notifications: SelectAll<Subscribtion>,
 }
 
 impl Register {
-    pub fn new() -> Self {
+    pub fn new(peer: String, tokens: ApiTokens) -> Self {
         Register {
+            peer,
+            tokens,
             methods: Vec::new(),
             registred_notifications: HashSet::new(),
             notifications: SelectAll::new(),
@@ -49,18 +67,70 @@ impl Register {
         self.methods.push(handler);
     }
 
+    pub fn is_subscribe(&self, req: &RawRequest) -> bool {
+        req.is_subscribe(&self.registred_notifications)
+    }
+
+    /// Authorizes and processes a request, attaching notifications of subscribe requests.
     pub async fn try_process(
         &mut self,
-        _method_type: &str,
+        token: &str,
+        req: RawRequest,
+    ) -> Result<RawResponse, Error> {
+        self.tokens.authorize(token, &self.peer, &req.0.kind)?;
+        self.process_as(token, req).await
+    }
+
+    /// Processes an authorized request, attaching notifications of subscribe requests.
+    /// Wallet notifications are limited to the accounts allowed by the token.
+    pub async fn process_as(&mut self, token: &str, req: RawRequest) -> Result<RawResponse, Error> {
+        if !req.is_subscribe(&self.registred_notifications) {
+            return self.process(req, false).await;
+        }
+        let accounts = match self.tokens.accounts(token) {
+            Some(accounts) => accounts,
+            None => return self.process(req, true).await,
+        };
+        let mut notifications = SelectAll::new();
+        let response = Self::process_with(&self.methods, req, &mut notifications, true).await?;
+        let notifications = notifications.filter(move |notification: &RawResponse| {
+            future::ready(allowed_notification(&accounts, &notification.0))
+        });
+        self.notifications.push(Box::new(notifications));
+        Ok(response)
+    }
+
+    /// Authorizes and processes a request, never attaching notifications.
+    /// Used when notifications are delivered from the journal.
+    pub async fn try_process_detached(
+        &mut self,
+        token: &str,
         req: RawRequest,
     ) -> Result<RawResponse, Error> {
-        let notification = req.is_subscribe(&self.registred_notifications);
+        self.tokens.authorize(token, &self.peer, &req.0.kind)?;
+        self.process(req, false).await
+    }
 
-        for api in &self.methods {
+    /// Processes a request without authorization.
+    pub async fn process(
+        &mut self,
+        req: RawRequest,
+        notification: bool,
+    ) -> Result<RawResponse, Error> {
+        Self::process_with(&self.methods, req, &mut self.notifications, notification).await
+    }
+
+    async fn process_with(
+        methods: &[Box<dyn ApiHandler>],
+        req: RawRequest,
+        notifications: &mut SelectAll<Subscribtion>,
+        notification: bool,
+    ) -> Result<RawResponse, Error> {
+        for api in methods {
             debug!("Trying to parse api request: api_name={}", api.name());
 
             match api
-                .try_process(req.clone(), &mut self.notifications, notification)
+                .try_process(req.clone(), notifications, notification)
                 .await
             {
                 Ok(response) => {
@@ -0,0 +1,681 @@
+//! WebSocket API - Webhooks.
+
+//
+// Copyright (c) 2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use super::api::{ApiHandler, RawResponse};
+use super::register::Register;
+use super::{open_private, subscribe_events};
+use crate::crypto::ApiToken;
+use crate::tokens::ApiTokens;
+use crate::ResponseKind;
+use failure::{bail, format_err, Error};
+use futures::future::{Fuse, FusedFuture};
+use futures::prelude::*;
+use futures::select;
+use hmac::{Hmac, Mac};
+use hyper::client::HttpConnector;
+use hyper::header::CONTENT_TYPE;
+use hyper::{Body, Client, Method};
+use hyper_rustls::HttpsConnector;
+use log::*;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::Sha256;
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::task::JoinHandle;
+
+/// HTTP header with the id of the delivery, the same for all attempts.
+pub const DELIVERY_HEADER: &str = "X-Stegos-Delivery";
+/// HTTP header with UNIX time of the attempt, in seconds.
+pub const TIMESTAMP_HEADER: &str = "X-Stegos-Timestamp";
+/// HTTP header with HMAC-SHA256 of `<timestamp>.<body>`, keyed by the API token.
+pub const SIGNATURE_HEADER: &str = "X-Stegos-Signature";
+
+/// Delay before the first retry, doubled for every next one.
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+/// The maximal delay between retries.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(3600);
+/// Deliveries are dropped after this number of failed attempts.
+const MAX_ATTEMPTS: u32 = 20;
+/// Timeout of a single HTTP request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often the outbox is checked for due deliveries.
+const DELIVERY_INTERVAL: Duration = Duration::from_secs(1);
+/// The oldest deliveries are dropped when the outbox is full.
+const MAX_PENDING: usize = 10_000;
+/// The outbox file is compacted after this number of records, if most of them are obsolete.
+const COMPACT_RECORDS: usize = 10_000;
+
+/// Webhook configuration.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhookConfig {
+    /// URL to POST events to.
+    pub url: String,
+    /// Event types, e.g. `received` or `macro_block_committed`, all wallet events if empty.
+    /// Chain events are delivered only if listed.
+    pub events: Vec<String>,
+    /// Deliver only wallet events of these accounts, all events if empty.
+    pub accounts: Vec<String>,
+}
+
+impl WebhookConfig {
+    /// Returns true if the event must be delivered to this webhook.
+    fn matches(&self, event: &ResponseKind, value: &Value) -> bool {
+        let event_type = value.get("type").and_then(Value::as_str).unwrap_or("");
+        if self.events.is_empty() {
+            match event {
+                ResponseKind::WalletNotification(_) => {}
+                _ => return false,
+            }
+        } else if !self.events.iter().any(|e| e == event_type) {
+            return false;
+        }
+        if !self.accounts.is_empty() {
+            return match value.get("account_id").and_then(Value::as_str) {
+                Some(account_id) => self.accounts.iter().any(|a| a == account_id),
+                None => false,
+            };
+        }
+        true
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Delivery {
+    id: u64,
+    url: String,
+    body: String,
+    /// The number of failed attempts.
+    attempts: u32,
+    /// UNIX time of the next attempt, in seconds.
+    next_attempt: u64,
+}
+
+/// A change of the outbox, appended to the file.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum OutboxRecord {
+    /// The last delivery id, written on compaction.
+    NextId(u64),
+    Queued(Delivery),
+    Failed {
+        id: u64,
+        attempts: u32,
+        next_attempt: u64,
+    },
+    /// Delivered or dropped.
+    Removed(u64),
+}
+
+/// Undelivered events, kept on disk across restarts.
+/// Changes are appended to the file, which is rewritten only on compaction.
+#[derive(Debug)]
+struct Outbox {
+    path: PathBuf,
+    file: fs::File,
+    next_id: u64,
+    deliveries: Vec<Delivery>,
+    /// The number of records in the file.
+    records: usize,
+}
+
+impl Outbox {
+    fn open(path: &Path) -> Result<Outbox, Error> {
+        let mut next_id = 0;
+        let mut deliveries: Vec<Delivery> = Vec::new();
+        let mut records = 0;
+        if path.exists() {
+            let data = fs::read_to_string(path)?;
+            for line in data.lines() {
+                let record = match serde_json::from_str(line) {
+                    Ok(record) => record,
+                    Err(e) => {
+                        // The last record is incomplete if the node was stopped while writing it.
+                        warn!(target: "stegos_api", "Skipped invalid record in {:?}: {}", path, e);
+                        continue;
+                    }
+                };
+                records += 1;
+                match record {
+                    OutboxRecord::NextId(id) => next_id = next_id.max(id),
+                    OutboxRecord::Queued(delivery) => {
+                        next_id = next_id.max(delivery.id);
+                        deliveries.push(delivery);
+                    }
+                    OutboxRecord::Failed {
+                        id,
+                        attempts,
+                        next_attempt,
+                    } => {
+                        if let Some(delivery) = deliveries.iter_mut().find(|d| d.id == id) {
+                            delivery.attempts = attempts;
+                            delivery.next_attempt = next_attempt;
+                        }
+                    }
+                    OutboxRecord::Removed(id) => deliveries.retain(|d| d.id != id),
+                }
+            }
+        }
+        let file = open_private(OpenOptions::new().create(true).append(true), path)?;
+        Ok(Outbox {
+            path: path.to_path_buf(),
+            file,
+            next_id,
+            deliveries,
+            records,
+        })
+    }
+
+    /// Appends records to the file, compacting it when most records are obsolete.
+    fn append(&mut self, records: &[OutboxRecord]) -> Result<(), Error> {
+        let mut data = Vec::new();
+        for record in records {
+            serde_json::to_writer(&mut data, record)?;
+            data.push(b'\n');
+        }
+        self.file.write_all(&data)?;
+        self.records += records.len();
+        if self.records > COMPACT_RECORDS && self.records > 2 * self.deliveries.len() {
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    /// Rewrites the file with pending deliveries only.
+    fn compact(&mut self) -> Result<(), Error> {
+        let mut data = Vec::new();
+        serde_json::to_writer(&mut data, &OutboxRecord::NextId(self.next_id))?;
+        data.push(b'\n');
+        for delivery in &self.deliveries {
+            serde_json::to_writer(&mut data, &OutboxRecord::Queued(delivery.clone()))?;
+            data.push(b'\n');
+        }
+        let tmp_path = self.path.with_extension("tmp");
+        let mut tmp = open_private(
+            OpenOptions::new().write(true).create(true).truncate(true),
+            &tmp_path,
+        )?;
+        tmp.write_all(&data)?;
+        fs::rename(&tmp_path, &self.path)?;
+        self.file = open_private(OpenOptions::new().append(true), &self.path)?;
+        self.records = self.deliveries.len() + 1;
+        Ok(())
+    }
+
+    /// Queues a delivery, dropping the oldest one if the outbox is full.
+    fn push(&mut self, url: String, body: String, now: u64) -> Result<(), Error> {
+        let mut records = Vec::new();
+        if self.deliveries.len() >= MAX_PENDING {
+            let dropped = self.deliveries.remove(0);
+            error!(target: "stegos_api", "Webhooks outbox is full, dropped webhook: id={}, url={}",
+                   dropped.id, dropped.url);
+            records.push(OutboxRecord::Removed(dropped.id));
+        }
+        self.next_id += 1;
+        let delivery = Delivery {
+            id: self.next_id,
+            url,
+            body,
+            attempts: 0,
+            next_attempt: now,
+        };
+        records.push(OutboxRecord::Queued(delivery.clone()));
+        self.deliveries.push(delivery);
+        self.append(&records)
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Returns the value of the signature header for the body sent at `timestamp`.
+/// Receivers should reject stale timestamps to prevent replays.
+pub fn signature(api_token: &ApiToken, timestamp: u64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_varkey(&api_token.0).expect("any key size");
+    mac.input(timestamp.to_string().as_bytes());
+    mac.input(b".");
+    mac.input(body);
+    format!("sha256={}", hex::encode(mac.result().code()))
+}
+
+/// Returns the delay after the given number of failed attempts.
+fn retry_delay(attempts: u32) -> Duration {
+    let factor = 1u32
+        .checked_shl(attempts.saturating_sub(1))
+        .unwrap_or(std::u32::MAX);
+    RETRY_DELAY
+        .checked_mul(factor)
+        .map_or(MAX_RETRY_DELAY, |delay| delay.min(MAX_RETRY_DELAY))
+}
+
+type HttpClient = Client<HttpsConnector<HttpConnector>>;
+
+async fn deliver(
+    client: &HttpClient,
+    api_token: &ApiToken,
+    delivery: &Delivery,
+    timestamp: u64,
+) -> Result<(), Error> {
+    let request = hyper::Request::builder()
+        .method(Method::POST)
+        .uri(&delivery.url)
+        .header(CONTENT_TYPE, "application/json")
+        .header(DELIVERY_HEADER, delivery.id.to_string())
+        .header(TIMESTAMP_HEADER, timestamp.to_string())
+        .header(
+            SIGNATURE_HEADER,
+            signature(api_token, timestamp, delivery.body.as_bytes()),
+        )
+        .body(Body::from(delivery.body.clone()))?;
+    let response = tokio::time::timeout(REQUEST_TIMEOUT, client.request(request))
+        .await
+        .map_err(|_| format_err!("Timed out"))??;
+    if !response.status().is_success() {
+        bail!("HTTP {}", response.status());
+    }
+    Ok(())
+}
+
+/// Result of a delivery attempt, by id of the delivery.
+type Attempt = (u64, Result<(), Error>);
+
+/// Attempts deliveries, in order for every URL.
+async fn deliver_round(
+    client: HttpClient,
+    api_token: ApiToken,
+    deliveries: Vec<Delivery>,
+) -> Vec<Attempt> {
+    // Don't wait for the same unavailable URL again.
+    let mut failed_urls = HashSet::new();
+    let mut attempts = Vec::new();
+    for delivery in deliveries {
+        if failed_urls.contains(&delivery.url) {
+            continue;
+        }
+        let result = deliver(&client, &api_token, &delivery, unix_now()).await;
+        if result.is_err() {
+            failed_urls.insert(delivery.url.clone());
+        }
+        attempts.push((delivery.id, result));
+    }
+    attempts
+}
+
+struct Dispatcher {
+    webhooks: Vec<WebhookConfig>,
+    api_token: ApiToken,
+    outbox: Outbox,
+    client: HttpClient,
+}
+
+impl Dispatcher {
+    /// Queues the event for all matching webhooks.
+    fn enqueue(&mut self, event: &ResponseKind) -> Result<(), Error> {
+        let value = serde_json::to_value(event)?;
+        let body = value.to_string();
+        let now = unix_now();
+        for webhook in &self.webhooks {
+            if webhook.matches(event, &value) {
+                self.outbox.push(webhook.url.clone(), body.clone(), now)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns deliveries which are due at `now`.
+    fn due(&self, now: u64) -> Vec<Delivery> {
+        self.outbox
+            .deliveries
+            .iter()
+            .filter(|delivery| delivery.next_attempt <= now)
+            .cloned()
+            .collect()
+    }
+
+    /// Saves results of delivery attempts.
+    fn complete(&mut self, attempts: Vec<Attempt>) {
+        let now = unix_now();
+        let mut records = Vec::new();
+        for (id, result) in attempts {
+            // The delivery can be dropped from the full outbox meanwhile.
+            let i = match self.outbox.deliveries.iter().position(|d| d.id == id) {
+                Some(i) => i,
+                None => continue,
+            };
+            let delivery = &mut self.outbox.deliveries[i];
+            match result {
+                Ok(()) => {
+                    debug!(target: "stegos_api", "Delivered webhook: id={}, url={}", delivery.id, delivery.url);
+                    records.push(OutboxRecord::Removed(delivery.id));
+                    self.outbox.deliveries.remove(i);
+                }
+                Err(e) => {
+                    delivery.attempts += 1;
+                    if delivery.attempts >= MAX_ATTEMPTS {
+                        error!(target: "stegos_api", "Dropped webhook: id={}, url={}, error={}", delivery.id, delivery.url, e);
+                        records.push(OutboxRecord::Removed(delivery.id));
+                        self.outbox.deliveries.remove(i);
+                    } else {
+                        let delay = retry_delay(delivery.attempts);
+                        warn!(target: "stegos_api", "Failed to deliver webhook: id={}, url={}, error={}, retry_in={:?}",
+                              delivery.id, delivery.url, e, delay);
+                        delivery.next_attempt = now + delay.as_secs();
+                        records.push(OutboxRecord::Failed {
+                            id: delivery.id,
+                            attempts: delivery.attempts,
+                            next_attempt: delivery.next_attempt,
+                        });
+                    }
+                }
+            }
+        }
+        if !records.is_empty() {
+            if let Err(e) = self.outbox.append(&records) {
+                error!(target: "stegos_api", "Failed to save webhooks outbox: {}", e);
+            }
+        }
+    }
+
+    /// Attempts all due deliveries and waits for the results.
+    #[cfg(test)]
+    async fn deliver_due(&mut self) {
+        let due = self.due(unix_now());
+        let attempts = deliver_round(self.client.clone(), self.api_token, due).await;
+        self.complete(attempts);
+    }
+
+    async fn run(mut self, mut register: Register) {
+        subscribe_events(&mut register).await;
+        let mut interval = tokio::time::interval(DELIVERY_INTERVAL);
+        // Deliveries run in background, events are queued meanwhile.
+        let mut round: Fuse<JoinHandle<Vec<Attempt>>> = Fuse::terminated();
+        loop {
+            select! {
+                event = register.notifications.next() => {
+                    if let Some(RawResponse(event)) = event {
+                        if let Err(e) = self.enqueue(&event) {
+                            error!(target: "stegos_api", "Failed to queue webhook: {}", e);
+                        }
+                    }
+                }
+                _ = interval.tick().fuse() => {
+                    if round.is_terminated() {
+                        let due = self.due(unix_now());
+                        if !due.is_empty() {
+                            let client = self.client.clone();
+                            round = tokio::spawn(deliver_round(client, self.api_token, due)).fuse();
+                        }
+                    }
+                }
+                attempts = round => match attempts {
+                    Ok(attempts) => self.complete(attempts),
+                    Err(e) => error!(target: "stegos_api", "Failed to deliver webhooks: {}", e),
+                },
+            }
+        }
+    }
+}
+
+/// Starts delivery of chain and wallet events to webhooks.
+/// Events are kept in the outbox file until delivered.
+pub fn spawn_webhooks(
+    webhooks: Vec<WebhookConfig>,
+    outbox: &Path,
+    api_token: ApiToken,
+    apis: Vec<Box<dyn ApiHandler>>,
+) -> Result<JoinHandle<()>, Error> {
+    for webhook in &webhooks {
+        webhook
+            .url
+            .parse::<hyper::Uri>()
+            .map_err(|e| format_err!("Invalid webhook URL '{}': {}", webhook.url, e))?;
+    }
+    let mut outbox = Outbox::open(outbox)?;
+    outbox.compact()?;
+    info!(target: "stegos_api", "Starting webhooks: webhooks={}, pending={}",
+          webhooks.len(), outbox.deliveries.len());
+    let mut register = Register::new("webhooks".to_string(), ApiTokens::new(None, None, None));
+    for api in apis {
+        register.add_api(api);
+    }
+    let dispatcher = Dispatcher {
+        webhooks,
+        api_token,
+        outbox,
+        client: Client::builder().build(HttpsConnector::new()),
+    };
+    Ok(tokio::spawn(dispatcher.run(register)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Server, StatusCode};
+    use serde_json::json;
+    use std::convert::Infallible;
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+    use stegos_blockchain::{MicroBlock, Timestamp};
+    use stegos_crypto::hash::Hash;
+    use stegos_crypto::pbc;
+    use stegos_node::ChainNotification;
+    use stegos_wallet::api::{AccountNotification, WalletNotification};
+    use tempdir::TempDir;
+
+    fn wallet_event(account_id: &str) -> ResponseKind {
+        ResponseKind::WalletNotification(WalletNotification {
+            account_id: account_id.to_string(),
+            notification: AccountNotification::Sealed,
+        })
+    }
+
+    fn chain_event() -> ResponseKind {
+        let (skey, pkey) = pbc::make_random_keys();
+        let random = pbc::make_VRF(&skey, &Hash::digest("random"));
+        let block = MicroBlock::empty(
+            Hash::zero(),
+            1,
+            0,
+            0,
+            None,
+            pkey,
+            random,
+            Vec::new(),
+            Timestamp::now(),
+        );
+        ResponseKind::ChainNotification(ChainNotification::MicroBlockPrepared(block))
+    }
+
+    #[test]
+    fn filters() {
+        let matches = |webhook: &WebhookConfig, event: &ResponseKind| {
+            webhook.matches(event, &serde_json::to_value(event).unwrap())
+        };
+        let sealed = wallet_event("1");
+        let block = chain_event();
+        let value = serde_json::to_value(&sealed).unwrap();
+        assert_eq!(value, json!({"type": "sealed", "account_id": "1"}));
+        let value = serde_json::to_value(&block).unwrap();
+        assert_eq!(value["type"], "micro_block_prepared");
+
+        // Only wallet events by default.
+        let default = WebhookConfig::default();
+        assert!(matches(&default, &sealed) && !matches(&default, &block));
+        let events = WebhookConfig {
+            events: vec!["micro_block_prepared".to_string()],
+            ..Default::default()
+        };
+        assert!(!matches(&events, &sealed) && matches(&events, &block));
+        let accounts = WebhookConfig {
+            accounts: vec!["2".to_string()],
+            ..Default::default()
+        };
+        assert!(!matches(&accounts, &sealed) && matches(&accounts, &wallet_event("2")));
+        assert!(!matches(&accounts, &block));
+
+        assert_eq!(retry_delay(1), RETRY_DELAY);
+        assert_eq!(retry_delay(3), RETRY_DELAY * 4);
+        assert_eq!(retry_delay(MAX_ATTEMPTS), MAX_RETRY_DELAY);
+        assert_eq!(retry_delay(100), MAX_RETRY_DELAY);
+    }
+
+    #[tokio::test]
+    async fn delivery() {
+        // A local HTTP server, which fails the first request.
+        let requests: Arc<Mutex<Vec<(String, String, String, Vec<u8>)>>> = Default::default();
+        let requests2 = requests.clone();
+        let service = make_service_fn(move |_| {
+            let requests = requests2.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req: hyper::Request<Body>| {
+                    let requests = requests.clone();
+                    async move {
+                        let header = |name: &str| req.headers()[name].to_str().unwrap().to_string();
+                        let id = header(DELIVERY_HEADER);
+                        let timestamp = header(TIMESTAMP_HEADER);
+                        let signature = header(SIGNATURE_HEADER);
+                        let body = hyper::body::to_bytes(req.into_body()).await.unwrap();
+                        let mut requests = requests.lock().unwrap();
+                        requests.push((id, timestamp, signature, body.to_vec()));
+                        let status = if requests.len() == 1 {
+                            StatusCode::INTERNAL_SERVER_ERROR
+                        } else {
+                            StatusCode::OK
+                        };
+                        let response = hyper::Response::builder().status(status);
+                        Ok::<_, Infallible>(response.body(Body::empty()).unwrap())
+                    }
+                }))
+            }
+        });
+        let server = Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(service);
+        let url = format!("http://{}/events", server.local_addr());
+        tokio::spawn(server);
+
+        let dir = TempDir::new("webhooks").unwrap();
+        let path = dir.path().join("outbox.json");
+        let api_token = ApiToken::new();
+        let mut dispatcher = Dispatcher {
+            webhooks: vec![WebhookConfig {
+                url,
+                ..Default::default()
+            }],
+            api_token,
+            outbox: Outbox::open(&path).unwrap(),
+            client: Client::builder().build(HttpsConnector::new()),
+        };
+        let event = wallet_event("1");
+        dispatcher.enqueue(&event).unwrap();
+        // Chain events are not delivered by default.
+        dispatcher.enqueue(&chain_event()).unwrap();
+        assert_eq!(Outbox::open(&path).unwrap().deliveries.len(), 1);
+
+        // The first attempt fails, the delivery is postponed.
+        dispatcher.deliver_due().await;
+        let outbox = Outbox::open(&path).unwrap();
+        assert_eq!(outbox.deliveries[0].attempts, 1);
+        assert!(outbox.deliveries[0].next_attempt > 0);
+        assert_eq!(requests.lock().unwrap().len(), 1);
+
+        dispatcher.outbox.deliveries[0].next_attempt = 0;
+        dispatcher.deliver_due().await;
+        assert!(Outbox::open(&path).unwrap().deliveries.is_empty());
+
+        let received = requests.lock().unwrap().clone();
+        assert_eq!(received.len(), 2);
+        let event = serde_json::to_value(&event).unwrap();
+        for (id, timestamp, sig, body) in received.iter() {
+            assert_eq!(id, "1");
+            let timestamp: u64 = timestamp.parse().unwrap();
+            assert!(timestamp > 0);
+            assert_eq!(sig, &signature(&api_token, timestamp, body));
+            assert_ne!(sig, &signature(&api_token, timestamp + 1, body));
+            assert_eq!(serde_json::from_slice::<Value>(body).unwrap(), event);
+        }
+
+        // Events are queued while a delivery round is in flight.
+        dispatcher.enqueue(&wallet_event("1")).unwrap();
+        let due = dispatcher.due(unix_now());
+        let round = tokio::spawn(deliver_round(dispatcher.client.clone(), api_token, due));
+        dispatcher.enqueue(&wallet_event("1")).unwrap();
+        dispatcher.complete(round.await.unwrap());
+        assert_eq!(dispatcher.outbox.deliveries.len(), 1);
+        assert_eq!(dispatcher.outbox.deliveries[0].attempts, 0);
+        assert_eq!(requests.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn outbox() {
+        let dir = TempDir::new("webhooks").unwrap();
+        let path = dir.path().join("outbox.json");
+        let mut outbox = Outbox::open(&path).unwrap();
+        for i in 0..=MAX_PENDING {
+            outbox.push("url".to_string(), i.to_string(), 0).unwrap();
+        }
+        // The oldest delivery has been dropped.
+        assert_eq!(outbox.deliveries.len(), MAX_PENDING);
+        assert_eq!(outbox.deliveries[0].id, 2);
+        let records = outbox.records;
+        outbox
+            .append(&[OutboxRecord::Failed {
+                id: 2,
+                attempts: 1,
+                next_attempt: 10,
+            }])
+            .unwrap();
+        // Changes are appended.
+        assert_eq!(outbox.records, records + 1);
+        let reopened = Outbox::open(&path).unwrap();
+        assert_eq!(reopened.deliveries.len(), MAX_PENDING);
+        assert_eq!(reopened.deliveries[0].attempts, 1);
+        assert_eq!(reopened.deliveries[0].next_attempt, 10);
+
+        // Delivered events are removed on compaction, the ids are kept.
+        let removed: Vec<_> = (2..MAX_PENDING as u64 + 2)
+            .map(OutboxRecord::Removed)
+            .collect();
+        outbox.deliveries.clear();
+        outbox.append(&removed).unwrap();
+        assert_eq!(outbox.records, 1);
+        // Webhook events are only accessible by the owner.
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        let mut reopened = Outbox::open(&path).unwrap();
+        assert!(reopened.deliveries.is_empty());
+        reopened.push("url".to_string(), String::new(), 0).unwrap();
+        assert_eq!(reopened.deliveries[0].id, MAX_PENDING as u64 + 2);
+    }
+}
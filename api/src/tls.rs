@@ -0,0 +1,343 @@
+//! WebSocket API - TLS.
+
+//
+// MIT License
+//
+// Copyright (c) 2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use failure::{bail, format_err, Error};
+use serde_derive::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio_rustls::rustls::internal::pemfile::{certs, pkcs8_private_keys, rsa_private_keys};
+use tokio_rustls::rustls::{
+    AllowAnyAuthenticatedClient, Certificate, ClientConfig, NoClientAuth, PrivateKey,
+    RootCertStore, ServerConfig,
+};
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+/// TLS configuration of the API server.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerTlsConfig {
+    /// PEM-encoded certificate chain.
+    pub certificate: PathBuf,
+    /// PEM-encoded private key (PKCS8 or RSA).
+    pub private_key: PathBuf,
+    /// PEM-encoded CA certificates, clients must present a certificate signed by them.
+    pub client_ca: Option<PathBuf>,
+}
+
+/// TLS configuration of the API client.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClientTlsConfig {
+    /// PEM-encoded CA certificates to trust instead of the public roots.
+    pub ca: Option<PathBuf>,
+    /// PEM-encoded client certificate chain.
+    pub certificate: Option<PathBuf>,
+    /// PEM-encoded client private key (PKCS8 or RSA).
+    pub private_key: Option<PathBuf>,
+    /// Name to verify the server certificate against, the host of the endpoint by default.
+    pub server_name: Option<String>,
+}
+
+fn open(path: &Path) -> Result<BufReader<File>, Error> {
+    let file = File::open(path).map_err(|e| format_err!("Failed to open {:?}: {}", path, e))?;
+    Ok(BufReader::new(file))
+}
+
+fn load_certificates(path: &Path) -> Result<Vec<Certificate>, Error> {
+    let certificates =
+        certs(&mut open(path)?).map_err(|()| format_err!("Invalid certificate: {:?}", path))?;
+    if certificates.is_empty() {
+        bail!("No certificates found in {:?}", path);
+    }
+    Ok(certificates)
+}
+
+fn load_private_key(path: &Path) -> Result<PrivateKey, Error> {
+    let mut keys = pkcs8_private_keys(&mut open(path)?)
+        .map_err(|()| format_err!("Invalid private key: {:?}", path))?;
+    if keys.is_empty() {
+        keys = rsa_private_keys(&mut open(path)?)
+            .map_err(|()| format_err!("Invalid private key: {:?}", path))?;
+    }
+    match keys.into_iter().next() {
+        Some(key) => Ok(key),
+        None => bail!("No private keys found in {:?}", path),
+    }
+}
+
+fn load_roots(path: &Path) -> Result<RootCertStore, Error> {
+    let mut roots = RootCertStore::empty();
+    let (valid, _invalid) = roots
+        .add_pem_file(&mut open(path)?)
+        .map_err(|()| format_err!("Invalid CA certificate: {:?}", path))?;
+    if valid == 0 {
+        bail!("No CA certificates found in {:?}", path);
+    }
+    Ok(roots)
+}
+
+/// Loads certificates and creates an acceptor, called again to reload certificates.
+pub fn tls_acceptor(cfg: &ServerTlsConfig) -> Result<TlsAcceptor, Error> {
+    let mut config = match &cfg.client_ca {
+        Some(client_ca) => {
+            ServerConfig::new(AllowAnyAuthenticatedClient::new(load_roots(client_ca)?))
+        }
+        None => ServerConfig::new(NoClientAuth::new()),
+    };
+    let certificates = load_certificates(&cfg.certificate)?;
+    let private_key = load_private_key(&cfg.private_key)?;
+    config
+        .set_single_cert(certificates, private_key)
+        .map_err(|e| format_err!("Invalid certificate {:?}: {}", cfg.certificate, e))?;
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+pub fn tls_connector(cfg: &ClientTlsConfig) -> Result<TlsConnector, Error> {
+    let mut config = ClientConfig::new();
+    match &cfg.ca {
+        Some(ca) => config.root_store = load_roots(ca)?,
+        None => config
+            .root_store
+            .add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS),
+    }
+    match (&cfg.certificate, &cfg.private_key) {
+        (Some(certificate), Some(private_key)) => {
+            let certificates = load_certificates(certificate)?;
+            let private_key = load_private_key(private_key)?;
+            config
+                .set_single_client_cert(certificates, private_key)
+                .map_err(|e| format_err!("Invalid certificate {:?}: {}", certificate, e))?;
+        }
+        (None, None) => {}
+        _ => bail!("Both client certificate and private key are required"),
+    }
+    Ok(TlsConnector::from(Arc::new(config)))
+}
+
+/// Splits `ws://host:port/path` into (is_tls, host, host:port).
+pub(crate) fn parse_endpoint(endpoint: &str) -> Result<(bool, String, String), Error> {
+    let (tls, rest) = if endpoint.starts_with("wss://") {
+        (true, &endpoint["wss://".len()..])
+    } else if endpoint.starts_with("ws://") {
+        (false, &endpoint["ws://".len()..])
+    } else {
+        bail!("Invalid endpoint, expected ws:// or wss://: {}", endpoint);
+    };
+    let authority = rest.split('/').next().unwrap_or_default();
+    let (host, port) = if authority.starts_with('[') {
+        // IPv6 address.
+        match authority.find(']') {
+            Some(end) => (
+                &authority[1..end],
+                authority[end + 1..].trim_start_matches(':'),
+            ),
+            None => bail!("Invalid endpoint: {}", endpoint),
+        }
+    } else {
+        match authority.rfind(':') {
+            Some(sep) => (&authority[..sep], &authority[sep + 1..]),
+            None => (authority, ""),
+        }
+    };
+    if host.is_empty() {
+        bail!("Invalid endpoint: {}", endpoint);
+    }
+    let port = match port {
+        "" if tls => 443,
+        "" => 80,
+        port => port
+            .parse::<u16>()
+            .map_err(|e| format_err!("Invalid port in {}: {}", endpoint, e))?,
+    };
+    let addr = if host.contains(':') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    };
+    Ok((tls, host.to_string(), addr))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+    use std::sync::RwLock;
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_rustls::webpki::DNSNameRef;
+
+    /// Certificates generated by tests/tls/generate.sh.
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/tls")
+            .join(name)
+    }
+
+    fn server_config(client_ca: bool) -> ServerTlsConfig {
+        ServerTlsConfig {
+            certificate: fixture("server.pem"),
+            private_key: fixture("server.key"),
+            client_ca: if client_ca {
+                Some(fixture("ca.pem"))
+            } else {
+                None
+            },
+        }
+    }
+
+    fn client_config(certificate: bool) -> ClientTlsConfig {
+        let (certificate, private_key) = if certificate {
+            (Some(fixture("client.pem")), Some(fixture("client.key")))
+        } else {
+            (None, None)
+        };
+        ClientTlsConfig {
+            ca: Some(fixture("ca.pem")),
+            certificate,
+            private_key,
+            server_name: None,
+        }
+    }
+
+    /// Echoes one byte on each accepted connection.
+    async fn serve(acceptor: Arc<RwLock<TlsAcceptor>>) -> SocketAddr {
+        let mut listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let acceptor = acceptor.read().unwrap().clone();
+                tokio::spawn(async move {
+                    if let Ok(mut stream) = acceptor.accept(stream).await {
+                        let mut buf = [0u8; 1];
+                        if stream.read_exact(&mut buf).await.is_ok() {
+                            let _ = stream.write_all(&buf).await;
+                            let _ = stream.flush().await;
+                        }
+                    }
+                });
+            }
+        });
+        addr
+    }
+
+    /// Returns true if a byte sent over TLS is echoed back.
+    async fn echo(addr: SocketAddr, cfg: &ClientTlsConfig, server_name: &str) -> bool {
+        let connector = tls_connector(cfg).unwrap();
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let server_name = DNSNameRef::try_from_ascii_str(server_name).unwrap();
+        let mut stream = match connector.connect(server_name, stream).await {
+            Ok(stream) => stream,
+            Err(_) => return false,
+        };
+        if stream.write_all(&[42]).await.is_err() || stream.flush().await.is_err() {
+            return false;
+        }
+        let mut buf = [0u8; 1];
+        stream.read_exact(&mut buf).await.is_ok() && buf == [42]
+    }
+
+    fn acceptor(cfg: &ServerTlsConfig) -> Arc<RwLock<TlsAcceptor>> {
+        Arc::new(RwLock::new(tls_acceptor(cfg).unwrap()))
+    }
+
+    #[tokio::test]
+    async fn handshake() {
+        let addr = serve(acceptor(&server_config(false))).await;
+        assert!(echo(addr, &client_config(false), "localhost").await);
+        // The certificate is issued for another name.
+        assert!(!echo(addr, &client_config(false), "reloaded.localhost").await);
+        // The certificate is not signed by a public CA.
+        let public_roots = ClientTlsConfig::default();
+        assert!(!echo(addr, &public_roots, "localhost").await);
+    }
+
+    #[tokio::test]
+    async fn client_certificate() {
+        let addr = serve(acceptor(&server_config(true))).await;
+        assert!(echo(addr, &client_config(true), "localhost").await);
+        assert!(!echo(addr, &client_config(false), "localhost").await);
+        let mut cfg = client_config(true);
+        cfg.private_key = None;
+        assert!(tls_connector(&cfg).is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn reload_on_sighup() {
+        let temp_dir = tempdir::TempDir::new("tls").unwrap();
+        let cfg = ServerTlsConfig {
+            certificate: temp_dir.path().join("api.crt"),
+            private_key: temp_dir.path().join("api.key"),
+            client_ca: None,
+        };
+        std::fs::copy(fixture("server.pem"), &cfg.certificate).unwrap();
+        std::fs::copy(fixture("server.key"), &cfg.private_key).unwrap();
+        let acceptor = crate::server::reloadable_tls_acceptor(cfg.clone()).unwrap();
+        let addr = serve(acceptor).await;
+        let client = client_config(false);
+        assert!(echo(addr, &client, "localhost").await);
+        assert!(!echo(addr, &client, "reloaded.localhost").await);
+
+        std::fs::copy(fixture("reloaded.pem"), &cfg.certificate).unwrap();
+        std::fs::copy(fixture("reloaded.key"), &cfg.private_key).unwrap();
+        let pid = std::process::id().to_string();
+        let status = std::process::Command::new("kill")
+            .args(&["-HUP", &pid])
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let mut reloaded = false;
+        for _ in 0..50 {
+            if echo(addr, &client, "reloaded.localhost").await {
+                reloaded = true;
+                break;
+            }
+            tokio::time::delay_for(Duration::from_millis(100)).await;
+        }
+        assert!(reloaded);
+        assert!(!echo(addr, &client, "localhost").await);
+    }
+
+    #[test]
+    fn endpoints() {
+        let (tls, host, addr) = parse_endpoint("ws://127.0.0.1:3145").unwrap();
+        assert!(!tls);
+        assert_eq!(host, "127.0.0.1");
+        assert_eq!(addr, "127.0.0.1:3145");
+        let (tls, host, addr) = parse_endpoint("wss://node.stegos.com/api").unwrap();
+        assert!(tls);
+        assert_eq!(host, "node.stegos.com");
+        assert_eq!(addr, "node.stegos.com:443");
+        let (_, host, addr) = parse_endpoint("wss://[::1]:3145").unwrap();
+        assert_eq!(host, "::1");
+        assert_eq!(addr, "[::1]:3145");
+        assert!(parse_endpoint("http://127.0.0.1:3145").is_err());
+        assert!(parse_endpoint("ws://127.0.0.1:port").is_err());
+        assert!(parse_endpoint("ws://:3145").is_err());
+    }
+}
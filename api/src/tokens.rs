@@ -0,0 +1,660 @@
+//! WebSocket API - Scoped tokens.
+
+//
+// MIT License
+//
+// Copyright (c) 2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::crypto::{try_decrypt, ApiToken};
+use crate::network_api::NetworkRequest;
+use crate::RequestKind;
+use failure::{bail, format_err, Error};
+use log::*;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use stegos_node::NodeRequest;
+use stegos_wallet::api::{AccountId, WalletControlRequest, WalletRequest};
+use subtle::ConstantTimeEq;
+
+/// Name of the token loaded from `api.token`, which has full access.
+pub const MASTER_TOKEN_NAME: &str = "master";
+
+/// What a token is allowed to do.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "scope")]
+#[serde(rename_all = "snake_case")]
+pub enum TokenScope {
+    /// Everything.
+    Admin,
+    /// Read-only access to the blockchain.
+    Chain,
+    /// Network messaging and statistics, broadcasting transactions.
+    Network,
+    /// All accounts of the wallet, except deleting them.
+    Wallet,
+    /// One account of the wallet.
+    Account { account_id: AccountId },
+}
+
+/// Permission required by a request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Permission {
+    Chain,
+    Network,
+    Wallet,
+    Account(AccountId),
+    /// Any account, e.g. to receive notifications of the accounts allowed by the token.
+    AnyAccount,
+    Admin,
+}
+
+impl TokenScope {
+    pub fn allows(&self, permission: &Permission) -> bool {
+        match (self, permission) {
+            (TokenScope::Admin, _) => true,
+            (_, Permission::Chain) => true,
+            (TokenScope::Network, Permission::Network) => true,
+            (TokenScope::Wallet, Permission::Wallet) => true,
+            (TokenScope::Wallet, Permission::Account(_)) => true,
+            (TokenScope::Wallet, Permission::AnyAccount) => true,
+            (TokenScope::Account { account_id }, Permission::Account(id)) => account_id == id,
+            (TokenScope::Account { .. }, Permission::AnyAccount) => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for TokenScope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TokenScope::Admin => write!(f, "admin"),
+            TokenScope::Chain => write!(f, "chain"),
+            TokenScope::Network => write!(f, "network"),
+            TokenScope::Wallet => write!(f, "wallet"),
+            TokenScope::Account { account_id } => write!(f, "account:{}", account_id),
+        }
+    }
+}
+
+impl FromStr for TokenScope {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let scope = match s {
+            "admin" => TokenScope::Admin,
+            "chain" => TokenScope::Chain,
+            "network" => TokenScope::Network,
+            "wallet" => TokenScope::Wallet,
+            _ if s.starts_with("account:") && s.len() > "account:".len() => TokenScope::Account {
+                account_id: s["account:".len()..].to_string(),
+            },
+            _ => bail!(
+                "Invalid scope '{}', expected admin, chain, network, wallet or account:ID",
+                s
+            ),
+        };
+        Ok(scope)
+    }
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Permission::Chain => write!(f, "chain"),
+            Permission::Network => write!(f, "network"),
+            Permission::Wallet => write!(f, "wallet"),
+            Permission::Account(account_id) => write!(f, "account:{}", account_id),
+            Permission::AnyAccount => write!(f, "any_account"),
+            Permission::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+/// Returns the permission required to process the request.
+/// All requests are listed explicitly, so new ones must be classified.
+pub fn required_permission(request: &RequestKind) -> Permission {
+    match request {
+        RequestKind::NodeRequest(request) => match request {
+            NodeRequest::ElectionInfo {}
+            | NodeRequest::EscrowInfo {}
+            | NodeRequest::ReplicationInfo {}
+            | NodeRequest::ChainName {}
+            | NodeRequest::OutputsList { .. }
+            | NodeRequest::CreateRawTransaction { .. }
+            | NodeRequest::ValidateCertificate { .. }
+            | NodeRequest::OutputProof { .. }
+            | NodeRequest::StatusInfo {}
+            | NodeRequest::ValidatorsInfo {}
+            | NodeRequest::SubscribeStatus {}
+            | NodeRequest::MacroBlockInfo { .. }
+            | NodeRequest::MicroBlockInfo { .. }
+            | NodeRequest::ValidatorStats { .. }
+            | NodeRequest::ElectionProof { .. }
+            | NodeRequest::ChainParameters {}
+            | NodeRequest::SubscribeChain { .. }
+            | NodeRequest::ApiSchema {} => Permission::Chain,
+            NodeRequest::BroadcastTransaction { .. } => Permission::Network,
+            NodeRequest::PopMicroBlock {}
+            | NodeRequest::EnableRestaking {}
+            | NodeRequest::DisableRestaking {}
+            | NodeRequest::ChangeUpstream {}
+            | NodeRequest::VoteChainParameter { .. } => Permission::Admin,
+        },
+        RequestKind::NetworkRequest(request) => match request {
+            NetworkRequest::VersionInfo {} | NetworkRequest::ChainName {} => Permission::Chain,
+            NetworkRequest::SubscribeUnicast { .. }
+            | NetworkRequest::SubscribeBroadcast { .. }
+            | NetworkRequest::SendUnicast { .. }
+            | NetworkRequest::PublishBroadcast { .. }
+            | NetworkRequest::ConnectedNodesRequest {}
+            | NetworkRequest::PeerScores {}
+            | NetworkRequest::TrafficStats {} => Permission::Network,
+            NetworkRequest::ClearPeerScores { .. } => Permission::Admin,
+        },
+        RequestKind::WalletsRequest(request) => match request {
+            WalletRequest::WalletControlRequest(WalletControlRequest::DeleteAccount { .. }) => {
+                Permission::Admin
+            }
+            // Notifications are filtered by the accounts of the token.
+            WalletRequest::WalletControlRequest(
+                WalletControlRequest::SubscribeWalletUpdates {},
+            ) => Permission::AnyAccount,
+            WalletRequest::WalletControlRequest(_) => Permission::Wallet,
+            WalletRequest::AccountRequest { account_id, .. } => {
+                Permission::Account(account_id.clone())
+            }
+        },
+        // Requests of external APIs are not known in advance.
+        RequestKind::Raw(_) => Permission::Admin,
+    }
+}
+
+/// A named token, as saved to the token file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenInfo {
+    pub name: String,
+    /// Base64-encoded token.
+    pub token: String,
+    pub scopes: Vec<TokenScope>,
+    /// Creation time (UNIX timestamp, secs).
+    pub created: u64,
+}
+
+impl TokenInfo {
+    pub fn allows(&self, permission: &Permission) -> bool {
+        self.scopes.iter().any(|scope| scope.allows(permission))
+    }
+
+    /// Returns the accounts allowed by the token, None if all accounts are allowed.
+    pub fn accounts(&self) -> Option<HashSet<AccountId>> {
+        let mut accounts = HashSet::new();
+        for scope in &self.scopes {
+            match scope {
+                TokenScope::Admin | TokenScope::Wallet => return None,
+                TokenScope::Account { account_id } => {
+                    accounts.insert(account_id.clone());
+                }
+                TokenScope::Chain | TokenScope::Network => {}
+            }
+        }
+        Some(accounts)
+    }
+}
+
+/// Named tokens, saved to a JSON file.
+pub struct TokenStore {
+    path: PathBuf,
+    tokens: Vec<TokenInfo>,
+    /// Modification time of the file when it was loaded.
+    modified: Option<SystemTime>,
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+impl TokenStore {
+    /// Loads tokens from the file, the file is created on the first save.
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        let mut store = TokenStore {
+            path: path.to_path_buf(),
+            tokens: Vec::new(),
+            modified: None,
+        };
+        store.load()?;
+        Ok(store)
+    }
+
+    fn load(&mut self) -> Result<(), Error> {
+        self.modified = modified(&self.path);
+        self.tokens = if self.path.exists() {
+            let data = fs::read_to_string(&self.path)
+                .map_err(|e| format_err!("Failed to read {:?}: {}", self.path, e))?;
+            serde_json::from_str(&data)
+                .map_err(|e| format_err!("Failed to parse {:?}: {}", self.path, e))?
+        } else {
+            Vec::new()
+        };
+        Ok(())
+    }
+
+    /// Reloads tokens if the file has been changed, e.g. a token has been revoked.
+    fn refresh(&mut self) {
+        if modified(&self.path) == self.modified {
+            return;
+        }
+        info!(target: "stegos_api", "Reloading API tokens from {:?}", self.path);
+        if let Err(e) = self.load() {
+            error!(target: "stegos_api", "{}", e);
+        }
+    }
+
+    pub fn save(&mut self) -> Result<(), Error> {
+        let data = serde_json::to_string_pretty(&self.tokens)?;
+        write_secret(&self.path, data.as_bytes())
+            .map_err(|e| format_err!("Failed to write {:?}: {}", self.path, e))?;
+        self.modified = modified(&self.path);
+        Ok(())
+    }
+
+    pub fn tokens(&self) -> &[TokenInfo] {
+        &self.tokens
+    }
+
+    pub fn get(&self, name: &str) -> Option<&TokenInfo> {
+        self.tokens.iter().find(|t| t.name == name)
+    }
+
+    /// Creates a new token, doesn't save the store.
+    pub fn create(&mut self, name: &str, scopes: Vec<TokenScope>) -> Result<ApiToken, Error> {
+        if name.is_empty() || name == MASTER_TOKEN_NAME {
+            bail!("Invalid token name: {}", name);
+        }
+        if self.get(name).is_some() {
+            bail!("Token already exists: {}", name);
+        }
+        if scopes.is_empty() {
+            bail!("At least one scope is required");
+        }
+        let token = ApiToken::new();
+        self.tokens.push(TokenInfo {
+            name: name.to_string(),
+            token: token.to_base64(),
+            scopes,
+            created: unix_now(),
+        });
+        Ok(token)
+    }
+
+    /// Removes the token, doesn't save the store.
+    pub fn revoke(&mut self, name: &str) -> Result<(), Error> {
+        let len = self.tokens.len();
+        self.tokens.retain(|t| t.name != name);
+        if self.tokens.len() == len {
+            bail!("Token not found: {}", name);
+        }
+        Ok(())
+    }
+}
+
+/// A privileged call, written to the audit log.
+#[derive(Debug, Serialize)]
+struct AuditRecord<'a> {
+    timestamp: u64,
+    token: &'a str,
+    peer: String,
+    permission: String,
+    request: String,
+}
+
+/// Returns the type of request, without parameters (which can contain passwords).
+fn request_type(request: &RequestKind) -> String {
+    serde_json::to_value(request)
+        .ok()
+        .and_then(|v| v.get("type").and_then(|t| t.as_str()).map(String::from))
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+struct Tokens {
+    /// The token from `api.token`, has full access.
+    master: Option<ApiToken>,
+    store: Option<TokenStore>,
+    /// Path to the audit log, privileged calls are only logged if not set.
+    audit_log: Option<PathBuf>,
+}
+
+/// Tokens accepted by the API server.
+#[derive(Clone)]
+pub struct ApiTokens {
+    inner: Arc<Mutex<Tokens>>,
+}
+
+impl From<ApiToken> for ApiTokens {
+    fn from(master: ApiToken) -> ApiTokens {
+        ApiTokens::new(Some(master), None, None)
+    }
+}
+
+impl ApiTokens {
+    pub fn new(
+        master: Option<ApiToken>,
+        store: Option<TokenStore>,
+        audit_log: Option<PathBuf>,
+    ) -> Self {
+        let inner = Tokens {
+            master,
+            store,
+            audit_log,
+        };
+        ApiTokens {
+            inner: Arc::new(Mutex::new(inner)),
+        }
+    }
+
+    /// Finds the token the message is encrypted with.
+    pub fn authenticate(&self, msg: &str) -> Option<(String, ApiToken)> {
+        let msg = base64::decode(msg).ok()?;
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(master) = inner.master {
+            if try_decrypt(&master, &msg).is_some() {
+                return Some((MASTER_TOKEN_NAME.to_string(), master));
+            }
+        }
+        let store = inner.store.as_mut()?;
+        store.refresh();
+        for info in store.tokens() {
+            let token = match ApiToken::from_base64(&info.token) {
+                Ok(token) => token,
+                Err(e) => {
+                    error!(target: "stegos_api", "Invalid API token: name={}, error={}", info.name, e);
+                    continue;
+                }
+            };
+            if try_decrypt(&token, &msg).is_some() {
+                return Some((info.name.clone(), token));
+            }
+        }
+        None
+    }
+
+    /// Finds the token by its base64 value, as sent in the `Authorization` header.
+    pub fn authenticate_bearer(&self, token: &str) -> Option<String> {
+        let token = ApiToken::from_base64(token.trim()).ok()?;
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(master) = inner.master {
+            if bool::from(master.0.ct_eq(&token.0)) {
+                return Some(MASTER_TOKEN_NAME.to_string());
+            }
+        }
+        let store = inner.store.as_mut()?;
+        store.refresh();
+        store
+            .tokens()
+            .iter()
+            .find(|info| match ApiToken::from_base64(&info.token) {
+                Ok(known) => known.0.ct_eq(&token.0).into(),
+                Err(_) => false,
+            })
+            .map(|info| info.name.clone())
+    }
+
+    /// Returns the accounts allowed by the token, None if all accounts are allowed.
+    pub fn accounts(&self, token: &str) -> Option<HashSet<AccountId>> {
+        if token == MASTER_TOKEN_NAME {
+            return None;
+        }
+        let mut inner = self.inner.lock().unwrap();
+        match inner.store.as_mut() {
+            Some(store) => {
+                store.refresh();
+                match store.get(token) {
+                    Some(info) => info.accounts(),
+                    None => Some(HashSet::new()),
+                }
+            }
+            None => Some(HashSet::new()),
+        }
+    }
+
+    /// Returns false if the token has been revoked.
+    pub fn is_valid(&self, token: &str) -> bool {
+        if token == MASTER_TOKEN_NAME {
+            return true;
+        }
+        let mut inner = self.inner.lock().unwrap();
+        match inner.store.as_mut() {
+            Some(store) => {
+                store.refresh();
+                store.get(token).is_some()
+            }
+            None => false,
+        }
+    }
+
+    /// Checks the request against the scopes of the token, records privileged calls.
+    pub fn authorize(&self, token: &str, peer: &str, request: &RequestKind) -> Result<(), Error> {
+        let permission = required_permission(request);
+        let mut inner = self.inner.lock().unwrap();
+        if token != MASTER_TOKEN_NAME {
+            let store = match inner.store.as_mut() {
+                Some(store) => store,
+                None => bail!("Token has been revoked"),
+            };
+            store.refresh();
+            match store.get(token) {
+                Some(info) if info.allows(&permission) => {}
+                Some(_) => bail!(
+                    "Permission denied: token={}, required={}",
+                    token,
+                    permission
+                ),
+                None => bail!("Token has been revoked"),
+            }
+        }
+        if permission != Permission::Chain {
+            let record = AuditRecord {
+                timestamp: unix_now(),
+                token,
+                peer: peer.to_string(),
+                permission: permission.to_string(),
+                request: request_type(request),
+            };
+            let record = serde_json::to_string(&record).expect("serialized");
+            info!(target: "stegos_api::audit", "{}", record);
+            if let Some(path) = &inner.audit_log {
+                if let Err(e) = append_line(path, &record) {
+                    error!(target: "stegos_api", "Failed to write audit log {:?}: {}", path, e);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes a file which is only accessible by the owner.
+fn write_secret(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    // The mode is only applied to new files.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(data)
+}
+
+fn append_line(path: &Path, line: &str) -> std::io::Result<()> {
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{}", line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encode;
+    use stegos_wallet::api::AccountRequest;
+    use tempdir::TempDir;
+
+    fn account_request(account_id: &str) -> RequestKind {
+        RequestKind::WalletsRequest(WalletRequest::AccountRequest {
+            account_id: account_id.to_string(),
+            request: AccountRequest::BalanceInfo {},
+        })
+    }
+
+    #[test]
+    fn scopes() {
+        let status = RequestKind::NodeRequest(NodeRequest::StatusInfo {});
+        let pop = RequestKind::NodeRequest(NodeRequest::PopMicroBlock {});
+        let delete = RequestKind::WalletsRequest(WalletRequest::WalletControlRequest(
+            WalletControlRequest::DeleteAccount {
+                account_id: "1".to_string(),
+            },
+        ));
+        let peers = RequestKind::NetworkRequest(NetworkRequest::ConnectedNodesRequest {});
+        let clear = RequestKind::NetworkRequest(NetworkRequest::ClearPeerScores { peer_id: None });
+        let subscribe = RequestKind::WalletsRequest(WalletRequest::WalletControlRequest(
+            WalletControlRequest::SubscribeWalletUpdates {},
+        ));
+        assert_eq!(required_permission(&status), Permission::Chain);
+        assert_eq!(required_permission(&pop), Permission::Admin);
+        assert_eq!(required_permission(&delete), Permission::Admin);
+        assert_eq!(required_permission(&peers), Permission::Network);
+        assert_eq!(required_permission(&clear), Permission::Admin);
+        assert_eq!(required_permission(&subscribe), Permission::AnyAccount);
+        assert_eq!(
+            required_permission(&account_request("1")),
+            Permission::Account("1".to_string())
+        );
+
+        let account: TokenScope = "account:1".parse().unwrap();
+        assert!(account.allows(&Permission::Chain));
+        assert!(account.allows(&Permission::Account("1".to_string())));
+        assert!(!account.allows(&Permission::Account("2".to_string())));
+        assert!(!account.allows(&Permission::Wallet));
+        assert!(!account.allows(&Permission::Network));
+        assert!(account.allows(&Permission::AnyAccount));
+        assert!(!TokenScope::Chain.allows(&Permission::AnyAccount));
+        assert!(TokenScope::Wallet.allows(&Permission::Account("2".to_string())));
+        assert!(!TokenScope::Wallet.allows(&Permission::Admin));
+        assert!(!TokenScope::Chain.allows(&Permission::Network));
+        assert!(TokenScope::Admin.allows(&Permission::Admin));
+        assert!("account:".parse::<TokenScope>().is_err());
+        assert!("root".parse::<TokenScope>().is_err());
+    }
+
+    #[test]
+    fn store() {
+        let temp_dir = TempDir::new("tokens").unwrap();
+        let path = temp_dir.path().join("api_tokens.json");
+        let audit_log = temp_dir.path().join("api_audit.log");
+        let mut store = TokenStore::open(&path).unwrap();
+        let reader = store.create("reader", vec![TokenScope::Chain]).unwrap();
+        let account = store
+            .create("account", vec!["account:1".parse().unwrap()])
+            .unwrap();
+        assert!(store.create("reader", vec![TokenScope::Admin]).is_err());
+        assert!(store.create("empty", vec![]).is_err());
+        store.save().unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let master = ApiToken::new();
+        let store = TokenStore::open(&path).unwrap();
+        assert_eq!(store.tokens().len(), 2);
+        let tokens = ApiTokens::new(Some(master), Some(store), Some(audit_log.clone()));
+        let peer = "127.0.0.1:3145";
+        let msg = |token: &ApiToken| encode(token, &serde_json::json!({"type": "status_info"}));
+        assert_eq!(tokens.authenticate(&msg(&master)).unwrap().0, "master");
+        assert_eq!(tokens.authenticate(&msg(&reader)).unwrap().0, "reader");
+        assert_eq!(tokens.authenticate(&msg(&account)).unwrap().0, "account");
+        assert!(tokens.authenticate(&msg(&ApiToken::new())).is_none());
+        assert_eq!(
+            tokens.authenticate_bearer(&reader.to_base64()).unwrap(),
+            "reader"
+        );
+        assert!(tokens
+            .authenticate_bearer(&ApiToken::new().to_base64())
+            .is_none());
+
+        assert_eq!(tokens.accounts("master"), None);
+        assert_eq!(tokens.accounts("reader"), Some(HashSet::new()));
+        let accounts: HashSet<AccountId> = vec!["1".to_string()].into_iter().collect();
+        assert_eq!(tokens.accounts("account"), Some(accounts));
+
+        let status = RequestKind::NodeRequest(NodeRequest::StatusInfo {});
+        tokens.authorize("reader", peer, &status).unwrap();
+        assert!(tokens
+            .authorize("reader", peer, &account_request("1"))
+            .is_err());
+        tokens
+            .authorize("account", peer, &account_request("1"))
+            .unwrap();
+        assert!(tokens
+            .authorize("account", peer, &account_request("2"))
+            .is_err());
+        tokens
+            .authorize("master", peer, &account_request("2"))
+            .unwrap();
+        let audit = fs::read_to_string(&audit_log).unwrap();
+        assert_eq!(audit.lines().count(), 2);
+        assert!(audit.contains("\"token\":\"account\""));
+        assert!(audit.contains("\"request\":\"balance_info\""));
+
+        // Revocation takes effect without restart.
+        let mut store = TokenStore::open(&path).unwrap();
+        store.revoke("account").unwrap();
+        assert!(store.revoke("account").is_err());
+        // Make sure that the modification time changes.
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        store.save().unwrap();
+        assert!(tokens.authenticate(&msg(&account)).is_none());
+        assert!(!tokens.is_valid("account"));
+        assert!(tokens.is_valid("reader"));
+        assert!(tokens
+            .authorize("account", peer, &account_request("1"))
+            .is_err());
+    }
+}
@@ -0,0 +1,26 @@
+#!/bin/sh
+# Generates certificates for TLS tests of the API server.
+# Certificates are valid for 100 years, keys are RSA in PKCS8.
+set -e
+cd "$(dirname "$0")"
+
+openssl req -x509 -newkey rsa:2048 -nodes -days 36500 -subj "/CN=Stegos Test CA" \
+    -addext "basicConstraints=critical,CA:TRUE" -addext "keyUsage=critical,keyCertSign,cRLSign" \
+    -keyout ca.key -out ca.pem
+
+issue() {
+    name=$1; subject=$2; extensions=$3
+    openssl req -newkey rsa:2048 -nodes -subj "/CN=$subject" -keyout "$name.key" -out "$name.csr"
+    printf "basicConstraints=critical,CA:FALSE\nkeyUsage=critical,digitalSignature,keyEncipherment\n%s\n" \
+        "$extensions" > "$name.ext"
+    openssl x509 -req -in "$name.csr" -CA ca.pem -CAkey ca.key -CAcreateserial -days 36500 \
+        -extfile "$name.ext" -out "$name.pem"
+    rm "$name.csr" "$name.ext"
+}
+
+issue server localhost "subjectAltName=DNS:localhost
+extendedKeyUsage=serverAuth"
+issue reloaded reloaded.localhost "subjectAltName=DNS:reloaded.localhost
+extendedKeyUsage=serverAuth"
+issue client client "extendedKeyUsage=clientAuth"
+rm -f ca.key ca.srl
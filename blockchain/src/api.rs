@@ -19,6 +19,7 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 use crate::block::{MacroBlock, MacroBlockHeader, MicroBlock, MicroBlockHeader};
+use crate::governance::ParameterVote;
 use crate::output::{Output, PaymentOutput, PublicPaymentOutput, StakeOutput};
 use crate::timestamp::Timestamp;
 use crate::transaction::{
@@ -26,11 +27,14 @@ use crate::transaction::{
     SlashingTransaction, Transaction,
 };
 use bit_vec::BitVec;
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use schemars::JsonSchema;
 use serde_derive::{Deserialize, Serialize};
 use stegos_crypto::{hash::Hash, pbc};
 
 /// Macro Block.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct MacroBlockInfo {
     pub block_hash: Hash,
     /// Header.
@@ -43,6 +47,7 @@ pub struct MacroBlockInfo {
     /// Bitmap of signers in the multi-signature.
     #[serde(deserialize_with = "stegos_crypto::utils::deserialize_bitvec")]
     #[serde(serialize_with = "stegos_crypto::utils::serialize_bitvec")]
+    #[schemars(with = "String")]
     pub multisigmap: BitVec,
 
     /// The list of transaction inputs in a Merkle Tree.
@@ -50,6 +55,10 @@ pub struct MacroBlockInfo {
 
     /// The list of transaction outputs in a Merkle Tree.
     pub outputs: Vec<Output>,
+
+    /// Votes of validators for changes of chain parameters.
+    #[serde(default)]
+    pub parameter_votes: Vec<ParameterVote>,
 }
 
 impl From<MacroBlock> for MacroBlockInfo {
@@ -61,6 +70,7 @@ impl From<MacroBlock> for MacroBlockInfo {
             multisigmap: b.multisigmap,
             inputs: b.inputs,
             outputs: b.outputs,
+            parameter_votes: b.parameter_votes,
         }
     }
 }
@@ -73,11 +83,12 @@ impl From<MacroBlockInfo> for MacroBlock {
             multisigmap: b.multisigmap,
             inputs: b.inputs,
             outputs: b.outputs,
+            parameter_votes: b.parameter_votes,
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct MicroBlockInfo {
     pub block_hash: Hash,
     /// Header.
@@ -112,7 +123,7 @@ impl From<MicroBlockInfo> for MicroBlock {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "type")]
 #[serde(rename_all = "snake_case")]
 pub enum OriginalTransaction {
@@ -123,7 +134,7 @@ pub enum OriginalTransaction {
     ServiceAwardTransaction(ServiceAwardTransaction),
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct TransactionInfo {
     tx_hash: Hash,
     #[serde(flatten)]
@@ -158,7 +169,7 @@ impl From<OriginalTransaction> for Transaction {
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "type")]
 #[serde(rename_all = "snake_case")]
 pub enum OriginalOutput {
@@ -167,7 +178,7 @@ pub enum OriginalOutput {
     StakeOutput(StakeOutput),
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct OutputInfo {
     output_hash: Hash,
     #[serde(flatten)]
@@ -208,8 +219,26 @@ impl From<OutputInfo> for Output {
     }
 }
 
+/// Reachability of the node from the network.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Reachability {
+    /// Not checked yet.
+    Unknown,
+    /// Peers are able to connect to the node.
+    Public,
+    /// The node doesn't accept inbound connections or is behind NAT.
+    Private,
+}
+
+impl Default for Reachability {
+    fn default() -> Self {
+        Reachability::Unknown
+    }
+}
+
 /// Notification about synchronization status.
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct StatusInfo {
     pub is_synchronized: bool,
     pub epoch: u64,
@@ -219,6 +248,10 @@ pub struct StatusInfo {
     pub last_macro_block_hash: Hash,
     pub last_macro_block_timestamp: Timestamp,
     pub local_timestamp: Timestamp,
+    #[serde(default)]
+    pub reachability: Reachability,
+    #[serde(default)]
+    pub advertised_address: Option<String>,
 }
 
 impl Default for StatusInfo {
@@ -232,6 +265,8 @@ impl Default for StatusInfo {
             last_macro_block_hash: Hash::zero(),
             last_macro_block_timestamp: Timestamp::now(),
             local_timestamp: Timestamp::now(),
+            reachability: Reachability::Unknown,
+            advertised_address: None,
         }
     }
 }
@@ -250,3 +285,23 @@ impl From<TransactionInfo> for Transaction {
         b.tx.into()
     }
 }
+
+/// Implements JsonSchema of a type serialized through `$info`.
+macro_rules! json_schema_as {
+    ($type:ident, $info:ty) => {
+        impl JsonSchema for $type {
+            fn schema_name() -> String {
+                stringify!($type).to_string()
+            }
+
+            fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+                <$info>::json_schema(gen)
+            }
+        }
+    };
+}
+
+json_schema_as!(MacroBlock, MacroBlockInfo);
+json_schema_as!(MicroBlock, MicroBlockInfo);
+json_schema_as!(Transaction, TransactionInfo);
+json_schema_as!(Output, OutputInfo);
@@ -22,6 +22,7 @@
 // TODO: Choose difficulty.
 use crate::metrics;
 use log::{debug, info, trace};
+use schemars::JsonSchema;
 use serde_derive::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::mem;
@@ -29,7 +30,7 @@ use stegos_crypto::hash::Hash;
 use stegos_crypto::scc::PublicKey;
 use stegos_crypto::utils::print_nbits;
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "status")]
 #[serde(rename_all = "snake_case")]
 pub enum ValidatorAwardState {
@@ -40,7 +41,7 @@ pub enum ValidatorAwardState {
 }
 
 /// Current award state, and budget count.
-#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct Awards {
     pub budget: i64,
     // num of bits to be zero in VRF.
@@ -22,12 +22,14 @@
 // SOFTWARE.
 
 use crate::error::TransactionError;
+use crate::governance::ParameterVote;
 use crate::merkle::*;
 use crate::output::*;
 use crate::timestamp::Timestamp;
-use crate::transaction::Transaction;
+use crate::transaction::{SlashingTransaction, Transaction};
 use crate::view_changes::ViewChangeProof;
 use bit_vec::BitVec;
+use schemars::JsonSchema;
 use serde_derive::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::collections::BTreeSet;
@@ -38,12 +40,15 @@ use stegos_crypto::scc::Fr;
 /// Blockchain version.
 pub const VERSION: u64 = 1;
 
+/// Version of macro blocks with governance votes.
+pub const GOVERNANCE_VERSION: u64 = 2;
+
 //--------------------------------------------------------------------------------------------------
 // Micro Blocks.
 //--------------------------------------------------------------------------------------------------
 
 /// Micro Block Header.
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct MicroBlockHeader {
     /// Version number.
     pub version: u64,
@@ -72,6 +77,7 @@ pub struct MicroBlockHeader {
     /// Solution for VDF.
     #[serde(deserialize_with = "stegos_crypto::utils::vec_deserialize_from_hex")]
     #[serde(serialize_with = "stegos_crypto::utils::vec_serialize_to_hex")]
+    #[schemars(with = "String")]
     pub solution: Vec<u8>,
 
     /// UNIX timestamp of block creation.
@@ -127,6 +133,27 @@ pub struct LightMicroBlock {
     pub output_hashes: Vec<Hash>,
     /// Output canaries.
     pub canaries: Vec<Canary>,
+    /// Slashing transactions, which remove validators until the end of epoch.
+    pub slashing_transactions: Vec<LightSlashingTransaction>,
+}
+
+/// Slashing transaction for the light node.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LightSlashingTransaction {
+    /// The transaction.
+    pub transaction: SlashingTransaction,
+    /// Merkle proof against transactions_range_hash of the block.
+    pub proof: MerkleProof,
+}
+
+impl LightSlashingTransaction {
+    /// Check that the transaction is included into the block.
+    pub fn verify(&self, transactions_range_hash: &Hash) -> bool {
+        let tx: Transaction = self.transaction.clone().into();
+        let mut hasher = Hasher::new();
+        tx.fullhash(&mut hasher);
+        self.proof.verify(&hasher.result(), transactions_range_hash)
+    }
 }
 
 impl Hashable for MicroBlockHeader {
@@ -313,12 +340,35 @@ impl MicroBlock {
         let input_hashes: Vec<Hash> = self.inputs().cloned().collect();
         let canaries: Vec<Canary> = self.outputs().map(|o| o.canary()).collect();
         let output_hashes: Vec<Hash> = self.outputs().map(Hash::digest).collect();
+        let transaction_hashes: Vec<Hash> = self
+            .transactions
+            .iter()
+            .map(|tx| {
+                let mut hasher = Hasher::new();
+                tx.fullhash(&mut hasher);
+                hasher.result()
+            })
+            .collect();
+        let slashing_transactions = self
+            .transactions
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, tx)| match tx {
+                Transaction::SlashingTransaction(transaction) => {
+                    let proof = Merkle::proof_from_array(&transaction_hashes, index)
+                        .expect("index is valid");
+                    Some(LightSlashingTransaction { transaction, proof })
+                }
+                _ => None,
+            })
+            .collect();
         LightMicroBlock {
             header: self.header,
             sig: self.sig,
             input_hashes,
             output_hashes,
             canaries,
+            slashing_transactions,
         }
     }
 }
@@ -337,7 +387,7 @@ impl From<MicroBlock> for LightMicroBlock {
 pub type StakersGroup = Vec<(pbc::PublicKey, i64)>;
 
 /// Macro Block Header.
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct MacroBlockHeader {
     /// Version number.
     pub version: u64,
@@ -372,6 +422,7 @@ pub struct MacroBlockHeader {
     /// Bitmap of active validators in epoch.
     #[serde(deserialize_with = "stegos_crypto::utils::deserialize_bitvec")]
     #[serde(serialize_with = "stegos_crypto::utils::serialize_bitvec")]
+    #[schemars(with = "String")]
     pub activity_map: BitVec,
 
     /// The number of validators for the next epoch.
@@ -394,6 +445,12 @@ pub struct MacroBlockHeader {
 
     /// Merklish root of all canary hashes.
     pub canaries_range_hash: Hash,
+
+    /// The number of governance votes in this block.
+    pub parameter_votes_len: u32,
+
+    /// Merklish root of all governance votes.
+    pub parameter_votes_range_hash: Hash,
 }
 
 impl Hashable for MacroBlockHeader {
@@ -420,6 +477,10 @@ impl Hashable for MacroBlockHeader {
         self.outputs_len.hash(state);
         self.outputs_range_hash.hash(state);
         self.canaries_range_hash.hash(state);
+        if self.version >= GOVERNANCE_VERSION {
+            self.parameter_votes_len.hash(state);
+            self.parameter_votes_range_hash.hash(state);
+        }
     }
 }
 
@@ -442,6 +503,9 @@ pub struct MacroBlock {
 
     /// The list of transaction outputs in a Merkle Tree.
     pub outputs: Vec<Output>,
+
+    /// Votes of validators for changes of chain parameters.
+    pub parameter_votes: Vec<ParameterVote>,
 }
 
 /// Macro Block for the light node.
@@ -463,6 +527,8 @@ pub struct LightMacroBlock {
     pub output_hashes: Vec<Hash>,
     /// Output canaries.
     pub canaries: Vec<Canary>,
+    /// Governance votes.
+    pub parameter_votes: Vec<ParameterVote>,
 }
 
 impl MacroBlock {
@@ -631,6 +697,8 @@ impl MacroBlock {
             outputs_len,
             outputs_range_hash,
             canaries_range_hash,
+            parameter_votes_len: 0,
+            parameter_votes_range_hash: Hash::zero(),
         };
 
         // Create the block.
@@ -642,9 +710,21 @@ impl MacroBlock {
             multisigmap,
             inputs,
             outputs,
+            parameter_votes: Vec::new(),
         }
     }
 
+    /// Set governance votes of this block, must be called before signing.
+    /// Upgrades the block to GOVERNANCE_VERSION.
+    pub fn set_parameter_votes(&mut self, mut parameter_votes: Vec<ParameterVote>) {
+        assert!(parameter_votes.len() <= std::u32::MAX as usize);
+        parameter_votes.sort_by_cached_key(Hash::digest);
+        self.header.version = GOVERNANCE_VERSION;
+        self.header.parameter_votes_len = parameter_votes.len() as u32;
+        self.header.parameter_votes_range_hash = Merkle::root_hash_from_array(&parameter_votes);
+        self.parameter_votes = parameter_votes;
+    }
+
     pub fn into_light_macro_block(self, validators: StakersGroup) -> LightMacroBlock {
         let input_hashes: Vec<Hash> = self.inputs;
         let outputs: Vec<Output> = self.outputs;
@@ -658,6 +738,7 @@ impl MacroBlock {
             input_hashes,
             output_hashes,
             canaries,
+            parameter_votes: self.parameter_votes,
         }
     }
 }
@@ -27,15 +27,22 @@ use crate::block::*;
 use crate::config::*;
 use crate::election::mix;
 use crate::election::ElectionInfo;
-use crate::election::{self, ElectionResult};
+use crate::election::{self, ElectionInputs, ElectionProof, ElectionResult, RandomProof};
 use crate::error::*;
 use crate::escrow::*;
+use crate::governance::{
+    tally_votes, ChainParametersInfo, ParameterChange, ParameterChanges, ParameterVote,
+};
+use crate::light::OutputProof;
 use crate::merkle::Merkle;
 use crate::metrics;
 use crate::mvcc::MultiVersionedMap;
 use crate::output::*;
 use crate::timestamp::Timestamp;
 use crate::transaction::{CoinbaseTransaction, ServiceAwardTransaction, Transaction};
+use crate::validator_stats::{
+    epoch_stats, is_complete_epoch, stats_key, MicroBlockStats, ValidatorEpochStats,
+};
 use crate::view_changes::ViewChangeProof;
 use crate::BlockReader;
 use bit_vec::BitVec;
@@ -44,6 +51,7 @@ use failure::Error;
 use log::*;
 use rocksdb;
 use rocksdb::{ColumnFamily, Snapshot, WriteBatch};
+use schemars::JsonSchema;
 use serde_derive::{Deserialize, Serialize};
 use std::borrow::Cow;
 use std::collections::{BTreeMap, HashMap, VecDeque};
@@ -60,7 +68,7 @@ pub type ViewCounter = u32;
 pub type ValidatorId = u32;
 
 /// Saved information about validator, and its slotcount in epoch.
-#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct ValidatorKeyInfo {
     pub network_pkey: pbc::PublicKey,
     pub account_pkey: scc::PublicKey,
@@ -68,13 +76,13 @@ pub struct ValidatorKeyInfo {
 }
 
 /// Information about service award payout.
-#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct PayoutInfo {
     pub recipient: scc::PublicKey,
     pub amount: i64,
 }
 /// Full information about service award state.
-#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct AwardsInfo {
     #[serde(flatten)]
     pub service_award_state: Awards,
@@ -83,7 +91,7 @@ pub struct AwardsInfo {
 }
 
 /// Retrospective information for some epoch.
-#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct EpochInfo {
     pub validators: Vec<ValidatorKeyInfo>,
     pub facilitator: pbc::PublicKey,
@@ -105,6 +113,7 @@ pub struct LightEpochInfo {
     pub header: MacroBlockHeader,
     pub validators: StakersGroup,
     pub facilitator: pbc::PublicKey,
+    pub parameter_changes: Vec<ParameterChange>,
 }
 
 /// Information of current chain, that is used as proof of viewchange.
@@ -215,6 +224,8 @@ const ESCROW: &'static str = "escrow";
 
 const SERVICE_AWARD: &'static str = "service_award";
 const EPOCH_INFOS: &'static str = "epoch_infos";
+const VALIDATOR_STATS: &'static str = "validator_stats";
+const ELECTION_INPUTS: &'static str = "election_inputs";
 const META: &'static str = "META";
 
 const COLON_FAMILIES: &[&'static str] = &[
@@ -223,6 +234,8 @@ const COLON_FAMILIES: &[&'static str] = &[
     ESCROW,
     SERVICE_AWARD,
     EPOCH_INFOS,
+    VALIDATOR_STATS,
+    ELECTION_INPUTS,
     META,
 ];
 
@@ -231,6 +244,7 @@ const BALANCE: &'static str = "balance";
 const EPOCH: &'static str = "epoch";
 const ELECTION_RESULT: &'static str = "election_result";
 const AWARDS: &'static str = "awards";
+const PARAMETER_CHANGES: &'static str = "parameter_changes";
 
 /// The blockchain database.
 pub struct Blockchain {
@@ -291,6 +305,20 @@ pub struct Blockchain {
     awards: Awards,
     epoch_activity: ValidatorsActivity,
 
+    //
+    // Validator statistics.
+    //
+    /// Leaders of micro blocks in the current epoch, by offset.
+    /// Not reverted by pop_micro_block() - entries are replaced when a new block is registered
+    /// at the same offset.
+    micro_blocks_stats: BTreeMap<u32, MicroBlockStats>,
+
+    //
+    // Governance.
+    //
+    /// All parameter changes accepted by validators, ordered by activation epoch.
+    parameter_changes: Vec<ParameterChange>,
+
     // Block ache
     cache: VecDeque<Block>,
 }
@@ -347,6 +375,14 @@ impl Blockchain {
         //
         let awards = Awards::new(cfg.awards_difficulty);
         let epoch_activity = MultiVersionedMap::new();
+        //
+        // Validator statistics.
+        //
+        let micro_blocks_stats = BTreeMap::new();
+        //
+        // Governance.
+        //
+        let parameter_changes = Vec::new();
         // Block cache.
         let cache = VecDeque::with_capacity(cfg.stake_epochs as usize + 1);
 
@@ -371,6 +407,8 @@ impl Blockchain {
             last_block_hash,
             awards,
             epoch_activity,
+            micro_blocks_stats,
+            parameter_changes,
             cache,
         };
 
@@ -463,6 +501,17 @@ impl Blockchain {
         self.difficulty = block.header.difficulty;
         debug!("Set difficulty to to {}", self.difficulty);
         self.awards = recover_meta!(AWARDS);
+        if let Some(changes) = self
+            .database
+            .get_cf(cf_meta, PARAMETER_CHANGES.as_bytes())?
+        {
+            let changes = ParameterChanges::from_buffer(&changes)?.0;
+            let epoch = self.epoch;
+            for change in changes.iter().filter(|c| c.epoch <= epoch) {
+                change.parameter.apply(&mut self.cfg);
+            }
+            self.parameter_changes = changes;
+        }
         info!("Snapshot recovered, recovering microblocks of last epoch.");
         // microblocks starting index is (next epoch, and zero offset);
         let mut microblock_lsn = lsn;
@@ -741,6 +790,66 @@ impl Blockchain {
         }
     }
 
+    /// Resolve UTXO by hash with a Merkle proof of inclusion into the block.
+    pub fn output_proof(&self, output_hash: &Hash) -> Result<Option<OutputProof>, StorageError> {
+        match self.output_by_hash.get(output_hash) {
+            Some(OutputKey::MacroBlock { epoch, output_id }) => {
+                let block = self.macro_block(*epoch)?;
+                let output = match block.outputs.get(*output_id as usize) {
+                    Some(output) => output.clone(),
+                    None => return Ok(None), // Pruned.
+                };
+                let output_hashes: Vec<Hash> = block.outputs.iter().map(Hash::digest).collect();
+                let proof = Merkle::proof_from_array(&output_hashes, *output_id as usize)
+                    .expect("output exists");
+                Ok(Some(OutputProof {
+                    output,
+                    epoch: *epoch,
+                    offset: None,
+                    block_hash: Hash::digest(block.as_ref()),
+                    proof,
+                }))
+            }
+            Some(OutputKey::MicroBlock {
+                epoch,
+                offset,
+                tx_id,
+                txout_id,
+            }) => {
+                let block = self.micro_block(*epoch, *offset)?;
+                let tx = block
+                    .transactions
+                    .get(*tx_id as usize)
+                    .expect("Corrupted outputs_by_hash (Micro-2)");
+                let output = tx
+                    .txouts()
+                    .get(*txout_id as usize)
+                    .expect("Corrupted outputs_by_hash (Micro-3)")
+                    .clone();
+                // Outputs of all transactions are hashed as a single array.
+                let index: usize = block.transactions[..*tx_id as usize]
+                    .iter()
+                    .map(|tx| tx.txouts().len())
+                    .sum::<usize>()
+                    + *txout_id as usize;
+                let output_hashes: Vec<Hash> = block
+                    .transactions
+                    .iter()
+                    .flat_map(|tx| tx.txouts().iter().map(Hash::digest))
+                    .collect();
+                let proof = Merkle::proof_from_array(&output_hashes, index).expect("output exists");
+                Ok(Some(OutputProof {
+                    output,
+                    epoch: *epoch,
+                    offset: Some(*offset),
+                    block_hash: Hash::digest(block.as_ref()),
+                    proof,
+                }))
+            }
+            None => Ok(None),
+        }
+    }
+
     /// Resolve UTXO by hash.
     #[inline]
     pub fn output_by_hash(&self, output_hash: &Hash) -> Result<Option<Output>, StorageError> {
@@ -917,6 +1026,169 @@ impl Blockchain {
             .transpose()?)
     }
 
+    /// Returns current chain parameters and accepted changes for future epochs.
+    pub fn chain_parameters(&self) -> ChainParametersInfo {
+        let pending = self
+            .parameter_changes
+            .iter()
+            .filter(|change| change.epoch > self.epoch)
+            .cloned()
+            .collect();
+        ChainParametersInfo {
+            epoch: self.epoch,
+            current: self.cfg.clone(),
+            pending,
+        }
+    }
+
+    /// Returns stakers of the election made by the macro block of `epoch`.
+    fn election_inputs(&self, epoch: u64) -> Result<Option<ElectionInputs>, BlockchainError> {
+        let cf_election_inputs = self.database.cf_handle(ELECTION_INPUTS).unwrap();
+        let election_inputs = self.database.get_cf(
+            cf_election_inputs,
+            &Self::block_key(LSN(epoch, MACRO_BLOCK_OFFSET)),
+        )?;
+        Ok(election_inputs
+            .map(|some| ProtoConvert::from_buffer(&some))
+            .transpose()?)
+    }
+
+    /// Returns inputs of the leader election for a block at (epoch, offset, view_change).
+    /// Micro blocks of past epochs are not kept, so only offset 0 is available for them.
+    pub fn election_proof(
+        &self,
+        epoch: u64,
+        offset: u32,
+        view_change: u32,
+    ) -> Result<ElectionProof, BlockchainError> {
+        if epoch == 0
+            || epoch > self.epoch
+            || (epoch < self.epoch && offset > 0)
+            || (epoch == self.epoch && offset > self.offset)
+        {
+            return Err(BlockchainError::ElectionProofUnavailable(epoch, offset));
+        }
+        let (inputs, epoch_info) = match (
+            self.election_inputs(epoch - 1)?,
+            self.epoch_info(epoch - 1)?,
+        ) {
+            (Some(inputs), Some(epoch_info)) => (inputs, epoch_info),
+            // Database was created before election inputs were saved.
+            _ => return Err(BlockchainError::ElectionProofUnavailable(epoch, offset)),
+        };
+
+        let header = self.macro_block(epoch - 1)?.header.clone();
+        let seed = if epoch - 1 == 0 {
+            Hash::digest("genesis")
+        } else {
+            self.macro_block(epoch - 2)?.header.random.rand
+        };
+        let epoch_random = RandomProof {
+            pkey: header.pkey,
+            seed,
+            view_change: header.view_change,
+            random: header.random,
+        };
+
+        let validators = epoch_info.into_stakers_group();
+        let (block_random, slashed) = if offset == 0 {
+            (None, Vec::new())
+        } else {
+            let block = self.micro_block(epoch, offset - 1)?;
+            let seed = if offset == 1 {
+                header.random.rand
+            } else {
+                self.micro_block(epoch, offset - 2)?.header.random.rand
+            };
+            let block_random = RandomProof {
+                pkey: block.header.pkey,
+                seed,
+                view_change: block.header.view_change,
+                random: block.header.random,
+            };
+            let election = self.election_result_by_offset(offset)?;
+            let slashed: Vec<pbc::PublicKey> = validators
+                .iter()
+                .map(|(k, _)| *k)
+                .filter(|k| !election.is_validator(k))
+                .collect();
+            (Some(block_random), slashed)
+        };
+
+        let random = block_random
+            .as_ref()
+            .map(|r| r.random.rand)
+            .unwrap_or(header.random.rand);
+        let active: StakersGroup = validators
+            .iter()
+            .filter(|(k, _)| !slashed.contains(k))
+            .cloned()
+            .collect();
+        let leader = election::select_leader(&active, &random, view_change);
+
+        Ok(ElectionProof {
+            epoch,
+            offset,
+            view_change,
+            stakers: inputs.stakers,
+            slot_count: inputs.slot_count,
+            epoch_random,
+            validators,
+            slashed,
+            block_random,
+            leader,
+        })
+    }
+
+    /// Returns statistics of validator for every finished epoch in [from_epoch, to_epoch].
+    pub fn validator_stats(
+        &self,
+        validator: &pbc::PublicKey,
+        from_epoch: u64,
+        to_epoch: u64,
+    ) -> Result<Vec<ValidatorEpochStats>, BlockchainError> {
+        let cf_validator_stats = self.database.cf_handle(VALIDATOR_STATS).unwrap();
+        let start = stats_key(validator, from_epoch);
+        let end = stats_key(validator, to_epoch);
+        let mode = rocksdb::IteratorMode::From(&start, rocksdb::Direction::Forward);
+        let mut stats = Vec::new();
+        for (key, value) in self.database.iterator_cf(cf_validator_stats, mode)? {
+            if key[..] > end[..] {
+                break;
+            }
+            stats.push(ValidatorEpochStats::from_buffer(&value)?);
+        }
+        Ok(stats)
+    }
+
+    /// Saves prevotes for the macro block of `epoch`, as observed by the local consensus.
+    pub fn record_prevotes(
+        &self,
+        epoch: u64,
+        prevoters: &[pbc::PublicKey],
+    ) -> Result<(), BlockchainError> {
+        if epoch == 0 {
+            return Ok(());
+        }
+        // Validators of epoch are saved in the epoch info of the previous macro block.
+        let validators = match self.epoch_info(epoch - 1)? {
+            Some(epoch_info) => epoch_info.validators,
+            None => return Ok(()),
+        };
+        let cf_validator_stats = self.database.cf_handle(VALIDATOR_STATS).unwrap();
+        let mut batch = WriteBatch::default();
+        for validator in validators {
+            let key = stats_key(&validator.network_pkey, epoch);
+            if let Some(value) = self.database.get_cf(cf_validator_stats, &key)? {
+                let mut stats = ValidatorEpochStats::from_buffer(&value)?;
+                stats.prevoted = Some(prevoters.contains(&validator.network_pkey));
+                batch.put_cf(cf_validator_stats, &key, &stats.into_buffer()?)?;
+            }
+        }
+        self.database.write(batch)?;
+        Ok(())
+    }
+
     /// Returns current state of election result.
     /// Note:
     /// Election result changes on epoch start, and on slashing.
@@ -1229,6 +1501,8 @@ impl Blockchain {
             last_macro_block_hash: self.last_macro_block_hash,
             last_macro_block_timestamp: self.last_macro_block_timestamp,
             local_timestamp: Timestamp::now(),
+            reachability: Default::default(),
+            advertised_address: None,
         }
     }
 
@@ -1328,6 +1602,7 @@ impl Blockchain {
         network_skey: &pbc::SecretKey,
         network_pkey: pbc::PublicKey,
         timestamp: Timestamp,
+        parameter_votes: Vec<ParameterVote>,
     ) -> (MacroBlock, Vec<Transaction>) {
         assert!(self.is_epoch_full());
         let epoch = self.epoch();
@@ -1402,7 +1677,7 @@ impl Blockchain {
 
         let validators = self.next_election_result(random).validators;
 
-        let block = MacroBlock::from_transactions(
+        let mut block = MacroBlock::from_transactions(
             previous,
             epoch,
             view_change,
@@ -1416,6 +1691,9 @@ impl Blockchain {
             &transactions,
         )
         .expect("Transactions are valid");
+        if self.cfg.macro_block_version(epoch) >= GOVERNANCE_VERSION {
+            block.set_parameter_votes(parameter_votes);
+        }
 
         (block, extra_transactions)
     }
@@ -1466,7 +1744,10 @@ impl Blockchain {
         lsn: LSN,
         block: MacroBlock,
     ) -> Result<(Vec<Hash>, HashMap<Hash, Output>), BlockchainError> {
-        assert_eq!(block.header.version, VERSION);
+        assert_eq!(
+            block.header.version,
+            self.cfg.macro_block_version(block.header.epoch)
+        );
         assert_eq!(self.epoch, block.header.epoch);
         assert_eq!(self.offset(), 0);
         assert_eq!(block.header.previous, self.last_macro_block_hash);
@@ -1549,6 +1830,44 @@ impl Blockchain {
             None
         };
 
+        //
+        // Calculate validator statistics (skip genesis).
+        //
+        let validator_stats = if epoch > 0 {
+            let validators = self.validators_at_epoch_start();
+            let accounts: HashMap<pbc::PublicKey, scc::PublicKey> = validators
+                .iter()
+                .filter_map(|(k, _)| self.escrow.account_by_network_key(k).map(|a| (*k, a)))
+                .collect();
+            let micro_blocks =
+                if is_complete_epoch(&self.micro_blocks_stats, self.cfg.micro_blocks_in_epoch) {
+                    Some(&self.micro_blocks_stats)
+                } else {
+                    None
+                };
+            epoch_stats(
+                epoch,
+                &validators,
+                micro_blocks,
+                &block.header.activity_map,
+                &block.multisigmap,
+                &accounts,
+                winner,
+            )
+        } else {
+            Vec::new()
+        };
+        self.micro_blocks_stats.clear();
+
+        //
+        // Count governance votes (skip genesis).
+        //
+        let accepted_changes = if epoch > 0 {
+            tally_votes(&block.parameter_votes, &self.validators_at_epoch_start())
+        } else {
+            Vec::new()
+        };
+
         //
         // Register block.
         //
@@ -1564,7 +1883,17 @@ impl Blockchain {
         //
         // Check validators.
         //
-        let election_result = self.next_election_result(block.header.random);
+        let election_inputs = ElectionInputs {
+            stakers: self
+                .escrow
+                .get_stakers_majority(self.epoch + 1, self.cfg.min_stake_amount),
+            slot_count: self.cfg.max_slot_count,
+        };
+        let election_result = election::select_validators_slots(
+            election_inputs.stakers.clone(),
+            block.header.random,
+            election_inputs.slot_count,
+        );
         let validators_len = election_result.validators.len() as u32;
         if block.header.validators_len != validators_len {
             panic!(
@@ -1597,6 +1926,31 @@ impl Blockchain {
         self.difficulty = block.header.difficulty;
         debug!("Set difficulty to to {}", self.difficulty);
 
+        //
+        // Apply governance.
+        //
+        for change in accepted_changes {
+            if self.parameter_changes.contains(&change) {
+                continue;
+            }
+            info!(
+                "Scheduled a change of chain parameter: epoch={}, parameter={:?}",
+                change.epoch, change.parameter
+            );
+            self.parameter_changes.push(change);
+        }
+        // Sic: stable sort keeps the order of acceptance for the same epoch.
+        self.parameter_changes.sort_by_key(|change| change.epoch);
+        for change in &self.parameter_changes {
+            if change.epoch == self.epoch {
+                info!(
+                    "Changed chain parameter: epoch={}, parameter={:?}",
+                    change.epoch, change.parameter
+                );
+                change.parameter.apply(&mut self.cfg);
+            }
+        }
+
         //
         // Update metrics.
         //
@@ -1631,6 +1985,8 @@ impl Blockchain {
         let cf_output_by_hash = self.database.cf_handle(OUTPUT_BY_HASH).unwrap();
         let cf_escrow = self.database.cf_handle(ESCROW).unwrap();
         let cf_epoch_infos = self.database.cf_handle(EPOCH_INFOS).unwrap();
+        let cf_validator_stats = self.database.cf_handle(VALIDATOR_STATS).unwrap();
+        let cf_election_inputs = self.database.cf_handle(ELECTION_INPUTS).unwrap();
         let cf_meta = self.database.cf_handle(META).unwrap();
         let mut batch = batch.unwrap_or_default();
         //
@@ -1663,6 +2019,12 @@ impl Blockchain {
             self.election_result(),
         )?;
         Self::write_meta(&mut batch, &cf_meta, AWARDS, &self.awards)?;
+        Self::write_meta(
+            &mut batch,
+            &cf_meta,
+            PARAMETER_CHANGES,
+            &ParameterChanges(self.parameter_changes.clone()),
+        )?;
 
         let validators = self
             .election_result()
@@ -1703,6 +2065,25 @@ impl Blockchain {
             &Self::block_key(LSN(epoch, MACRO_BLOCK_OFFSET)),
             &data,
         )?;
+        batch.put_cf(
+            cf_election_inputs,
+            &Self::block_key(LSN(epoch, MACRO_BLOCK_OFFSET)),
+            &election_inputs.into_buffer()?,
+        )?;
+        for (validator, mut stats) in validator_stats {
+            let key = stats_key(&validator, epoch);
+            if let Some(prev) = self.database.get_cf(cf_validator_stats, &key)? {
+                // Keep information which can't be recovered from the macro block alone.
+                let prev = ValidatorEpochStats::from_buffer(&prev)?;
+                if stats.blocks_produced.is_none() {
+                    stats.blocks_produced = prev.blocks_produced;
+                    stats.blocks_missed = prev.blocks_missed;
+                    stats.block_rewards = prev.block_rewards;
+                }
+                stats.prevoted = stats.prevoted.or(prev.prevoted);
+            }
+            batch.put_cf(cf_validator_stats, &key, &stats.into_buffer()?)?;
+        }
         self.epoch_activity.reset();
         self.database.write(batch)?;
 
@@ -2024,6 +2405,21 @@ impl Blockchain {
                 .insert(lsn, leader, ValidatorAwardState::Active);
         }
 
+        //
+        // Update validator statistics.
+        //
+        let skipped = (0..block.header.view_change)
+            .map(|view_change| self.election_result().select_leader(view_change))
+            .collect();
+        // Drop blocks which were reverted by pop_micro_block().
+        let _ = self.micro_blocks_stats.split_off(&offset);
+        let stats = MicroBlockStats {
+            leader,
+            skipped,
+            block_reward,
+        };
+        self.micro_blocks_stats.insert(offset, stats);
+
         //
         // Register block.
         //
@@ -2260,6 +2656,8 @@ impl Blockchain {
 pub mod tests {
     use super::*;
 
+    use crate::governance::{ChainParameter, MAX_VOTES_PER_VALIDATOR};
+    use crate::light::LightVerifier;
     use crate::test;
     use crate::timestamp::Timestamp;
     use rand::Rng;
@@ -2912,6 +3310,156 @@ pub mod tests {
             chain.last_macro_block_timestamp()
         );
     }
+
+    fn push_fake_epoch(
+        chain: &mut Blockchain,
+        keychains: &[test::KeyChain],
+        timestamp: &mut Timestamp,
+        parameter_votes: Vec<ParameterVote>,
+    ) {
+        for _offset in 0..chain.cfg().micro_blocks_in_epoch {
+            *timestamp += Duration::from_millis(1);
+            let (block, _input_hashes, _output_hashes) =
+                test::create_fake_micro_block(chain, keychains, *timestamp);
+            chain
+                .push_micro_block(block, *timestamp)
+                .expect("Invalid block");
+        }
+        *timestamp += Duration::from_millis(1);
+        let (block, _extra_transactions) =
+            test::create_fake_macro_block_with_votes(chain, keychains, *timestamp, parameter_votes);
+        while chain.offset() > 0 {
+            chain.pop_micro_block().expect("Should be ok");
+        }
+        chain
+            .push_macro_block(block, *timestamp)
+            .expect("Invalid block");
+    }
+
+    #[test]
+    fn governance() {
+        simple_logger::init_with_level(log::Level::Debug).unwrap_or_default();
+        const NUM_NODES: usize = 3;
+        let mut cfg: ChainConfig = Default::default();
+        cfg.micro_blocks_in_epoch = 1;
+        let mut timestamp = Timestamp::now();
+        let (keychains, genesis) = test::fake_genesis(
+            cfg.min_stake_amount,
+            (NUM_NODES as i64) * cfg.min_stake_amount + 100,
+            cfg.max_slot_count,
+            NUM_NODES,
+            timestamp,
+            None,
+        );
+        let chain_dir = TempDir::new("test").unwrap();
+        let mut chain = Blockchain::new(
+            cfg.clone(),
+            chain_dir.path(),
+            ConsistencyCheck::Full,
+            genesis.clone(),
+            timestamp,
+        )
+        .expect("Failed to create blockchain");
+        assert_eq!(chain.epoch(), 1);
+
+        // Votes of all validators.
+        let reward = ChainParameter::BlockReward(10);
+        let votes: Vec<ParameterVote> = keychains
+            .iter()
+            .map(|k| ParameterVote::new(1, reward, 3, &k.network_skey, &k.network_pkey))
+            .collect();
+        for vote in &votes {
+            chain.validate_parameter_vote(vote).expect("valid vote");
+        }
+
+        // Too many votes from one validator.
+        let spam: Vec<ParameterVote> = (0..=MAX_VOTES_PER_VALIDATOR as u64)
+            .map(|i| {
+                let k = &keychains[0];
+                ParameterVote::new(1, reward, 3 + i, &k.network_skey, &k.network_pkey)
+            })
+            .collect();
+        for _offset in 0..chain.cfg().micro_blocks_in_epoch {
+            timestamp += Duration::from_millis(1);
+            let (block, _input_hashes, _output_hashes) =
+                test::create_fake_micro_block(&chain, &keychains, timestamp);
+            chain
+                .push_micro_block(block, timestamp)
+                .expect("Invalid block");
+        }
+        timestamp += Duration::from_millis(1);
+        let (block, _extra_transactions) =
+            test::create_fake_macro_block_with_votes(&chain, &keychains, timestamp, spam);
+        while chain.offset() > 0 {
+            chain.pop_micro_block().expect("Should be ok");
+        }
+        match chain.validate_macro_block(&block, timestamp) {
+            Err(BlockchainError::GovernanceError(GovernanceError::TooManyVotes(pkey))) => {
+                assert_eq!(pkey, keychains[0].network_pkey)
+            }
+            e => panic!("{:?}", e),
+        }
+
+        // The change is scheduled.
+        push_fake_epoch(&mut chain, &keychains, &mut timestamp, votes);
+        assert_eq!(chain.epoch(), 2);
+        let change = ParameterChange {
+            epoch: 3,
+            parameter: reward,
+        };
+        assert_eq!(chain.chain_parameters().pending, vec![change]);
+        assert_eq!(chain.cfg().block_reward, cfg.block_reward);
+
+        // The change is applied.
+        push_fake_epoch(&mut chain, &keychains, &mut timestamp, Vec::new());
+        assert_eq!(chain.epoch(), 3);
+        assert!(chain.chain_parameters().pending.is_empty());
+        assert_eq!(chain.cfg().block_reward, 10);
+
+        // Light clients follow the change.
+        let mut verifier = LightVerifier::new(Hash::digest(&genesis), cfg.clone());
+        for block in chain.light_blocks_starting(0, 0) {
+            verifier.apply_light_block(&block).expect("valid block");
+        }
+        assert_eq!(verifier.cfg().block_reward, 10);
+
+        // Recovery.
+        drop(chain);
+        let chain = Blockchain::new(
+            cfg.clone(),
+            chain_dir.path(),
+            ConsistencyCheck::Full,
+            genesis.clone(),
+            timestamp,
+        )
+        .expect("Failed to create blockchain");
+        assert_eq!(chain.epoch(), 3);
+        assert_eq!(chain.cfg().block_reward, 10);
+
+        // Governance is not activated yet.
+        let mut cfg = cfg;
+        cfg.governance_epoch = 5;
+        let chain_dir = TempDir::new("test").unwrap();
+        let mut chain = Blockchain::new(
+            cfg,
+            chain_dir.path(),
+            ConsistencyCheck::Full,
+            genesis,
+            timestamp,
+        )
+        .expect("Failed to create blockchain");
+        let k = &keychains[0];
+        let vote = ParameterVote::new(1, reward, 3, &k.network_skey, &k.network_pkey);
+        match chain.validate_parameter_vote(&vote) {
+            Err(BlockchainError::GovernanceError(GovernanceError::NotActivated(1))) => {}
+            e => panic!("{:?}", e),
+        }
+        // Votes are not included into macro blocks.
+        push_fake_epoch(&mut chain, &keychains, &mut timestamp, vec![vote]);
+        let block = chain.macro_block(1).expect("exists");
+        assert_eq!(block.header.version, VERSION);
+        assert!(block.parameter_votes.is_empty());
+    }
 }
 
 impl BlockReader for Blockchain {
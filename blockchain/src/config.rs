@@ -21,6 +21,8 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
+use crate::block::{GOVERNANCE_VERSION, VERSION};
+use schemars::JsonSchema;
 use serde_derive::{Deserialize, Serialize};
 use std::time::Duration;
 
@@ -38,7 +40,7 @@ pub enum ConsistencyCheck {
 }
 
 /// Blockchain configuration.
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
 #[serde(default)]
 pub struct ChainConfig {
     /// Maximal number of slots for election.
@@ -59,6 +61,8 @@ pub struct ChainConfig {
     pub vetted_timestamp_delta: Duration,
     /// When change is_synchronized to false.
     pub sync_timeout: Duration,
+    /// The first epoch with governance votes in macro blocks.
+    pub governance_epoch: u64,
 }
 
 const STG: i64 = 1_000_000;
@@ -77,6 +81,18 @@ impl Default for ChainConfig {
             // Sic: synchronize this value with NodeConfig::{micro, macro}_block_timeout.
             vetted_timestamp_delta: Duration::from_secs(30),
             sync_timeout: Duration::from_secs(5 * 60), // should >= block_timeout.
+            governance_epoch: 1,                       // genesis has no validators to vote.
+        }
+    }
+}
+
+impl ChainConfig {
+    /// Returns the expected version of the macro block of `epoch`.
+    pub fn macro_block_version(&self, epoch: u64) -> u64 {
+        if epoch > 0 && epoch >= self.governance_epoch {
+            GOVERNANCE_VERSION
+        } else {
+            VERSION
         }
     }
 }
@@ -22,14 +22,16 @@
 //! Leader election and group formation algorithms and tests.
 
 use crate::block::StakersGroup;
+use crate::error::ElectionProofError;
 use log::error;
+use schemars::JsonSchema;
 use serde_derive::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use stegos_crypto::hash::{Hash, Hashable, Hasher};
 use stegos_crypto::pbc;
 
 /// User-friendly printable representation of state.
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 pub struct ElectionInfo {
     pub epoch: u64,
     pub offset: u32,
@@ -91,6 +93,57 @@ impl ElectionResult {
     }
 }
 
+/// VRF of a block together with the inputs used to produce it.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct RandomProof {
+    /// Author of the block.
+    pub pkey: pbc::PublicKey,
+    /// Random of the previous block.
+    pub seed: Hash,
+    /// View change of the block.
+    pub view_change: u32,
+    /// VRF produced by the author from `mix(seed, view_change)`.
+    pub random: pbc::VRF,
+}
+
+impl RandomProof {
+    /// Returns true if `random` was produced by `pkey` from the seed.
+    pub fn is_valid(&self) -> bool {
+        let seed = mix(self.seed, self.view_change);
+        pbc::validate_VRF_source(&self.random, &self.pkey, &seed).is_ok()
+    }
+}
+
+/// Inputs of the leader election, enough to replay it.
+/// See `verify_election_proof()`.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ElectionProof {
+    pub epoch: u64,
+    pub offset: u32,
+    pub view_change: u32,
+    /// Stakers which took part in the election of validators.
+    pub stakers: StakersGroup,
+    /// Number of slots distributed between stakers.
+    pub slot_count: i64,
+    /// Random of the macro block which started the epoch.
+    pub epoch_random: RandomProof,
+    /// Validators with their slots, elected at the start of the epoch.
+    pub validators: StakersGroup,
+    /// Validators removed by slashing before `offset`.
+    pub slashed: Vec<pbc::PublicKey>,
+    /// Random of the previous micro block, None if offset is 0.
+    pub block_random: Option<RandomProof>,
+    /// The leader for `view_change`.
+    pub leader: pbc::PublicKey,
+}
+
+/// Stakers of an election, saved to replay the election later.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct ElectionInputs {
+    pub stakers: StakersGroup,
+    pub slot_count: i64,
+}
+
 /// Choose random validator, based on `random_number`.
 /// Accepts list of validators stakes consistently sorted on all participants,
 /// Returns index of the validator which stake are won.
@@ -176,6 +229,65 @@ pub fn select_facilitator(random: &Hash, validators: &StakersGroup) -> pbc::Publ
     validators[facilitator_id].0.clone()
 }
 
+/// Replay the election described by `proof`.
+///
+/// Checks both VRFs, re-runs the slot assignment from the stakers and selects
+/// the leader from the validators which remain after slashing.
+/// Note: `block_random.seed` can only be checked against the chain, except for offset 1.
+pub fn verify_election_proof(proof: &ElectionProof) -> Result<(), ElectionProofError> {
+    let epoch = proof.epoch;
+    let offset = proof.offset;
+    if !proof.epoch_random.is_valid() {
+        return Err(ElectionProofError::InvalidEpochRandom(epoch));
+    }
+
+    if proof.stakers.is_empty() || proof.slot_count <= 0 {
+        return Err(ElectionProofError::NoStakers(epoch));
+    }
+    let election = select_validators_slots(
+        proof.stakers.clone(),
+        proof.epoch_random.random,
+        proof.slot_count,
+    );
+    if election.validators != proof.validators {
+        return Err(ElectionProofError::SlotsMismatch(epoch));
+    }
+
+    let random = match (&proof.block_random, offset) {
+        (None, 0) => &proof.epoch_random.random,
+        (Some(block_random), offset) if offset > 0 => {
+            if !block_random.is_valid() {
+                return Err(ElectionProofError::InvalidBlockRandom(epoch, offset));
+            }
+            if offset == 1 && block_random.seed != proof.epoch_random.random.rand {
+                return Err(ElectionProofError::InvalidBlockRandom(epoch, offset));
+            }
+            &block_random.random
+        }
+        _ => return Err(ElectionProofError::MissingBlockRandom(epoch, offset)),
+    };
+
+    for slashed in &proof.slashed {
+        if !election.is_validator(slashed) {
+            return Err(ElectionProofError::NotValidator(*slashed));
+        }
+    }
+    let validators: StakersGroup = election
+        .validators
+        .into_iter()
+        .filter(|(k, _)| !proof.slashed.contains(k))
+        .collect();
+    if validators.is_empty() {
+        return Err(ElectionProofError::NoValidators(epoch, offset));
+    }
+
+    let leader = select_leader(&validators, &random.rand, proof.view_change);
+    if leader != proof.leader {
+        return Err(ElectionProofError::WrongLeader(proof.leader, leader));
+    }
+    Ok(())
+}
+
 /// Mix seed hash with round value to produce new hash.
 pub fn mix(random: Hash, round: u32) -> Hash {
     let mut hasher = Hasher::new();
@@ -202,7 +314,10 @@ fn generate_u64(seed: Hash, index: u32) -> i64 {
 
 #[cfg(test)]
 mod test {
-    use super::{select_validators_slots, select_winner};
+    use super::{
+        mix, select_leader, select_validators_slots, select_winner, verify_election_proof,
+        ElectionProof, RandomProof,
+    };
     use std::collections::{HashMap, HashSet};
 
     use stegos_crypto::hash::Hash;
@@ -329,4 +444,71 @@ mod test {
             assert_eq!(acc, *i as usize)
         }
     }
+
+    fn random_proof(skey: &pbc::SecretKey, pkey: pbc::PublicKey, seed: Hash) -> RandomProof {
+        let view_change = 1;
+        let random = pbc::make_VRF(skey, &mix(seed, view_change));
+        RandomProof {
+            pkey,
+            seed,
+            view_change,
+            random,
+        }
+    }
+
+    #[test]
+    fn test_election_proof() {
+        let keys: Vec<_> = (0..4).map(|_| pbc::make_random_keys()).collect();
+        let stakers: Vec<_> = keys.iter().map(|(_, pkey)| (*pkey, 100)).collect();
+        let slot_count = 100;
+        let epoch_random = random_proof(&keys[0].0, keys[0].1, Hash::digest("seed"));
+        let validators =
+            select_validators_slots(stakers.clone(), epoch_random.random, slot_count).validators;
+        let leader = select_leader(&validators, &epoch_random.random.rand, 2);
+        let mut proof = ElectionProof {
+            epoch: 1,
+            offset: 0,
+            view_change: 2,
+            stakers,
+            slot_count,
+            epoch_random,
+            validators: validators.clone(),
+            slashed: Vec::new(),
+            block_random: None,
+            leader,
+        };
+        verify_election_proof(&proof).expect("valid proof");
+
+        // Wrong leader.
+        let (_, other) = pbc::make_random_keys();
+        proof.leader = other;
+        assert!(verify_election_proof(&proof).is_err());
+        proof.leader = leader;
+
+        // Changed stakes.
+        proof.stakers[0].1 = 1;
+        assert!(verify_election_proof(&proof).is_err());
+        proof.stakers[0].1 = 100;
+
+        // Forged random.
+        let epoch_random = proof.epoch_random.clone();
+        proof.epoch_random.pkey = keys[1].1;
+        assert!(verify_election_proof(&proof).is_err());
+        proof.epoch_random = epoch_random;
+
+        // Next offset, with slashed validator.
+        let (slashed, _) = validators[0];
+        let (skey, pkey) = keys.iter().find(|(_, k)| *k == slashed).unwrap();
+        let block_random = random_proof(skey, *pkey, proof.epoch_random.random.rand);
+        let active: Vec<_> = validators[1..].to_vec();
+        proof.offset = 1;
+        proof.slashed = vec![slashed];
+        proof.leader = select_leader(&active, &block_random.random.rand, 2);
+        proof.block_random = Some(block_random);
+        verify_election_proof(&proof).expect("valid proof");
+
+        // Missing block random.
+        proof.block_random = None;
+        assert!(verify_election_proof(&proof).is_err());
+    }
 }
@@ -21,6 +21,7 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
+use crate::governance::ChainParameter;
 use crate::output::OutputError;
 use crate::timestamp::Timestamp;
 use crate::view_changes::ViewChangeProof;
@@ -67,6 +68,11 @@ pub enum BlockchainError {
         _0, _1, _2
     )]
     StakeIsLocked(pbc::PublicKey, i64, i64),
+    #[fail(
+        display = "Election proof is not available: epoch={}, offset={}",
+        _0, _1
+    )]
+    ElectionProofUnavailable(u64, u32),
     #[fail(display = "Storage I/O error={}", _0)]
     StorageError(StorageError),
     #[fail(display = "Transaction error={}", _0)]
@@ -79,6 +85,8 @@ pub enum BlockchainError {
     CryptoError(CryptoError),
     #[fail(display = "Cannot convert utf8 string = {}", _0)]
     UtfError(Utf8Error),
+    #[fail(display = "Governance error={}", _0)]
+    GovernanceError(GovernanceError),
     #[fail(display = "Other error={}", _0)]
     FailureError(failure::Error),
 }
@@ -253,11 +261,31 @@ pub enum BlockError {
         _0, _1, _2, _3
     )]
     InvalidMacroBlockInputsLen(u64, Hash, usize, usize),
+    #[fail(
+        display = "Invalid parameter_votes_range_hash in a macro block: epoch={}, block={}, expected={}, got={}",
+        _0, _1, _2, _3
+    )]
+    InvalidMacroBlockVotesHash(u64, Hash, Hash, Hash),
+    #[fail(
+        display = "Invalid parameter_votes_len in a macro block: epoch={}, block={}, expected={}, got={}",
+        _0, _1, _2, _3
+    )]
+    InvalidMacroBlockVotesLen(u64, Hash, usize, usize),
     #[fail(
         display = "Invalid outputs_len in a macro block: epoch={}, block={}, expected={}, got={}",
         _0, _1, _2, _3
     )]
     InvalidMacroBlockOutputsLen(u64, Hash, usize, usize),
+    #[fail(
+        display = "Invalid validators_range_hash in a macro block: epoch={}, block={}, expected={}, got={}",
+        _0, _1, _2, _3
+    )]
+    InvalidMacroBlockValidatorsHash(u64, Hash, Hash, Hash),
+    #[fail(
+        display = "Invalid validators_len in a macro block: epoch={}, block={}, expected={}, got={}",
+        _0, _1, _2, _3
+    )]
+    InvalidMacroBlockValidatorsLen(u64, Hash, usize, usize),
     #[fail(
         display = "Invalid transactions_range_hash in a micro block: epoch={}, offset={}, block={}, expected={}, got={}",
         _0, _1, _2, _3, _4
@@ -278,6 +306,11 @@ pub enum BlockError {
         _0, _1, _2, _3, _4
     )]
     InvalidMicroBlockCanariesHash(u64, u32, Hash, Hash, Hash),
+    #[fail(
+        display = "Slashing transaction is not included into a micro block: epoch={}, offset={}, block={}",
+        _0, _1, _2
+    )]
+    InvalidMicroBlockSlashingProof(u64, u32, Hash),
     #[fail(
         display = "Invalid transactions_len in a micro block: epoch={}, offset={}, block={}, expected={}, got={}",
         _0, _1, _2, _3, _4
@@ -490,6 +523,61 @@ pub enum SlashingError {
     IncorrectTxouts(Hash),
 }
 
+#[derive(Debug, Fail)]
+pub enum GovernanceError {
+    #[fail(display = "Invalid value of parameter: parameter={:?}", _0)]
+    InvalidParameter(ChainParameter),
+    #[fail(display = "Invalid signature of vote: validator={}", _0)]
+    InvalidSignature(pbc::PublicKey),
+    #[fail(display = "Vote from other epoch: vote_epoch={}, epoch={}", _0, _1)]
+    OutOfDateVote(u64, u64),
+    #[fail(
+        display = "Activation epoch is too early: activation_epoch={}, min_activation_epoch={}",
+        _0, _1
+    )]
+    TooEarlyActivation(u64, u64),
+    #[fail(display = "Vote from non-validator: pkey={}", _0)]
+    NotValidator(pbc::PublicKey),
+    #[fail(display = "Duplicate vote: validator={}", _0)]
+    DuplicateVote(pbc::PublicKey),
+    #[fail(display = "Too many votes from validator: validator={}", _0)]
+    TooManyVotes(pbc::PublicKey),
+    #[fail(display = "Governance is not activated: epoch={}", _0)]
+    NotActivated(u64),
+}
+
+#[derive(Debug, Fail)]
+pub enum ElectionProofError {
+    #[fail(display = "Invalid random of the macro block: epoch={}", _0)]
+    InvalidEpochRandom(u64),
+    #[fail(
+        display = "Invalid random of the micro block: epoch={}, offset={}",
+        _0, _1
+    )]
+    InvalidBlockRandom(u64, u32),
+    #[fail(
+        display = "Block random doesn't match offset: epoch={}, offset={}",
+        _0, _1
+    )]
+    MissingBlockRandom(u64, u32),
+    #[fail(display = "No stakers in election: epoch={}", _0)]
+    NoStakers(u64),
+    #[fail(display = "Slots assignment mismatch: epoch={}", _0)]
+    SlotsMismatch(u64),
+    #[fail(display = "Slashed validator was not elected: validator={}", _0)]
+    NotValidator(pbc::PublicKey),
+    #[fail(
+        display = "No validators left after slashing: epoch={}, offset={}",
+        _0, _1
+    )]
+    NoValidators(u64, u32),
+    #[fail(
+        display = "Found incorrect leader: leader_in_proof={}, actual_leader={}",
+        _0, _1
+    )]
+    WrongLeader(pbc::PublicKey, pbc::PublicKey),
+}
+
 impl From<rocksdb::Error> for BlockchainError {
     fn from(error: rocksdb::Error) -> BlockchainError {
         BlockchainError::StorageError(error)
@@ -531,6 +619,12 @@ impl From<SlashingError> for BlockchainError {
     }
 }
 
+impl From<GovernanceError> for BlockchainError {
+    fn from(error: GovernanceError) -> BlockchainError {
+        BlockchainError::GovernanceError(error)
+    }
+}
+
 impl From<Utf8Error> for BlockchainError {
     fn from(error: Utf8Error) -> BlockchainError {
         BlockchainError::UtfError(error)
@@ -27,6 +27,7 @@ use crate::error::BlockchainError;
 use crate::mvcc::MultiVersionedMap;
 use crate::output::Output;
 use log::*;
+use schemars::JsonSchema;
 use serde_derive::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::collections::HashMap;
@@ -56,12 +57,12 @@ pub struct Escrow {
     pub(crate) escrow: EscrowMap,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct EscrowInfo {
     pub validators: Vec<ValidatorInfo>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct ValidatorInfo {
     pub network_pkey: pbc::PublicKey,
     pub active_stake: i64,
@@ -69,7 +70,7 @@ pub struct ValidatorInfo {
     pub stakes: Vec<StakeInfo>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct StakeInfo {
     pub output_hash: Hash,
     pub account_pkey: scc::PublicKey,
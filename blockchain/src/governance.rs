@@ -0,0 +1,275 @@
+//
+// MIT License
+//
+// Copyright (c) 2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! On-chain governance of chain parameters.
+//!
+//! Validators vote for a new value of a parameter, votes are included into macro blocks.
+//! A change is scheduled if validators with the supermajority of slots voted for it
+//! within the same epoch, and it is applied at the start of `activation_epoch`.
+//! Macro blocks carry votes starting from `ChainConfig::governance_epoch`.
+
+use crate::block::StakersGroup;
+use crate::config::ChainConfig;
+use crate::error::GovernanceError;
+use crate::multisignature::check_supermajority;
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use stegos_crypto::hash::{Hash, Hashable, Hasher};
+use stegos_crypto::pbc;
+
+/// Minimal number of epochs between a vote and activation of the change.
+pub const PARAMETER_CHANGE_DELAY: u64 = 2;
+
+/// Maximal number of votes from one validator per epoch.
+pub const MAX_VOTES_PER_VALIDATOR: usize = 8;
+
+/// A chain parameter, which can be changed by validators.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "name", content = "value")]
+#[serde(rename_all = "snake_case")]
+pub enum ChainParameter {
+    BlockReward(i64),
+    MinStakeAmount(i64),
+    StakeEpochs(u64),
+    MicroBlocksInEpoch(u32),
+    ServiceAwardPerEpoch(i64),
+}
+
+impl ChainParameter {
+    /// Returns true if the value is acceptable for this parameter.
+    pub fn is_valid(&self) -> bool {
+        match *self {
+            ChainParameter::BlockReward(value) => value >= 0,
+            ChainParameter::MinStakeAmount(value) => value > 0,
+            ChainParameter::StakeEpochs(value) => value > 0,
+            ChainParameter::MicroBlocksInEpoch(value) => value > 0,
+            ChainParameter::ServiceAwardPerEpoch(value) => value >= 0,
+        }
+    }
+
+    /// Set the new value in the configuration.
+    pub fn apply(&self, cfg: &mut ChainConfig) {
+        match *self {
+            ChainParameter::BlockReward(value) => cfg.block_reward = value,
+            ChainParameter::MinStakeAmount(value) => cfg.min_stake_amount = value,
+            ChainParameter::StakeEpochs(value) => cfg.stake_epochs = value,
+            ChainParameter::MicroBlocksInEpoch(value) => cfg.micro_blocks_in_epoch = value,
+            ChainParameter::ServiceAwardPerEpoch(value) => cfg.service_award_per_epoch = value,
+        }
+    }
+}
+
+impl Hashable for ChainParameter {
+    fn hash(&self, state: &mut Hasher) {
+        match self {
+            ChainParameter::BlockReward(value) => {
+                "BlockReward".hash(state);
+                value.hash(state);
+            }
+            ChainParameter::MinStakeAmount(value) => {
+                "MinStakeAmount".hash(state);
+                value.hash(state);
+            }
+            ChainParameter::StakeEpochs(value) => {
+                "StakeEpochs".hash(state);
+                value.hash(state);
+            }
+            ChainParameter::MicroBlocksInEpoch(value) => {
+                "MicroBlocksInEpoch".hash(state);
+                value.hash(state);
+            }
+            ChainParameter::ServiceAwardPerEpoch(value) => {
+                "ServiceAwardPerEpoch".hash(state);
+                value.hash(state);
+            }
+        }
+    }
+}
+
+/// Vote of a validator for a parameter change.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ParameterVote {
+    /// Epoch when the vote was cast.
+    pub epoch: u64,
+    /// Parameter with the new value.
+    pub parameter: ChainParameter,
+    /// The first epoch with the new value.
+    pub activation_epoch: u64,
+    /// Network key of the validator.
+    pub pkey: pbc::PublicKey,
+    /// BLS signature of the validator.
+    pub sig: pbc::Signature,
+}
+
+impl ParameterVote {
+    /// Create a signed vote.
+    pub fn new(
+        epoch: u64,
+        parameter: ChainParameter,
+        activation_epoch: u64,
+        skey: &pbc::SecretKey,
+        pkey: &pbc::PublicKey,
+    ) -> Self {
+        let mut vote = ParameterVote {
+            epoch,
+            parameter,
+            activation_epoch,
+            pkey: *pkey,
+            sig: pbc::Signature::zero(),
+        };
+        vote.sig = pbc::sign_hash(&vote.signed_hash(), skey);
+        vote
+    }
+
+    /// Hash of the signed part of vote.
+    fn signed_hash(&self) -> Hash {
+        let mut state = Hasher::new();
+        "ParameterVote".hash(&mut state);
+        self.epoch.hash(&mut state);
+        self.parameter.hash(&mut state);
+        self.activation_epoch.hash(&mut state);
+        self.pkey.hash(&mut state);
+        state.result()
+    }
+
+    /// Check the value and the signature.
+    pub fn validate(&self) -> Result<(), GovernanceError> {
+        if !self.parameter.is_valid() {
+            return Err(GovernanceError::InvalidParameter(self.parameter));
+        }
+        pbc::check_hash(&self.signed_hash(), &self.sig, &self.pkey)
+            .map_err(|_| GovernanceError::InvalidSignature(self.pkey))
+    }
+}
+
+impl Hashable for ParameterVote {
+    fn hash(&self, state: &mut Hasher) {
+        self.signed_hash().hash(state);
+        self.sig.hash(state);
+    }
+}
+
+/// Parameter change accepted by validators.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ParameterChange {
+    /// The first epoch with the new value.
+    pub epoch: u64,
+    /// Parameter with the new value.
+    pub parameter: ChainParameter,
+}
+
+/// History of accepted parameter changes, stored in the database.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct ParameterChanges(pub Vec<ParameterChange>);
+
+/// Current and scheduled chain parameters.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ChainParametersInfo {
+    /// Current epoch.
+    pub epoch: u64,
+    /// Parameters used in the current epoch.
+    pub current: ChainConfig,
+    /// Accepted changes, which are not active yet.
+    pub pending: Vec<ParameterChange>,
+}
+
+/// Returns changes supported by the supermajority of validators' slots.
+/// Every validator is counted once per (activation_epoch, parameter).
+pub(crate) fn tally_votes(
+    votes: &[ParameterVote],
+    validators: &StakersGroup,
+) -> Vec<ParameterChange> {
+    let total_slots: i64 = validators.iter().map(|(_, slots)| slots).sum();
+    if total_slots == 0 {
+        return Vec::new();
+    }
+    let mut ballots: BTreeMap<(u64, ChainParameter), HashSet<pbc::PublicKey>> = BTreeMap::new();
+    for vote in votes {
+        ballots
+            .entry((vote.activation_epoch, vote.parameter))
+            .or_default()
+            .insert(vote.pkey);
+    }
+    ballots
+        .into_iter()
+        .filter_map(|((epoch, parameter), voters)| {
+            let slots: i64 = validators
+                .iter()
+                .filter(|(k, _)| voters.contains(k))
+                .map(|(_, slots)| slots)
+                .sum();
+            if check_supermajority(slots, total_slots) {
+                Some(ParameterChange { epoch, parameter })
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn vote_signature() {
+        let (skey, pkey) = pbc::make_random_keys();
+        let mut vote = ParameterVote::new(1, ChainParameter::BlockReward(10), 3, &skey, &pkey);
+        vote.validate().expect("valid vote");
+        vote.activation_epoch = 4;
+        assert!(vote.validate().is_err());
+
+        let vote = ParameterVote::new(1, ChainParameter::StakeEpochs(0), 3, &skey, &pkey);
+        assert!(vote.validate().is_err());
+    }
+
+    #[test]
+    fn supermajority() {
+        let keys: Vec<_> = (0..3).map(|_| pbc::make_random_keys()).collect();
+        let validators: StakersGroup = vec![(keys[0].1, 50), (keys[1].1, 30), (keys[2].1, 20)];
+        let vote =
+            |i: usize, parameter| ParameterVote::new(1, parameter, 3, &keys[i].0, &keys[i].1);
+        let reward = ChainParameter::BlockReward(10);
+        let stake = ChainParameter::MinStakeAmount(10);
+
+        // 30 + 20 slots of 100 - not enough.
+        let votes = vec![vote(1, reward), vote(2, reward), vote(0, stake)];
+        assert!(tally_votes(&votes, &validators).is_empty());
+
+        // 50 + 30 slots of 100, duplicate votes are ignored.
+        let votes = vec![vote(0, reward), vote(1, reward), vote(1, reward)];
+        let changes = tally_votes(&votes, &validators);
+        assert_eq!(
+            changes,
+            vec![ParameterChange {
+                epoch: 3,
+                parameter: reward
+            }]
+        );
+
+        let mut cfg = ChainConfig::default();
+        changes[0].parameter.apply(&mut cfg);
+        assert_eq!(cfg.block_reward, 10);
+    }
+}
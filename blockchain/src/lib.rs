@@ -31,6 +31,8 @@ mod config;
 pub mod election;
 mod error;
 mod escrow;
+mod governance;
+mod light;
 mod merkle;
 mod metrics;
 mod multisignature;
@@ -42,21 +44,28 @@ pub mod test;
 mod timestamp;
 mod transaction;
 mod validation;
+mod validator_stats;
 pub mod view_changes;
 
 pub use crate::awards::ValidatorAwardState;
 pub use crate::block::*;
 pub use crate::blockchain::*;
 pub use crate::config::*;
-pub use crate::election::{mix, ElectionInfo, ElectionResult};
+pub use crate::election::{mix, ElectionInfo, ElectionProof, ElectionResult, RandomProof};
 pub use crate::error::*;
 pub use crate::escrow::*;
+pub use crate::governance::{
+    ChainParameter, ChainParametersInfo, ParameterChange, ParameterVote, MAX_VOTES_PER_VALIDATOR,
+    PARAMETER_CHANGE_DELAY,
+};
+pub use crate::light::{LightVerifier, OutputProof};
 pub use crate::merkle::*;
 pub use crate::multisignature::*;
 pub use crate::output::*;
 pub use crate::slashing::*;
 pub use crate::timestamp::Timestamp;
 pub use crate::transaction::*;
+pub use crate::validator_stats::ValidatorEpochStats;
 
 use failure::{format_err, Error};
 use stegos_serialization::traits::ProtoConvert;
@@ -84,12 +93,16 @@ pub fn initialize_chain(chain: &str) -> Result<(MacroBlock, ChainConfig), Error>
         "testnet" => (
             include_bytes!("../../chains/testnet/genesis.bin"),
             ChainConfig {
+                // Sic: votes change hashes of macro blocks, the fork is not scheduled yet.
+                governance_epoch: std::u64::MAX,
                 ..Default::default()
             },
         ),
         "mainnet" => (
             include_bytes!("../../chains/mainnet/genesis.bin"),
             ChainConfig {
+                // Sic: votes change hashes of macro blocks, the fork is not scheduled yet.
+                governance_epoch: std::u64::MAX,
                 ..Default::default()
             },
         ),
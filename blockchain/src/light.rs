@@ -0,0 +1,872 @@
+//
+// MIT License
+//
+// Copyright (c) 2018-2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Light client verification of headers and outputs.
+
+use crate::block::{
+    LightBlock, LightMacroBlock, LightMicroBlock, StakersGroup, GOVERNANCE_VERSION, VERSION,
+};
+use crate::blockchain::{ChainInfo, LightEpochInfo};
+use crate::config::ChainConfig;
+use crate::election::{self, mix};
+use crate::error::{BlockError, BlockchainError, GovernanceError};
+use crate::governance::{tally_votes, ChainParametersInfo, ParameterChange};
+use crate::merkle::{Merkle, MerkleProof};
+use crate::multisignature::check_multi_signature;
+use crate::output::Output;
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use stegos_crypto::hash::Hash;
+use stegos_crypto::pbc;
+
+/// Output with a proof of inclusion into a block.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct OutputProof {
+    /// The output.
+    pub output: Output,
+    /// Epoch of the block.
+    pub epoch: u64,
+    /// Offset of micro block, None for macro blocks.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u32>,
+    /// Hash of the block.
+    pub block_hash: Hash,
+    /// Merkle proof against outputs_range_hash of the block.
+    pub proof: MerkleProof,
+}
+
+///
+/// Verifier of light blocks received from an untrusted upstream.
+///
+/// Tracks validators from macro blocks, checks signatures of micro blocks,
+/// multi-signatures of macro blocks and range hashes of headers.
+/// Governance votes from macro blocks are counted to follow chain parameters.
+///
+pub struct LightVerifier {
+    /// Hash of the genesis block.
+    genesis_hash: Hash,
+    /// Chain parameters of the current epoch.
+    cfg: ChainConfig,
+    /// All parameter changes accepted by validators, ordered by activation epoch.
+    parameter_changes: Vec<ParameterChange>,
+    /// The next expected epoch.
+    epoch: u64,
+    /// The next expected offset.
+    offset: u32,
+    /// Validators of the current epoch.
+    validators: StakersGroup,
+    /// Hash of the last macro block.
+    last_macro_block_hash: Hash,
+    /// Random of the last macro block.
+    last_macro_block_random: Hash,
+    /// Hash of the last block.
+    last_block_hash: Hash,
+    /// Random of the last block.
+    last_block_random: Hash,
+}
+
+impl LightVerifier {
+    /// Create a verifier which starts from the genesis block.
+    pub fn new(genesis_hash: Hash, cfg: ChainConfig) -> Self {
+        // Sic: the same seed is used by Blockchain for the genesis block.
+        let random = Hash::digest("genesis");
+        LightVerifier {
+            genesis_hash,
+            cfg,
+            parameter_changes: Vec::new(),
+            epoch: 0,
+            offset: 0,
+            validators: Vec::new(),
+            last_macro_block_hash: Hash::zero(),
+            last_macro_block_random: random,
+            last_block_hash: Hash::zero(),
+            last_block_random: random,
+        }
+    }
+
+    ///
+    /// Create a verifier which continues after the last macro block.
+    ///
+    /// `cfg` is the configuration of the genesis, accepted changes are applied on top of it.
+    ///
+    pub fn from_epoch_info(
+        genesis_hash: Hash,
+        cfg: ChainConfig,
+        epoch_info: &LightEpochInfo,
+    ) -> Self {
+        let mut verifier = Self::new(genesis_hash, cfg);
+        let header = &epoch_info.header;
+        let block_hash = Hash::digest(header);
+        verifier.epoch = header.epoch + 1;
+        verifier.validators = epoch_info.validators.clone();
+        verifier.parameter_changes = epoch_info.parameter_changes.clone();
+        for change in &verifier.parameter_changes {
+            if change.epoch <= verifier.epoch {
+                change.parameter.apply(&mut verifier.cfg);
+            }
+        }
+        verifier.last_macro_block_hash = block_hash;
+        verifier.last_macro_block_random = header.random.rand;
+        verifier.last_block_hash = block_hash;
+        verifier.last_block_random = header.random.rand;
+        verifier
+    }
+
+    /// The next expected epoch.
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// The next expected offset.
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+
+    /// Hash of the last verified block.
+    pub fn last_block_hash(&self) -> Hash {
+        self.last_block_hash
+    }
+
+    /// Validators of the current epoch.
+    pub fn validators(&self) -> &StakersGroup {
+        &self.validators
+    }
+
+    /// Chain parameters of the current epoch.
+    pub fn cfg(&self) -> &ChainConfig {
+        &self.cfg
+    }
+
+    /// All parameter changes accepted by validators.
+    pub fn parameter_changes(&self) -> &[ParameterChange] {
+        &self.parameter_changes
+    }
+
+    /// Returns current chain parameters and accepted changes for future epochs.
+    pub fn chain_parameters(&self) -> ChainParametersInfo {
+        let pending = self
+            .parameter_changes
+            .iter()
+            .filter(|change| change.epoch > self.epoch)
+            .cloned()
+            .collect();
+        ChainParametersInfo {
+            epoch: self.epoch,
+            current: self.cfg.clone(),
+            pending,
+        }
+    }
+
+    /// Verify and apply the next light block.
+    pub fn apply_light_block(&mut self, block: &LightBlock) -> Result<(), BlockchainError> {
+        match block {
+            LightBlock::LightMacroBlock(block) => self.apply_light_macro_block(block),
+            LightBlock::LightMicroBlock(block) => self.apply_light_micro_block(block),
+        }
+    }
+
+    ///
+    /// Verify and apply the next light macro block.
+    ///
+    /// Micro blocks of the epoch are replaced by the macro block.
+    ///
+    pub fn apply_light_macro_block(
+        &mut self,
+        block: &LightMacroBlock,
+    ) -> Result<(), BlockchainError> {
+        self.verify_light_macro_block(block)?;
+        self.commit_light_macro_block(block);
+        Ok(())
+    }
+
+    ///
+    /// Verify the next light macro block without changing the state.
+    ///
+    pub fn verify_light_macro_block(&self, block: &LightMacroBlock) -> Result<(), BlockchainError> {
+        let header = &block.header;
+        let block_hash = Hash::digest(header);
+
+        // Check genesis.
+        if self.epoch == 0 && block_hash != self.genesis_hash {
+            return Err(BlockchainError::IncompatibleGenesis(
+                self.genesis_hash,
+                block_hash,
+            ));
+        }
+
+        // Check block version.
+        let version = self.cfg.macro_block_version(header.epoch);
+        if header.version != version {
+            return Err(BlockError::InvalidBlockVersion(
+                header.epoch,
+                block_hash,
+                header.version,
+                version,
+            )
+            .into());
+        }
+
+        // Check epoch.
+        if header.epoch != self.epoch {
+            return Err(
+                BlockError::OutOfOrderMacroBlock(block_hash, header.epoch, self.epoch).into(),
+            );
+        }
+
+        // Check previous hash.
+        if self.epoch > 0 && header.previous != self.last_macro_block_hash {
+            return Err(BlockError::InvalidMacroBlockPreviousHash(
+                header.epoch,
+                block_hash,
+                header.previous,
+                self.last_macro_block_hash,
+            )
+            .into());
+        }
+
+        // Check multi-signature.
+        if self.epoch > 0 {
+            check_multi_signature(
+                &block_hash,
+                &block.multisig,
+                &block.multisigmap,
+                &self.validators,
+                self.cfg.max_slot_count,
+            )
+            .map_err(|e| BlockError::InvalidBlockSignature(e, header.epoch, block_hash))?;
+        }
+
+        // Check VRF.
+        let seed = mix(self.last_macro_block_random, header.view_change);
+        if pbc::validate_VRF_source(&header.random, &header.pkey, &seed).is_err() {
+            return Err(BlockError::IncorrectRandom(header.epoch, block_hash).into());
+        }
+
+        // Check inputs.
+        if header.inputs_len as usize != block.input_hashes.len() {
+            return Err(BlockError::InvalidMacroBlockInputsLen(
+                header.epoch,
+                block_hash,
+                header.inputs_len as usize,
+                block.input_hashes.len(),
+            )
+            .into());
+        }
+        let inputs_range_hash = Merkle::root_hash_from_array(&block.input_hashes);
+        if header.inputs_range_hash != inputs_range_hash {
+            return Err(BlockError::InvalidMacroBlockInputsHash(
+                header.epoch,
+                block_hash,
+                inputs_range_hash,
+                header.inputs_range_hash,
+            )
+            .into());
+        }
+
+        // Check outputs.
+        if header.outputs_len as usize != block.output_hashes.len() {
+            return Err(BlockError::InvalidMacroBlockOutputsLen(
+                header.epoch,
+                block_hash,
+                header.outputs_len as usize,
+                block.output_hashes.len(),
+            )
+            .into());
+        }
+        let outputs_range_hash = Merkle::root_hash_from_array(&block.output_hashes);
+        if header.outputs_range_hash != outputs_range_hash {
+            return Err(BlockError::InvalidMacroBlockOutputsHash(
+                header.epoch,
+                block_hash,
+                outputs_range_hash,
+                header.outputs_range_hash,
+            )
+            .into());
+        }
+
+        // Check canaries.
+        let canary_hashes: Vec<Hash> = block.canaries.iter().map(Hash::digest).collect();
+        let canaries_range_hash = Merkle::root_hash_from_array(&canary_hashes);
+        if header.canaries_range_hash != canaries_range_hash {
+            return Err(BlockError::InvalidMacroBlockCanariesHash(
+                header.epoch,
+                block_hash,
+                canaries_range_hash,
+                header.canaries_range_hash,
+            )
+            .into());
+        }
+
+        // Check validators of the next epoch.
+        if header.validators_len as usize != block.validators.len() {
+            return Err(BlockError::InvalidMacroBlockValidatorsLen(
+                header.epoch,
+                block_hash,
+                header.validators_len as usize,
+                block.validators.len(),
+            )
+            .into());
+        }
+        let validators_range_hash = Merkle::root_hash_from_array(&block.validators);
+        if header.validators_range_hash != validators_range_hash {
+            return Err(BlockError::InvalidMacroBlockValidatorsHash(
+                header.epoch,
+                block_hash,
+                validators_range_hash,
+                header.validators_range_hash,
+            )
+            .into());
+        }
+
+        // Check governance votes, they are covered by the multi-signature.
+        if header.version < GOVERNANCE_VERSION && !block.parameter_votes.is_empty() {
+            return Err(GovernanceError::NotActivated(header.epoch).into());
+        }
+        if header.parameter_votes_len as usize != block.parameter_votes.len() {
+            return Err(BlockError::InvalidMacroBlockVotesLen(
+                header.epoch,
+                block_hash,
+                header.parameter_votes_len as usize,
+                block.parameter_votes.len(),
+            )
+            .into());
+        }
+        let parameter_votes_range_hash = Merkle::root_hash_from_array(&block.parameter_votes);
+        if header.version >= GOVERNANCE_VERSION
+            && header.parameter_votes_range_hash != parameter_votes_range_hash
+        {
+            return Err(BlockError::InvalidMacroBlockVotesHash(
+                header.epoch,
+                block_hash,
+                parameter_votes_range_hash,
+                header.parameter_votes_range_hash,
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    ///
+    /// Apply the next light macro block, verified by `verify_light_macro_block()`.
+    ///
+    pub fn commit_light_macro_block(&mut self, block: &LightMacroBlock) {
+        let header = &block.header;
+        let block_hash = Hash::digest(header);
+        assert_eq!(header.epoch, self.epoch, "verified block");
+        let accepted_changes = tally_votes(&block.parameter_votes, &self.validators);
+
+        self.epoch += 1;
+        self.offset = 0;
+        self.validators = block.validators.clone();
+        for change in accepted_changes {
+            if !self.parameter_changes.contains(&change) {
+                self.parameter_changes.push(change);
+            }
+        }
+        // Sic: stable sort keeps the order of acceptance for the same epoch.
+        self.parameter_changes.sort_by_key(|change| change.epoch);
+        for change in &self.parameter_changes {
+            if change.epoch == self.epoch {
+                change.parameter.apply(&mut self.cfg);
+            }
+        }
+        self.last_macro_block_hash = block_hash;
+        self.last_macro_block_random = header.random.rand;
+        self.last_block_hash = block_hash;
+        self.last_block_random = header.random.rand;
+    }
+
+    /// Verify and apply the next light micro block.
+    pub fn apply_light_micro_block(
+        &mut self,
+        block: &LightMicroBlock,
+    ) -> Result<(), BlockchainError> {
+        self.verify_light_micro_block(block)?;
+        self.commit_light_micro_block(block);
+        Ok(())
+    }
+
+    /// Verify the next light micro block without changing the state.
+    pub fn verify_light_micro_block(&self, block: &LightMicroBlock) -> Result<(), BlockchainError> {
+        let header = &block.header;
+        let block_hash = Hash::digest(header);
+
+        // Check block version.
+        if header.version != VERSION {
+            return Err(BlockError::InvalidBlockVersion(
+                header.epoch,
+                block_hash,
+                header.version,
+                VERSION,
+            )
+            .into());
+        }
+
+        // Check epoch and offset.
+        if self.epoch == 0 || header.epoch != self.epoch || header.offset != self.offset {
+            return Err(BlockError::OutOfOrderMicroBlock(
+                block_hash,
+                header.epoch,
+                header.offset,
+                self.epoch,
+                self.offset,
+            )
+            .into());
+        }
+
+        // Check the block order.
+        if self.offset >= self.cfg.micro_blocks_in_epoch {
+            return Err(BlockchainError::ExpectedMacroBlock(
+                self.epoch,
+                self.offset,
+                block_hash,
+            ));
+        }
+
+        // Check previous hash.
+        if header.previous != self.last_block_hash {
+            return Err(BlockError::InvalidMicroBlockPreviousHash(
+                header.epoch,
+                header.offset,
+                block_hash,
+                header.previous,
+                self.last_block_hash,
+            )
+            .into());
+        }
+
+        // Check view change.
+        if header.view_change > 0 {
+            match header.view_change_proof {
+                Some(ref proof) => {
+                    let chain = ChainInfo {
+                        epoch: header.epoch,
+                        offset: header.offset,
+                        view_change: header.view_change - 1,
+                        last_block: header.previous,
+                    };
+                    let chain_hash = Hash::digest(&chain);
+                    if let Err(e) = check_multi_signature(
+                        &chain_hash,
+                        &proof.multisig,
+                        &proof.multimap,
+                        &self.validators,
+                        self.cfg.max_slot_count,
+                    ) {
+                        return Err(BlockError::InvalidViewChangeProof(
+                            header.epoch,
+                            proof.clone(),
+                            e.into(),
+                        )
+                        .into());
+                    }
+                }
+                None => {
+                    return Err(BlockError::NoProofWasFound(
+                        header.epoch,
+                        header.offset,
+                        block_hash,
+                        header.view_change,
+                        0,
+                    )
+                    .into());
+                }
+            }
+        }
+
+        // Check signature.
+        let leader = election::select_leader(
+            &self.validators,
+            &self.last_block_random,
+            header.view_change,
+        );
+        if leader != header.pkey {
+            return Err(BlockError::DifferentPublicKey(leader, header.pkey).into());
+        }
+        if let Err(_e) = pbc::check_hash(&block_hash, &block.sig, &leader) {
+            return Err(BlockError::InvalidLeaderSignature(header.epoch, block_hash).into());
+        }
+
+        // Check VRF.
+        let seed = mix(self.last_block_random, header.view_change);
+        if pbc::validate_VRF_source(&header.random, &header.pkey, &seed).is_err() {
+            return Err(BlockError::IncorrectRandom(header.epoch, block_hash).into());
+        }
+
+        // Check inputs.
+        if header.inputs_len as usize != block.input_hashes.len() {
+            return Err(BlockError::InvalidMicroBlockInputsLen(
+                header.epoch,
+                header.offset,
+                block_hash,
+                header.inputs_len as usize,
+                block.input_hashes.len(),
+            )
+            .into());
+        }
+        let inputs_range_hash = Merkle::root_hash_from_array(&block.input_hashes);
+        if header.inputs_range_hash != inputs_range_hash {
+            return Err(BlockError::InvalidMicroBlockInputsHash(
+                header.epoch,
+                header.offset,
+                block_hash,
+                inputs_range_hash,
+                header.inputs_range_hash,
+            )
+            .into());
+        }
+
+        // Check outputs.
+        if header.outputs_len as usize != block.output_hashes.len() {
+            return Err(BlockError::InvalidMicroBlockOutputsLen(
+                header.epoch,
+                header.offset,
+                block_hash,
+                header.outputs_len as usize,
+                block.output_hashes.len(),
+            )
+            .into());
+        }
+        let outputs_range_hash = Merkle::root_hash_from_array(&block.output_hashes);
+        if header.outputs_range_hash != outputs_range_hash {
+            return Err(BlockError::InvalidMicroBlockOutputsHash(
+                header.epoch,
+                header.offset,
+                block_hash,
+                outputs_range_hash,
+                header.outputs_range_hash,
+            )
+            .into());
+        }
+
+        // Check canaries.
+        let canary_hashes: Vec<Hash> = block.canaries.iter().map(Hash::digest).collect();
+        let canaries_range_hash = Merkle::root_hash_from_array(&canary_hashes);
+        if header.canaries_range_hash != canaries_range_hash {
+            return Err(BlockError::InvalidMicroBlockCanariesHash(
+                header.epoch,
+                header.offset,
+                block_hash,
+                canaries_range_hash,
+                header.canaries_range_hash,
+            )
+            .into());
+        }
+
+        // Check slashing transactions.
+        // Sic: an upstream can hide a slashing transaction, then the following blocks
+        // fail the leader check.
+        for tx in &block.slashing_transactions {
+            let proof = &tx.transaction.proof;
+            if !tx.verify(&header.transactions_range_hash)
+                || proof.block1.header.pkey != proof.block2.header.pkey
+            {
+                return Err(BlockError::InvalidMicroBlockSlashingProof(
+                    header.epoch,
+                    header.offset,
+                    block_hash,
+                )
+                .into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Apply the next light micro block, verified by `verify_light_micro_block()`.
+    pub fn commit_light_micro_block(&mut self, block: &LightMicroBlock) {
+        let header = &block.header;
+        assert_eq!(
+            (header.epoch, header.offset),
+            (self.epoch, self.offset),
+            "verified block"
+        );
+        // Remove cheaters until the end of epoch, as Blockchain does.
+        for tx in &block.slashing_transactions {
+            let cheater = tx.transaction.cheater();
+            self.validators.retain(|(pkey, _)| *pkey != cheater);
+        }
+        self.offset += 1;
+        self.last_block_hash = Hash::digest(header);
+        self.last_block_random = header.random.rand;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::block::MicroBlock;
+    use crate::blockchain::Blockchain;
+    use crate::config::ConsistencyCheck;
+    use crate::slashing::{confiscate_tx, SlashingProof};
+    use crate::test;
+    use crate::timestamp::Timestamp;
+    use std::time::Duration;
+    use tempdir::TempDir;
+
+    #[test]
+    fn verify_light_blocks() {
+        const NUM_NODES: usize = 3;
+        let mut cfg: ChainConfig = Default::default();
+        cfg.micro_blocks_in_epoch = 2;
+        let mut timestamp = Timestamp::now();
+        let (keychains, genesis) = test::fake_genesis(
+            cfg.min_stake_amount,
+            (NUM_NODES as i64) * cfg.min_stake_amount + 100,
+            cfg.max_slot_count,
+            NUM_NODES,
+            timestamp,
+            None,
+        );
+        let chain_dir = TempDir::new("test").unwrap();
+        let mut chain = Blockchain::new(
+            cfg.clone(),
+            chain_dir.path(),
+            ConsistencyCheck::None,
+            genesis.clone(),
+            timestamp,
+        )
+        .expect("Failed to create blockchain");
+        let mut verifier = LightVerifier::new(Hash::digest(&genesis), cfg.clone());
+
+        for _epoch in 0..2 {
+            for _offset in 0..cfg.micro_blocks_in_epoch {
+                timestamp += Duration::from_millis(1);
+                let block = test::create_micro_block_with_coinbase(&chain, &keychains, timestamp);
+                chain
+                    .push_micro_block(block, timestamp)
+                    .expect("Invalid block");
+            }
+            timestamp += Duration::from_millis(1);
+            let (block, _extra_transactions) =
+                test::create_fake_macro_block(&chain, &keychains, timestamp);
+            while chain.offset() > 0 {
+                chain.pop_micro_block().expect("Should be ok");
+            }
+            chain
+                .push_macro_block(block, timestamp)
+                .expect("Invalid block");
+        }
+        timestamp += Duration::from_millis(1);
+        let block = test::create_micro_block_with_coinbase(&chain, &keychains, timestamp);
+        chain
+            .push_micro_block(block, timestamp)
+            .expect("Invalid block");
+
+        for block in chain.light_blocks_starting(0, 0) {
+            verifier.apply_light_block(&block).expect("valid block");
+        }
+        assert_eq!(verifier.epoch(), chain.epoch());
+        assert_eq!(verifier.offset(), chain.offset());
+        assert_eq!(verifier.last_block_hash(), chain.last_block_hash());
+    }
+
+    #[test]
+    fn reject_forged_blocks() {
+        let cfg: ChainConfig = Default::default();
+        let timestamp = Timestamp::now();
+        let (keychains, genesis) = test::fake_genesis(
+            cfg.min_stake_amount,
+            3 * cfg.min_stake_amount + 100,
+            cfg.max_slot_count,
+            3,
+            timestamp,
+            None,
+        );
+        let genesis_hash = Hash::digest(&genesis);
+        let chain_dir = TempDir::new("test").unwrap();
+        let mut chain = Blockchain::new(
+            cfg.clone(),
+            chain_dir.path(),
+            ConsistencyCheck::None,
+            genesis,
+            timestamp,
+        )
+        .expect("Failed to create blockchain");
+        let timestamp = timestamp + Duration::from_millis(1);
+        let block = test::create_micro_block_with_coinbase(&chain, &keychains, timestamp);
+        chain
+            .push_micro_block(block, timestamp)
+            .expect("Invalid block");
+        let mut blocks = chain.light_blocks_starting(0, 0);
+        let genesis = blocks.next().unwrap();
+        let micro_block = match blocks.next().unwrap() {
+            LightBlock::LightMicroBlock(block) => block,
+            _ => panic!("Expected micro block"),
+        };
+
+        // Unknown genesis.
+        let mut verifier = LightVerifier::new(Hash::digest("other"), cfg.clone());
+        match verifier.apply_light_block(&genesis) {
+            Err(BlockchainError::IncompatibleGenesis(..)) => {}
+            e => panic!("{:?}", e),
+        }
+
+        let mut verifier = LightVerifier::new(genesis_hash, cfg);
+        verifier.apply_light_block(&genesis).unwrap();
+
+        // Fake outputs.
+        let mut block = micro_block.clone();
+        block.output_hashes.push(Hash::digest("fake"));
+        match verifier.apply_light_micro_block(&block) {
+            Err(BlockchainError::BlockError(BlockError::InvalidMicroBlockOutputsLen(..))) => {}
+            e => panic!("{:?}", e),
+        }
+
+        // Signed by other validator.
+        let mut block = micro_block.clone();
+        let leader = keychains
+            .iter()
+            .find(|k| k.network_pkey != block.header.pkey)
+            .unwrap();
+        block.sig = pbc::sign_hash(&Hash::digest(&block.header), &leader.network_skey);
+        match verifier.apply_light_micro_block(&block) {
+            Err(BlockchainError::BlockError(BlockError::InvalidLeaderSignature(..))) => {}
+            e => panic!("{:?}", e),
+        }
+
+        // Verification doesn't change the state.
+        verifier.verify_light_micro_block(&micro_block).unwrap();
+        assert_eq!(verifier.offset(), 0);
+        verifier.commit_light_micro_block(&micro_block);
+        assert_eq!(verifier.offset(), 1);
+        assert_eq!(verifier.last_block_hash(), chain.last_block_hash());
+    }
+
+    #[test]
+    fn verify_slashed_validator() {
+        const NUM_NODES: usize = 3;
+        let mut cfg: ChainConfig = Default::default();
+        cfg.micro_blocks_in_epoch = 5;
+        let mut timestamp = Timestamp::now();
+        let (keychains, genesis) = test::fake_genesis(
+            cfg.min_stake_amount,
+            (NUM_NODES as i64) * cfg.min_stake_amount + 100,
+            cfg.max_slot_count,
+            NUM_NODES,
+            timestamp,
+            None,
+        );
+        let chain_dir = TempDir::new("test").unwrap();
+        let mut chain = Blockchain::new(
+            cfg.clone(),
+            chain_dir.path(),
+            ConsistencyCheck::None,
+            genesis.clone(),
+            timestamp,
+        )
+        .expect("Failed to create blockchain");
+        for _offset in 0..cfg.micro_blocks_in_epoch {
+            timestamp += Duration::from_millis(1);
+            let block = test::create_micro_block_with_coinbase(&chain, &keychains, timestamp);
+            chain
+                .push_micro_block(block, timestamp)
+                .expect("Invalid block");
+        }
+        timestamp += Duration::from_millis(1);
+        let (block, _extra_transactions) =
+            test::create_fake_macro_block(&chain, &keychains, timestamp);
+        while chain.offset() > 0 {
+            chain.pop_micro_block().expect("Should be ok");
+        }
+        chain
+            .push_macro_block(block, timestamp)
+            .expect("Invalid block");
+
+        // The leader signs two different blocks.
+        timestamp += Duration::from_millis(1);
+        let block1 = test::create_micro_block_with_coinbase(&chain, &keychains, timestamp);
+        let block2 = test::create_micro_block_with_coinbase(
+            &chain,
+            &keychains,
+            timestamp + Duration::from_millis(1),
+        );
+        let cheater = block1.header.pkey;
+        chain
+            .push_micro_block(block1.clone(), timestamp)
+            .expect("Invalid block");
+
+        // The next leader slashes the cheater.
+        timestamp += Duration::from_millis(2);
+        let block = test::create_micro_block_with_coinbase(&chain, &keychains, timestamp);
+        let leader = keychains
+            .iter()
+            .find(|k| k.network_pkey == block.header.pkey)
+            .unwrap();
+        let proof = SlashingProof::new_unchecked(block1, block2);
+        let slashing_tx = confiscate_tx(&chain, &leader.network_pkey, proof).expect("valid proof");
+        let mut transactions = block.transactions;
+        transactions.push(slashing_tx.into());
+        let mut block = MicroBlock::new(
+            block.header.previous,
+            block.header.epoch,
+            block.header.offset,
+            block.header.view_change,
+            None,
+            block.header.pkey,
+            block.header.random,
+            block.header.solution,
+            block.header.timestamp,
+            transactions,
+        );
+        block.sign(&leader.network_skey, &leader.network_pkey);
+        chain
+            .push_micro_block(block, timestamp)
+            .expect("Invalid block");
+        assert!(!chain.validators().iter().any(|(k, _)| *k == cheater));
+
+        // Leaders are selected without the cheater.
+        for _offset in chain.offset()..cfg.micro_blocks_in_epoch {
+            timestamp += Duration::from_millis(1);
+            let block = test::create_micro_block_with_coinbase(&chain, &keychains, timestamp);
+            chain
+                .push_micro_block(block, timestamp)
+                .expect("Invalid block");
+        }
+
+        let mut verifier = LightVerifier::new(Hash::digest(&genesis), cfg.clone());
+        let blocks: Vec<LightBlock> = chain.light_blocks_starting(0, 0).collect();
+        for block in &blocks[..3] {
+            verifier.apply_light_block(block).expect("valid block");
+        }
+
+        // Forged proof of inclusion.
+        let mut slashing_block = match blocks[3].clone() {
+            LightBlock::LightMicroBlock(block) => block,
+            _ => panic!("Expected micro block"),
+        };
+        assert_eq!(slashing_block.slashing_transactions.len(), 1);
+        slashing_block.slashing_transactions[0].proof.index ^= 1;
+        match verifier.apply_light_micro_block(&slashing_block) {
+            Err(BlockchainError::BlockError(BlockError::InvalidMicroBlockSlashingProof(..))) => {}
+            e => panic!("{:?}", e),
+        }
+
+        for block in &blocks[3..] {
+            verifier.apply_light_block(block).expect("valid block");
+        }
+        assert_eq!(verifier.validators(), chain.validators());
+        assert_eq!(verifier.offset(), cfg.micro_blocks_in_epoch);
+        assert_eq!(verifier.last_block_hash(), chain.last_block_hash());
+    }
+}
@@ -22,6 +22,8 @@
 // SOFTWARE.
 
 use failure::Fail;
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
 use std::fmt;
 use std::vec::Vec;
 use stegos_crypto::hash::{Hash, Hashable, Hasher};
@@ -108,8 +110,63 @@ type Path = u32;
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct MerklePath(Path);
 
+/// Proof of inclusion of an element into Merkle Tree.
+///
+/// Contains hashes of siblings on the path from a leaf to the root.
+/// A node without the right sibling is paired with itself, see pull_left().
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct MerkleProof {
+    /// Position of the element in the source array.
+    pub index: u32,
+    /// Hashes of siblings, from leaf to root.
+    pub siblings: Vec<Hash>,
+}
+
+impl MerkleProof {
+    /// Calculate the root hash of the tree for the given element.
+    /// Returns None if the proof is malformed.
+    pub fn root_hash<T: Hashable>(&self, value: &T) -> Option<Hash> {
+        let mut hash = leaf_hash(value);
+        let mut index = self.index;
+        for sibling in &self.siblings {
+            hash = if index & 1 == 0 {
+                inner_hash(&hash, sibling)
+            } else {
+                inner_hash(sibling, &hash)
+            };
+            index >>= 1;
+        }
+        if index != 0 {
+            return None;
+        }
+        Some(hash)
+    }
+
+    /// Check that the element is included into the tree with the given root hash.
+    pub fn verify<T: Hashable>(&self, value: &T, root: &Hash) -> bool {
+        self.root_hash(value).as_ref() == Some(root)
+    }
+}
+
 // -------------------------------------
 
+/// Calculate the hash of a leaf.
+fn leaf_hash<T: Hashable>(value: &T) -> Hash {
+    let mut hasher = Hasher::new();
+    LEAF_PREFIX.hash(&mut hasher);
+    value.hash(&mut hasher);
+    hasher.result()
+}
+
+/// Calculate the hash of an inner node.
+fn inner_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = Hasher::new();
+    INNER_PREFIX.hash(&mut hasher);
+    left.hash(&mut hasher);
+    right.hash(&mut hasher);
+    hasher.result()
+}
+
 /// Calculate the next power of two
 fn next_pow2(mut n: usize) -> usize {
     n -= 1;
@@ -200,6 +257,35 @@ impl<T: Hashable + fmt::Debug> Merkle<T> {
         tree.roothash().clone()
     }
 
+    /// Create a proof of inclusion for the element of array.
+    ///
+    /// Returns None if index is out of bounds.
+    ///
+    pub fn proof_from_array(src: &[T], index: usize) -> Option<MerkleProof> {
+        assert!(src.len() <= Path::max_value() as usize);
+        if index >= src.len() {
+            return None;
+        }
+
+        let mut level: Vec<Hash> = src.iter().map(leaf_hash).collect();
+        let mut position = index;
+        let mut siblings = Vec::new();
+        while level.len() > 1 {
+            let sibling = level.get(position ^ 1).unwrap_or(&level[position]);
+            siblings.push(sibling.clone());
+            level = level
+                .chunks(2)
+                .map(|pair| inner_hash(&pair[0], pair.get(1).unwrap_or(&pair[0])))
+                .collect();
+            position >>= 1;
+        }
+
+        Some(MerkleProof {
+            index: index as u32,
+            siblings,
+        })
+    }
+
     /// Create a Merkle Tree from an array.
     ///
     /// Returns the new tree.
@@ -1186,6 +1272,28 @@ pub mod tests {
         }
     }
 
+    #[test]
+    fn proofs() {
+        for size in 1..34 {
+            let data: Vec<u32> = (0..size).collect();
+            let root = Merkle::root_hash_from_array(&data);
+            for (index, value) in data.iter().enumerate() {
+                let proof = Merkle::proof_from_array(&data, index).unwrap();
+                assert_eq!(proof.siblings.len(), expected_height(data.len()) as usize);
+                assert!(proof.verify(value, &root));
+                assert!(!proof.verify(&(value + 1), &root));
+                let mut proof2 = proof.clone();
+                proof2.index ^= 1;
+                if size > 1 && data.get(index ^ 1).is_some() {
+                    assert!(!proof2.verify(value, &root));
+                }
+                proof2.index = 1 << proof.siblings.len();
+                assert!(!proof2.verify(value, &root));
+            }
+            assert_eq!(Merkle::proof_from_array(&data, data.len()), None);
+        }
+    }
+
     #[test]
     fn serialize_errors() {
         simple_logger::init_with_level(log::Level::Debug).unwrap_or_default();
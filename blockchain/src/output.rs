@@ -25,6 +25,7 @@ use crate::timestamp::Timestamp;
 use crate::BlockchainError;
 use failure::{Error, Fail};
 use rand::random;
+use schemars::JsonSchema;
 use serde_derive::{Deserialize, Serialize};
 use std::mem::transmute;
 use stegos_crypto::bulletproofs::{fee_a, make_range_proof, validate_range_proof, BulletProof};
@@ -95,7 +96,7 @@ impl From<std::str::Utf8Error> for OutputError {
 }
 
 /// Payment UTXO.
-#[derive(Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct PaymentOutput {
     /// Cloaked public key of recipient.
     pub recipient: PublicKey,
@@ -111,6 +112,7 @@ pub struct PaymentOutput {
     /// Encrypted payload.
     #[serde(deserialize_with = "stegos_crypto::utils::vec_deserialize_from_hex")]
     #[serde(serialize_with = "stegos_crypto::utils::vec_serialize_to_hex")]
+    #[schemars(with = "String")]
     pub payload: Vec<u8>,
 }
 
@@ -128,7 +130,7 @@ impl fmt::Debug for PaymentOutput {
 }
 
 /// PublicPayment UTXO.
-#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct PublicPaymentOutput {
     /// Uncloaked public key of recipient.
     pub recipient: PublicKey,
@@ -141,7 +143,7 @@ pub struct PublicPaymentOutput {
 }
 
 /// Stake UTXO.
-#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct StakeOutput {
     /// Uncloaked account key of validator.
     pub recipient: PublicKey,
@@ -234,7 +236,7 @@ fn cloak_key(recipient_pkey: &PublicKey, gamma: &Fr) -> Result<(PublicKey, Fr),
 }
 
 /// Unpacked data field of PaymentPayload.
-#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum PaymentPayloadData {
     /// A string up to PAYLOAD_DATA_LEN - 2 bytes inclusive.
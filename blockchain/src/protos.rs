@@ -43,6 +43,8 @@ pub enum ProtoError {
 
 // link protobuf dependencies
 use crate::awards::Awards;
+use crate::election::ElectionInputs;
+use crate::governance::ParameterChanges;
 use bit_vec::BitVec;
 use std::collections::BTreeMap;
 use std::iter::FromIterator;
@@ -207,6 +209,37 @@ impl ProtoConvert for ElectionResult {
     }
 }
 
+impl ProtoConvert for ElectionInputs {
+    type Proto = blockchain::ElectionInputs;
+
+    fn into_proto(&self) -> Self::Proto {
+        let mut msg = Self::Proto::new();
+        for staker in &self.stakers {
+            let mut staker_proto = blockchain::Staker::new();
+            staker_proto.set_network_pkey(staker.0.into_proto());
+            staker_proto.set_amount(staker.1);
+            msg.stakers.push(staker_proto)
+        }
+        msg.set_slot_count(self.slot_count);
+        msg
+    }
+
+    fn from_proto(proto: &Self::Proto) -> Result<Self, Error> {
+        let mut stakers = Vec::new();
+        for staker in &proto.stakers {
+            stakers.push((
+                ProtoConvert::from_proto(staker.get_network_pkey())?,
+                staker.get_amount(),
+            ))
+        }
+
+        Ok(Self {
+            stakers,
+            slot_count: proto.get_slot_count(),
+        })
+    }
+}
+
 impl ProtoConvert for Balance {
     type Proto = blockchain::Balance;
 
@@ -403,6 +436,61 @@ impl ProtoConvert for EpochInfo {
     }
 }
 
+impl ProtoConvert for ValidatorEpochStats {
+    type Proto = blockchain::ValidatorEpochStats;
+
+    fn into_proto(&self) -> Self::Proto {
+        let mut msg = Self::Proto::new();
+        msg.set_epoch(self.epoch);
+        msg.set_slots(self.slots);
+        if let (Some(produced), Some(missed), Some(block_rewards)) =
+            (self.blocks_produced, self.blocks_missed, self.block_rewards)
+        {
+            msg.set_has_blocks(true);
+            msg.set_blocks_produced(produced);
+            msg.set_blocks_missed(missed);
+            msg.set_block_rewards(block_rewards);
+        }
+        if let Some(prevoted) = self.prevoted {
+            msg.set_has_prevote(true);
+            msg.set_prevoted(prevoted);
+        }
+        msg.set_precommitted(self.precommitted);
+        msg.set_active(self.active);
+        msg.set_service_award(self.service_award);
+        msg
+    }
+
+    fn from_proto(proto: &Self::Proto) -> Result<Self, Error> {
+        let (blocks_produced, blocks_missed, block_rewards) = if proto.get_has_blocks() {
+            (
+                Some(proto.get_blocks_produced()),
+                Some(proto.get_blocks_missed()),
+                Some(proto.get_block_rewards()),
+            )
+        } else {
+            (None, None, None)
+        };
+        let prevoted = if proto.get_has_prevote() {
+            Some(proto.get_prevoted())
+        } else {
+            None
+        };
+
+        Ok(Self {
+            epoch: proto.get_epoch(),
+            slots: proto.get_slots(),
+            blocks_produced,
+            blocks_missed,
+            block_rewards,
+            prevoted,
+            precommitted: proto.get_precommitted(),
+            active: proto.get_active(),
+            service_award: proto.get_service_award(),
+        })
+    }
+}
+
 impl ProtoConvert for LightEpochInfo {
     type Proto = blockchain::LightEpochInfo;
 
@@ -416,6 +504,9 @@ impl ProtoConvert for LightEpochInfo {
             validator_proto.set_amount(validator.1);
             msg.validators.push(validator_proto)
         }
+        for change in &self.parameter_changes {
+            msg.parameter_changes.push(change.into_proto());
+        }
         msg
     }
 
@@ -429,11 +520,16 @@ impl ProtoConvert for LightEpochInfo {
                 validator.get_amount(),
             ))
         }
+        let mut parameter_changes = Vec::with_capacity(proto.parameter_changes.len());
+        for change in proto.parameter_changes.iter() {
+            parameter_changes.push(ParameterChange::from_proto(change)?);
+        }
 
         Ok(Self {
             header,
             facilitator,
             validators,
+            parameter_changes,
         })
     }
 }
@@ -1007,6 +1103,9 @@ impl ProtoConvert for LightMicroBlock {
         for canary in &self.canaries {
             proto.canaries.push(canary.into_proto());
         }
+        for tx in &self.slashing_transactions {
+            proto.slashing_transactions.push(tx.into_proto());
+        }
         proto
     }
 
@@ -1025,16 +1124,58 @@ impl ProtoConvert for LightMicroBlock {
         for canary in proto.canaries.iter() {
             canaries.push(Canary::from_proto(canary)?);
         }
+        let mut slashing_transactions = Vec::with_capacity(proto.slashing_transactions.len());
+        for tx in proto.slashing_transactions.iter() {
+            slashing_transactions.push(LightSlashingTransaction::from_proto(tx)?);
+        }
         Ok(LightMicroBlock {
             header,
             sig,
             input_hashes,
             output_hashes,
             canaries,
+            slashing_transactions,
         })
     }
 }
 
+impl ProtoConvert for MerkleProof {
+    type Proto = blockchain::MerkleProof;
+    fn into_proto(&self) -> Self::Proto {
+        let mut proto = blockchain::MerkleProof::new();
+        proto.set_index(self.index);
+        for sibling in &self.siblings {
+            proto.siblings.push(sibling.into_proto());
+        }
+        proto
+    }
+
+    fn from_proto(proto: &Self::Proto) -> Result<Self, Error> {
+        let index = proto.get_index();
+        let mut siblings = Vec::<Hash>::with_capacity(proto.siblings.len());
+        for sibling in proto.siblings.iter() {
+            siblings.push(Hash::from_proto(sibling)?);
+        }
+        Ok(MerkleProof { index, siblings })
+    }
+}
+
+impl ProtoConvert for LightSlashingTransaction {
+    type Proto = blockchain::LightSlashingTransaction;
+    fn into_proto(&self) -> Self::Proto {
+        let mut proto = blockchain::LightSlashingTransaction::new();
+        proto.set_transaction(self.transaction.into_proto());
+        proto.set_proof(self.proof.into_proto());
+        proto
+    }
+
+    fn from_proto(proto: &Self::Proto) -> Result<Self, Error> {
+        let transaction = SlashingTransaction::from_proto(proto.get_transaction())?;
+        let proof = MerkleProof::from_proto(proto.get_proof())?;
+        Ok(LightSlashingTransaction { transaction, proof })
+    }
+}
+
 impl ProtoConvert for MacroBlockHeader {
     type Proto = blockchain::MacroBlockHeader;
     fn into_proto(&self) -> Self::Proto {
@@ -1057,6 +1198,10 @@ impl ProtoConvert for MacroBlockHeader {
         proto.set_outputs_len(self.outputs_len);
         proto.set_outputs_range_hash(self.outputs_range_hash.into_proto());
         proto.set_canaries_range_hash(self.canaries_range_hash.into_proto());
+        if self.version >= GOVERNANCE_VERSION {
+            proto.set_parameter_votes_len(self.parameter_votes_len);
+            proto.set_parameter_votes_range_hash(self.parameter_votes_range_hash.into_proto());
+        }
         proto
     }
 
@@ -1079,6 +1224,12 @@ impl ProtoConvert for MacroBlockHeader {
         let outputs_len = proto.get_outputs_len();
         let outputs_range_hash = Hash::from_proto(proto.get_outputs_range_hash())?;
         let canaries_range_hash = Hash::from_proto(proto.get_canaries_range_hash())?;
+        let parameter_votes_len = proto.get_parameter_votes_len();
+        let parameter_votes_range_hash = if proto.has_parameter_votes_range_hash() {
+            Hash::from_proto(proto.get_parameter_votes_range_hash())?
+        } else {
+            Hash::zero()
+        };
         Ok(MacroBlockHeader {
             version,
             previous,
@@ -1098,10 +1249,114 @@ impl ProtoConvert for MacroBlockHeader {
             outputs_len,
             outputs_range_hash,
             canaries_range_hash,
+            parameter_votes_len,
+            parameter_votes_range_hash,
         })
     }
 }
 
+impl ProtoConvert for ChainParameter {
+    type Proto = blockchain::ChainParameter;
+    fn into_proto(&self) -> Self::Proto {
+        let mut proto = blockchain::ChainParameter::new();
+        match *self {
+            ChainParameter::BlockReward(value) => proto.set_block_reward(value),
+            ChainParameter::MinStakeAmount(value) => proto.set_min_stake_amount(value),
+            ChainParameter::StakeEpochs(value) => proto.set_stake_epochs(value),
+            ChainParameter::MicroBlocksInEpoch(value) => proto.set_micro_blocks_in_epoch(value),
+            ChainParameter::ServiceAwardPerEpoch(value) => proto.set_service_award_per_epoch(value),
+        }
+        proto
+    }
+
+    fn from_proto(proto: &Self::Proto) -> Result<Self, Error> {
+        let parameter = match proto.parameter {
+            Some(blockchain::ChainParameter_oneof_parameter::block_reward(value)) => {
+                ChainParameter::BlockReward(value)
+            }
+            Some(blockchain::ChainParameter_oneof_parameter::min_stake_amount(value)) => {
+                ChainParameter::MinStakeAmount(value)
+            }
+            Some(blockchain::ChainParameter_oneof_parameter::stake_epochs(value)) => {
+                ChainParameter::StakeEpochs(value)
+            }
+            Some(blockchain::ChainParameter_oneof_parameter::micro_blocks_in_epoch(value)) => {
+                ChainParameter::MicroBlocksInEpoch(value)
+            }
+            Some(blockchain::ChainParameter_oneof_parameter::service_award_per_epoch(value)) => {
+                ChainParameter::ServiceAwardPerEpoch(value)
+            }
+            None => {
+                return Err(ProtoError::MissingField(
+                    "parameter".to_string(),
+                    "parameter".to_string(),
+                )
+                .into());
+            }
+        };
+        Ok(parameter)
+    }
+}
+
+impl ProtoConvert for ParameterVote {
+    type Proto = blockchain::ParameterVote;
+    fn into_proto(&self) -> Self::Proto {
+        let mut proto = blockchain::ParameterVote::new();
+        proto.set_epoch(self.epoch);
+        proto.set_parameter(self.parameter.into_proto());
+        proto.set_activation_epoch(self.activation_epoch);
+        proto.set_pkey(self.pkey.into_proto());
+        proto.set_sig(self.sig.into_proto());
+        proto
+    }
+
+    fn from_proto(proto: &Self::Proto) -> Result<Self, Error> {
+        Ok(ParameterVote {
+            epoch: proto.get_epoch(),
+            parameter: ChainParameter::from_proto(proto.get_parameter())?,
+            activation_epoch: proto.get_activation_epoch(),
+            pkey: pbc::PublicKey::from_proto(proto.get_pkey())?,
+            sig: pbc::Signature::from_proto(proto.get_sig())?,
+        })
+    }
+}
+
+impl ProtoConvert for ParameterChange {
+    type Proto = blockchain::ParameterChange;
+    fn into_proto(&self) -> Self::Proto {
+        let mut proto = blockchain::ParameterChange::new();
+        proto.set_epoch(self.epoch);
+        proto.set_parameter(self.parameter.into_proto());
+        proto
+    }
+
+    fn from_proto(proto: &Self::Proto) -> Result<Self, Error> {
+        Ok(ParameterChange {
+            epoch: proto.get_epoch(),
+            parameter: ChainParameter::from_proto(proto.get_parameter())?,
+        })
+    }
+}
+
+impl ProtoConvert for ParameterChanges {
+    type Proto = blockchain::ParameterChanges;
+    fn into_proto(&self) -> Self::Proto {
+        let mut proto = blockchain::ParameterChanges::new();
+        for change in &self.0 {
+            proto.changes.push(change.into_proto());
+        }
+        proto
+    }
+
+    fn from_proto(proto: &Self::Proto) -> Result<Self, Error> {
+        let mut changes = Vec::with_capacity(proto.changes.len());
+        for change in proto.changes.iter() {
+            changes.push(ParameterChange::from_proto(change)?);
+        }
+        Ok(ParameterChanges(changes))
+    }
+}
+
 impl ProtoConvert for MacroBlock {
     type Proto = blockchain::MacroBlock;
     fn into_proto(&self) -> Self::Proto {
@@ -1115,6 +1370,9 @@ impl ProtoConvert for MacroBlock {
         for output in &self.outputs {
             proto.outputs.push(output.into_proto());
         }
+        for vote in &self.parameter_votes {
+            proto.parameter_votes.push(vote.into_proto());
+        }
         proto
     }
 
@@ -1137,12 +1395,18 @@ impl ProtoConvert for MacroBlock {
             outputs.push(Output::from_proto(output)?);
         }
 
+        let mut parameter_votes = Vec::with_capacity(proto.parameter_votes.len());
+        for vote in proto.parameter_votes.iter() {
+            parameter_votes.push(ParameterVote::from_proto(vote)?);
+        }
+
         Ok(MacroBlock {
             header,
             multisig,
             multisigmap,
             inputs,
             outputs,
+            parameter_votes,
         })
     }
 }
@@ -1169,6 +1433,9 @@ impl ProtoConvert for LightMacroBlock {
         for canary in &self.canaries {
             proto.canaries.push(canary.into_proto());
         }
+        for vote in &self.parameter_votes {
+            proto.parameter_votes.push(vote.into_proto());
+        }
         proto
     }
 
@@ -1199,6 +1466,10 @@ impl ProtoConvert for LightMacroBlock {
         for canary in proto.canaries.iter() {
             canaries.push(Canary::from_proto(canary)?);
         }
+        let mut parameter_votes = Vec::with_capacity(proto.parameter_votes.len());
+        for vote in proto.parameter_votes.iter() {
+            parameter_votes.push(ParameterVote::from_proto(vote)?);
+        }
         Ok(LightMacroBlock {
             header,
             multisig,
@@ -1207,6 +1478,7 @@ impl ProtoConvert for LightMacroBlock {
             input_hashes,
             output_hashes,
             canaries,
+            parameter_votes,
         })
     }
 }
@@ -1572,6 +1844,22 @@ mod tests {
         let block2 = roundtrip(&block);
         assert_eq!(block, block2);
 
+        let mut voted_block = block.clone();
+        let vote = ParameterVote::new(
+            epoch,
+            ChainParameter::StakeEpochs(5),
+            epoch + 2,
+            &skeypbc,
+            &pkeypbc,
+        );
+        voted_block.set_parameter_votes(vec![vote]);
+        assert_ne!(Hash::digest(&voted_block), Hash::digest(&block));
+        let voted_block2 = roundtrip(&voted_block);
+        assert_eq!(voted_block, voted_block2);
+        let light_block = voted_block.into_light_macro_block(validators.clone());
+        let light_block2 = roundtrip(&light_block);
+        assert_eq!(light_block, light_block2);
+
         let light_block = block.clone().into_light_macro_block(validators);
         let light_block2 = roundtrip(&light_block);
         assert_eq!(light_block, light_block2);
@@ -1704,4 +1992,52 @@ mod tests {
         };
         roundtrip_eq(&epoch_info);
     }
+
+    #[test]
+    fn roundtrip_validator_epoch_stats() {
+        let mut stats = ValidatorEpochStats {
+            epoch: 12,
+            slots: 455,
+            blocks_produced: Some(3),
+            blocks_missed: Some(1),
+            block_rewards: Some(300),
+            prevoted: Some(true),
+            precommitted: true,
+            active: false,
+            service_award: 756,
+        };
+        roundtrip_eq(&stats);
+
+        stats.blocks_produced = None;
+        stats.blocks_missed = None;
+        stats.block_rewards = None;
+        stats.prevoted = None;
+        roundtrip_eq(&stats);
+    }
+
+    #[test]
+    fn roundtrip_election_inputs() {
+        let (_, pkey1) = pbc::make_random_keys();
+        let (_, pkey2) = pbc::make_random_keys();
+        let inputs = ElectionInputs {
+            stakers: vec![(pkey1, 100), (pkey2, 200)],
+            slot_count: 1000,
+        };
+        roundtrip_eq(&inputs);
+    }
+
+    #[test]
+    fn roundtrip_parameter_changes() {
+        let changes = ParameterChanges(vec![
+            ParameterChange {
+                epoch: 10,
+                parameter: ChainParameter::BlockReward(100),
+            },
+            ParameterChange {
+                epoch: 12,
+                parameter: ChainParameter::MicroBlocksInEpoch(30),
+            },
+        ]);
+        roundtrip_eq(&changes);
+    }
 }
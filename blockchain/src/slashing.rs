@@ -23,11 +23,12 @@ use crate::error::SlashingError;
 use crate::transaction::SlashingTransaction;
 use crate::{Blockchain, BlockchainError, MicroBlock, PublicPaymentOutput};
 use log::debug;
+use schemars::JsonSchema;
 use serde_derive::{Deserialize, Serialize};
 use stegos_crypto::hash::{Hash, Hashable, Hasher};
 use stegos_crypto::pbc;
 
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct SlashingProof {
     pub block1: MicroBlock,
     pub block2: MicroBlock,
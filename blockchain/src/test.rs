@@ -27,6 +27,7 @@ use super::block::{Block, MacroBlock, MicroBlock};
 use super::blockchain::{Blockchain, OutputRecovery};
 use super::election::mix;
 use super::error::BlockchainError;
+use super::governance::ParameterVote;
 use super::multisignature::create_multi_signature;
 use super::output::{Output, PaymentOutput, PaymentPayloadData, StakeOutput};
 use super::timestamp::Timestamp;
@@ -285,6 +286,15 @@ pub fn create_fake_macro_block(
     chain: &Blockchain,
     keychains: &[KeyChain],
     timestamp: Timestamp,
+) -> (MacroBlock, Vec<Transaction>) {
+    create_fake_macro_block_with_votes(chain, keychains, timestamp, Vec::new())
+}
+
+pub fn create_fake_macro_block_with_votes(
+    chain: &Blockchain,
+    keychains: &[KeyChain],
+    timestamp: Timestamp,
+    parameter_votes: Vec<ParameterVote>,
 ) -> (MacroBlock, Vec<Transaction>) {
     let view_change = chain.view_change();
     let key = chain.select_leader(view_change);
@@ -295,6 +305,7 @@ pub fn create_fake_macro_block(
         &keys.network_skey,
         keys.network_pkey,
         timestamp,
+        parameter_votes,
     );
     sign_fake_macro_block(&mut block, chain, keychains);
     (block, extra_transactions)
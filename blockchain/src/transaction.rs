@@ -25,6 +25,7 @@ use crate::error::*;
 use crate::output::*;
 use crate::SlashingProof;
 use failure::Error;
+use schemars::JsonSchema;
 use serde_derive::{Deserialize, Serialize};
 use stegos_crypto::hash::{Hash, Hashable, Hasher};
 use stegos_crypto::pbc;
@@ -37,7 +38,7 @@ use stegos_crypto::scc::{
 //--------------------------------------------------------------------------------------------------
 
 /// Transaction that confiscate stake from cheater.
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct ServiceAwardTransaction {
     pub winner_reward: Vec<Output>,
 }
@@ -56,7 +57,7 @@ impl Hashable for ServiceAwardTransaction {
 //--------------------------------------------------------------------------------------------------
 
 /// Transaction that confiscate stake from cheater.
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct SlashingTransaction {
     pub proof: SlashingProof,
     /// List of inputs.
@@ -88,7 +89,7 @@ impl Hashable for SlashingTransaction {
 //--------------------------------------------------------------------------------------------------
 
 /// Coinbase Transaction.
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct CoinbaseTransaction {
     /// Block reward.
     pub block_reward: i64,
@@ -140,7 +141,7 @@ impl SlashingTransaction {
 //--------------------------------------------------------------------------------------------------
 
 /// PaymentTransaction.
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct PaymentTransaction {
     /// List of inputs.
     pub txins: Vec<Hash>,
@@ -366,7 +367,7 @@ impl PaymentTransaction {
 //--------------------------------------------------------------------------------------------------
 
 /// RestakeTransaction.
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct RestakeTransaction {
     /// List of inputs.
     pub txins: Vec<Hash>,
@@ -665,7 +666,7 @@ impl From<ServiceAwardTransaction> for Transaction {
 // Transaction Status (enum).
 //--------------------------------------------------------------------------------------------------
 
-#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq, JsonSchema)]
 #[serde(tag = "status")]
 #[serde(rename_all = "snake_case")]
 pub enum TransactionStatus {
@@ -21,10 +21,11 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
-use crate::block::{Block, MacroBlock, MacroBlockHeader, MicroBlock, VERSION};
+use crate::block::{Block, MacroBlock, MacroBlockHeader, MicroBlock, GOVERNANCE_VERSION, VERSION};
 use crate::blockchain::{Blockchain, ChainInfo};
 use crate::election::mix;
-use crate::error::{BlockError, BlockchainError, SlashingError, TransactionError};
+use crate::error::{BlockError, BlockchainError, GovernanceError, SlashingError, TransactionError};
+use crate::governance::{ParameterVote, MAX_VOTES_PER_VALIDATOR, PARAMETER_CHANGE_DELAY};
 use crate::multisignature::check_multi_signature;
 use crate::output::{Output, PublicPaymentOutput};
 use crate::slashing::confiscate_tx;
@@ -457,12 +458,13 @@ impl Blockchain {
         let epoch = header.epoch;
 
         // Check block version.
-        if header.version != VERSION {
+        let version = self.cfg().macro_block_version(epoch);
+        if header.version != version {
             return Err(BlockError::InvalidBlockVersion(
                 epoch,
                 *block_hash,
                 header.version,
-                VERSION,
+                version,
             )
             .into());
         }
@@ -668,6 +670,35 @@ impl Blockchain {
         //
         block.validate_balance(&inputs)?;
 
+        //
+        // Validate governance votes.
+        //
+        if block.header.version < GOVERNANCE_VERSION && !block.parameter_votes.is_empty() {
+            return Err(GovernanceError::NotActivated(epoch).into());
+        }
+        if block.header.parameter_votes_len as usize != block.parameter_votes.len() {
+            return Err(BlockError::InvalidMacroBlockVotesLen(
+                epoch,
+                block_hash,
+                block.header.parameter_votes_len as usize,
+                block.parameter_votes.len(),
+            )
+            .into());
+        }
+        let parameter_votes_range_hash = Merkle::root_hash_from_array(&block.parameter_votes);
+        if block.header.version >= GOVERNANCE_VERSION
+            && block.header.parameter_votes_range_hash != parameter_votes_range_hash
+        {
+            return Err(BlockError::InvalidMacroBlockVotesHash(
+                epoch,
+                block_hash,
+                parameter_votes_range_hash,
+                block.header.parameter_votes_range_hash,
+            )
+            .into());
+        }
+        self.validate_parameter_votes(&block.parameter_votes)?;
+
         //
         // Sic: the following fields can't be validated properly
         // without processing the block itself:
@@ -688,6 +719,7 @@ impl Blockchain {
         block_hash: &Hash,
         header: &MacroBlockHeader,
         transactions: &[Transaction],
+        parameter_votes: &[ParameterVote],
     ) -> Result<MacroBlock, BlockchainError> {
         if header.epoch != self.epoch() {
             return Err(BlockError::InvalidBlockEpoch(header.epoch, self.epoch()).into());
@@ -819,8 +851,11 @@ impl Blockchain {
         // Select validators.
         let validators = self.next_election_result(header.random).validators;
 
+        // Validate governance votes.
+        self.validate_parameter_votes(parameter_votes)?;
+
         // Re-create original block.
-        let block = MacroBlock::from_transactions(
+        let mut block = MacroBlock::from_transactions(
             header.previous,
             epoch,
             header.view_change,
@@ -833,6 +868,9 @@ impl Blockchain {
             validators,
             &transactions,
         )?;
+        if header.version >= GOVERNANCE_VERSION {
+            block.set_parameter_votes(parameter_votes.to_vec());
+        }
 
         // Check that block has the same hash.
         let expected_block_hash = Hash::digest(&block);
@@ -848,6 +886,58 @@ impl Blockchain {
         Ok(block)
     }
 
+    ///
+    /// Validate a governance vote for the current epoch.
+    ///
+    pub fn validate_parameter_vote(&self, vote: &ParameterVote) -> Result<(), BlockchainError> {
+        let epoch = self.epoch();
+        if self.cfg().macro_block_version(epoch) < GOVERNANCE_VERSION {
+            return Err(GovernanceError::NotActivated(epoch).into());
+        }
+        if vote.epoch != epoch {
+            return Err(GovernanceError::OutOfDateVote(vote.epoch, epoch).into());
+        }
+        let min_activation_epoch = epoch + PARAMETER_CHANGE_DELAY;
+        if vote.activation_epoch < min_activation_epoch {
+            return Err(GovernanceError::TooEarlyActivation(
+                vote.activation_epoch,
+                min_activation_epoch,
+            )
+            .into());
+        }
+        // Genesis has no validators.
+        if epoch == 0
+            || !self
+                .validators_at_epoch_start()
+                .iter()
+                .any(|(pkey, _)| *pkey == vote.pkey)
+        {
+            return Err(GovernanceError::NotValidator(vote.pkey).into());
+        }
+        vote.validate()?;
+        Ok(())
+    }
+
+    ///
+    /// A helper for validate_macro_block() and validate_proposed_macro_block().
+    ///
+    fn validate_parameter_votes(&self, votes: &[ParameterVote]) -> Result<(), BlockchainError> {
+        let mut hashes: HashSet<Hash> = HashSet::with_capacity(votes.len());
+        let mut counts: HashMap<pbc::PublicKey, usize> = HashMap::new();
+        for vote in votes {
+            self.validate_parameter_vote(vote)?;
+            if !hashes.insert(Hash::digest(vote)) {
+                return Err(GovernanceError::DuplicateVote(vote.pkey).into());
+            }
+            let count = counts.entry(vote.pkey).or_insert(0);
+            *count += 1;
+            if *count > MAX_VOTES_PER_VALIDATOR {
+                return Err(GovernanceError::TooManyVotes(vote.pkey).into());
+            }
+        }
+        Ok(())
+    }
+
     ///
     /// A helper for validate_micro_block().
     ///
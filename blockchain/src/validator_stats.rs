@@ -0,0 +1,238 @@
+//
+// MIT License
+//
+// Copyright (c) 2018-2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Per-validator, per-epoch performance reports.
+
+use crate::block::StakersGroup;
+use bit_vec::BitVec;
+use byteorder::{BigEndian, ByteOrder};
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use stegos_crypto::{pbc, scc};
+
+/// Performance of a validator during one epoch.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ValidatorEpochStats {
+    /// Epoch number.
+    pub epoch: u64,
+    /// Number of slots owned by validator in this epoch.
+    pub slots: i64,
+    /// Number of micro blocks produced by validator.
+    /// None if micro blocks of this epoch were not observed by this node.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blocks_produced: Option<u32>,
+    /// Number of leader timeouts which led to view changes.
+    /// None if micro blocks of this epoch were not observed by this node.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blocks_missed: Option<u32>,
+    /// Sum of block rewards for produced micro blocks.
+    /// None if micro blocks of this epoch were not observed by this node.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_rewards: Option<i64>,
+    /// Validator sent a prevote for the macro block, as observed by the local consensus.
+    /// None if this node didn't participate in the consensus.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prevoted: Option<bool>,
+    /// Validator signed the macro block (precommit).
+    pub precommitted: bool,
+    /// Validator was marked as active in the activity map of the macro block.
+    pub active: bool,
+    /// Service award paid to validator's account at the end of this epoch.
+    pub service_award: i64,
+}
+
+/// Information about one micro block, needed to calculate `ValidatorEpochStats`.
+#[derive(Clone, Debug)]
+pub(crate) struct MicroBlockStats {
+    /// The leader who produced this block.
+    pub leader: pbc::PublicKey,
+    /// Leaders which failed to produce this block.
+    pub skipped: Vec<pbc::PublicKey>,
+    /// Block reward from coinbase.
+    pub block_reward: i64,
+}
+
+#[derive(Default)]
+struct BlockCounters {
+    produced: u32,
+    missed: u32,
+    block_rewards: i64,
+}
+
+/// Returns true if `micro_blocks` covers every offset of an epoch.
+pub(crate) fn is_complete_epoch(
+    micro_blocks: &BTreeMap<u32, MicroBlockStats>,
+    micro_blocks_in_epoch: u32,
+) -> bool {
+    micro_blocks.keys().cloned().eq(0..micro_blocks_in_epoch)
+}
+
+/// Calculate statistics for all validators of finished epoch.
+///
+/// `micro_blocks` is None if some micro blocks of this epoch were not observed,
+/// block counters are omitted in this case.
+pub(crate) fn epoch_stats(
+    epoch: u64,
+    validators: &StakersGroup,
+    micro_blocks: Option<&BTreeMap<u32, MicroBlockStats>>,
+    activity_map: &BitVec,
+    multisigmap: &BitVec,
+    accounts: &HashMap<pbc::PublicKey, scc::PublicKey>,
+    payout: Option<(scc::PublicKey, i64)>,
+) -> Vec<(pbc::PublicKey, ValidatorEpochStats)> {
+    let is_complete = micro_blocks.is_some();
+    let mut counters: HashMap<pbc::PublicKey, BlockCounters> = HashMap::new();
+    for block in micro_blocks.into_iter().flat_map(|m| m.values()) {
+        let leader = counters.entry(block.leader).or_default();
+        leader.produced += 1;
+        leader.block_rewards += block.block_reward;
+        for skipped in &block.skipped {
+            counters.entry(*skipped).or_default().missed += 1;
+        }
+    }
+
+    let mut stats = Vec::with_capacity(validators.len());
+    for (validator_id, (validator, slots)) in validators.iter().enumerate() {
+        let (blocks_produced, blocks_missed, block_rewards) = if is_complete {
+            let c = counters.remove(validator).unwrap_or_default();
+            (Some(c.produced), Some(c.missed), Some(c.block_rewards))
+        } else {
+            (None, None, None)
+        };
+        let service_award = match (payout, accounts.get(validator)) {
+            (Some((recipient, amount)), Some(account)) if *account == recipient => amount,
+            _ => 0,
+        };
+        let validator_stats = ValidatorEpochStats {
+            epoch,
+            slots: *slots,
+            blocks_produced,
+            blocks_missed,
+            block_rewards,
+            prevoted: None,
+            precommitted: multisigmap.get(validator_id).unwrap_or(false),
+            active: activity_map.get(validator_id).unwrap_or(false),
+            service_award,
+        };
+        stats.push((*validator, validator_stats));
+    }
+    stats
+}
+
+/// Database key for validator stats, ordered by validator and then by epoch.
+pub(crate) fn stats_key(validator: &pbc::PublicKey, epoch: u64) -> Vec<u8> {
+    let mut key = validator.to_bytes().to_vec();
+    let mut epoch_bytes = [0u8; 8];
+    BigEndian::write_u64(&mut epoch_bytes, epoch);
+    key.extend_from_slice(&epoch_bytes);
+    key
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn micro_block(leader: pbc::PublicKey, skipped: Vec<pbc::PublicKey>) -> MicroBlockStats {
+        MicroBlockStats {
+            leader,
+            skipped,
+            block_reward: 10,
+        }
+    }
+
+    #[test]
+    fn epoch_stats_counters() {
+        let (_, pkey1) = pbc::make_random_keys();
+        let (_, pkey2) = pbc::make_random_keys();
+        let (_, account1) = scc::make_random_keys();
+        let (_, account2) = scc::make_random_keys();
+        let validators: StakersGroup = vec![(pkey1, 10), (pkey2, 20)];
+        let mut accounts = HashMap::new();
+        accounts.insert(pkey1, account1);
+        accounts.insert(pkey2, account2);
+
+        let mut micro_blocks = BTreeMap::new();
+        micro_blocks.insert(0, micro_block(pkey1, vec![]));
+        micro_blocks.insert(1, micro_block(pkey2, vec![pkey1]));
+        micro_blocks.insert(2, micro_block(pkey2, vec![]));
+
+        let mut activity_map = BitVec::from_elem(2, true);
+        activity_map.set(0, false);
+        let mut multisigmap = BitVec::from_elem(2, false);
+        multisigmap.set(1, true);
+
+        assert!(is_complete_epoch(&micro_blocks, 3));
+        let stats = epoch_stats(
+            5,
+            &validators,
+            Some(&micro_blocks),
+            &activity_map,
+            &multisigmap,
+            &accounts,
+            Some((account2, 100)),
+        );
+        assert_eq!(stats.len(), 2);
+        let (key, s1) = &stats[0];
+        assert_eq!(key, &pkey1);
+        assert_eq!(s1.epoch, 5);
+        assert_eq!(s1.slots, 10);
+        assert_eq!(s1.blocks_produced, Some(1));
+        assert_eq!(s1.blocks_missed, Some(1));
+        assert_eq!(s1.block_rewards, Some(10));
+        assert!(!s1.active);
+        assert!(!s1.precommitted);
+        assert_eq!(s1.service_award, 0);
+        let (key, s2) = &stats[1];
+        assert_eq!(key, &pkey2);
+        assert_eq!(s2.blocks_produced, Some(2));
+        assert_eq!(s2.blocks_missed, Some(0));
+        assert_eq!(s2.block_rewards, Some(20));
+        assert!(s2.active);
+        assert!(s2.precommitted);
+        assert_eq!(s2.service_award, 100);
+
+        // Incomplete epoch - no block counters.
+        micro_blocks.remove(&0);
+        assert!(!is_complete_epoch(&micro_blocks, 3));
+        let stats = epoch_stats(
+            5,
+            &validators,
+            None,
+            &activity_map,
+            &multisigmap,
+            &accounts,
+            None,
+        );
+        assert_eq!(stats[0].1.blocks_produced, None);
+        assert_eq!(stats[1].1.blocks_missed, None);
+        assert_eq!(stats[1].1.service_award, 0);
+    }
+
+    #[test]
+    fn stats_key_order() {
+        let (_, pkey) = pbc::make_random_keys();
+        assert!(stats_key(&pkey, 1) < stats_key(&pkey, 2));
+        assert!(stats_key(&pkey, 255) < stats_key(&pkey, 256));
+    }
+}
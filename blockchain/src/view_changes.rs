@@ -22,14 +22,16 @@
 use crate::blockchain::{Blockchain, ChainInfo};
 use crate::multisignature::{check_multi_signature, create_multi_signature_index};
 use bit_vec::BitVec;
+use schemars::JsonSchema;
 use serde_derive::{Deserialize, Serialize};
 use stegos_crypto::hash::{Hash, Hashable, Hasher};
 use stegos_crypto::pbc;
 
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct ViewChangeProof {
     #[serde(deserialize_with = "stegos_crypto::utils::deserialize_bitvec")]
     #[serde(serialize_with = "stegos_crypto::utils::serialize_bitvec")]
+    #[schemars(with = "String")]
     pub multimap: BitVec,
     pub multisig: pbc::Signature,
 }
@@ -302,6 +302,8 @@ do_install() {
 do_test() {
     do_builddep
     cargo test --all
+    # Nodes without the compatibility bridge.
+    cargo test -p stegos_network --no-default-features
 }
 
 do_build_release() {
@@ -0,0 +1,49 @@
+//! API Client - Errors.
+
+//
+// MIT License
+//
+// Copyright (c) 2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use failure::{Error, Fail};
+use stegos_api::Limit;
+
+#[derive(Debug, Fail)]
+pub enum ClientError {
+    #[fail(display = "Request timed out")]
+    Timeout,
+    #[fail(display = "Client is closed")]
+    Closed,
+    #[fail(display = "Server error: {}", _0)]
+    Server(String),
+    #[fail(display = "Limit exceeded: limit={:?}, error={}", _0, _1)]
+    LimitExceeded(Limit, String),
+    #[fail(display = "Unexpected response: {}", _0)]
+    UnexpectedResponse(String),
+    #[fail(display = "Connection error: {}", _0)]
+    Connection(Error),
+}
+
+impl From<Error> for ClientError {
+    fn from(error: Error) -> ClientError {
+        ClientError::Connection(error)
+    }
+}
@@ -0,0 +1,220 @@
+//! API Client.
+
+//
+// MIT License
+//
+// Copyright (c) 2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+mod error;
+mod network;
+mod node;
+mod service;
+mod wallet;
+
+pub use crate::error::ClientError;
+pub use crate::network::NetworkClient;
+pub use crate::node::NodeClient;
+pub use crate::wallet::{AccountClient, WalletClient};
+
+use crate::service::{Command, Service};
+use futures::channel::{mpsc, oneshot};
+use futures::task::{Context, Poll};
+use futures::{Stream, StreamExt};
+use log::*;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::time::Duration;
+use stegos_api::{
+    ApiToken, ClientTlsConfig, NetworkRequest, RequestKind, ResponseKind, WebSocketClient,
+};
+use stegos_node::NodeRequest;
+use stegos_wallet::api::{AccountId, WalletControlRequest, WalletRequest};
+
+/// Default time to wait for a response.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Client configuration.
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    /// Used for wss:// endpoints.
+    pub tls: ClientTlsConfig,
+    /// Time to wait for a response, including reconnects.
+    pub request_timeout: Duration,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        ClientConfig {
+            tls: ClientTlsConfig::default(),
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+        }
+    }
+}
+
+/// Checks if the request creates a subscription.
+pub fn is_subscribe(kind: &RequestKind) -> bool {
+    match kind {
+        RequestKind::NodeRequest(NodeRequest::SubscribeChain { .. })
+        | RequestKind::NodeRequest(NodeRequest::SubscribeStatus {})
+        | RequestKind::WalletsRequest(WalletRequest::WalletControlRequest(
+            WalletControlRequest::SubscribeWalletUpdates {},
+        ))
+        | RequestKind::NetworkRequest(NetworkRequest::SubscribeUnicast { .. })
+        | RequestKind::NetworkRequest(NetworkRequest::SubscribeBroadcast { .. }) => true,
+        _ => false,
+    }
+}
+
+pub(crate) fn unexpected<T: Debug>(response: T) -> ClientError {
+    ClientError::UnexpectedResponse(format!("{:?}", response))
+}
+
+/// Stream of notifications of a subscription.
+/// Subscriptions are restored after reconnect, the stream ends when all clients are dropped.
+pub struct Notifications<T> {
+    rx: mpsc::UnboundedReceiver<Value>,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned> Stream for Notifications<T> {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<T>> {
+        loop {
+            let notification = match self.rx.poll_next_unpin(cx) {
+                Poll::Ready(Some(notification)) => notification,
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            };
+            match serde_json::from_value(notification) {
+                Ok(notification) => return Poll::Ready(Some(notification)),
+                Err(e) => warn!("Skipped invalid notification: {}", e),
+            }
+        }
+    }
+}
+
+/// Client of the WebSocket API.
+/// Clones share the connection, which is closed when all of them are dropped.
+#[derive(Clone)]
+pub struct Client {
+    commands: mpsc::UnboundedSender<Command>,
+    request_timeout: Duration,
+}
+
+impl Client {
+    /// Connects to ws://, wss:// or unix:PATH endpoint.
+    pub async fn new(endpoint: String, api_token: ApiToken) -> Result<Client, ClientError> {
+        Self::with_config(endpoint, api_token, ClientConfig::default()).await
+    }
+
+    pub async fn with_config(
+        endpoint: String,
+        api_token: ApiToken,
+        config: ClientConfig,
+    ) -> Result<Client, ClientError> {
+        let client = WebSocketClient::with_tls(endpoint, api_token, config.tls).await?;
+        let (commands, rx) = mpsc::unbounded();
+        let service = Service::new(client, rx, config.request_timeout);
+        tokio::spawn(service.run());
+        Ok(Client {
+            commands,
+            request_timeout: config.request_timeout,
+        })
+    }
+
+    async fn send(
+        &self,
+        command: Command,
+        rx: oneshot::Receiver<Result<ResponseKind, ClientError>>,
+    ) -> Result<ResponseKind, ClientError> {
+        self.commands
+            .unbounded_send(command)
+            .map_err(|_| ClientError::Closed)?;
+        // Requests are queued, the service has its own timeout for each of them.
+        match tokio::time::timeout(self.request_timeout, rx).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_canceled)) => Err(ClientError::Closed),
+            Err(_elapsed) => Err(ClientError::Timeout),
+        }
+    }
+
+    /// Sends a request, error responses are returned as errors.
+    pub async fn request(&self, kind: RequestKind) -> Result<ResponseKind, ClientError> {
+        let (tx, rx) = oneshot::channel();
+        self.send(Command::Request { kind, tx }, rx).await
+    }
+
+    /// Sends a subscribe request, returns the response and notifications.
+    pub async fn subscribe<T: DeserializeOwned>(
+        &self,
+        kind: RequestKind,
+    ) -> Result<(ResponseKind, Notifications<T>), ClientError> {
+        let (notifications, notifications_rx) = mpsc::unbounded();
+        let (tx, rx) = oneshot::channel();
+        let command = Command::Subscribe {
+            kind,
+            notifications,
+            tx,
+        };
+        let response = self.send(command, rx).await?;
+        let notifications = Notifications {
+            rx: notifications_rx,
+            marker: PhantomData,
+        };
+        Ok((response, notifications))
+    }
+
+    /// Sends a request of an external API, e.g. the vault.
+    pub async fn raw(&self, request: Value) -> Result<Value, ClientError> {
+        let response = self.request(RequestKind::Raw(request)).await?;
+        serde_json::to_value(&response).map_err(unexpected)
+    }
+
+    /// Sends a subscribe request of an external API.
+    pub async fn subscribe_raw(
+        &self,
+        request: Value,
+    ) -> Result<(Value, Notifications<Value>), ClientError> {
+        let (response, notifications) = self.subscribe(RequestKind::Raw(request)).await?;
+        let response = serde_json::to_value(&response).map_err(unexpected)?;
+        Ok((response, notifications))
+    }
+
+    pub fn node(&self) -> NodeClient<'_> {
+        NodeClient::new(self)
+    }
+
+    pub fn network(&self) -> NetworkClient<'_> {
+        NetworkClient::new(self)
+    }
+
+    pub fn wallet(&self) -> WalletClient<'_> {
+        WalletClient::new(self)
+    }
+
+    pub fn account(&self, account_id: impl Into<AccountId>) -> AccountClient<'_> {
+        AccountClient::new(self, account_id.into())
+    }
+}
@@ -0,0 +1,117 @@
+//! API Client - Network API.
+
+//
+// MIT License
+//
+// Copyright (c) 2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::{unexpected, Client, ClientError, Notifications};
+use stegos_api::{NetworkNotification, NetworkRequest, NetworkResponse, RequestKind, ResponseKind};
+use stegos_crypto::pbc;
+
+/// Network API.
+pub struct NetworkClient<'a> {
+    client: &'a Client,
+}
+
+impl<'a> NetworkClient<'a> {
+    pub(crate) fn new(client: &'a Client) -> Self {
+        NetworkClient { client }
+    }
+
+    pub async fn request(&self, request: NetworkRequest) -> Result<NetworkResponse, ClientError> {
+        match self
+            .client
+            .request(RequestKind::NetworkRequest(request))
+            .await?
+        {
+            ResponseKind::NetworkResponse(response) => Ok(response),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn version(&self) -> Result<String, ClientError> {
+        match self.request(NetworkRequest::VersionInfo {}).await? {
+            NetworkResponse::VersionInfo { version } => Ok(version),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn chain_name(&self) -> Result<String, ClientError> {
+        match self.request(NetworkRequest::ChainName {}).await? {
+            NetworkResponse::ChainName { name } => Ok(name),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn send_unicast(
+        &self,
+        topic: String,
+        to: pbc::PublicKey,
+        data: Vec<u8>,
+    ) -> Result<(), ClientError> {
+        match self
+            .request(NetworkRequest::SendUnicast { topic, to, data })
+            .await?
+        {
+            NetworkResponse::SentUnicast => Ok(()),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn publish_broadcast(&self, topic: String, data: Vec<u8>) -> Result<(), ClientError> {
+        match self
+            .request(NetworkRequest::PublishBroadcast { topic, data })
+            .await?
+        {
+            NetworkResponse::PublishedBroadcast => Ok(()),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Returns unicast messages of the topic.
+    pub async fn subscribe_unicast(
+        &self,
+        topic: String,
+    ) -> Result<Notifications<NetworkNotification>, ClientError> {
+        let request = RequestKind::NetworkRequest(NetworkRequest::SubscribeUnicast { topic });
+        match self.client.subscribe(request).await? {
+            (ResponseKind::NetworkResponse(NetworkResponse::SubscribedUnicast { .. }), rx) => {
+                Ok(rx)
+            }
+            (response, _) => Err(unexpected(response)),
+        }
+    }
+
+    /// Returns broadcast messages of the topic.
+    pub async fn subscribe_broadcast(
+        &self,
+        topic: String,
+    ) -> Result<Notifications<NetworkNotification>, ClientError> {
+        let request = RequestKind::NetworkRequest(NetworkRequest::SubscribeBroadcast { topic });
+        match self.client.subscribe(request).await? {
+            (ResponseKind::NetworkResponse(NetworkResponse::SubscribedBroadcast { .. }), rx) => {
+                Ok(rx)
+            }
+            (response, _) => Err(unexpected(response)),
+        }
+    }
+}
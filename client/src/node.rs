@@ -0,0 +1,169 @@
+//! API Client - Node API.
+
+//
+// MIT License
+//
+// Copyright (c) 2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::{unexpected, Client, ClientError, Notifications};
+use stegos_api::{RequestKind, ResponseKind};
+use stegos_blockchain::api::StatusInfo;
+use stegos_blockchain::{
+    ChainParametersInfo, ElectionInfo, EscrowInfo, MicroBlock, Output, OutputProof, Transaction,
+    TransactionStatus,
+};
+use stegos_crypto::hash::Hash;
+use stegos_node::{
+    ChainNotification, ExtendedMacroBlock, NodeRequest, NodeResponse, StatusNotification,
+};
+use stegos_wallet::api::ReplicationInfo;
+
+/// Node API.
+pub struct NodeClient<'a> {
+    client: &'a Client,
+}
+
+impl<'a> NodeClient<'a> {
+    pub(crate) fn new(client: &'a Client) -> Self {
+        NodeClient { client }
+    }
+
+    pub async fn request(&self, request: NodeRequest) -> Result<NodeResponse, ClientError> {
+        match self
+            .client
+            .request(RequestKind::NodeRequest(request))
+            .await?
+        {
+            ResponseKind::NodeResponse(response) => Ok(response),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn status(&self) -> Result<StatusInfo, ClientError> {
+        match self.request(NodeRequest::StatusInfo {}).await? {
+            NodeResponse::StatusInfo(status) => Ok(status),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn election_info(&self) -> Result<ElectionInfo, ClientError> {
+        match self.request(NodeRequest::ElectionInfo {}).await? {
+            NodeResponse::ElectionInfo(info) => Ok(info),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn escrow_info(&self) -> Result<EscrowInfo, ClientError> {
+        match self.request(NodeRequest::EscrowInfo {}).await? {
+            NodeResponse::EscrowInfo(info) => Ok(info),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn replication_info(&self) -> Result<ReplicationInfo, ClientError> {
+        match self.request(NodeRequest::ReplicationInfo {}).await? {
+            NodeResponse::ReplicationInfo(info) => Ok(info),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn chain_parameters(&self) -> Result<ChainParametersInfo, ClientError> {
+        match self.request(NodeRequest::ChainParameters {}).await? {
+            NodeResponse::ChainParameters(info) => Ok(info),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn macro_block(&self, epoch: u64) -> Result<ExtendedMacroBlock, ClientError> {
+        match self.request(NodeRequest::MacroBlockInfo { epoch }).await? {
+            NodeResponse::MacroBlockInfo(block) => Ok(block),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn micro_block(&self, epoch: u64, offset: u32) -> Result<MicroBlock, ClientError> {
+        match self
+            .request(NodeRequest::MicroBlockInfo { epoch, offset })
+            .await?
+        {
+            NodeResponse::MicroBlockInfo(block) => Ok(block),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Returns unspent outputs.
+    pub async fn outputs(&self, utxos: Vec<Hash>) -> Result<Vec<Output>, ClientError> {
+        match self.request(NodeRequest::OutputsList { utxos }).await? {
+            NodeResponse::OutputsList { utxos } => Ok(utxos),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn output_proof(&self, output_hash: Hash) -> Result<OutputProof, ClientError> {
+        match self
+            .request(NodeRequest::OutputProof { output_hash })
+            .await?
+        {
+            NodeResponse::OutputProof(proof) => Ok(proof),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Sends a transaction to the network, returns its hash and status.
+    pub async fn broadcast_transaction(
+        &self,
+        data: Transaction,
+    ) -> Result<(Hash, TransactionStatus), ClientError> {
+        match self
+            .request(NodeRequest::BroadcastTransaction { data })
+            .await?
+        {
+            NodeResponse::BroadcastTransaction { hash, status } => Ok((hash, status)),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Returns the current status and its changes.
+    pub async fn subscribe_status(
+        &self,
+    ) -> Result<(StatusInfo, Notifications<StatusNotification>), ClientError> {
+        let request = RequestKind::NodeRequest(NodeRequest::SubscribeStatus {});
+        match self.client.subscribe(request).await? {
+            (ResponseKind::NodeResponse(NodeResponse::SubscribedStatus { status, .. }), rx) => {
+                Ok((status, rx))
+            }
+            (response, _) => Err(unexpected(response)),
+        }
+    }
+
+    /// Returns blocks starting from `epoch` and `offset`.
+    pub async fn subscribe_chain(
+        &self,
+        epoch: u64,
+        offset: u32,
+    ) -> Result<Notifications<ChainNotification>, ClientError> {
+        let request = RequestKind::NodeRequest(NodeRequest::SubscribeChain { epoch, offset });
+        match self.client.subscribe(request).await? {
+            (ResponseKind::NodeResponse(NodeResponse::SubscribedChain { .. }), rx) => Ok(rx),
+            (response, _) => Err(unexpected(response)),
+        }
+    }
+}
@@ -0,0 +1,554 @@
+//! API Client - Connection.
+
+//
+// MIT License
+//
+// Copyright (c) 2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::error::ClientError;
+use futures::channel::{mpsc, oneshot};
+use futures::{select, FutureExt, StreamExt};
+use log::*;
+use serde_json::Value;
+use std::time::Duration;
+use stegos_api::server::journal::JournalKind;
+use stegos_api::{
+    InnerResponses, NetworkNotification, NetworkRequest, NetworkResponse, Request, RequestKind,
+    Response, ResponseKind, WebSocketClient,
+};
+use stegos_node::{ChainNotification, NodeRequest, NodeResponse};
+use stegos_wallet::api::{
+    AccountResponse, WalletControlRequest, WalletControlResponse, WalletRequest, WalletResponse,
+};
+
+pub(crate) type Responder = oneshot::Sender<Result<ResponseKind, ClientError>>;
+
+pub(crate) enum Command {
+    /// Sends a request.
+    Request { kind: RequestKind, tx: Responder },
+    /// Sends a subscribe request and forwards its notifications.
+    Subscribe {
+        kind: RequestKind,
+        notifications: mpsc::UnboundedSender<Value>,
+        tx: Responder,
+    },
+}
+
+/// Converts error responses to errors.
+pub(crate) fn check(kind: ResponseKind) -> Result<ResponseKind, ClientError> {
+    match kind {
+        ResponseKind::Inner(InnerResponses::InternalError { error })
+        | ResponseKind::NodeResponse(NodeResponse::Error { error })
+        | ResponseKind::NetworkResponse(NetworkResponse::Error { error })
+        | ResponseKind::WalletResponse(WalletResponse::WalletControlResponse(
+            WalletControlResponse::Error { error },
+        ))
+        | ResponseKind::WalletResponse(WalletResponse::AccountResponse {
+            response: AccountResponse::Error { error },
+            ..
+        }) => Err(ClientError::Server(error)),
+        ResponseKind::Inner(InnerResponses::LimitExceeded { limit, error }) => {
+            Err(ClientError::LimitExceeded(limit, error))
+        }
+        kind => Ok(kind),
+    }
+}
+
+struct Subscription {
+    /// The request, which is sent again after reconnect.
+    request: RequestKind,
+    tx: mpsc::UnboundedSender<Value>,
+    /// Sequence id of the last notification, set if the server keeps a journal.
+    last_seq: Option<u64>,
+}
+
+impl Subscription {
+    fn new(request: RequestKind, last_seq: Option<u64>, tx: mpsc::UnboundedSender<Value>) -> Self {
+        // Blocks before the current position are sent without sequence ids,
+        // so the chain is resumed from the journal only after a journaled block.
+        let last_seq = match JournalKind::of_request(&request) {
+            Some(JournalKind::Chain) => None,
+            _ => last_seq,
+        };
+        Subscription {
+            request,
+            tx,
+            last_seq,
+        }
+    }
+
+    /// Checks if the notification belongs to this subscription.
+    fn accepts(&self, kind: &ResponseKind) -> bool {
+        match (&self.request, kind) {
+            (
+                RequestKind::NodeRequest(NodeRequest::SubscribeChain { .. }),
+                ResponseKind::ChainNotification(_),
+            ) => true,
+            (
+                RequestKind::NodeRequest(NodeRequest::SubscribeStatus {}),
+                ResponseKind::StatusNotification(_),
+            ) => true,
+            (
+                RequestKind::WalletsRequest(WalletRequest::WalletControlRequest(
+                    WalletControlRequest::SubscribeWalletUpdates {},
+                )),
+                ResponseKind::WalletNotification(_),
+            ) => true,
+            // Some notifications, e.g. `sealed`, look like account responses.
+            (
+                RequestKind::WalletsRequest(WalletRequest::WalletControlRequest(
+                    WalletControlRequest::SubscribeWalletUpdates {},
+                )),
+                ResponseKind::WalletResponse(WalletResponse::AccountResponse { .. }),
+            ) => true,
+            (
+                RequestKind::NetworkRequest(NetworkRequest::SubscribeUnicast { topic }),
+                ResponseKind::NetworkNotification(NetworkNotification::UnicastMessage {
+                    topic: t,
+                    ..
+                }),
+            )
+            | (
+                RequestKind::NetworkRequest(NetworkRequest::SubscribeBroadcast { topic }),
+                ResponseKind::NetworkNotification(NetworkNotification::BroadcastMessage {
+                    topic: t,
+                    ..
+                }),
+            ) => topic == t,
+            (RequestKind::Raw(_), ResponseKind::Raw(_)) => true,
+            _ => false,
+        }
+    }
+
+    /// Moves the position of a chain subscription past the notification.
+    fn advance(&mut self, kind: &ResponseKind) {
+        let (epoch, offset) = match &mut self.request {
+            RequestKind::NodeRequest(NodeRequest::SubscribeChain { epoch, offset }) => {
+                (epoch, offset)
+            }
+            _ => return,
+        };
+        match kind {
+            ResponseKind::ChainNotification(ChainNotification::MicroBlockPrepared(block)) => {
+                *epoch = block.header.epoch;
+                *offset = block.header.offset + 1;
+            }
+            ResponseKind::ChainNotification(ChainNotification::MicroBlockReverted(block)) => {
+                *epoch = block.block.header.epoch;
+                *offset = block.block.header.offset;
+            }
+            ResponseKind::ChainNotification(ChainNotification::MacroBlockCommitted(block)) => {
+                *epoch = block.block.header.epoch + 1;
+                *offset = 0;
+            }
+            _ => {}
+        }
+    }
+
+    fn notify(&mut self, seq: Option<u64>, kind: &ResponseKind, notification: Value) {
+        if let Some(seq) = seq {
+            if self.last_seq.map_or(false, |last_seq| seq <= last_seq) {
+                trace!("Skipped replayed notification: seq={}", seq);
+                return;
+            }
+            self.last_seq = Some(seq);
+        }
+        self.advance(kind);
+        self.tx.unbounded_send(notification).ok();
+    }
+
+    /// Returns the request to restore the subscription.
+    /// Subscriptions are resumed from the journal of the server after the last sequenced
+    /// notification, otherwise the chain is resubscribed from the next block.
+    fn resume(&self) -> (RequestKind, Option<u64>) {
+        (self.request.clone(), self.last_seq)
+    }
+
+    /// Notifications after `since_seq` are replayed by the journal, otherwise they follow
+    /// the last sequence id of the journal.
+    fn resumed(&mut self, seq: Option<u64>, since_seq: Option<u64>) {
+        if since_seq.is_none() {
+            self.last_seq = match JournalKind::of_request(&self.request) {
+                Some(JournalKind::Chain) => None,
+                _ => seq,
+            };
+        }
+    }
+}
+
+/// Owns the connection and serves requests of clients.
+pub(crate) struct Service {
+    client: WebSocketClient,
+    commands: mpsc::UnboundedReceiver<Command>,
+    request_timeout: Duration,
+    /// The id of the last request.
+    last_id: u64,
+    subscriptions: Vec<Subscription>,
+}
+
+impl Service {
+    pub(crate) fn new(
+        client: WebSocketClient,
+        commands: mpsc::UnboundedReceiver<Command>,
+        request_timeout: Duration,
+    ) -> Self {
+        Service {
+            client,
+            commands,
+            request_timeout,
+            last_id: 0,
+            subscriptions: Vec::new(),
+        }
+    }
+
+    async fn request(
+        &mut self,
+        kind: RequestKind,
+        since_seq: Option<u64>,
+    ) -> Result<Response, ClientError> {
+        self.last_id += 1;
+        let request = Request {
+            kind,
+            id: self.last_id,
+            since_seq,
+        };
+        match tokio::time::timeout(self.request_timeout, self.client.request(request)).await {
+            Ok(response) => Ok(response?),
+            Err(_) => Err(ClientError::Timeout),
+        }
+    }
+
+    async fn handle_command(&mut self, command: Command) {
+        match command {
+            Command::Request { kind, tx } => {
+                let result = match self.request(kind, None).await {
+                    Ok(response) => check(response.kind),
+                    Err(e) => Err(e),
+                };
+                tx.send(result).ok();
+            }
+            Command::Subscribe {
+                kind,
+                notifications,
+                tx,
+            } => {
+                let result = match self.request(kind.clone(), None).await {
+                    Ok(response) => {
+                        let seq = response.seq;
+                        let result = check(response.kind);
+                        if result.is_ok() {
+                            let subscription = Subscription::new(kind, seq, notifications);
+                            self.subscriptions.push(subscription);
+                        }
+                        result
+                    }
+                    Err(e) => Err(e),
+                };
+                tx.send(result).ok();
+            }
+        }
+    }
+
+    /// Restores subscriptions after reconnect, or journaled subscriptions of the kind.
+    async fn resubscribe(&mut self, journaled: Option<JournalKind>) {
+        self.subscriptions.retain(|s| !s.tx.is_closed());
+        for i in 0..self.subscriptions.len() {
+            let kind = JournalKind::of_request(&self.subscriptions[i].request);
+            if journaled.is_some() && journaled != kind {
+                continue;
+            }
+            let (request, mut since_seq) = self.subscriptions[i].resume();
+            debug!(
+                "Resubscribing: request={:?}, since_seq={:?}",
+                request, since_seq
+            );
+            let mut result = self.request(request.clone(), since_seq).await;
+            if let Ok(Response {
+                kind: ResponseKind::Inner(InnerResponses::NotificationsLost { .. }),
+                ..
+            }) = &result
+            {
+                warn!(
+                    "Missed notifications are no longer available, subscribing again: request={:?}",
+                    request
+                );
+                since_seq = None;
+                result = self.request(request, None).await;
+            }
+            let result = match result {
+                Ok(response) => {
+                    let seq = response.seq;
+                    check(response.kind).map(|_| seq)
+                }
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(seq) => self.subscriptions[i].resumed(seq, since_seq),
+                Err(e) => error!("Failed to resubscribe: {}", e),
+            }
+        }
+    }
+
+    async fn handle_notification(&mut self, response: Response) {
+        match &response.kind {
+            ResponseKind::Inner(InnerResponses::Reconnect) => {
+                info!("Reconnected, restoring subscriptions");
+                return self.resubscribe(None).await;
+            }
+            ResponseKind::Inner(InnerResponses::NotificationsLost {
+                subscription,
+                since_seq,
+                first_seq,
+            }) => {
+                warn!(
+                    "Subscription has fallen behind the journal: since_seq={}, first_seq={}",
+                    since_seq, first_seq
+                );
+                // The server has closed the subscription.
+                let subscription = *subscription;
+                for s in &mut self.subscriptions {
+                    if JournalKind::of_request(&s.request) == Some(subscription) {
+                        s.last_seq = None;
+                    }
+                }
+                return self.resubscribe(Some(subscription)).await;
+            }
+            _ => {}
+        }
+        let notification = match serde_json::to_value(&response.kind) {
+            Ok(notification) => notification,
+            Err(e) => {
+                error!("Failed to serialize notification: {}", e);
+                return;
+            }
+        };
+        self.subscriptions.retain(|s| !s.tx.is_closed());
+        for subscription in &mut self.subscriptions {
+            if subscription.accepts(&response.kind) {
+                subscription.notify(response.seq, &response.kind, notification.clone());
+            }
+        }
+    }
+
+    pub(crate) async fn run(mut self) {
+        loop {
+            let notification = self.client.notification();
+            select! {
+                command = self.commands.next() => match command {
+                    Some(command) => self.handle_command(command).await,
+                    None => {
+                        debug!("All clients are dropped, closing connection");
+                        return;
+                    }
+                },
+                notification = notification.fuse() => match notification {
+                    Ok(response) => self.handle_notification(response).await,
+                    Err(e) => warn!("Failed to receive notification: {}", e),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Client, Notifications};
+    use async_trait::async_trait;
+    use failure::{bail, Error};
+    use futures::future;
+    use serde_json::json;
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+    use stegos_api::server::api::{ApiHandler, RawRequest, RawResponse};
+    use stegos_api::server::journal::Journal;
+    use stegos_api::server::limits::{ApiLimits, Limiter};
+    use stegos_api::server::spawn_server;
+    use stegos_api::ApiToken;
+    use stegos_wallet::api::{AccountNotification, WalletNotification};
+    use tempdir::TempDir;
+    use tokio::net::{TcpListener, TcpStream};
+
+    /// Wallet API, which sends notifications of the test.
+    #[derive(Clone, Default)]
+    struct TestWallet {
+        subscribers: Arc<Mutex<Vec<mpsc::UnboundedSender<WalletNotification>>>>,
+    }
+
+    impl TestWallet {
+        fn notify(&self, account_id: &str) {
+            let notification = WalletNotification {
+                account_id: account_id.to_string(),
+                notification: AccountNotification::Sealed,
+            };
+            let mut subscribers = self.subscribers.lock().unwrap();
+            subscribers.retain(|tx| tx.unbounded_send(notification.clone()).is_ok());
+        }
+    }
+
+    #[async_trait]
+    impl ApiHandler for TestWallet {
+        async fn process_request(&self, req: RawRequest) -> Result<RawResponse, Error> {
+            match req.0.kind {
+                RequestKind::WalletsRequest(WalletRequest::WalletControlRequest(
+                    WalletControlRequest::SubscribeWalletUpdates {},
+                )) => {
+                    let (tx, rx) = mpsc::unbounded();
+                    self.subscribers.lock().unwrap().push(tx);
+                    let response = WalletControlResponse::SubscribedWalletUpdates { rx: Some(rx) };
+                    Ok(WalletResponse::WalletControlResponse(response).into())
+                }
+                kind => bail!("Unsupported request: {:?}", kind),
+            }
+        }
+
+        fn cloned(&self) -> Box<dyn ApiHandler> {
+            Box::new(self.clone())
+        }
+    }
+
+    /// Forwards connections to the server, the current one is closed on `disconnects`.
+    async fn proxy(
+        mut listener: TcpListener,
+        server: SocketAddr,
+        mut disconnects: mpsc::UnboundedReceiver<()>,
+    ) {
+        while let Ok((mut client, _)) = listener.accept().await {
+            let mut server = TcpStream::connect(server).await.unwrap();
+            let (mut client_rx, mut client_tx) = client.split();
+            let (mut server_rx, mut server_tx) = server.split();
+            let forward = future::try_join(
+                tokio::io::copy(&mut client_rx, &mut server_tx),
+                tokio::io::copy(&mut server_rx, &mut client_tx),
+            );
+            select! {
+                _ = forward.fuse() => {}
+                _ = disconnects.next() => {}
+            }
+        }
+    }
+
+    async fn next(notifications: &mut Notifications<WalletNotification>) -> String {
+        let notification = tokio::time::timeout(Duration::from_secs(30), notifications.next());
+        notification.await.unwrap().unwrap().account_id
+    }
+
+    #[tokio::test]
+    async fn reconnect() {
+        let dir = TempDir::new("reconnect").unwrap();
+        let journal = Journal::open(&dir.path().join("journal"), 100).unwrap();
+        let server_addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let wallet = TestWallet::default();
+        let api_token = ApiToken::new();
+        spawn_server(
+            vec![server_addr.to_string()],
+            None,
+            api_token.into(),
+            vec![Box::new(wallet.clone())],
+            None,
+            "test".to_string(),
+            "dev".to_string(),
+            Some(journal),
+            Arc::new(Limiter::new(ApiLimits::default())),
+        )
+        .await
+        .unwrap();
+        // Wait for the journal to subscribe.
+        while wallet.subscribers.lock().unwrap().is_empty() {
+            tokio::time::delay_for(Duration::from_millis(10)).await;
+        }
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy_addr = listener.local_addr().unwrap();
+        let (disconnect, disconnects) = mpsc::unbounded();
+        tokio::spawn(proxy(listener, server_addr, disconnects));
+
+        let client = Client::new(format!("ws://{}", proxy_addr), api_token)
+            .await
+            .unwrap();
+        let request = RequestKind::WalletsRequest(WalletRequest::WalletControlRequest(
+            WalletControlRequest::SubscribeWalletUpdates {},
+        ));
+        let (_, mut notifications) = client
+            .subscribe::<WalletNotification>(request)
+            .await
+            .unwrap();
+        wallet.notify("1");
+        assert_eq!(next(&mut notifications).await, "1");
+
+        // Notifications sent while disconnected are replayed from the journal once.
+        disconnect.unbounded_send(()).unwrap();
+        wallet.notify("2");
+        wallet.notify("3");
+        assert_eq!(next(&mut notifications).await, "2");
+        assert_eq!(next(&mut notifications).await, "3");
+        wallet.notify("4");
+        assert_eq!(next(&mut notifications).await, "4");
+    }
+
+    #[test]
+    fn resume() {
+        let (tx, mut rx) = mpsc::unbounded();
+        let request = RequestKind::WalletsRequest(WalletRequest::WalletControlRequest(
+            WalletControlRequest::SubscribeWalletUpdates {},
+        ));
+        let mut subscription = Subscription::new(request, Some(10), tx);
+        let kind = ResponseKind::WalletNotification(WalletNotification {
+            account_id: "1".to_string(),
+            notification: AccountNotification::Sealed,
+        });
+        assert!(subscription.accepts(&kind));
+        assert!(!subscription.accepts(&ResponseKind::Raw(json!({}))));
+
+        subscription.notify(Some(11), &kind, json!(1));
+        subscription.notify(Some(12), &kind, json!(2));
+        let (_, since_seq) = subscription.resume();
+        assert_eq!(since_seq, Some(12));
+        subscription.resumed(Some(13), since_seq);
+        // Notifications received before reconnect are skipped.
+        for &(seq, i) in &[(12, 2), (13, 3)] {
+            subscription.notify(Some(seq), &kind, json!(i));
+        }
+        let (_, since_seq) = subscription.resume();
+        assert_eq!(since_seq, Some(13));
+
+        let mut received = Vec::new();
+        while let Ok(Some(notification)) = rx.try_next() {
+            received.push(notification);
+        }
+        assert_eq!(received, vec![json!(1), json!(2), json!(3)]);
+    }
+
+    #[test]
+    fn errors() {
+        let kind = ResponseKind::NodeResponse(NodeResponse::Error {
+            error: "failed".to_string(),
+        });
+        match check(kind) {
+            Err(ClientError::Server(error)) => assert_eq!(error, "failed"),
+            result => panic!("{:?}", result),
+        }
+        let kind = ResponseKind::Raw(json!({}));
+        assert!(check(kind).is_ok());
+    }
+}
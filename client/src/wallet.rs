@@ -0,0 +1,283 @@
+//! API Client - Wallet API.
+
+//
+// MIT License
+//
+// Copyright (c) 2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::{unexpected, Client, ClientError, Notifications};
+use std::collections::BTreeMap;
+use stegos_api::{RequestKind, ResponseKind};
+use stegos_blockchain::Timestamp;
+use stegos_crypto::hash::Hash;
+use stegos_crypto::scc;
+use stegos_wallet::api::{
+    AccountBalance, AccountId, AccountInfo, AccountRecovery, AccountRequest, AccountResponse,
+    LogEntryInfo, TransactionInfo, WalletControlRequest, WalletControlResponse, WalletNotification,
+    WalletRequest, WalletResponse,
+};
+
+/// Wallet API.
+pub struct WalletClient<'a> {
+    client: &'a Client,
+}
+
+impl<'a> WalletClient<'a> {
+    pub(crate) fn new(client: &'a Client) -> Self {
+        WalletClient { client }
+    }
+
+    pub async fn request(
+        &self,
+        request: WalletControlRequest,
+    ) -> Result<WalletControlResponse, ClientError> {
+        let request = RequestKind::WalletsRequest(WalletRequest::WalletControlRequest(request));
+        match self.client.request(request).await? {
+            ResponseKind::WalletResponse(WalletResponse::WalletControlResponse(response)) => {
+                Ok(response)
+            }
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn accounts(&self) -> Result<BTreeMap<AccountId, AccountInfo>, ClientError> {
+        match self.request(WalletControlRequest::AccountsInfo {}).await? {
+            WalletControlResponse::AccountsInfo { accounts, .. } => Ok(accounts),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn create_account(&self, password: String) -> Result<AccountId, ClientError> {
+        match self
+            .request(WalletControlRequest::CreateAccount { password })
+            .await?
+        {
+            WalletControlResponse::AccountCreated { account_id } => Ok(account_id),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn recover_account(
+        &self,
+        recovery: AccountRecovery,
+        password: String,
+    ) -> Result<AccountId, ClientError> {
+        match self
+            .request(WalletControlRequest::RecoverAccount { recovery, password })
+            .await?
+        {
+            WalletControlResponse::AccountCreated { account_id } => Ok(account_id),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn delete_account(&self, account_id: AccountId) -> Result<(), ClientError> {
+        match self
+            .request(WalletControlRequest::DeleteAccount { account_id })
+            .await?
+        {
+            WalletControlResponse::AccountDeleted { .. } => Ok(()),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Returns notifications of all accounts.
+    pub async fn subscribe(&self) -> Result<Notifications<WalletNotification>, ClientError> {
+        let request = RequestKind::WalletsRequest(WalletRequest::WalletControlRequest(
+            WalletControlRequest::SubscribeWalletUpdates {},
+        ));
+        match self.client.subscribe(request).await? {
+            (
+                ResponseKind::WalletResponse(WalletResponse::WalletControlResponse(
+                    WalletControlResponse::SubscribedWalletUpdates { .. },
+                )),
+                rx,
+            ) => Ok(rx),
+            (response, _) => Err(unexpected(response)),
+        }
+    }
+}
+
+/// Account API.
+pub struct AccountClient<'a> {
+    client: &'a Client,
+    account_id: AccountId,
+}
+
+impl<'a> AccountClient<'a> {
+    pub(crate) fn new(client: &'a Client, account_id: AccountId) -> Self {
+        AccountClient { client, account_id }
+    }
+
+    pub async fn request(&self, request: AccountRequest) -> Result<AccountResponse, ClientError> {
+        let request = RequestKind::WalletsRequest(WalletRequest::AccountRequest {
+            account_id: self.account_id.clone(),
+            request,
+        });
+        match self.client.request(request).await? {
+            ResponseKind::WalletResponse(WalletResponse::AccountResponse { response, .. }) => {
+                Ok(response)
+            }
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn unseal(&self, password: String) -> Result<(), ClientError> {
+        match self.request(AccountRequest::Unseal { password }).await? {
+            AccountResponse::Unsealed => Ok(()),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn seal(&self) -> Result<(), ClientError> {
+        match self.request(AccountRequest::Seal).await? {
+            AccountResponse::Sealed => Ok(()),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn change_password(&self, new_password: String) -> Result<(), ClientError> {
+        match self
+            .request(AccountRequest::ChangePassword { new_password })
+            .await?
+        {
+            AccountResponse::PasswordChanged => Ok(()),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn info(&self) -> Result<AccountInfo, ClientError> {
+        match self.request(AccountRequest::AccountInfo {}).await? {
+            AccountResponse::AccountInfo(info) => Ok(info),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn balance(&self) -> Result<AccountBalance, ClientError> {
+        match self.request(AccountRequest::BalanceInfo {}).await? {
+            AccountResponse::BalanceInfo(balance) => Ok(balance),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn history(
+        &self,
+        starting_from: Timestamp,
+        limit: u64,
+    ) -> Result<Vec<LogEntryInfo>, ClientError> {
+        match self
+            .request(AccountRequest::HistoryInfo {
+                starting_from,
+                limit,
+            })
+            .await?
+        {
+            AccountResponse::HistoryInfo { log } => Ok(log),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn recovery(&self) -> Result<AccountRecovery, ClientError> {
+        match self.request(AccountRequest::GetRecovery {}).await? {
+            AccountResponse::Recovery(recovery) => Ok(recovery),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    async fn transaction(&self, request: AccountRequest) -> Result<TransactionInfo, ClientError> {
+        match self.request(request).await? {
+            AccountResponse::TransactionCreated(info) => Ok(info),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn pay(
+        &self,
+        recipient: scc::PublicKey,
+        amount: i64,
+        payment_fee: i64,
+        comment: String,
+    ) -> Result<TransactionInfo, ClientError> {
+        self.transaction(AccountRequest::Payment {
+            recipient,
+            amount,
+            payment_fee,
+            comment,
+            with_certificate: false,
+            raw: false,
+        })
+        .await
+    }
+
+    pub async fn public_pay(
+        &self,
+        recipient: scc::PublicKey,
+        amount: i64,
+        payment_fee: i64,
+    ) -> Result<TransactionInfo, ClientError> {
+        self.transaction(AccountRequest::PublicPayment {
+            recipient,
+            amount,
+            payment_fee,
+            raw: false,
+        })
+        .await
+    }
+
+    pub async fn stake(
+        &self,
+        amount: i64,
+        payment_fee: i64,
+    ) -> Result<TransactionInfo, ClientError> {
+        self.transaction(AccountRequest::Stake {
+            amount,
+            payment_fee,
+        })
+        .await
+    }
+
+    pub async fn unstake(
+        &self,
+        amount: i64,
+        payment_fee: i64,
+    ) -> Result<TransactionInfo, ClientError> {
+        self.transaction(AccountRequest::Unstake {
+            amount,
+            payment_fee,
+        })
+        .await
+    }
+
+    /// Sends an encrypted message, returns its hash.
+    pub async fn send_message(
+        &self,
+        recipient: scc::PublicKey,
+        text: String,
+    ) -> Result<Hash, ClientError> {
+        match self
+            .request(AccountRequest::SendMessage { recipient, text })
+            .await?
+        {
+            AccountResponse::MessageSent { message_hash } => Ok(message_hash),
+            response => Err(unexpected(response)),
+        }
+    }
+}
@@ -22,7 +22,7 @@
 // SOFTWARE.
 
 use crate::error::*;
-use stegos_blockchain::{MacroBlockHeader, Transaction};
+use stegos_blockchain::{MacroBlockHeader, ParameterVote, Transaction};
 use stegos_crypto::hash::{Hash, Hashable, Hasher};
 use stegos_crypto::pbc;
 
@@ -30,6 +30,7 @@ use stegos_crypto::pbc;
 pub struct MacroBlockProposal {
     pub header: MacroBlockHeader,
     pub transactions: Vec<Transaction>,
+    pub parameter_votes: Vec<ParameterVote>,
 }
 
 impl Hashable for MacroBlockProposal {
@@ -40,6 +41,11 @@ impl Hashable for MacroBlockProposal {
         for tx in &self.transactions {
             tx.fullhash(state);
         }
+        let votes_count: u64 = self.parameter_votes.len() as u64;
+        votes_count.hash(state);
+        for vote in &self.parameter_votes {
+            vote.hash(state);
+        }
     }
 }
 
@@ -47,6 +47,9 @@ impl ProtoConvert for ConsensusMessageBody {
                 for transaction in &block_proposal.transactions {
                     proposal.transactions.push(transaction.into_proto());
                 }
+                for vote in &block_proposal.parameter_votes {
+                    proposal.parameter_votes.push(vote.into_proto());
+                }
                 proto.set_macro_block_proposal(proposal);
             }
             ConsensusMessageBody::Prevote => {
@@ -69,9 +72,15 @@ impl ProtoConvert for ConsensusMessageBody {
                 for transaction in msg.transactions.iter() {
                     transactions.push(Transaction::from_proto(transaction)?);
                 }
+                let mut parameter_votes =
+                    Vec::<ParameterVote>::with_capacity(msg.parameter_votes.len());
+                for vote in msg.parameter_votes.iter() {
+                    parameter_votes.push(ParameterVote::from_proto(vote)?);
+                }
                 let block_proposal = MacroBlockProposal {
                     header,
                     transactions,
+                    parameter_votes,
                 };
                 ConsensusMessageBody::Proposal(block_proposal)
             }
@@ -270,9 +279,17 @@ mod tests {
         //
         // MacroBlockProposal
         //
+        let parameter_votes = vec![ParameterVote::new(
+            epoch,
+            ChainParameter::BlockReward(10),
+            epoch + 2,
+            &nskey,
+            &npkey,
+        )];
         let proposal = ConsensusMessageBody::Proposal(MacroBlockProposal {
             header: block.header,
             transactions,
+            parameter_votes,
         });
         roundtrip(&proposal);
     }
@@ -198,6 +198,11 @@ impl Consensus {
         }
     }
 
+    /// Returns validators which have sent a prevote in the current round.
+    pub fn prevoters(&self) -> Vec<pbc::PublicKey> {
+        self.prevotes.keys().cloned().collect()
+    }
+
     fn lock(&mut self) {
         assert_eq!(self.state, ConsensusState::Precommit);
         let block = self.block.take().expect("expected some block");
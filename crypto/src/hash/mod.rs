@@ -26,6 +26,9 @@ use crate::CryptoError;
 
 use rand::thread_rng;
 use rand::Rng;
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use schemars::JsonSchema;
 use serde::de::{Deserialize, Deserializer};
 use serde::ser::{Serialize, Serializer};
 use sha3::{Digest, Sha3_256};
@@ -174,6 +177,16 @@ impl<'de> Deserialize<'de> for Hash {
     }
 }
 
+impl JsonSchema for Hash {
+    fn schema_name() -> String {
+        "Hash".to_string()
+    }
+
+    fn json_schema(_gen: &mut SchemaGenerator) -> Schema {
+        string_schema("Hex-encoded hash", Some(HEX_PATTERN))
+    }
+}
+
 pub fn hash_nbytes(nb: usize, msg: &[u8]) -> Vec<u8> {
     let nmsg = msg.len();
     let mut ct = nb;
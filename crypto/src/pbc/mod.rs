@@ -34,6 +34,9 @@ use ff::*;
 use paired::bls12_381::{Bls12, Fq12, Fq2, Fq6, Fr, G1Compressed, G2Compressed};
 use paired::*;
 use rand::prelude::*;
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use schemars::JsonSchema;
 use serde::de::{Deserialize, Deserializer};
 use serde::ser::{Serialize, Serializer};
 use serde_derive::{Deserialize, Serialize};
@@ -607,6 +610,16 @@ impl<'de> Deserialize<'de> for PublicKey {
     }
 }
 
+impl JsonSchema for PublicKey {
+    fn schema_name() -> String {
+        "ValidatorPublicKey".to_string()
+    }
+
+    fn json_schema(_gen: &mut SchemaGenerator) -> Schema {
+        string_schema("Hex-encoded validator public key", Some(HEX_PATTERN))
+    }
+}
+
 impl Serialize for Signature {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -626,6 +639,16 @@ impl<'de> Deserialize<'de> for Signature {
     }
 }
 
+impl JsonSchema for Signature {
+    fn schema_name() -> String {
+        "ValidatorSignature".to_string()
+    }
+
+    fn json_schema(_gen: &mut SchemaGenerator) -> Schema {
+        string_schema("Hex-encoded BLS signature", Some(HEX_PATTERN))
+    }
+}
+
 impl Serialize for G1 {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -645,6 +668,16 @@ impl<'de> Deserialize<'de> for G1 {
     }
 }
 
+impl JsonSchema for G1 {
+    fn schema_name() -> String {
+        "G1".to_string()
+    }
+
+    fn json_schema(_gen: &mut SchemaGenerator) -> Schema {
+        string_schema("Hex-encoded point of G1", Some(HEX_PATTERN))
+    }
+}
+
 impl Eq for PublicKey {}
 
 impl PartialEq for PublicKey {
@@ -1050,7 +1083,7 @@ pub fn ibe_decrypt(pack: &EncryptedPacket, skey: &SecretKey) -> Result<Vec<u8>,
 
 // -------------------------------------------------------
 
-#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct VRF {
     pub rand: Hash,
     // hash digest of generated randomness in pairing field
@@ -40,6 +40,9 @@ use lazy_static::lazy_static;
 use rand::prelude::*;
 use rand::thread_rng;
 use ristretto_bulletproofs::{BulletproofGens, PedersenGens};
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use schemars::JsonSchema;
 use serde::de::{Deserialize, Deserializer};
 use serde::ser::{Serialize, Serializer};
 use serde_derive::{Deserialize, Serialize};
@@ -106,18 +109,19 @@ pub struct SecretKey(Fr);
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub struct PublicKey(Pt);
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct SchnorrSig {
     pub u: Fr,
     pub K: Pt,
 }
 
-#[derive(Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct EncryptedPayload {
     pub ag: Pt, // key hint = alpha*G
 
     #[serde(deserialize_with = "crate::utils::vec_deserialize_from_hex")]
     #[serde(serialize_with = "crate::utils::vec_serialize_to_hex")]
+    #[schemars(with = "String")]
     pub ctxt: Vec<u8>, // ciphertext
 }
 
@@ -229,6 +233,16 @@ impl<'de> Deserialize<'de> for Fr {
     }
 }
 
+impl JsonSchema for Fr {
+    fn schema_name() -> String {
+        "Fr".to_string()
+    }
+
+    fn json_schema(_gen: &mut SchemaGenerator) -> Schema {
+        string_schema("Hex-encoded scalar", Some(HEX_PATTERN))
+    }
+}
+
 impl Serialize for Pt {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -248,6 +262,16 @@ impl<'de> Deserialize<'de> for Pt {
     }
 }
 
+impl JsonSchema for Pt {
+    fn schema_name() -> String {
+        "Pt".to_string()
+    }
+
+    fn json_schema(_gen: &mut SchemaGenerator) -> Schema {
+        string_schema("Hex-encoded curve point", Some(HEX_PATTERN))
+    }
+}
+
 impl Hashable for Fr {
     fn hash(&self, state: &mut Hasher) {
         "Fr".hash(state);
@@ -877,6 +901,16 @@ impl<'de> Deserialize<'de> for PublicKey {
     }
 }
 
+impl JsonSchema for PublicKey {
+    fn schema_name() -> String {
+        "PublicKey".to_string()
+    }
+
+    fn json_schema(_gen: &mut SchemaGenerator) -> Schema {
+        string_schema("Account address", None)
+    }
+}
+
 impl Ord for PublicKey {
     fn cmp(&self, other: &PublicKey) -> Ordering {
         self.to_bytes().cmp(&other.to_bytes())
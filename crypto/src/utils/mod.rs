@@ -26,6 +26,7 @@ use crate::CryptoError;
 use bit_vec::BitVec;
 use hex;
 use ristretto_bulletproofs::RangeProof;
+use schemars::schema::{InstanceType, Schema, SchemaObject};
 use serde::{de, ser, Deserialize, Deserializer, Serializer};
 use std::cmp::Ordering;
 use std::fmt::Write;
@@ -323,3 +324,19 @@ where
     let string_vec = hex::encode(bytes);
     serializer.serialize_str(&string_vec)
 }
+
+/// Pattern of hex-encoded values.
+pub const HEX_PATTERN: &str = "^[0-9a-f]+$";
+
+/// JSON Schema of a value serialized as a string.
+pub fn string_schema(description: &str, pattern: Option<&str>) -> Schema {
+    let mut schema = SchemaObject {
+        instance_type: Some(InstanceType::String.into()),
+        ..Default::default()
+    };
+    schema.metadata().description = Some(description.to_string());
+    if let Some(pattern) = pattern {
+        schema.string().pattern = Some(pattern.to_string());
+    }
+    schema.into()
+}
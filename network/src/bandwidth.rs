@@ -0,0 +1,342 @@
+//
+// MIT License
+//
+// Copyright (c) 2018-2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Bandwidth limits.
+//!
+//! Upload and download are limited by token buckets, globally and per peer.
+//! Every bucket is refilled at the configured rate and holds at most one second of traffic.
+//!
+//! Priority topics are set by `NetworkProvider::subscribe_priority()` and by configuration.
+//! Messages of priority topics are always passed, but their size is charged,
+//! so they take bandwidth from the rest of traffic. Other messages are dropped while
+//! the bucket is empty. Streams (replication) are delayed until the bucket is refilled.
+
+use crate::config::NetworkConfig;
+use crate::traffic::Direction;
+use crate::PeerId;
+use futures::prelude::*;
+use futures::task::{Context, Poll};
+use std::collections::{HashMap, HashSet};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::time::{delay_for, Delay};
+
+/// Priority of traffic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Priority {
+    /// Never dropped.
+    High,
+    /// Dropped while the limit is exceeded.
+    Normal,
+}
+
+#[derive(Debug, Clone)]
+struct TokenBucket {
+    /// Bytes per second, 0 means unlimited.
+    rate: u64,
+    /// Available bytes, negative when more than available has been charged.
+    tokens: i64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: u64, now: Instant) -> Self {
+        TokenBucket {
+            rate,
+            tokens: rate as i64,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        if self.rate == 0 {
+            return;
+        }
+        let elapsed = now.duration_since(self.updated);
+        let refilled = (elapsed.as_micros() as u64).saturating_mul(self.rate) / 1_000_000;
+        if refilled > 0 {
+            self.tokens = std::cmp::min(self.tokens + refilled as i64, self.rate as i64);
+            self.updated = now;
+        }
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.rate != 0 && self.tokens < 0
+    }
+
+    fn charge(&mut self, bytes: usize) {
+        if self.rate != 0 {
+            self.tokens -= bytes as i64;
+        }
+    }
+
+    /// Time until the bucket is not exhausted.
+    fn wait(&self) -> Duration {
+        if !self.is_exhausted() {
+            return Duration::from_secs(0);
+        }
+        Duration::from_micros((-self.tokens) as u64 * 1_000_000 / self.rate)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Buckets {
+    upload: TokenBucket,
+    download: TokenBucket,
+}
+
+impl Buckets {
+    fn new(upload_rate: u64, download_rate: u64, now: Instant) -> Self {
+        Buckets {
+            upload: TokenBucket::new(upload_rate, now),
+            download: TokenBucket::new(download_rate, now),
+        }
+    }
+
+    fn bucket(&mut self, direction: Direction) -> &mut TokenBucket {
+        match direction {
+            Direction::Incoming => &mut self.download,
+            Direction::Outgoing => &mut self.upload,
+        }
+    }
+}
+
+/// Global and per-peer bandwidth limits.
+#[derive(Debug)]
+pub struct Bandwidth {
+    global: Buckets,
+    peer_upload_limit: u64,
+    peer_download_limit: u64,
+    peers: HashMap<PeerId, Buckets>,
+    priority_topics: HashSet<String>,
+}
+
+pub type SharedBandwidth = Arc<Mutex<Bandwidth>>;
+
+impl Bandwidth {
+    pub fn new(config: &NetworkConfig) -> Self {
+        let now = Instant::now();
+        Bandwidth {
+            global: Buckets::new(config.upload_limit, config.download_limit, now),
+            peer_upload_limit: config.peer_upload_limit,
+            peer_download_limit: config.peer_download_limit,
+            peers: HashMap::new(),
+            priority_topics: config.priority_topics.iter().cloned().collect(),
+        }
+    }
+
+    pub fn shared(config: &NetworkConfig) -> SharedBandwidth {
+        Arc::new(Mutex::new(Self::new(config)))
+    }
+
+    /// Never drop messages of the topic.
+    pub fn add_priority_topic(&mut self, topic: &str) {
+        if !self.priority_topics.contains(topic) {
+            self.priority_topics.insert(topic.to_string());
+        }
+    }
+
+    /// Returns priority of messages of the topic.
+    fn priority(&self, topic: &str) -> Priority {
+        if self.priority_topics.contains(topic) {
+            Priority::High
+        } else {
+            Priority::Normal
+        }
+    }
+
+    fn refill(&mut self, peer_id: Option<&PeerId>, direction: Direction, now: Instant) {
+        self.global.bucket(direction).refill(now);
+        if let Some(peer_id) = peer_id {
+            let (upload_limit, download_limit) = (self.peer_upload_limit, self.peer_download_limit);
+            self.peers
+                .entry(peer_id.clone())
+                .or_insert_with(|| Buckets::new(upload_limit, download_limit, now))
+                .bucket(direction)
+                .refill(now);
+        }
+    }
+
+    /// Returns true if there is bandwidth left for the peer (or globally if `peer_id` is None).
+    pub(crate) fn has_capacity(&mut self, peer_id: Option<&PeerId>, direction: Direction) -> bool {
+        self.refill(peer_id, direction, Instant::now());
+        if self.global.bucket(direction).is_exhausted() {
+            return false;
+        }
+        match peer_id.and_then(|peer_id| self.peers.get_mut(peer_id)) {
+            Some(peer) => !peer.bucket(direction).is_exhausted(),
+            None => true,
+        }
+    }
+
+    /// Charge a message of the topic, returns false if the message should be dropped.
+    pub(crate) fn admit(
+        &mut self,
+        peer_id: Option<&PeerId>,
+        direction: Direction,
+        topic: &str,
+        bytes: usize,
+    ) -> bool {
+        if self.priority(topic) == Priority::Normal && !self.has_capacity(peer_id, direction) {
+            return false;
+        }
+        self.charge(peer_id, direction, bytes);
+        true
+    }
+
+    /// Charge a part of stream, returns how long to wait before the next part.
+    pub(crate) fn charge(
+        &mut self,
+        peer_id: Option<&PeerId>,
+        direction: Direction,
+        bytes: usize,
+    ) -> Duration {
+        self.refill(peer_id, direction, Instant::now());
+        let global = self.global.bucket(direction);
+        global.charge(bytes);
+        let mut wait = global.wait();
+        if let Some(peer) = peer_id.and_then(|peer_id| self.peers.get_mut(peer_id)) {
+            let peer = peer.bucket(direction);
+            peer.charge(bytes);
+            wait = std::cmp::max(wait, peer.wait());
+        }
+        wait
+    }
+
+    /// Forget buckets of disconnected peer.
+    pub fn remove_peer(&mut self, peer_id: &PeerId) {
+        self.peers.remove(peer_id);
+    }
+}
+
+/// Stream which is delayed according to bandwidth limits.
+pub(crate) struct Throttled<S: Stream> {
+    inner: S,
+    bandwidth: SharedBandwidth,
+    peer_id: PeerId,
+    direction: Direction,
+    size: fn(&S::Item) -> usize,
+    delay: Option<Delay>,
+}
+
+impl<S: Stream> Throttled<S> {
+    pub fn new(
+        inner: S,
+        bandwidth: SharedBandwidth,
+        peer_id: PeerId,
+        direction: Direction,
+        size: fn(&S::Item) -> usize,
+    ) -> Self {
+        Throttled {
+            inner,
+            bandwidth,
+            peer_id,
+            direction,
+            size,
+            delay: None,
+        }
+    }
+}
+
+impl<S: Stream + Unpin> Stream for Throttled<S> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if let Some(delay) = &mut this.delay {
+            if delay.poll_unpin(cx).is_pending() {
+                return Poll::Pending;
+            }
+            this.delay = None;
+        }
+        let item = match this.inner.poll_next_unpin(cx) {
+            Poll::Ready(Some(item)) => item,
+            other => return other,
+        };
+        let wait = this.bandwidth.lock().unwrap().charge(
+            Some(&this.peer_id),
+            this.direction,
+            (this.size)(&item),
+        );
+        if wait > Duration::from_secs(0) {
+            this.delay = Some(delay_for(wait));
+        }
+        Poll::Ready(Some(item))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_bucket() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new(1000, now);
+        assert!(!bucket.is_exhausted());
+        bucket.charge(1500);
+        assert!(bucket.is_exhausted());
+        assert_eq!(bucket.wait(), Duration::from_millis(500));
+        bucket.refill(now + Duration::from_millis(250));
+        assert_eq!(bucket.tokens, -250);
+        bucket.refill(now + Duration::from_secs(10));
+        assert_eq!(bucket.tokens, 1000);
+
+        // Unlimited.
+        let mut bucket = TokenBucket::new(0, now);
+        bucket.charge(1_000_000);
+        assert!(!bucket.is_exhausted());
+        assert_eq!(bucket.wait(), Duration::from_secs(0));
+    }
+
+    #[test]
+    fn priority() {
+        let config = NetworkConfig {
+            upload_limit: 1000,
+            peer_upload_limit: 100,
+            priority_topics: vec!["consensus".to_string()],
+            ..Default::default()
+        };
+        let mut bandwidth = Bandwidth::new(&config);
+        let peer1 = PeerId::random();
+        let peer2 = PeerId::random();
+        assert_eq!(bandwidth.priority("tx"), Priority::Normal);
+        assert_eq!(bandwidth.priority("consensus"), Priority::High);
+        bandwidth.add_priority_topic("view_changes");
+        assert_eq!(bandwidth.priority("view_changes"), Priority::High);
+
+        // Per-peer limit.
+        assert!(bandwidth.admit(Some(&peer1), Direction::Outgoing, "tx", 200));
+        assert!(!bandwidth.admit(Some(&peer1), Direction::Outgoing, "tx", 10));
+        assert!(bandwidth.admit(Some(&peer1), Direction::Outgoing, "consensus", 10));
+        // Download is not limited.
+        assert!(bandwidth.admit(Some(&peer1), Direction::Incoming, "tx", 10_000));
+
+        // Global limit.
+        assert!(bandwidth.admit(Some(&peer2), Direction::Outgoing, "tx", 50));
+        assert!(bandwidth.admit(None, Direction::Outgoing, "consensus", 1000));
+        assert!(!bandwidth.admit(Some(&peer2), Direction::Outgoing, "tx", 10));
+        assert!(!bandwidth.has_capacity(None, Direction::Outgoing));
+    }
+}
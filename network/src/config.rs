@@ -47,6 +47,45 @@ pub struct NetworkConfig {
     pub hanshake_puzzle_difficulty: u64,
     /// Network readiness threshold (number of handshake-enabled established connections)
     pub readiness_threshold: usize,
+    /// Authentication and encryption protocols of connections.
+    pub transport_security: TransportSecurity,
+    /// Peers with score below -peer_ban_threshold are banned.
+    pub peer_ban_threshold: i64,
+    /// Duration of the first ban of a peer (secs), doubled for every subsequent ban.
+    pub peer_ban_duration: u64,
+    /// Maximal number of gossip messages per second from one peer, 0 means unlimited.
+    pub gossip_rate_limit: u32,
+    /// Total upload limit (bytes per second), 0 means unlimited.
+    pub upload_limit: u64,
+    /// Total download limit (bytes per second), 0 means unlimited.
+    pub download_limit: u64,
+    /// Upload limit for one peer (bytes per second), 0 means unlimited.
+    pub peer_upload_limit: u64,
+    /// Download limit for one peer (bytes per second), 0 means unlimited.
+    pub peer_download_limit: u64,
+    /// Extra topics which messages are never dropped by bandwidth limits,
+    /// in addition to topics subscribed by `NetworkProvider::subscribe_priority()`.
+    pub priority_topics: Vec<String>,
+    /// SOCKS5 proxy for outbound connections (ip:port), empty means direct connections.
+    pub socks5_proxy: String,
+    /// Resolve DNS names by the SOCKS5 proxy instead of the local resolver.
+    pub socks5_proxy_dns: bool,
+    /// Accept inbound connections on the endpoint.
+    pub listen: bool,
+    /// Map the listening port on the gateway by UPnP or NAT-PMP (requires `port_mapping` feature).
+    pub port_mapping: bool,
+}
+
+/// Authentication and encryption protocols of connections.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TransportSecurity {
+    /// Secio only (deprecated by libp2p).
+    Secio,
+    /// Noise (XX handshake), secio is negotiated for secio-only peers.
+    NoiseOrSecio,
+    /// Noise (XX handshake) only.
+    Noise,
 }
 
 /// Default values for network configuration.
@@ -63,6 +102,19 @@ impl Default for NetworkConfig {
             monitoring_interval: 60,
             hanshake_puzzle_difficulty: 100,
             readiness_threshold: 2,
+            transport_security: TransportSecurity::NoiseOrSecio,
+            peer_ban_threshold: 100,
+            peer_ban_duration: 600,
+            gossip_rate_limit: 1000,
+            upload_limit: 0,
+            download_limit: 0,
+            peer_upload_limit: 0,
+            peer_download_limit: 0,
+            priority_topics: vec![],
+            socks5_proxy: "".to_string(),
+            socks5_proxy_dns: true,
+            listen: true,
+            port_mapping: false,
         }
     }
 }
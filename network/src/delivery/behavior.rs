@@ -24,6 +24,8 @@
 use super::handler::{DeliveryHandler, DeliveryRecvEvent, DeliverySendEvent};
 pub use super::protocol::{DeliveryMessage, Unicast};
 
+use crate::bandwidth::SharedBandwidth;
+use crate::traffic::{self, Direction};
 use crate::utils::ExpiringQueue;
 use futures::task::{Context, Poll};
 use libp2p_core::{connection::ConnectionId, Multiaddr, PeerId};
@@ -41,6 +43,7 @@ use stegos_crypto::utils::u8v_to_hexstr;
 
 // Set timeout for connecting to peer to 15 secs
 const DIAL_TIMEOUT: Duration = Duration::from_secs(15);
+const TRAFFIC_LABEL: &str = "delivery";
 
 /// Network behaviour that automatically identifies nodes periodically, and returns information
 /// about them.
@@ -56,26 +59,24 @@ pub struct Delivery {
 
     // Sending queue
     send_queue: HashMap<PeerId, SmallVec<[DeliveryMessage; 16]>>,
+
+    // Bandwidth limits, unicast messages are charged, but never dropped
+    bandwidth: SharedBandwidth,
 }
 
 impl Delivery {
     /// Creates a `Delivery`.
-    pub fn new() -> Self {
+    pub fn new(bandwidth: SharedBandwidth) -> Self {
         Delivery {
             events: VecDeque::new(),
             connected_peers: HashSet::new(),
             dial_queue: ExpiringQueue::new(DIAL_TIMEOUT),
             send_queue: HashMap::new(),
+            bandwidth,
         }
     }
 }
 
-impl Default for Delivery {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
 impl Delivery {
     pub fn deliver_unicast(&mut self, next_hop: &PeerId, message: Unicast) {
         if self.connected_peers.contains(next_hop) {
@@ -151,6 +152,18 @@ impl NetworkBehaviour for Delivery {
             DeliveryRecvEvent::Message(msg) => match msg {
                 DeliveryMessage::UnicastMessage(unicast) => {
                     debug!(target: "stegos_network::delivery", "received unicast message from peer: peer_id={}, seq_no={}", propagation_source, u8v_to_hexstr(&unicast.seq_no));
+                    traffic::record(
+                        TRAFFIC_LABEL,
+                        None,
+                        Some(&propagation_source),
+                        Direction::Incoming,
+                        unicast.payload.len(),
+                    );
+                    self.bandwidth.lock().unwrap().charge(
+                        Some(&propagation_source),
+                        Direction::Incoming,
+                        unicast.payload.len(),
+                    );
                     self.events.push_back(NetworkBehaviourAction::GenerateEvent(
                         DeliveryEvent::Message(DeliveryMessage::UnicastMessage(unicast)),
                     ))
@@ -171,6 +184,25 @@ impl NetworkBehaviour for Delivery {
         >,
     > {
         if let Some(event) = self.events.pop_front() {
+            if let NetworkBehaviourAction::NotifyHandler {
+                peer_id,
+                event: DeliverySendEvent::Deliver(DeliveryMessage::UnicastMessage(unicast)),
+                ..
+            } = &event
+            {
+                traffic::record(
+                    TRAFFIC_LABEL,
+                    None,
+                    Some(peer_id),
+                    Direction::Outgoing,
+                    unicast.payload.len(),
+                );
+                self.bandwidth.lock().unwrap().charge(
+                    Some(peer_id),
+                    Direction::Outgoing,
+                    unicast.payload.len(),
+                );
+            }
             return Poll::Ready(event);
         }
 
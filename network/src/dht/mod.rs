@@ -0,0 +1,591 @@
+//
+// MIT License
+//
+// Copyright (c) 2018-2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Peer discovery and resolution of network keys.
+//!
+//! Built on the upstream libp2p Kademlia and Identify protocols:
+//!
+//! - Every node publishes a `NodeRecord` signed by its network key, which maps the key
+//!   to the PeerId and addresses of the node. Records are verified on lookup,
+//!   so storing nodes don't need to be trusted.
+//! - Identify exchanges listen addresses, which populate the Kademlia routing table,
+//!   and reports the address this node is observed at, which is used for NAT detection.
+//! - Unicast messages are sent directly to the node found by its record.
+//!
+//! Replaces NCP and the forked Kademlia (see `old_protos`), which stay enabled by default
+//! for one release: nodes of the previous release have no records, so messages to them
+//! are routed by `old_protos`.
+
+mod peers;
+mod proto;
+mod record;
+
+use self::peers::{Peers, PeersEvent};
+pub(crate) use self::record::NodeRecord;
+use self::record::{record_key, MAX_ADDRESSES};
+use crate::config::NetworkConfig;
+use crate::delivery::Unicast;
+use crate::peer_store::unix_now;
+use crate::utils::socket_to_multi_addr;
+use crate::{Multiaddr, PeerId};
+use futures::task::{Context, Poll};
+use libp2p::identify::{Identify, IdentifyEvent};
+use libp2p::kad::record::store::MemoryStore;
+use libp2p::kad::record::Record;
+use libp2p::kad::{GetRecordOk, Kademlia, KademliaConfig, KademliaEvent, Quorum};
+use libp2p_core::identity;
+use libp2p_core_derive::NetworkBehaviour;
+use libp2p_swarm::{
+    IntoProtocolsHandler, NetworkBehaviour, NetworkBehaviourAction, NetworkBehaviourEventProcess,
+    PollParameters, ProtocolsHandler,
+};
+use log::*;
+use lru_time_cache::LruCache;
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::time::Duration;
+use stegos_crypto::pbc;
+use stegos_crypto::utils::u8v_to_hexstr;
+use tokio::time::Interval;
+
+use crate::utils::ExpiringQueue;
+
+/// Kademlia protocol name, differs from the one of the forked implementation.
+const KADEMLIA_PROTOCOL: &[u8] = b"/stegos/dht/1.0.0";
+/// Identify protocol version.
+const PROTOCOL_VERSION: &str = "/stegos/1.0.0";
+/// Records of nodes which haven't been republished for so long are dropped.
+const RECORD_TTL: Duration = Duration::from_secs(60 * 60);
+/// How often to republish the own record.
+const REPUBLISH_INTERVAL: Duration = Duration::from_secs(10 * 60);
+/// Messages to a node are dropped if its record hasn't been found in time.
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(60);
+/// Maximal number of remembered node records.
+const MAX_NODES: usize = 1024;
+/// Maximal number of remembered unicast messages, used to drop duplicates.
+const RECEIVED_CACHE_SIZE: usize = 16384;
+/// Records signed too far in the future are ignored.
+const MAX_CLOCK_SKEW: u64 = 10 * 60;
+
+/// Node Info struct for passing to API
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct NodeInfo {
+    /// Libp2p PeerId of the node
+    peer_id: String,
+    ///Network Public Key of the node
+    network_pkey: pbc::PublicKey,
+    /// Known addresses of the node
+    #[schemars(with = "Vec<String>")]
+    addresses: Vec<Multiaddr>,
+}
+
+impl NodeInfo {
+    pub(crate) fn new(
+        peer_id: &PeerId,
+        network_pkey: pbc::PublicKey,
+        addresses: Vec<Multiaddr>,
+    ) -> Self {
+        NodeInfo {
+            peer_id: peer_id.to_base58(),
+            network_pkey,
+            addresses,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum DhtOutEvent {
+    Connected {
+        peer_id: PeerId,
+    },
+    Disconnected {
+        peer_id: PeerId,
+    },
+    DialPeer {
+        peer_id: PeerId,
+    },
+    ObservedAddress {
+        peer_id: PeerId,
+        address: Multiaddr,
+    },
+    DiscoveredPeer {
+        node_id: pbc::PublicKey,
+        peer_id: PeerId,
+        addresses: Vec<Multiaddr>,
+    },
+    Route {
+        next_hop: PeerId,
+        message: Unicast,
+    },
+    /// The record of the recipient hasn't been found.
+    NotFound {
+        messages: Vec<Unicast>,
+    },
+}
+
+#[derive(NetworkBehaviour)]
+#[behaviour(poll_method = "poll")]
+#[behaviour(out_event = "DhtOutEvent")]
+pub struct Dht {
+    kademlia: Kademlia<MemoryStore>,
+    identify: Identify,
+    peers: Peers,
+
+    #[behaviour(ignore)]
+    network_pkey: pbc::PublicKey,
+    #[behaviour(ignore)]
+    network_skey: pbc::SecretKey,
+    #[behaviour(ignore)]
+    peer_id: PeerId,
+    /// Address advertised in the own record, followed by listening addresses.
+    #[behaviour(ignore)]
+    advertised_address: Option<Multiaddr>,
+    /// The routing table has been bootstrapped since the last connection.
+    #[behaviour(ignore)]
+    joined: bool,
+    /// The own record must be published on the next poll.
+    #[behaviour(ignore)]
+    publish_needed: bool,
+    /// Publish and look up node records.
+    /// Disabled in tests to emulate nodes of the previous release.
+    #[behaviour(ignore)]
+    node_records: bool,
+    #[behaviour(ignore)]
+    republish_timer: Interval,
+    /// Verified records of other nodes.
+    #[behaviour(ignore)]
+    nodes: LruCache<pbc::PublicKey, NodeRecord>,
+    /// Pending lookups of records.
+    #[behaviour(ignore)]
+    lookups: ExpiringQueue<pbc::PublicKey, ()>,
+    /// Messages waiting for lookups.
+    #[behaviour(ignore)]
+    pending: HashMap<pbc::PublicKey, Vec<Unicast>>,
+    /// Digests of sent and received unicast messages.
+    #[behaviour(ignore)]
+    received: LruCache<u64, ()>,
+    #[behaviour(ignore)]
+    min_connections: usize,
+    #[behaviour(ignore)]
+    monitor_timer: Interval,
+    #[behaviour(ignore)]
+    out_events: VecDeque<DhtOutEvent>,
+}
+
+impl Dht {
+    pub fn new(
+        config: &NetworkConfig,
+        network_skey: pbc::SecretKey,
+        network_pkey: pbc::PublicKey,
+        local_key: identity::PublicKey,
+    ) -> Self {
+        let peer_id = local_key.clone().into_peer_id();
+        let mut kademlia_config = KademliaConfig::default();
+        kademlia_config
+            .set_protocol_name(KADEMLIA_PROTOCOL)
+            .set_query_timeout(LOOKUP_TIMEOUT)
+            .set_record_ttl(Some(RECORD_TTL));
+        let store = MemoryStore::new(peer_id.clone());
+        let kademlia = Kademlia::with_config(peer_id.clone(), store, kademlia_config);
+        let agent_version = format!(
+            "stegos/{} {}",
+            env!("CARGO_PKG_VERSION"),
+            network_pkey.to_hex()
+        );
+        let identify = Identify::new(PROTOCOL_VERSION.to_string(), agent_version, local_key);
+
+        let advertised_address = if config.advertised_endpoint != "" {
+            let endpoint = SocketAddr::from_str(&config.advertised_endpoint)
+                .expect("Invalid advertised_endpoint");
+            Some(socket_to_multi_addr(&endpoint))
+        } else {
+            None
+        };
+        let monitoring_interval = Duration::from_secs(config.monitoring_interval);
+
+        Dht {
+            kademlia,
+            identify,
+            peers: Peers::new(),
+            network_pkey,
+            network_skey,
+            peer_id,
+            advertised_address,
+            joined: false,
+            publish_needed: false,
+            node_records: true,
+            republish_timer: tokio::time::interval(REPUBLISH_INTERVAL),
+            nodes: LruCache::with_capacity(MAX_NODES),
+            lookups: ExpiringQueue::new(LOOKUP_TIMEOUT),
+            pending: HashMap::new(),
+            received: LruCache::with_capacity(RECEIVED_CACHE_SIZE),
+            min_connections: config.min_connections,
+            monitor_timer: tokio::time::interval(monitoring_interval),
+            out_events: VecDeque::new(),
+        }
+    }
+
+    /// Adds a peer known from the previous run.
+    pub fn add_known_peer(&mut self, peer_id: &PeerId, addresses: Vec<Multiaddr>) {
+        for address in &addresses {
+            self.kademlia.add_address(peer_id, address.clone());
+        }
+        self.peers.set_addresses(peer_id, addresses);
+    }
+
+    /// Changes the advertised address and republishes the own record.
+    pub fn set_advertised_address(&mut self, address: Option<Multiaddr>) {
+        if self.advertised_address == address {
+            return;
+        }
+        debug!(target: "stegos_network::dht", "Public Network endpoint: {:?}", address);
+        self.advertised_address = address;
+        self.publish_needed = true;
+    }
+
+    /// Stops publishing the own record, messages are routed by `old_protos` only.
+    #[cfg(test)]
+    pub(crate) fn disable_node_records(&mut self) {
+        self.node_records = false;
+    }
+
+    /// Returns connected nodes with known network keys.
+    pub fn connected_nodes(&self) -> Vec<NodeInfo> {
+        self.nodes
+            .peek_iter()
+            .filter(|(_, record)| self.peers.is_connected(&record.peer_id))
+            .map(|(node_id, record)| {
+                NodeInfo::new(&record.peer_id, *node_id, record.addresses.clone())
+            })
+            .collect()
+    }
+
+    /// Sends a message to the node directly, looking up its record if needed.
+    pub fn deliver_unicast(&mut self, to: &pbc::PublicKey, payload: Vec<u8>) {
+        let message = Unicast {
+            to: *to,
+            payload,
+            dont_route: true,
+            seq_no: rand::random::<[u8; 20]>().to_vec(),
+        };
+        self.received.insert(message.digest(), ());
+        if !self.node_records {
+            let messages = vec![message];
+            self.out_events
+                .push_back(DhtOutEvent::NotFound { messages });
+            return;
+        }
+        let next_hop = self.nodes.get(to).map(|record| record.peer_id.clone());
+        match next_hop {
+            Some(next_hop) => {
+                self.out_events
+                    .push_back(DhtOutEvent::Route { next_hop, message });
+            }
+            None => {
+                self.pending
+                    .entry(*to)
+                    .or_insert_with(Vec::new)
+                    .push(message);
+                self.lookup(to);
+            }
+        }
+    }
+
+    /// Returns true if the message has been already received.
+    pub fn is_duplicate(&mut self, message: &Unicast) -> bool {
+        let digest = message.digest();
+        if self.received.contains_key(&digest) {
+            return true;
+        }
+        self.received.insert(digest, ());
+        false
+    }
+
+    fn lookup(&mut self, node_id: &pbc::PublicKey) {
+        if self.lookups.contains_key(node_id) {
+            return;
+        }
+        debug!(target: "stegos_network::dht", "looking up node: node_id={}", node_id);
+        self.lookups.insert(*node_id, ());
+        self.kademlia.get_record(&record_key(node_id), Quorum::One);
+    }
+
+    fn finish_lookup(&mut self, node_id: &pbc::PublicKey) {
+        self.lookups.remove(node_id);
+        let messages = match self.pending.remove(node_id) {
+            Some(messages) => messages,
+            None => return,
+        };
+        let next_hop = self.nodes.get(node_id).map(|record| record.peer_id.clone());
+        match next_hop {
+            Some(next_hop) => {
+                for message in messages {
+                    self.out_events.push_back(DhtOutEvent::Route {
+                        next_hop: next_hop.clone(),
+                        message,
+                    });
+                }
+            }
+            None => {
+                debug!(target: "stegos_network::dht", "node not found: node_id={}, messages={}", node_id, messages.len());
+                self.out_events
+                    .push_back(DhtOutEvent::NotFound { messages });
+            }
+        }
+    }
+
+    /// Remembers a verified record, if it is newer than the known one.
+    fn on_record(&mut self, record: NodeRecord) {
+        if record.network_pkey == self.network_pkey || record.peer_id == self.peer_id {
+            return;
+        }
+        if record.timestamp > unix_now() + MAX_CLOCK_SKEW {
+            debug!(target: "stegos_network::dht", "record from the future: node_id={}", record.network_pkey);
+            return;
+        }
+        if let Some(known) = self.nodes.peek(&record.network_pkey) {
+            if known.timestamp >= record.timestamp {
+                return;
+            }
+        }
+        debug!(target: "stegos_network::dht", "discovered node: node_id={}, peer_id={}", record.network_pkey, record.peer_id);
+        self.peers
+            .set_addresses(&record.peer_id, record.addresses.clone());
+        self.out_events.push_back(DhtOutEvent::DiscoveredPeer {
+            node_id: record.network_pkey,
+            peer_id: record.peer_id.clone(),
+            addresses: record.addresses.clone(),
+        });
+        self.nodes.insert(record.network_pkey, record);
+    }
+
+    fn publish(&mut self, listened_addresses: Vec<Multiaddr>) {
+        let mut addresses: Vec<Multiaddr> = Vec::new();
+        for address in self
+            .advertised_address
+            .iter()
+            .cloned()
+            .chain(listened_addresses)
+        {
+            if !addresses.contains(&address) && addresses.len() < MAX_ADDRESSES {
+                addresses.push(address);
+            }
+        }
+        let record = NodeRecord::new(
+            &self.network_skey,
+            self.network_pkey,
+            self.peer_id.clone(),
+            addresses,
+            unix_now(),
+        );
+        debug!(target: "stegos_network::dht", "publishing node record: addresses={:?}", record.addresses);
+        let record = Record::new(record_key(&self.network_pkey), record.encode());
+        // Failures are reported by PutRecordResult.
+        self.kademlia.put_record(record, Quorum::One);
+    }
+
+    /// Dials known nodes if there are not enough connections.
+    fn check_connections(&mut self) {
+        let connected = self.peers.len();
+        debug!(target: "stegos_network::dht", "monitoring event: connected_peers={}, known_nodes={}", connected, self.nodes.len());
+        if !self.joined {
+            return;
+        }
+        self.kademlia.bootstrap();
+        if connected >= self.min_connections {
+            return;
+        }
+        let dial: Vec<PeerId> = self
+            .nodes
+            .peek_iter()
+            .map(|(_, record)| record.peer_id.clone())
+            .filter(|peer_id| !self.peers.is_connected(peer_id))
+            .take(self.min_connections - connected)
+            .collect();
+        for peer_id in dial {
+            self.out_events.push_back(DhtOutEvent::DialPeer { peer_id });
+        }
+    }
+
+    fn poll(&mut self,
+        cx: &mut Context,
+        poll_parameters: &mut impl PollParameters) -> Poll<NetworkBehaviourAction<
+        <<<Self as NetworkBehaviour>::ProtocolsHandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::InEvent, <Self as NetworkBehaviour>::OutEvent>,>
+    {
+        while let Poll::Ready(_) = self.republish_timer.poll_tick(cx) {
+            self.publish_needed = true;
+        }
+        if self.publish_needed && self.joined && self.node_records {
+            self.publish_needed = false;
+            self.publish(poll_parameters.listened_addresses().collect());
+        }
+
+        while let Poll::Ready(_) = self.monitor_timer.poll_tick(cx) {
+            self.check_connections();
+        }
+
+        loop {
+            match self.lookups.poll(cx) {
+                Poll::Ready(Ok((node_id, _))) => self.finish_lookup(&node_id),
+                Poll::Ready(Err(e)) => {
+                    error!(target: "stegos_network::dht", "lookup timer error: {}", e);
+                    break;
+                }
+                Poll::Pending => break,
+            }
+        }
+
+        if let Some(event) = self.out_events.pop_front() {
+            return Poll::Ready(NetworkBehaviourAction::GenerateEvent(event));
+        }
+        Poll::Pending
+    }
+}
+
+/// Extracts the network key from the agent version announced by Identify.
+fn parse_agent_version(agent_version: &str) -> Option<pbc::PublicKey> {
+    let mut parts = agent_version.split_whitespace();
+    if !parts.next()?.starts_with("stegos/") {
+        return None;
+    }
+    pbc::PublicKey::try_from_hex(parts.next()?).ok()
+}
+
+impl NetworkBehaviourEventProcess<PeersEvent> for Dht {
+    fn inject_event(&mut self, event: PeersEvent) {
+        match event {
+            PeersEvent::Connected { peer_id, address } => {
+                trace!(target: "stegos_network::dht", "connected: peer_id={}, address={}", peer_id, address);
+                self.out_events
+                    .push_back(DhtOutEvent::Connected { peer_id });
+            }
+            PeersEvent::Disconnected { peer_id } => {
+                if self.peers.is_empty() {
+                    self.joined = false;
+                }
+                self.out_events
+                    .push_back(DhtOutEvent::Disconnected { peer_id });
+            }
+        }
+    }
+}
+
+impl NetworkBehaviourEventProcess<IdentifyEvent> for Dht {
+    fn inject_event(&mut self, event: IdentifyEvent) {
+        match event {
+            IdentifyEvent::Received {
+                peer_id,
+                info,
+                observed_addr,
+            } => {
+                debug!(target: "stegos_network::dht", "identified peer: peer_id={}, agent={}", peer_id, info.agent_version);
+                let kademlia_protocol = String::from_utf8_lossy(KADEMLIA_PROTOCOL);
+                if info.protocols.iter().any(|p| *p == kademlia_protocol) {
+                    for address in info.listen_addrs.iter().take(MAX_ADDRESSES) {
+                        self.kademlia.add_address(&peer_id, address.clone());
+                    }
+                    if !self.joined {
+                        // The first DHT peer, populate the routing table and publish the record.
+                        self.joined = true;
+                        self.publish_needed = true;
+                        self.kademlia.bootstrap();
+                    }
+                }
+                self.out_events.push_back(DhtOutEvent::ObservedAddress {
+                    peer_id: peer_id.clone(),
+                    address: observed_addr,
+                });
+                // Resolve the network key of peer, the claim is checked by the signed record.
+                if let Some(node_id) = parse_agent_version(&info.agent_version) {
+                    let known = self.nodes.peek(&node_id).map(|r| r.peer_id == peer_id);
+                    if known != Some(true) && node_id != self.network_pkey {
+                        self.lookup(&node_id);
+                    }
+                }
+            }
+            IdentifyEvent::Sent { .. } => {}
+            IdentifyEvent::Error { peer_id, error } => {
+                debug!(target: "stegos_network::dht", "identify failed: peer_id={}, error={}", peer_id, error);
+            }
+        }
+    }
+}
+
+impl NetworkBehaviourEventProcess<KademliaEvent> for Dht {
+    fn inject_event(&mut self, event: KademliaEvent) {
+        match event {
+            KademliaEvent::GetRecordResult(Ok(GetRecordOk { records })) => {
+                let mut found = Vec::new();
+                for record in records {
+                    match NodeRecord::decode(&record.value) {
+                        Ok(node_record) if record.key == record_key(&node_record.network_pkey) => {
+                            found.push(node_record.network_pkey);
+                            self.on_record(node_record);
+                        }
+                        Ok(_) => {
+                            debug!(target: "stegos_network::dht", "record stored under wrong key: key={}", u8v_to_hexstr(&record.key.to_vec()));
+                        }
+                        Err(e) => {
+                            debug!(target: "stegos_network::dht", "invalid node record: key={}, error={}", u8v_to_hexstr(&record.key.to_vec()), e);
+                        }
+                    }
+                }
+                found.sort();
+                found.dedup();
+                for node_id in found {
+                    self.finish_lookup(&node_id);
+                }
+            }
+            KademliaEvent::GetRecordResult(Err(e)) => {
+                if let Ok(node_id) = pbc::PublicKey::try_from_bytes(&e.key().to_vec()) {
+                    debug!(target: "stegos_network::dht", "lookup failed: node_id={}, error={:?}", node_id, e);
+                    self.finish_lookup(&node_id);
+                }
+            }
+            KademliaEvent::PutRecordResult(Err(e)) => {
+                debug!(target: "stegos_network::dht", "failed to publish node record: {:?}", e);
+            }
+            event => {
+                trace!(target: "stegos_network::dht", "kademlia event: {:?}", event);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn agent_version() {
+        let (_skey, pkey) = pbc::make_random_keys();
+        let agent_version = format!("stegos/1.0.0 {}", pkey.to_hex());
+        assert_eq!(parse_agent_version(&agent_version), Some(pkey));
+        assert_eq!(parse_agent_version("stegos/1.0.0"), None);
+        assert_eq!(parse_agent_version("rust-libp2p/0.18.0"), None);
+        assert_eq!(parse_agent_version("other/1.0.0 00"), None);
+    }
+}
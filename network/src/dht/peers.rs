@@ -0,0 +1,150 @@
+//
+// MIT License
+//
+// Copyright (c) 2018-2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Tracking of connected peers and the addresses learned from node records.
+
+use crate::{Multiaddr, PeerId};
+use futures::task::{Context, Poll};
+use libp2p_core::connection::ConnectionId;
+use libp2p_core::ConnectedPoint;
+use libp2p_swarm::protocols_handler::DummyProtocolsHandler;
+use libp2p_swarm::{NetworkBehaviour, NetworkBehaviourAction, PollParameters, ProtocolsHandler};
+use log::*;
+use lru_time_cache::LruCache;
+use std::collections::{HashMap, VecDeque};
+use void::Void;
+
+/// Maximal number of peers in the address book.
+const ADDRESS_BOOK_SIZE: usize = 1024;
+
+#[derive(Debug)]
+pub enum PeersEvent {
+    Connected { peer_id: PeerId, address: Multiaddr },
+    Disconnected { peer_id: PeerId },
+}
+
+/// Network behaviour which keeps the list of connected peers and provides
+/// known addresses of peers for dialing.
+pub struct Peers {
+    /// Connected peers with the remote address of the first connection.
+    connected: HashMap<PeerId, Multiaddr>,
+    /// Addresses of peers, by PeerId bytes.
+    addresses: LruCache<Vec<u8>, Vec<Multiaddr>>,
+    events: VecDeque<PeersEvent>,
+}
+
+impl Peers {
+    pub fn new() -> Self {
+        Peers {
+            connected: HashMap::new(),
+            addresses: LruCache::with_capacity(ADDRESS_BOOK_SIZE),
+            events: VecDeque::new(),
+        }
+    }
+
+    pub fn is_connected(&self, peer_id: &PeerId) -> bool {
+        self.connected.contains_key(peer_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.connected.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.connected.is_empty()
+    }
+
+    /// Replaces the known addresses of the peer.
+    pub fn set_addresses(&mut self, peer_id: &PeerId, addresses: Vec<Multiaddr>) {
+        self.addresses
+            .insert(peer_id.as_bytes().to_vec(), addresses);
+    }
+}
+
+impl NetworkBehaviour for Peers {
+    type ProtocolsHandler = DummyProtocolsHandler;
+    type OutEvent = PeersEvent;
+
+    fn new_handler(&mut self) -> Self::ProtocolsHandler {
+        DummyProtocolsHandler::default()
+    }
+
+    fn addresses_of_peer(&mut self, peer_id: &PeerId) -> Vec<Multiaddr> {
+        self.addresses
+            .get(&peer_id.as_bytes().to_vec())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn inject_connected(&mut self, _peer_id: &PeerId) {}
+
+    fn inject_connection_established(
+        &mut self,
+        peer_id: &PeerId,
+        _: &ConnectionId,
+        endpoint: &ConnectedPoint,
+    ) {
+        if self.connected.contains_key(peer_id) {
+            return;
+        }
+        let address = match endpoint {
+            ConnectedPoint::Dialer { address } => address,
+            ConnectedPoint::Listener { send_back_addr, .. } => send_back_addr,
+        };
+        debug!(target: "stegos_network::dht", "peer connected: peer_id={}, address={}", peer_id, address);
+        self.connected.insert(peer_id.clone(), address.clone());
+        self.events.push_back(PeersEvent::Connected {
+            peer_id: peer_id.clone(),
+            address: address.clone(),
+        });
+    }
+
+    fn inject_disconnected(&mut self, peer_id: &PeerId) {
+        debug!(target: "stegos_network::dht", "peer disconnected: peer_id={}", peer_id);
+        if self.connected.remove(peer_id).is_some() {
+            self.events.push_back(PeersEvent::Disconnected {
+                peer_id: peer_id.clone(),
+            });
+        }
+    }
+
+    fn inject_event(&mut self, _: PeerId, _: ConnectionId, event: Void) {
+        void::unreachable(event)
+    }
+
+    fn poll(
+        &mut self,
+        _: &mut Context,
+        _: &mut impl PollParameters,
+    ) -> Poll<
+        NetworkBehaviourAction<
+            <Self::ProtocolsHandler as ProtocolsHandler>::InEvent,
+            Self::OutEvent,
+        >,
+    > {
+        if let Some(event) = self.events.pop_front() {
+            return Poll::Ready(NetworkBehaviourAction::GenerateEvent(event));
+        }
+        Poll::Pending
+    }
+}
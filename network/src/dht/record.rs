@@ -0,0 +1,178 @@
+//
+// MIT License
+//
+// Copyright (c) 2018-2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Signed node records published in the DHT.
+
+use super::proto::node_record as node_record_proto;
+use crate::{Multiaddr, PeerId};
+use failure::{format_err, Error};
+use libp2p::kad::record::Key;
+use protobuf::Message;
+use std::convert::TryFrom;
+use stegos_crypto::hash::{Hash, Hashable, Hasher};
+use stegos_crypto::pbc;
+
+/// Maximal number of addresses in a record.
+pub(crate) const MAX_ADDRESSES: usize = 16;
+
+/// Maps the network key of a node to its PeerId and addresses.
+/// Signed by the network key, so any peer can store and serve it.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct NodeRecord {
+    pub network_pkey: pbc::PublicKey,
+    pub peer_id: PeerId,
+    pub addresses: Vec<Multiaddr>,
+    /// Creation time (UNIX timestamp, secs), newer records replace older ones.
+    pub timestamp: u64,
+    pub signature: pbc::Signature,
+}
+
+/// Returns the DHT key of the record for the network key.
+pub(crate) fn record_key(network_pkey: &pbc::PublicKey) -> Key {
+    Key::new(&network_pkey.to_bytes().to_vec())
+}
+
+fn signing_hash(
+    network_pkey: &pbc::PublicKey,
+    peer_id: &PeerId,
+    addresses: &[Multiaddr],
+    timestamp: u64,
+) -> Hash {
+    let mut hasher = Hasher::new();
+    "node_record".hash(&mut hasher);
+    network_pkey.hash(&mut hasher);
+    peer_id.as_bytes().hash(&mut hasher);
+    for address in addresses {
+        address.as_ref().hash(&mut hasher);
+    }
+    timestamp.hash(&mut hasher);
+    hasher.result()
+}
+
+impl NodeRecord {
+    /// Creates a new record signed by the network key.
+    pub fn new(
+        network_skey: &pbc::SecretKey,
+        network_pkey: pbc::PublicKey,
+        peer_id: PeerId,
+        mut addresses: Vec<Multiaddr>,
+        timestamp: u64,
+    ) -> Self {
+        addresses.truncate(MAX_ADDRESSES);
+        let hash = signing_hash(&network_pkey, &peer_id, &addresses, timestamp);
+        let signature = pbc::sign_hash(&hash, network_skey);
+        NodeRecord {
+            network_pkey,
+            peer_id,
+            addresses,
+            timestamp,
+            signature,
+        }
+    }
+
+    /// Checks the signature of the record.
+    pub fn validate(&self) -> Result<(), Error> {
+        let hash = signing_hash(
+            &self.network_pkey,
+            &self.peer_id,
+            &self.addresses,
+            self.timestamp,
+        );
+        pbc::check_hash(&hash, &self.signature, &self.network_pkey)
+            .map_err(|_| format_err!("Invalid signature of node record"))
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut msg = node_record_proto::NodeRecord::new();
+        msg.set_network_pkey(self.network_pkey.to_bytes().to_vec());
+        msg.set_peer_id(self.peer_id.as_bytes().to_vec());
+        for address in &self.addresses {
+            msg.mut_addresses().push(address.to_vec());
+        }
+        msg.set_timestamp(self.timestamp);
+        msg.set_signature(self.signature.to_bytes().to_vec());
+        msg.write_to_bytes()
+            .expect("protobuf encoding should never fail")
+    }
+
+    /// Decodes and validates a record.
+    pub fn decode(input: &[u8]) -> Result<Self, Error> {
+        let mut msg: node_record_proto::NodeRecord = protobuf::parse_from_bytes(input)?;
+        let network_pkey = pbc::PublicKey::try_from_bytes(msg.get_network_pkey())?;
+        let peer_id = PeerId::from_bytes(msg.take_peer_id())
+            .map_err(|_| format_err!("Invalid PeerId in node record"))?;
+        if msg.get_addresses().len() > MAX_ADDRESSES {
+            return Err(format_err!("Too many addresses in node record"));
+        }
+        let mut addresses = Vec::with_capacity(msg.get_addresses().len());
+        for address in msg.take_addresses().into_iter() {
+            addresses.push(Multiaddr::try_from(address)?);
+        }
+        let signature = pbc::Signature::try_from_bytes(msg.get_signature())?;
+        let record = NodeRecord {
+            network_pkey,
+            peer_id,
+            addresses,
+            timestamp: msg.get_timestamp(),
+            signature,
+        };
+        record.validate()?;
+        Ok(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_decode() {
+        let (skey, pkey) = pbc::make_random_keys();
+        let peer_id = PeerId::random();
+        let addresses: Vec<Multiaddr> = vec![
+            "/ip4/1.2.3.4/tcp/10203".parse().unwrap(),
+            "/ip4/127.0.0.1/tcp/10203".parse().unwrap(),
+        ];
+        let record = NodeRecord::new(&skey, pkey, peer_id, addresses, 1234);
+        record.validate().unwrap();
+        let decoded = NodeRecord::decode(&record.encode()).unwrap();
+        assert_eq!(decoded, record);
+
+        // Signed by another key.
+        let (skey2, _pkey2) = pbc::make_random_keys();
+        let mut forged = NodeRecord::new(
+            &skey2,
+            pkey,
+            PeerId::random(),
+            record.addresses.clone(),
+            1235,
+        );
+        assert!(NodeRecord::decode(&forged.encode()).is_err());
+
+        // Tampered addresses.
+        forged = record;
+        forged.addresses.pop();
+        assert!(forged.validate().is_err());
+        assert!(NodeRecord::decode(&forged.encode()).is_err());
+    }
+}
@@ -45,6 +45,7 @@ use stegos_crypto::vdf::VDF;
 use super::handler::{GatekeeperHandler, GatekeeperSendEvent};
 use super::protocol::{GatekeeperMessage, NetworkName, VDFProof};
 use crate::config::NetworkConfig;
+use crate::traffic::{self, Direction};
 use crate::utils::{socket_to_multi_addr, ExpiringQueue, PeerIdKey};
 use libp2p_core::multiaddr::Protocol;
 use std::collections::HashMap;
@@ -59,6 +60,8 @@ const HASH_CASH_TIMEOUT: Duration = Duration::from_secs(5 * 60);
 const HASH_CASH_PROOF_TTL: Duration = Duration::from_secs(365 * 24 * 60 * 60);
 // How long to wait for next event
 const HANDSHAKE_STEP_TIMEOUT: Duration = Duration::from_secs(30);
+// Label for traffic accounting
+const TRAFFIC_LABEL: &str = "gatekeeper";
 
 /// Network behavior to handle initial nodes handshake
 pub struct Gatekeeper {
@@ -129,7 +132,7 @@ impl Gatekeeper {
         }
 
         let (solution_sink, solution_stream) = unbounded::<Solution>();
-        let solver_threads = max(num_cpus::get() - 2, 1);
+        let solver_threads = solver_threads(num_cpus::get());
         debug!(target: "stegos_network::gatekeeper", "number of VDF solver threads: {}", solver_threads);
         Gatekeeper {
             events,
@@ -190,6 +193,7 @@ impl Gatekeeper {
                     event: GatekeeperSendEvent::Send(GatekeeperMessage::PermitReply {
                         connection_allowed: true,
                         reason: String::new(),
+                        back_connect: false,
                     }),
                 });
         } else {
@@ -270,6 +274,7 @@ impl Gatekeeper {
                     event: GatekeeperSendEvent::Send(GatekeeperMessage::PermitReply {
                         connection_allowed: false,
                         reason: String::from("Trying to back-connect, only public topics allowed."),
+                        back_connect: true,
                     }),
                 });
             self.events
@@ -345,6 +350,8 @@ impl Gatekeeper {
                     return;
                 }
                 None => {
+                    // Peers of the previous release don't send metadata,
+                    // only nodes with `old_protos` can talk to them.
                     let ban = !cfg!(feature = "old_protos");
                     info!(target: "stegos_network::gatekeeper", "Skiped back connect procedure, not enough info for peer found. Maybe peer version is old, peer_id={}, banning={}", peer_id, ban);
                     if ban {
@@ -355,6 +362,7 @@ impl Gatekeeper {
                                 event: GatekeeperSendEvent::Send(GatekeeperMessage::PermitReply {
                                     connection_allowed: false,
                                     reason: String::from("Old protos is not supported."),
+                                    back_connect: false,
                                 }),
                             });
                         self.events.push_back(NetworkBehaviourAction::GenerateEvent(
@@ -413,6 +421,7 @@ impl Gatekeeper {
                             "Network is different from other, expected={}, got={}.",
                             self.my_metadata.network, network
                         ),
+                        back_connect: false,
                     }),
                 });
             self.events.push_back(NetworkBehaviourAction::GenerateEvent(
@@ -470,6 +479,39 @@ impl Gatekeeper {
         // put peer into the queue to be solved.
         self.challenges_queue.push_back(peer_id);
     }
+
+    /// Starts a solver thread for the next queued challenge, if there is a free slot.
+    fn start_solvers(&mut self) {
+        if self.solvers.len() < self.solver_threads && !self.challenges_queue.is_empty() {
+            loop {
+                if self.challenges_queue.is_empty() {
+                    break;
+                }
+                let peer_id = self.challenges_queue.pop_front().unwrap();
+                if let Some(challenge) = self.solved_vdfs.get(&peer_id.clone().into()) {
+                    debug!(target: "stegos_network::gatekeeper", "starting thread to solve puzzle: peer_id={}", peer_id);
+                    let tx = self.solution_sink.clone();
+                    let p = challenge.0.clone();
+                    self.solvers.insert(peer_id.clone());
+                    thread::spawn(move || {
+                        let start = SystemTime::now();
+                        let vdf = VDF::new();
+                        info!("Solving a VDF puzzle: peer_id={:?}", peer_id);
+                        let proof = vdf.solve(&p.challenge, p.difficulty);
+                        info!("Solved a VDF puzzle: peer_id={:?}", peer_id);
+                        if let Err(e) = tx.unbounded_send((
+                            peer_id,
+                            proof,
+                            start.elapsed().expect("VDF always takes some time"),
+                        )) {
+                            debug!(target: "stegos_network::gatekeeper", "failed to send VDF proof to the channel: {}", e);
+                        }
+                    });
+                    break;
+                }
+            }
+        }
+    }
 }
 
 impl NetworkBehaviour for Gatekeeper {
@@ -597,6 +639,11 @@ impl NetworkBehaviour for Gatekeeper {
     /// failed.
     fn inject_dial_failure(&mut self, peer_id: &PeerId) {
         debug!(target: "stegos_network::gatekeeper", "failure reaching address: peer_id={}", peer_id);
+        self.events.push_back(NetworkBehaviourAction::GenerateEvent(
+            GatekeeperOutEvent::DialFailed {
+                peer_id: peer_id.clone(),
+            },
+        ));
     }
 
     fn inject_event(
@@ -607,6 +654,13 @@ impl NetworkBehaviour for Gatekeeper {
     ) {
         // Process received Gatekeeper message (passed from Handler as Custom(message))
         debug!(target: "stegos_network::gatekeeper", "Received a message: {:?}", event);
+        traffic::record(
+            TRAFFIC_LABEL,
+            Some(""),
+            Some(&propagation_source),
+            Direction::Incoming,
+            event.encoded_len(),
+        );
         match event {
             GatekeeperMessage::UnlockRequest { proof, metadata } => {
                 self.register_metadata(propagation_source.clone(), metadata);
@@ -622,6 +676,7 @@ impl NetworkBehaviour for Gatekeeper {
             GatekeeperMessage::PermitReply {
                 connection_allowed,
                 reason,
+                back_connect,
             } => {
                 if connection_allowed {
                     debug!(target: "stegos_network::gatekeeper", "succesfully negotiated VDF handshake: peer_id={}", propagation_source);
@@ -639,11 +694,23 @@ impl NetworkBehaviour for Gatekeeper {
                             GatekeeperOutEvent::NetworkReady,
                         ));
                     }
+                } else if back_connect {
+                    debug!(target: "stegos_network::gatekeeper", "peer is going to back-connect: peer_id={}", propagation_source);
+                    self.events.push_back(NetworkBehaviourAction::GenerateEvent(
+                        GatekeeperOutEvent::BackConnectStarted {
+                            peer_id: propagation_source,
+                        },
+                    ));
                 } else {
                     info!(target: "stegos_network::gatekeeper", "Received permit reply that disallow connection: peer_id={}, reason={}", propagation_source, reason);
                 }
             }
             GatekeeperMessage::PublicIpUnlock {} => {
+                self.events.push_back(NetworkBehaviourAction::GenerateEvent(
+                    GatekeeperOutEvent::BackConnected {
+                        peer_id: propagation_source.clone(),
+                    },
+                ));
                 // send last vdf again
                 let challenge = self.solved_vdfs.get(&propagation_source.clone().into());
                 let proof = match challenge {
@@ -691,35 +758,7 @@ impl NetworkBehaviour for Gatekeeper {
             Poll::Pending => {}
         }
 
-        if self.solvers.len() < self.solver_threads && self.challenges_queue.is_empty() {
-            loop {
-                if self.challenges_queue.is_empty() {
-                    break;
-                }
-                let peer_id = self.challenges_queue.pop_front().unwrap();
-                if let Some(challenge) = self.solved_vdfs.get(&peer_id.clone().into()) {
-                    debug!(target: "stegos_network::gatekeeper", "starting thread to solve puzzle: peer_id={}", peer_id);
-                    let tx = self.solution_sink.clone();
-                    let p = challenge.0.clone();
-                    self.solvers.insert(peer_id.clone());
-                    thread::spawn(move || {
-                        let start = SystemTime::now();
-                        let vdf = VDF::new();
-                        info!("Solving a VDF puzzle: peer_id={:?}", peer_id);
-                        let proof = vdf.solve(&p.challenge, p.difficulty);
-                        info!("Solved a VDF puzzle: peer_id={:?}", peer_id);
-                        if let Err(e) = tx.unbounded_send((
-                            peer_id,
-                            proof,
-                            start.elapsed().expect("VDF always takes some time"),
-                        )) {
-                            debug!(target: "stegos_network::gatekeeper", "failed to send VDF proof to the channel: {}", e);
-                        }
-                    });
-                    break;
-                }
-            }
-        }
+        self.start_solvers();
 
         if let Some(event) = self.protocol_updates.pop_front() {
             match event {
@@ -799,6 +838,20 @@ impl NetworkBehaviour for Gatekeeper {
         }
 
         if let Some(event) = self.events.pop_front() {
+            if let NetworkBehaviourAction::NotifyHandler {
+                peer_id,
+                event: GatekeeperSendEvent::Send(message),
+                ..
+            } = &event
+            {
+                traffic::record(
+                    TRAFFIC_LABEL,
+                    Some(""),
+                    Some(peer_id),
+                    Direction::Outgoing,
+                    message.encoded_len(),
+                );
+            }
             return Poll::Ready(event);
         }
 
@@ -828,11 +881,19 @@ pub enum GatekeeperOutEvent {
     PrepareListener { peer_id: PeerId },
     UnlockedDialer { peer_id: PeerId },
     BanPeer { peer_id: PeerId },
+    DialFailed { peer_id: PeerId },
+    BackConnectStarted { peer_id: PeerId },
+    BackConnected { peer_id: PeerId },
     NetworkReady,
 }
 
 type Solution = (PeerId, Vec<u8>, Duration);
 
+/// Number of VDF solver threads, leaves two CPUs for the node itself.
+fn solver_threads(cpus: usize) -> usize {
+    max(cpus.saturating_sub(2), 1)
+}
+
 #[derive(Clone)]
 struct VDFChallenge {
     challenge: Vec<u8>,
@@ -875,3 +936,46 @@ impl StegosDisplay for ConnectedPoint {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+
+    #[test]
+    fn solver_threads_on_small_hosts() {
+        assert_eq!(solver_threads(1), 1);
+        assert_eq!(solver_threads(2), 1);
+        assert_eq!(solver_threads(3), 1);
+        assert_eq!(solver_threads(8), 6);
+    }
+
+    #[test]
+    fn queued_challenge_starts_solver() {
+        let metadata = Metadata {
+            network: "test".to_string(),
+            version: 1,
+            port: 0,
+        };
+        let mut gatekeeper = Gatekeeper::new(&NetworkConfig::default(), metadata);
+        let peer_id = PeerId::random();
+        let challenge = VDFChallenge {
+            challenge: generate_challenge(&peer_id),
+            difficulty: 10,
+        };
+        gatekeeper
+            .solved_vdfs
+            .insert(peer_id.clone().into(), (challenge.clone(), None));
+        gatekeeper.challenges_queue.push_back(peer_id.clone());
+
+        gatekeeper.start_solvers();
+        assert!(gatekeeper.challenges_queue.is_empty());
+        assert!(gatekeeper.solvers.contains(&peer_id));
+
+        let (solved, proof, _) = block_on(gatekeeper.solution_stream.next()).unwrap();
+        assert_eq!(solved, peer_id);
+        VDF::new()
+            .verify(&challenge.challenge, challenge.difficulty, &proof)
+            .unwrap();
+    }
+}
@@ -152,12 +152,9 @@ pub struct GatekeeperCodec {
     length_prefix: codec::UviBytes,
 }
 
-impl Encoder for GatekeeperCodec {
-    type Item = GatekeeperMessage;
-    type Error = io::Error;
-
-    fn encode(&mut self, item: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
-        let proto = match item {
+impl GatekeeperMessage {
+    fn into_proto(self) -> gatekeeper_proto::Message {
+        match self {
             GatekeeperMessage::UnlockRequest { proof, metadata } => {
                 let mut msg_typ = gatekeeper_proto::UnlockRequest::new();
                 if let Some(proof) = proof {
@@ -202,10 +199,12 @@ impl Encoder for GatekeeperCodec {
             GatekeeperMessage::PermitReply {
                 connection_allowed,
                 reason,
+                back_connect,
             } => {
                 let mut msg_typ = gatekeeper_proto::PermitReply::new();
                 msg_typ.set_connection_allowed(connection_allowed);
                 msg_typ.set_reason(reason);
+                msg_typ.set_back_connect(back_connect);
                 let mut proto_msg = gatekeeper_proto::Message::new();
                 proto_msg.set_permit_reply(msg_typ);
                 proto_msg
@@ -216,7 +215,21 @@ impl Encoder for GatekeeperCodec {
                 proto_msg.set_public_ip_unlock(msg_typ);
                 proto_msg
             }
-        };
+        }
+    }
+
+    /// Size of the encoded message.
+    pub fn encoded_len(&self) -> usize {
+        self.clone().into_proto().compute_size() as usize
+    }
+}
+
+impl Encoder for GatekeeperCodec {
+    type Item = GatekeeperMessage;
+    type Error = io::Error;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let proto = item.into_proto();
 
         let msg_size = proto.compute_size();
         // Reserve enough space for the data and the length. The length has a maximum of 32 bits,
@@ -303,6 +316,7 @@ impl Decoder for GatekeeperCodec {
                 Ok(Some(GatekeeperMessage::PermitReply {
                     connection_allowed: reply_msg.get_connection_allowed(),
                     reason: reply_msg.get_reason().to_string(),
+                    back_connect: reply_msg.get_back_connect(),
                 }))
             }
 
@@ -349,6 +363,8 @@ pub enum GatekeeperMessage {
     PermitReply {
         connection_allowed: bool,
         reason: String,
+        /// The server is going to connect back to the client.
+        back_connect: bool,
     }, // Response from server
     PublicIpUnlock {}, // Repeat UnlockRequest but from listener side
 }
@@ -407,6 +423,7 @@ mod tests {
         let permit_reply = GatekeeperMessage::PermitReply {
             connection_allowed: false,
             reason: String::from("test"),
+            back_connect: true,
         };
         test_one(permit_reply, "127.0.0.1:13643".parse().unwrap());
     }
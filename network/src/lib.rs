@@ -28,11 +28,15 @@ use futures::channel::{mpsc, oneshot};
 use std::fmt;
 use stegos_crypto::pbc;
 
+pub mod delivery;
+mod dht;
 pub mod gatekeeper;
 mod libp2p_network;
 pub mod metrics;
+#[cfg(feature = "old_protos")]
 mod old_protos;
 mod proto;
+#[cfg(feature = "old_protos")]
 pub use old_protos::*;
 
 pub use self::replication::{ReplicationEvent, ReplicationVersion};
@@ -44,10 +48,20 @@ pub use self::libp2p_network::Libp2pNetwork;
 pub use self::libp2p_network::Multiaddr;
 pub use self::libp2p_network::PeerId;
 pub use self::libp2p_network::NETWORK_STATUS_TOPIC;
+mod loopback;
+pub use self::loopback::{LoopbackConfig, LoopbackHub, LoopbackNetwork};
 
+mod bandwidth;
 mod config;
 pub use self::config::*;
-pub use self::old_protos::ncp::NodeInfo;
+mod nat;
+pub use self::nat::{NatStatus, Reachability};
+mod peer_store;
+mod reputation;
+mod traffic;
+pub use self::dht::NodeInfo;
+pub use self::reputation::{Misbehavior, PeerScoreInfo};
+pub use self::traffic::{PeerTraffic, TopicTraffic, TrafficCounters};
 use std::time::Duration;
 
 pub const NETWORK_IDLE_TIMEOUT: Duration = Duration::from_secs(15);
@@ -61,6 +75,22 @@ where
     /// Subscribe to topic, returns Stream<Vec<u8>> of messages incoming to topic
     fn subscribe(&self, topic: &str) -> Result<mpsc::UnboundedReceiver<Vec<u8>>, Error>;
 
+    /// Subscribe to topic, received messages are forwarded to other peers only after
+    /// validation by the consumer, see `validate_message()`.
+    fn subscribe_validated(&self, topic: &str) -> Result<mpsc::UnboundedReceiver<Vec<u8>>, Error>;
+
+    /// Subscribe to topic, messages of the topic are never dropped by bandwidth limits,
+    /// but their size is still charged.
+    fn subscribe_priority(&self, topic: &str) -> Result<mpsc::UnboundedReceiver<Vec<u8>>, Error>;
+
+    /// Report the result of validation of a message received by `subscribe_validated()`.
+    fn validate_message(
+        &self,
+        topic: &str,
+        data: &[u8],
+        validation: MessageValidation,
+    ) -> Result<(), Error>;
+
     /// Published message to topic
     fn publish(&self, topic: &str, data: Vec<u8>) -> Result<(), Error>;
 
@@ -81,6 +111,26 @@ where
     /// Request list of connected nodes
     fn list_connected_nodes(&self) -> Result<oneshot::Receiver<NetworkResponse>, Error>;
 
+    /// Lower the score of the peer which has published the message `data` to `topic`.
+    fn report_misbehavior(
+        &self,
+        topic: &str,
+        data: &[u8],
+        misbehavior: Misbehavior,
+    ) -> Result<(), Error>;
+
+    /// Request scores and bans of peers.
+    fn peer_scores(&self) -> Result<oneshot::Receiver<NetworkResponse>, Error>;
+
+    /// Forget scores and lift bans of one or all peers.
+    fn clear_peer_scores(&self, peer_id: Option<PeerId>) -> Result<(), Error>;
+
+    /// Request traffic counters per topic and per peer.
+    fn traffic_stats(&self) -> Result<oneshot::Receiver<NetworkResponse>, Error>;
+
+    /// Returns the last reachability status of the node.
+    fn nat_status(&self) -> NatStatus;
+
     /// Helper for cloning boxed object
     fn box_clone(&self) -> Network;
 
@@ -98,9 +148,29 @@ pub struct UnicastMessage {
     pub data: Vec<u8>,
 }
 
+/// Result of validation of a received broadcast message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageValidation {
+    /// The message is valid and should be forwarded.
+    Accept,
+    /// The message can't be validated now, don't forward it.
+    Ignore,
+    /// The message is invalid, don't forward it and penalize the sender.
+    Reject(Misbehavior),
+}
+
 #[derive(Debug, Clone)]
 pub enum NetworkResponse {
-    ConnectedNodes { nodes: Vec<NodeInfo> },
+    ConnectedNodes {
+        nodes: Vec<NodeInfo>,
+    },
+    PeerScores {
+        peers: Vec<PeerScoreInfo>,
+    },
+    TrafficStats {
+        topics: Vec<TopicTraffic>,
+        peers: Vec<PeerTraffic>,
+    },
 }
 
 impl Clone for Network {
@@ -30,13 +30,17 @@ use futures::future;
 use futures::stream::StreamExt;
 
 pub use libp2p::gossipsub::Topic;
-use libp2p::gossipsub::{self, Gossipsub, GossipsubMessage, MessageId};
+use libp2p::gossipsub::{self, Gossipsub, GossipsubMessage, MessageAcceptance, MessageId};
 use libp2p::gossipsub::{GossipsubEvent, TopicHash};
+use libp2p_core::either::EitherOutput;
 pub use libp2p_core::multiaddr::Multiaddr;
+use libp2p_core::transport::boxed::Boxed;
+use libp2p_core::upgrade::{InboundUpgradeExt, OptionalUpgrade, OutboundUpgradeExt, SelectUpgrade};
 pub use libp2p_core::PeerId;
 use libp2p_core::{identity, transport::TransportError, Transport};
 use libp2p_core_derive::NetworkBehaviour;
 use libp2p_dns as dns;
+use libp2p_noise as noise;
 use libp2p_secio as secio;
 use libp2p_swarm::{NetworkBehaviourEventProcess, Swarm, SwarmBuilder};
 use libp2p_tcp as tcp;
@@ -51,33 +55,53 @@ use std::future::Future;
 use std::task::{Context, Poll};
 
 // use protobuf::Message as ProtoMessage;
-use crate::config::NetworkConfig;
+use crate::config::{NetworkConfig, TransportSecurity};
 use std::error;
 use std::io;
 
 // use std::time::Duration;
 use stegos_crypto::utils::u8v_to_hexstr;
 pub mod proto;
+mod socks5;
+use socks5::Socks5Transport;
 
+use crate::bandwidth::{Bandwidth, SharedBandwidth};
+use crate::delivery::{Delivery, DeliveryEvent, DeliveryMessage, Unicast};
+use crate::dht::{Dht, DhtOutEvent};
 use crate::gatekeeper::{Gatekeeper, GatekeeperOutEvent};
-use crate::old_protos::delivery::{Delivery, DeliveryEvent, DeliveryMessage};
+#[cfg(feature = "old_protos")]
 use crate::old_protos::discovery::{Discovery, DiscoveryOutEvent};
+#[cfg(feature = "old_protos")]
 use crate::old_protos::ncp::{Ncp, NcpOutEvent};
+#[cfg(feature = "old_protos")]
 use crate::old_protos::pubsub::{Floodsub, FloodsubEvent};
 
 use crate::gatekeeper::{Metadata, NetworkName};
+use crate::nat::{self, Nat, SharedNatStatus};
+use crate::peer_store::PeerStore;
 use crate::replication::{Replication, ReplicationEvent};
-use crate::{Network, NetworkProvider, NetworkResponse, UnicastMessage};
+use crate::reputation::Reputation;
+use crate::traffic::{self, Direction};
+use crate::utils::ExpiringQueue;
+use crate::{
+    MessageValidation, Misbehavior, NatStatus, Network, NetworkProvider, NetworkResponse,
+    UnicastMessage,
+};
 use libp2p_swarm::PollParameters;
 use libp2p_swarm::{
     IntoProtocolsHandler, NetworkBehaviour, NetworkBehaviourAction, ProtocolsHandler,
 };
+use lru_time_cache::LruCache;
 use std::collections::VecDeque;
+use std::path::PathBuf;
 use std::str::FromStr;
+use std::time::Instant;
+use tokio::time::Interval;
 
 #[derive(Clone, Debug)]
 pub struct Libp2pNetwork {
     control_tx: mpsc::UnboundedSender<ControlMessage>,
+    nat_status: SharedNatStatus,
 }
 
 pub const NETWORK_STATUS_TOPIC: &str = "stegos-network-status";
@@ -86,11 +110,23 @@ pub const GOSSIP_VERSION: u64 = 1;
 // Max number of topic for one floodsub message.
 
 pub const NETWORK_READY_TOKEN: &[u8] = &[1, 0, 0, 0];
-use crate::utils::{encode_unicast, UnicastPayload};
+/// How long to remember which peer has published a gossip message.
+const MESSAGE_SOURCE_TTL: Duration = Duration::from_secs(300);
+/// Maximal number of remembered gossip message sources.
+const MESSAGE_SOURCE_CAPACITY: usize = 16384;
+/// How often to forget peers with recovered scores.
+const REPUTATION_PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+/// How often to save known peers to disk.
+const PEER_STORE_SAVE_INTERVAL: Duration = Duration::from_secs(60);
+/// Traffic accounting labels.
+const GOSSIPSUB_PROTOCOL: &str = "gossipsub";
+const DELIVERY_PROTOCOL: &str = "delivery";
+use crate::utils::{encode_unicast, message_key, UnicastPayload};
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum BehaviourEvent {
     BanPeer { peer_id: PeerId },
+    UnbanPeer { peer_id: PeerId },
 }
 
 impl Libp2pNetwork {
@@ -99,6 +135,7 @@ impl Libp2pNetwork {
         network_name: NetworkName,
         network_skey: pbc::SecretKey,
         network_pkey: pbc::PublicKey,
+        peers_file: Option<PathBuf>,
     ) -> Result<
         (
             Network,
@@ -109,13 +146,26 @@ impl Libp2pNetwork {
         Error,
     > {
         // Resolve network.seed_pool.
+        if config.socks5_proxy != "" && config.seed_pool != "" {
+            warn!(
+                "network.seed_pool is resolved without SOCKS5 proxy, use network.seed_nodes instead"
+            );
+        }
         config.seed_nodes.extend_from_slice(
             &utils::resolve_seed_nodes(&config.seed_pool, &config.dns_servers).await?,
         );
 
-        let (service, control_tx, peer_id, replication_rx) =
-            new_service(&config, network_name, network_skey, network_pkey)?;
-        let network = Libp2pNetwork { control_tx };
+        let (service, control_tx, nat_status, peer_id, replication_rx) = new_service(
+            &config,
+            network_name,
+            network_skey,
+            network_pkey,
+            peers_file,
+        )?;
+        let network = Libp2pNetwork {
+            control_tx,
+            nat_status,
+        };
         Ok((Box::new(network), service, peer_id, replication_rx))
     }
 }
@@ -125,11 +175,57 @@ impl NetworkProvider for Libp2pNetwork {
     fn subscribe(&self, topic: &str) -> Result<mpsc::UnboundedReceiver<Vec<u8>>, Error> {
         let topic = topic.to_owned();
         let (tx, rx) = mpsc::unbounded();
-        let msg = ControlMessage::Subscribe { topic, handler: tx };
+        let msg = ControlMessage::Subscribe {
+            topic,
+            handler: tx,
+            validated: false,
+            priority: false,
+        };
+        self.control_tx.unbounded_send(msg)?;
+        Ok(rx)
+    }
+
+    fn subscribe_validated(&self, topic: &str) -> Result<mpsc::UnboundedReceiver<Vec<u8>>, Error> {
+        let topic = topic.to_owned();
+        let (tx, rx) = mpsc::unbounded();
+        let msg = ControlMessage::Subscribe {
+            topic,
+            handler: tx,
+            validated: true,
+            priority: false,
+        };
+        self.control_tx.unbounded_send(msg)?;
+        Ok(rx)
+    }
+
+    fn subscribe_priority(&self, topic: &str) -> Result<mpsc::UnboundedReceiver<Vec<u8>>, Error> {
+        let topic = topic.to_owned();
+        let (tx, rx) = mpsc::unbounded();
+        let msg = ControlMessage::Subscribe {
+            topic,
+            handler: tx,
+            validated: false,
+            priority: true,
+        };
         self.control_tx.unbounded_send(msg)?;
         Ok(rx)
     }
 
+    fn validate_message(
+        &self,
+        topic: &str,
+        data: &[u8],
+        validation: MessageValidation,
+    ) -> Result<(), Error> {
+        let msg = ControlMessage::ValidateMessage {
+            topic: topic.to_string(),
+            message_key: message_key(data),
+            validation,
+        };
+        self.control_tx.unbounded_send(msg)?;
+        Ok(())
+    }
+
     /// Published message to topic
     fn publish(&self, topic: &str, data: Vec<u8>) -> Result<(), Error> {
         let topic = topic.to_owned();
@@ -184,6 +280,45 @@ impl NetworkProvider for Libp2pNetwork {
         Ok(rx)
     }
 
+    fn report_misbehavior(
+        &self,
+        topic: &str,
+        data: &[u8],
+        misbehavior: Misbehavior,
+    ) -> Result<(), Error> {
+        let msg = ControlMessage::ReportMisbehavior {
+            topic: topic.to_string(),
+            message_key: message_key(data),
+            misbehavior,
+        };
+        self.control_tx.unbounded_send(msg)?;
+        Ok(())
+    }
+
+    fn peer_scores(&self) -> Result<oneshot::Receiver<NetworkResponse>, Error> {
+        let (tx, rx) = oneshot::channel::<NetworkResponse>();
+        self.control_tx
+            .unbounded_send(ControlMessage::PeerScoresRequest { tx })?;
+        Ok(rx)
+    }
+
+    fn clear_peer_scores(&self, peer_id: Option<PeerId>) -> Result<(), Error> {
+        let msg = ControlMessage::ClearPeerScores { peer_id };
+        self.control_tx.unbounded_send(msg)?;
+        Ok(())
+    }
+
+    fn traffic_stats(&self) -> Result<oneshot::Receiver<NetworkResponse>, Error> {
+        let (tx, rx) = oneshot::channel::<NetworkResponse>();
+        self.control_tx
+            .unbounded_send(ControlMessage::TrafficStatsRequest { tx })?;
+        Ok(rx)
+    }
+
+    fn nat_status(&self) -> NatStatus {
+        self.nat_status.lock().unwrap().clone()
+    }
+
     // Clone self as a box
     fn box_clone(&self) -> Network {
         Box::new((*self).clone())
@@ -206,10 +341,12 @@ fn new_service(
     network_name: NetworkName,
     network_skey: pbc::SecretKey,
     network_pkey: pbc::PublicKey,
+    peers_file: Option<PathBuf>,
 ) -> Result<
     (
         impl Future<Output = ()>,
         mpsc::UnboundedSender<ControlMessage>,
+        SharedNatStatus,
         PeerId,
         mpsc::UnboundedReceiver<ReplicationEvent>,
     ),
@@ -218,28 +355,29 @@ fn new_service(
     let keypair = utils::ed25519_from_pbc(&network_skey);
     let local_key = identity::Keypair::Ed25519(keypair);
     let local_pub_key = local_key.public();
-    let peer_id = local_pub_key.into_peer_id();
+    let peer_id = local_pub_key.clone().into_peer_id();
 
     let (replication_tx, replication_rx) = mpsc::unbounded();
 
-    // Set up a an encrypted DNS-enabled TCP Transport over the Mplex and Yamux protocols
-    // let transpor t = build_tcp_ws_secio_yamux(local_key);
-    let transport = build_tcp_ws_secio_yamux(local_key);
+    // Set up a an encrypted DNS-enabled TCP Transport over the Mplex protocol
+    let transport = build_tcp_ws_secure_mplex(local_key, config);
     // Create a Swarm to manage peers and events
     let behaviour = Libp2pBehaviour::new(
         config,
         network_name,
         network_skey,
         network_pkey,
-        peer_id.clone(),
+        local_pub_key,
         replication_tx,
+        peers_file,
     );
+    let nat_status = behaviour.nat.shared_status();
 
     let mut swarm = SwarmBuilder::new(transport, behaviour, peer_id.clone())
         .peer_connection_limit(2)
         .build();
 
-    if config.endpoint != "" {
+    if config.listen && config.endpoint != "" {
         let endpoint = SocketAddr::from_str(&config.endpoint).expect("Invalid endpoint");
         let endpoint = utils::socket_to_multi_addr(&endpoint);
         info!("Listening addr {}", endpoint);
@@ -270,6 +408,10 @@ fn new_service(
                         info!(target: "stegos_network", "Ban peer: peer_id={}", peer_id);
                         Swarm::ban_peer_id(&mut swarm, peer_id)
                     }
+                    BehaviourEvent::UnbanPeer { peer_id } => {
+                        info!(target: "stegos_network", "Unban peer: peer_id={}", peer_id);
+                        Swarm::unban_peer_id(&mut swarm, peer_id)
+                    }
                 },
                 Poll::Ready(None) => return Poll::Ready(()),
                 Poll::Pending => {
@@ -287,35 +429,37 @@ fn new_service(
         Poll::Pending
     });
 
-    Ok((service, control_tx, peer_id, replication_rx))
+    Ok((service, control_tx, nat_status, peer_id, replication_rx))
 }
 
 #[derive(NetworkBehaviour)]
 #[behaviour(poll_method = "poll")]
 #[behaviour(out_event = "BehaviourEvent")]
 pub struct Libp2pBehaviour {
-    // gossipsub: Gossipsub,
     gatekeeper: Gatekeeper, // handshake
     replication: Replication,
     gossipsub: Gossipsub,
+    dht: Dht, // Peer discovery, resolution of network keys
+    delivery: Delivery,
 
     // OLD PROTOS BEGIN
+    #[cfg(feature = "old_protos")]
     floodsub: Floodsub,
-
+    #[cfg(feature = "old_protos")]
     ncp: Ncp, // Peer sharing, ping (should be merged with discovery)
+    #[cfg(feature = "old_protos")]
     discovery: Discovery,
-    delivery: Delivery,
-
+    #[cfg(feature = "old_protos")]
     #[behaviour(ignore)]
-    unicast_consumers: HashMap<String, SmallVec<[mpsc::UnboundedSender<UnicastMessage>; 3]>>,
+    floodsub_consumers: HashMap<String, SmallVec<[mpsc::UnboundedSender<Vec<u8>>; 3]>>,
     // OLD PROTOS END
     #[behaviour(ignore)]
+    unicast_consumers: HashMap<String, SmallVec<[mpsc::UnboundedSender<UnicastMessage>; 3]>>,
+    #[behaviour(ignore)]
     replication_tx: mpsc::UnboundedSender<ReplicationEvent>,
     #[behaviour(ignore)]
     gossip_consumers: HashMap<TopicHash, SmallVec<[mpsc::UnboundedSender<Vec<u8>>; 3]>>,
 
-    #[behaviour(ignore)]
-    floodsub_consumers: HashMap<String, SmallVec<[mpsc::UnboundedSender<Vec<u8>>; 3]>>,
     #[behaviour(ignore)]
     my_pkey: pbc::PublicKey,
     #[behaviour(ignore)]
@@ -328,6 +472,37 @@ pub struct Libp2pBehaviour {
 
     #[behaviour(ignore)]
     banned_peers: HashSet<PeerId>,
+
+    /// Scores of peers.
+    #[behaviour(ignore)]
+    reputation: Reputation,
+    /// Peers banned by reputation, expire when the ban is over.
+    #[behaviour(ignore)]
+    reputation_bans: ExpiringQueue<PeerId, ()>,
+    /// Peers which have published recent gossip messages, by (topic, message_key).
+    #[behaviour(ignore)]
+    message_sources: LruCache<(String, u64), PeerId>,
+    #[behaviour(ignore)]
+    reputation_timer: Interval,
+    /// Results of validation of messages, which haven't been received by gossipsub yet.
+    #[behaviour(ignore)]
+    validation_results: LruCache<(String, u64), MessageValidation>,
+
+    /// Known peers, persisted between restarts.
+    #[behaviour(ignore)]
+    peer_store: PeerStore,
+    #[behaviour(ignore)]
+    peer_store_timer: Interval,
+
+    /// Bandwidth limits, shared with gossipsub, delivery, replication and floodsub.
+    #[behaviour(ignore)]
+    bandwidth: SharedBandwidth,
+
+    /// Observed addresses and reachability.
+    #[behaviour(ignore)]
+    nat: Nat,
+    #[behaviour(ignore)]
+    nat_timer: Interval,
 }
 
 impl Libp2pBehaviour {
@@ -336,11 +511,12 @@ impl Libp2pBehaviour {
         network_name: NetworkName,
         network_skey: pbc::SecretKey,
         network_pkey: pbc::PublicKey,
-        peer_id: PeerId,
+        local_key: identity::PublicKey,
         replication_tx: mpsc::UnboundedSender<ReplicationEvent>,
+        peers_file: Option<PathBuf>,
     ) -> Self {
-        let relaying = config.advertised_endpoint != "";
-        let port = if config.endpoint != "" {
+        let peer_id = local_key.clone().into_peer_id();
+        let port = if config.listen && config.endpoint != "" {
             let endpoint = SocketAddrV4::from_str(&config.endpoint).expect("Invalid endpoint");
             endpoint.port()
         } else {
@@ -354,13 +530,8 @@ impl Libp2pBehaviour {
         debug!("Network metadata = {:?}", metadata);
 
         // To content-address message, we can take the hash of message and use it as an ID.
-        let message_id_fn = |message: &GossipsubMessage| {
-            use std::collections::hash_map::DefaultHasher;
-            use std::hash::{Hash, Hasher};
-            let mut s = DefaultHasher::new();
-            message.data.hash(&mut s);
-            MessageId(s.finish().to_string())
-        };
+        let message_id_fn =
+            |message: &GossipsubMessage| MessageId(message_key(&message.data).to_string());
 
         // set custom gossipsub
         let gossipsub_config = gossipsub::GossipsubConfigBuilder::new()
@@ -371,25 +542,73 @@ impl Libp2pBehaviour {
             //same content will be propagated.
             .build();
 
-        let behaviour = Libp2pBehaviour {
-            gossipsub: Gossipsub::new(peer_id.clone(), gossipsub_config),
+        let bandwidth = Bandwidth::shared(config);
+        let mut gossipsub = Gossipsub::new(peer_id.clone(), gossipsub_config);
+        gossipsub.set_send_filter({
+            let bandwidth = bandwidth.clone();
+            let local_peer_id = peer_id.clone();
+            Box::new(move |peer_id: &PeerId, message: &GossipsubMessage| {
+                let mut bandwidth = bandwidth.lock().unwrap();
+                if message.source == local_peer_id {
+                    // Own messages are never dropped.
+                    bandwidth.charge(Some(peer_id), Direction::Outgoing, message.data.len());
+                    return true;
+                }
+                let topic = message.topics.first().map_or("", |t| t.as_str());
+                bandwidth.admit(
+                    Some(peer_id),
+                    Direction::Outgoing,
+                    topic,
+                    message.data.len(),
+                )
+            })
+        });
+
+        let mut behaviour = Libp2pBehaviour {
+            gossipsub,
             gossip_consumers: HashMap::new(),
             my_pkey: network_pkey,
-            my_skey: network_skey,
+            my_skey: network_skey.clone(),
             connected_peers: HashSet::new(),
 
+            #[cfg(feature = "old_protos")]
             ncp: Ncp::new(config, network_pkey),
-            floodsub: Floodsub::new(relaying),
+            #[cfg(feature = "old_protos")]
+            floodsub: Floodsub::new(config.advertised_endpoint != "", bandwidth.clone()),
+            #[cfg(feature = "old_protos")]
             floodsub_consumers: HashMap::new(),
-            gatekeeper: Gatekeeper::new(config, metadata),
-            delivery: Delivery::new(),
+            #[cfg(feature = "old_protos")]
             discovery: Discovery::new(network_pkey),
-            replication: Replication::new(),
+            gatekeeper: Gatekeeper::new(config, metadata),
+            dht: Dht::new(config, network_skey, network_pkey, local_key),
+            delivery: Delivery::new(bandwidth.clone()),
+            replication: Replication::new(bandwidth.clone()),
             replication_tx,
             unicast_consumers: HashMap::new(),
             events: VecDeque::new(),
             banned_peers: HashSet::new(),
+            reputation: Reputation::new(
+                config.peer_ban_threshold,
+                Duration::from_secs(config.peer_ban_duration),
+                config.gossip_rate_limit,
+            ),
+            reputation_bans: ExpiringQueue::new(Duration::from_secs(config.peer_ban_duration)),
+            message_sources: LruCache::with_expiry_duration_and_capacity(
+                MESSAGE_SOURCE_TTL,
+                MESSAGE_SOURCE_CAPACITY,
+            ),
+            validation_results: LruCache::with_expiry_duration_and_capacity(
+                MESSAGE_SOURCE_TTL,
+                MESSAGE_SOURCE_CAPACITY,
+            ),
+            peer_store: PeerStore::open(peers_file),
+            reputation_timer: tokio::time::interval(REPUTATION_PRUNE_INTERVAL),
+            peer_store_timer: tokio::time::interval(PEER_STORE_SAVE_INTERVAL),
+            bandwidth,
+            nat: Nat::new(config),
+            nat_timer: tokio::time::interval(nat::PROBE_TIMEOUT),
         };
+        behaviour.dial_known_peers(config.max_connections);
         debug!(target: "stegos_network::delivery", "Network endpoints: node_id={}, peer_id={}", network_pkey, peer_id);
         behaviour
     }
@@ -397,35 +616,55 @@ impl Libp2pBehaviour {
     fn process_event(&mut self, msg: ControlMessage) {
         trace!("Control event: {:#?}", msg);
         match msg {
-            ControlMessage::Subscribe { topic, handler } => {
+            ControlMessage::Subscribe {
+                topic,
+                handler,
+                validated,
+                priority,
+            } => {
                 if topic != NETWORK_STATUS_TOPIC {
                     debug!(target: "stegos_network::pubsub",
                         "Subscribing: topic={}",
                         topic,
                     );
+                    traffic::register_topic(&topic);
+                    if priority {
+                        self.bandwidth.lock().unwrap().add_priority_topic(&topic);
+                    }
                     let gossipsub_topic = Topic::new(topic.clone());
                     self.gossip_consumers
                         .entry(gossipsub_topic.no_hash())
                         .or_insert_with(SmallVec::new)
                         .push(handler.clone());
-                    self.floodsub_consumers
-                        .entry(topic.clone())
-                        .or_insert_with(SmallVec::new)
-                        .push(handler);
+                    #[cfg(feature = "old_protos")]
+                    {
+                        self.floodsub_consumers
+                            .entry(topic.clone())
+                            .or_insert_with(SmallVec::new)
+                            .push(handler);
+                        if validated {
+                            self.floodsub.enable_validation(&topic);
+                        }
+                        self.floodsub.subscribe(topic);
+                    }
+                    if validated {
+                        self.gossipsub.enable_validation(&gossipsub_topic);
+                    }
                     self.gossipsub.subscribe(gossipsub_topic);
-                    self.floodsub.subscribe(topic);
                     return;
                 }
             }
             ControlMessage::Publish { topic, data } => {
+                traffic::register_topic(&topic);
                 debug!(target: "stegos_network::pubsub",
                     "Sending broadcast message: topic={}, size={}",
                     topic,
                     data.len(),
                 );
                 let gossipsub_topic = Topic::new(topic.clone());
-                self.gossipsub.publish(&gossipsub_topic, data.clone());
-                self.floodsub.publish(topic, data);
+                #[cfg(feature = "old_protos")]
+                self.floodsub.publish(topic, data.clone());
+                self.gossipsub.publish(&gossipsub_topic, data);
             }
             ControlMessage::ChangeNetworkKeys { new_pkey, new_skey } => {
                 debug!(target: "stegos_network::libp2p_network","changing network key: from={}, to={}", self.my_pkey, new_pkey);
@@ -442,6 +681,7 @@ impl Libp2pBehaviour {
                 protocol_id,
                 consumer,
             } => {
+                traffic::register_topic(&protocol_id);
                 self.unicast_consumers
                     .entry(protocol_id)
                     .or_insert_with(SmallVec::new)
@@ -452,6 +692,7 @@ impl Libp2pBehaviour {
                 protocol_id,
                 data,
             } => {
+                traffic::register_topic(&protocol_id);
                 debug!(target: "stegos_network::delivery",
                     "Sending unicast message: to={}, from={}, protocol={}, size={}",
                     to,
@@ -476,6 +717,13 @@ impl Libp2pBehaviour {
                             }
                         })
                 } else {
+                    traffic::record(
+                        DELIVERY_PROTOCOL,
+                        Some(&protocol_id),
+                        None,
+                        Direction::Outgoing,
+                        data.len(),
+                    );
                     let payload = UnicastPayload {
                         from: self.my_pkey,
                         to,
@@ -483,7 +731,7 @@ impl Libp2pBehaviour {
                         data,
                     };
                     let msg = encode_unicast(payload, &self.my_skey);
-                    self.discovery.deliver_unicast(&to, msg);
+                    self.dht.deliver_unicast(&to, msg);
                 }
             }
             ControlMessage::EnableReplicationUpstream { peer_id } => {
@@ -493,19 +741,205 @@ impl Libp2pBehaviour {
                 self.replication.disconnect(peer_id);
             }
             ControlMessage::ConnectedNodesRequest { tx } => {
-                let nodes = self.ncp.get_connected_nodes();
+                let nodes = self.dht.connected_nodes();
                 if let Err(_v) = tx.send(NetworkResponse::ConnectedNodes { nodes }) {
                     warn!(target: "stegos_network", "Failed send API response for connected nodes");
                 }
             }
+            ControlMessage::ReportMisbehavior {
+                topic,
+                message_key,
+                misbehavior,
+            } => self.report_source(topic, message_key, misbehavior),
+            ControlMessage::ValidateMessage {
+                topic,
+                message_key,
+                validation,
+            } => {
+                #[cfg(feature = "old_protos")]
+                self.floodsub.report_message_validation_result(
+                    &topic,
+                    message_key,
+                    validation == MessageValidation::Accept,
+                );
+                if !self.apply_validation(&topic, message_key, validation) {
+                    // The message hasn't been received by gossipsub yet (e.g. came by floodsub).
+                    self.validation_results
+                        .insert((topic, message_key), validation);
+                }
+            }
+            ControlMessage::PeerScoresRequest { tx } => {
+                let peers = self.reputation.scores(Instant::now());
+                if let Err(_v) = tx.send(NetworkResponse::PeerScores { peers }) {
+                    warn!(target: "stegos_network", "Failed send API response for peer scores");
+                }
+            }
+            ControlMessage::ClearPeerScores { peer_id } => {
+                let unbanned = self.reputation.clear(peer_id.as_ref(), Instant::now());
+                for peer_id in unbanned {
+                    self.unban_peer(peer_id);
+                }
+            }
+            ControlMessage::TrafficStatsRequest { tx } => {
+                let (topics, peers) = traffic::stats();
+                if let Err(_v) = tx.send(NetworkResponse::TrafficStats { topics, peers }) {
+                    warn!(target: "stegos_network", "Failed send API response for traffic stats");
+                }
+            }
+            #[cfg(test)]
+            ControlMessage::DisableNodeRecords => self.dht.disable_node_records(),
+        }
+    }
+
+    /// Pass the result of validation to gossipsub, penalize the sender of rejected message.
+    /// Returns false if the message is not waiting for validation.
+    fn apply_validation(
+        &mut self,
+        topic: &str,
+        message_key: u64,
+        validation: MessageValidation,
+    ) -> bool {
+        let message_id = MessageId(message_key.to_string());
+        let acceptance = match validation {
+            MessageValidation::Accept => MessageAcceptance::Accept,
+            MessageValidation::Ignore => MessageAcceptance::Ignore,
+            MessageValidation::Reject(_) => MessageAcceptance::Reject,
+        };
+        match self
+            .gossipsub
+            .report_message_validation_result(&message_id, acceptance)
+        {
+            Some(_propagation_source) => {
+                if let MessageValidation::Reject(misbehavior) = validation {
+                    self.report_source(topic.to_string(), message_key, misbehavior);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Lower the score of the peer which has published an invalid gossip message.
+    fn report_source(&mut self, topic: String, message_key: u64, misbehavior: Misbehavior) {
+        let source = self
+            .message_sources
+            .get(&(topic.clone(), message_key))
+            .cloned();
+        match source {
+            Some(peer_id) => self.report_peer(peer_id, misbehavior),
+            None => {
+                debug!(target: "stegos_network::reputation",
+                    "Unknown source of message: topic={}, misbehavior={:?}",
+                    topic,
+                    misbehavior
+                );
+            }
+        }
+    }
+
+    /// Restore known peers from the previous run and connect to the best of them.
+    fn dial_known_peers(&mut self, limit: usize) {
+        let mut dial = Vec::new();
+        for (peer_id, record, addresses) in self.peer_store.best_peers(limit) {
+            #[cfg(feature = "old_protos")]
+            {
+                if let Some(node_id) = record.node_id {
+                    self.discovery.add_node(node_id, peer_id.clone());
+                    self.discovery.set_peer_id(&node_id, peer_id.clone());
+                    for address in &addresses {
+                        self.discovery
+                            .add_not_connected_address(&node_id, address.clone());
+                    }
+                }
+            }
+            #[cfg(not(feature = "old_protos"))]
+            let _ = record;
+            self.dht.add_known_peer(&peer_id, addresses.clone());
+            dial.extend(addresses.into_iter().take(1));
+        }
+        debug!(target: "stegos_network::peer_store", "Dialing known peers: count={}", dial.len());
+        for address in dial {
+            self.gatekeeper.dial_address(address);
+        }
+    }
+
+    /// Route a message through the forked Kademlia of the previous releases.
+    #[cfg(feature = "old_protos")]
+    fn route_old(&mut self, message: Unicast) {
+        let dest = message.to;
+        self.discovery.route(&dest, message);
+    }
+
+    #[cfg(not(feature = "old_protos"))]
+    fn route_old(&mut self, message: Unicast) {
+        debug!(target: "stegos_network::delivery", "no route to node, dropping message: dest={}", message.to);
+    }
+
+    /// Lower the score of peer, ban and disconnect it if the score drops below the threshold.
+    fn report_peer(&mut self, peer_id: PeerId, misbehavior: Misbehavior) {
+        debug!(target: "stegos_network::reputation",
+            "Peer misbehavior: peer_id={}, misbehavior={:?}",
+            peer_id,
+            misbehavior
+        );
+        if let Some(duration) = self
+            .reputation
+            .report(&peer_id, misbehavior, Instant::now())
+        {
+            warn!(target: "stegos_network::reputation",
+                "Banning peer: peer_id={}, duration={:?}",
+                peer_id,
+                duration
+            );
+            self.reputation_bans.insert(peer_id.clone(), ());
+            self.reputation_bans.reset(&peer_id, duration);
+            self.banned_peers.insert(peer_id.clone());
+            self.events.push_back(BehaviourEvent::BanPeer { peer_id });
         }
     }
 
+    /// Lift the ban of peer.
+    fn unban_peer(&mut self, peer_id: PeerId) {
+        self.reputation.unban(&peer_id, Instant::now());
+        self.reputation_bans.remove(&peer_id);
+        self.banned_peers.remove(&peer_id);
+        self.events.push_back(BehaviourEvent::UnbanPeer { peer_id });
+    }
+
     fn poll(&mut self,
-        _cx: &mut Context,
+        cx: &mut Context,
         _poll_parameters: &mut impl PollParameters) -> Poll<NetworkBehaviourAction<
         <<<Self as NetworkBehaviour>::ProtocolsHandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::InEvent, <Self as NetworkBehaviour>::OutEvent>,>
     {
+        while let Poll::Ready(_) = self.peer_store_timer.poll_tick(cx) {
+            if let Err(e) = self.peer_store.save() {
+                error!(target: "stegos_network::peer_store", "Failed to save known peers: {}", e);
+            }
+        }
+
+        while let Poll::Ready(_) = self.reputation_timer.poll_tick(cx) {
+            self.reputation.prune(Instant::now());
+        }
+
+        while let Poll::Ready(_) = self.nat_timer.poll_tick(cx) {}
+        if let Poll::Ready(status) = self.nat.poll(cx, Instant::now()) {
+            #[cfg(feature = "old_protos")]
+            self.ncp
+                .set_advertised_endpoint(status.advertised_address.clone());
+            self.dht.set_advertised_address(status.advertised_address);
+        }
+
+        loop {
+            match self.reputation_bans.poll(cx) {
+                Poll::Ready(Ok((peer_id, _))) => self.unban_peer(peer_id),
+                Poll::Ready(Err(e)) => {
+                    error!(target: "stegos_network::reputation", "Ban timer failure: {}", e);
+                    break;
+                }
+                Poll::Pending => break,
+            }
+        }
+
         if let Some(event) = self.events.pop_front() {
             return Poll::Ready(NetworkBehaviourAction::GenerateEvent(event));
         }
@@ -523,7 +957,7 @@ impl NetworkBehaviourEventProcess<GossipsubEvent> for Libp2pBehaviour {
     // Send received message to consumers.
     fn inject_event(&mut self, message: GossipsubEvent) {
         match message {
-            GossipsubEvent::Message(_peer_id, _message_id, message) => {
+            GossipsubEvent::Message(peer_id, message_id, message) => {
                 debug!("Receiving floodsub message {:?}", message);
                 // ignore messages with NETWORK_STATUS_TOPIC or if use send to many topics
                 if message.topics.len() > 1
@@ -540,6 +974,46 @@ impl NetworkBehaviourEventProcess<GossipsubEvent> for Libp2pBehaviour {
                        topic.as_str(),
                        message.data.len(),
                 );
+                traffic::record(
+                    GOSSIPSUB_PROTOCOL,
+                    Some(topic.as_str()),
+                    Some(&peer_id),
+                    Direction::Incoming,
+                    message.data.len(),
+                );
+                if !self.bandwidth.lock().unwrap().admit(
+                    Some(&peer_id),
+                    Direction::Incoming,
+                    topic.as_str(),
+                    message.data.len(),
+                ) {
+                    debug!(target: "stegos_network::gossip",
+                           "Download limit exceeded, dropping message: peer_id={}, topic={}",
+                           peer_id,
+                           topic.as_str(),
+                    );
+                    self.gossipsub
+                        .report_message_validation_result(&message_id, MessageAcceptance::Ignore);
+                    return;
+                }
+                if self.banned_peers.contains(&peer_id) {
+                    self.gossipsub
+                        .report_message_validation_result(&message_id, MessageAcceptance::Ignore);
+                    return;
+                }
+                if !self.reputation.record_message(&peer_id, Instant::now()) {
+                    self.report_peer(peer_id.clone(), Misbehavior::GossipSpam);
+                }
+                let key = (topic.as_str().to_string(), message_key(&message.data));
+                // Sic: messages are not signed, a relayed message can't be attributed
+                // to its author and honest relays must not be punished for it.
+                if message.source == peer_id {
+                    self.message_sources.insert(key.clone(), peer_id);
+                }
+                if let Some(validation) = self.validation_results.remove(&key) {
+                    // The message has been already validated by the consumer.
+                    self.apply_validation(&key.0, key.1, validation);
+                }
                 let consumers = self
                     .gossip_consumers
                     .entry(topic)
@@ -556,12 +1030,28 @@ impl NetworkBehaviourEventProcess<GossipsubEvent> for Libp2pBehaviour {
                     }
                 })
             }
+            GossipsubEvent::MessageSent {
+                peer_id,
+                topics,
+                size,
+            } => {
+                for topic in &topics {
+                    traffic::record(
+                        GOSSIPSUB_PROTOCOL,
+                        Some(topic.as_str()),
+                        Some(&peer_id),
+                        Direction::Outgoing,
+                        size,
+                    );
+                }
+            }
             GossipsubEvent::Subscribed { .. } => info!("Subsribed"),
             GossipsubEvent::Unsubscribed { .. } => info!("UnSubsribed"),
         }
     }
 }
 
+#[cfg(feature = "old_protos")]
 impl NetworkBehaviourEventProcess<FloodsubEvent> for Libp2pBehaviour {
     // Called when `floodsub` produces an event.
     // Send received message to consumers.
@@ -605,6 +1095,10 @@ pub enum ControlMessage {
     Subscribe {
         topic: String,
         handler: mpsc::UnboundedSender<Vec<u8>>,
+        /// Forward gossip messages only after validation.
+        validated: bool,
+        /// Never drop messages by bandwidth limits.
+        priority: bool,
     },
     Publish {
         topic: String,
@@ -632,16 +1126,41 @@ pub enum ControlMessage {
     ConnectedNodesRequest {
         tx: oneshot::Sender<NetworkResponse>,
     },
+    ReportMisbehavior {
+        topic: String,
+        message_key: u64,
+        misbehavior: Misbehavior,
+    },
+    ValidateMessage {
+        topic: String,
+        message_key: u64,
+        validation: MessageValidation,
+    },
+    PeerScoresRequest {
+        tx: oneshot::Sender<NetworkResponse>,
+    },
+    ClearPeerScores {
+        peer_id: Option<PeerId>,
+    },
+    TrafficStatsRequest {
+        tx: oneshot::Sender<NetworkResponse>,
+    },
+    /// Emulate a node of the previous release, see `Dht::disable_node_records()`.
+    #[cfg(test)]
+    DisableNodeRecords,
 }
 
 /// Builds an implementation of `Transport` that is suitable for usage with the `Swarm`.
 ///
-/// The implementation supports TCP/IP, WebSockets over TCP/IP, secio as the encryption layer,
-/// and mplex or yamux as the multiplexing layer.
+/// The implementation supports TCP/IP, WebSockets over TCP/IP, Noise and/or secio
+/// as the encryption layer, and mplex as the multiplexing layer.
+///
+/// Encryption protocols are negotiated by multistream-select, Noise is preferred.
 ///
 /// > **Note**: If you ever need to express the type of this `Transport`.
-pub fn build_tcp_ws_secio_yamux(
+pub fn build_tcp_ws_secure_mplex(
     keypair: identity::Keypair,
+    config: &NetworkConfig,
 ) -> impl Transport<
     Output = (
         PeerId,
@@ -664,15 +1183,91 @@ pub fn build_tcp_ws_secio_yamux(
     // yamux_config.set_window_update_mode(yamux::WindowUpdateMode::OnRead);
     // yamux_config.set_read_after_close(true);
     // let yamux_config = libp2p_yamux::Config::new(yamux_config);
-    CommonTransport::new()
+    let (use_noise, use_secio) = match config.transport_security {
+        TransportSecurity::Secio => (false, true),
+        TransportSecurity::NoiseOrSecio => (true, true),
+        TransportSecurity::Noise => (true, false),
+    };
+    let noise_config = if use_noise {
+        let dh_keys = noise::Keypair::<noise::X25519>::new()
+            .into_authentic(&keypair)
+            .expect("ed25519 keys are supported by noise");
+        OptionalUpgrade::some(noise::NoiseConfig::xx(dh_keys).into_authenticated())
+    } else {
+        OptionalUpgrade::none()
+    };
+    let secio_config = if use_secio {
+        OptionalUpgrade::some(secio::SecioConfig::new(keypair))
+    } else {
+        OptionalUpgrade::none()
+    };
+    let security_config = SelectUpgrade::new(noise_config, secio_config)
+        .map_inbound(unwrap_either_output)
+        .map_outbound(unwrap_either_output);
+    CommonTransport::new(config)
         .upgrade(libp2p_core::upgrade::Version::V1)
-        .authenticate(secio::SecioConfig::new(keypair))
+        .authenticate(security_config)
         .multiplex(mplex_config)
         // .multiplex(yamux_config)
         .map(|(peer, muxer), _| (peer, libp2p_core::muxing::StreamMuxerBox::new(muxer)))
         .timeout(Duration::from_secs(20))
 }
 
+/// Move PeerId out of the result of SelectUpgrade.
+fn unwrap_either_output<A, B>(
+    output: EitherOutput<(PeerId, A), (PeerId, B)>,
+) -> (PeerId, EitherOutput<A, B>) {
+    match output {
+        EitherOutput::First((peer_id, stream)) => (peer_id, EitherOutput::First(stream)),
+        EitherOutput::Second((peer_id, stream)) => (peer_id, EitherOutput::Second(stream)),
+    }
+}
+
+impl NetworkBehaviourEventProcess<DhtOutEvent> for Libp2pBehaviour {
+    fn inject_event(&mut self, event: DhtOutEvent) {
+        match event {
+            DhtOutEvent::Connected { peer_id } => {
+                self.peer_store.connected(&peer_id);
+                self.connected_peers.insert(peer_id);
+            }
+            DhtOutEvent::Disconnected { peer_id } => {
+                self.bandwidth.lock().unwrap().remove_peer(&peer_id);
+                self.nat.remove_peer(&peer_id);
+                self.connected_peers.remove(&peer_id);
+            }
+            DhtOutEvent::DialPeer { peer_id } => {
+                debug!(target: "stegos_network::dht", "connecting to node: peer_id={}", peer_id);
+                if self.banned_peers.contains(&peer_id) {
+                    debug!(target: "stegos_network::dht", "peer banned, dont connect: {}", peer_id);
+                } else {
+                    self.gatekeeper.dial_peer(peer_id);
+                }
+            }
+            DhtOutEvent::ObservedAddress { peer_id, address } => {
+                self.nat.observed(peer_id, &address);
+            }
+            DhtOutEvent::DiscoveredPeer {
+                node_id,
+                peer_id,
+                addresses,
+            } => {
+                self.peer_store.discovered(peer_id, node_id, &addresses);
+            }
+            DhtOutEvent::Route { next_hop, message } => {
+                debug!(target: "stegos_network::delivery", "delivering payload: node_id={}, peer_id={}", message.to, next_hop);
+                self.delivery.deliver_unicast(&next_hop, message);
+            }
+            DhtOutEvent::NotFound { messages } => {
+                for mut message in messages {
+                    message.dont_route = false;
+                    self.route_old(message);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "old_protos")]
 impl NetworkBehaviourEventProcess<NcpOutEvent> for Libp2pBehaviour {
     fn inject_event(&mut self, event: NcpOutEvent) {
         match event {
@@ -687,11 +1282,10 @@ impl NetworkBehaviourEventProcess<NcpOutEvent> for Libp2pBehaviour {
                     self.gatekeeper.dial_peer(peer_id);
                 }
             }
-            NcpOutEvent::Connected { peer_id } => {
-                self.connected_peers.insert(peer_id);
-            }
-            NcpOutEvent::Disconnected { peer_id } => {
-                self.connected_peers.remove(&peer_id);
+            // Connections are tracked by the DHT.
+            NcpOutEvent::Connected { .. } | NcpOutEvent::Disconnected { .. } => {}
+            NcpOutEvent::ObservedAddress { peer_id, address } => {
+                self.nat.observed(peer_id, &address);
             }
             NcpOutEvent::DiscoveredPeer {
                 node_id,
@@ -700,6 +1294,8 @@ impl NetworkBehaviourEventProcess<NcpOutEvent> for Libp2pBehaviour {
             } => {
                 debug!(target: "stegos_network::discovery", "discovered node: node_id={}, peer_id={}", node_id, peer_id);
                 self.discovery.add_node(node_id.clone(), peer_id.clone());
+                self.peer_store
+                    .discovered(peer_id.clone(), node_id.clone(), &addresses);
                 if !addresses.is_empty() {
                     self.discovery.set_peer_id(&node_id, peer_id.clone());
                     if self.connected_peers.contains(&peer_id) {
@@ -725,9 +1321,12 @@ impl NetworkBehaviourEventProcess<GatekeeperOutEvent> for Libp2pBehaviour {
                 self.gatekeeper.enable_listener(peer_id);
             }
             GatekeeperOutEvent::PrepareDialer { peer_id, version } => {
-                if version < GOSSIP_VERSION {
-                    self.floodsub.enable_incoming(&peer_id);
-                    self.floodsub.enable_outgoing(&peer_id);
+                #[cfg(feature = "old_protos")]
+                {
+                    if version < GOSSIP_VERSION {
+                        self.floodsub.enable_incoming(&peer_id);
+                        self.floodsub.enable_outgoing(&peer_id);
+                    }
                 }
 
                 if version >= GOSSIP_VERSION {
@@ -737,13 +1336,16 @@ impl NetworkBehaviourEventProcess<GatekeeperOutEvent> for Libp2pBehaviour {
                 self.gatekeeper.enable_dialer(peer_id);
             }
             GatekeeperOutEvent::UnlockedDialer { peer_id } => {
+                #[cfg(feature = "old_protos")]
                 self.floodsub.enable_outgoing(&peer_id);
+                #[cfg(not(feature = "old_protos"))]
+                let _ = peer_id;
             }
             GatekeeperOutEvent::NetworkReady => {
                 debug!(target: "stegos_network::gatekeeper", "network is ready");
                 let consumers = self
-                    .floodsub_consumers
-                    .entry(NETWORK_STATUS_TOPIC.to_string())
+                    .gossip_consumers
+                    .entry(Topic::new(NETWORK_STATUS_TOPIC.to_string()).no_hash())
                     .or_insert_with(SmallVec::new);
                 consumers.retain(move |c| c.unbounded_send(NETWORK_READY_TOKEN.to_vec()).is_ok());
             }
@@ -751,10 +1353,20 @@ impl NetworkBehaviourEventProcess<GatekeeperOutEvent> for Libp2pBehaviour {
             GatekeeperOutEvent::BanPeer { peer_id } => {
                 self.events.push_back(BehaviourEvent::BanPeer { peer_id })
             }
+            GatekeeperOutEvent::DialFailed { peer_id } => {
+                self.peer_store.dial_failed(&peer_id);
+            }
+            GatekeeperOutEvent::BackConnectStarted { peer_id } => {
+                self.nat.probe_started(peer_id, Instant::now());
+            }
+            GatekeeperOutEvent::BackConnected { peer_id } => {
+                self.nat.probe_succeeded(&peer_id);
+            }
         }
     }
 }
 
+#[cfg(feature = "old_protos")]
 impl NetworkBehaviourEventProcess<DiscoveryOutEvent> for Libp2pBehaviour {
     fn inject_event(&mut self, event: DiscoveryOutEvent) {
         match event {
@@ -782,7 +1394,7 @@ impl NetworkBehaviourEventProcess<DeliveryEvent> for Libp2pBehaviour {
                 DeliveryMessage::UnicastMessage(unicast) => {
                     debug!(target: "stegos_network::delivery", "received message: dest={}", unicast.to);
                     // Check for duplicate
-                    if self.discovery.is_duplicate(&unicast) {
+                    if self.dht.is_duplicate(&unicast) {
                         debug!(target: "stegos_network::delivery", "got duplicate unicast message: seq_no={}", u8v_to_hexstr(&unicast.seq_no));
                         return;
                     }
@@ -811,6 +1423,13 @@ impl NetworkBehaviourEventProcess<DeliveryEvent> for Libp2pBehaviour {
                                         payload.protocol_id,
                                         payload.data.len()
                                     );
+                                    traffic::record(
+                                        DELIVERY_PROTOCOL,
+                                        Some(&payload.protocol_id),
+                                        None,
+                                        Direction::Incoming,
+                                        payload.data.len(),
+                                    );
                                     let msg = UnicastMessage {
                                         from: payload.from,
                                         data: payload.data,
@@ -836,9 +1455,21 @@ impl NetworkBehaviourEventProcess<DeliveryEvent> for Libp2pBehaviour {
                         }
                         return;
                     }
+                    if unicast.dont_route {
+                        debug!(target: "stegos_network::delivery", "dropping direct message to another node: dest={}", unicast.to);
+                        return;
+                    }
                     // Mesage to somebody else, try to route again...
-                    let dest = unicast.to;
-                    self.discovery.route(&dest, unicast);
+                    if !self
+                        .bandwidth
+                        .lock()
+                        .unwrap()
+                        .has_capacity(None, Direction::Outgoing)
+                    {
+                        debug!(target: "stegos_network::delivery", "upload limit exceeded, dropping message: dest={}", unicast.to);
+                        return;
+                    }
+                    self.route_old(unicast);
                 }
                 DeliveryMessage::BroadcastMessage(_) => unimplemented!(),
             },
@@ -857,15 +1488,15 @@ impl NetworkBehaviourEventProcess<ReplicationEvent> for Libp2pBehaviour {
 
 /// Implementation of `Transport` that supports the most common protocols.
 ///
-/// The list currently is TCP/IP, DNS, and WebSockets. However this list could change in the
-/// future to get new transports.
+/// The list currently is TCP/IP and DNS, optionally dialed through a SOCKS5 proxy.
+/// However this list could change in the future to get new transports.
 #[derive(Debug, Clone)]
 struct CommonTransport {
     // The actual implementation of everything.
     inner: CommonTransportInner,
 }
 
-type InnerImplementation = dns::DnsConfig<tcp::TcpConfig>;
+type InnerImplementation = Boxed<<tcp::TcpConfig as Transport>::Output, io::Error>;
 
 #[derive(Debug, Clone)]
 struct CommonTransportInner {
@@ -874,9 +1505,30 @@ struct CommonTransportInner {
 
 impl CommonTransport {
     /// Initializes the `CommonTransport`.
-    pub fn new() -> CommonTransport {
+    pub fn new(config: &NetworkConfig) -> CommonTransport {
         let tcp = tcp::TcpConfig::new().nodelay(true);
-        let transport = dns::DnsConfig::new(tcp).expect("cannot init dns.");
+        let transport = if config.socks5_proxy == "" {
+            dns::DnsConfig::new(tcp)
+                .expect("cannot init dns.")
+                .map_err(dns_error)
+                .boxed()
+        } else {
+            let proxy = SocketAddr::from_str(&config.socks5_proxy).expect("Invalid socks5_proxy");
+            let proxy = utils::socket_to_multi_addr(&proxy);
+            info!(
+                "Connecting through SOCKS5 proxy {}, proxy_dns={}",
+                proxy, config.socks5_proxy_dns
+            );
+            let socks5 = Socks5Transport::new(tcp, proxy);
+            if config.socks5_proxy_dns {
+                socks5.boxed()
+            } else {
+                dns::DnsConfig::new(socks5)
+                    .expect("cannot init dns.")
+                    .map_err(dns_error)
+                    .boxed()
+            }
+        };
 
         CommonTransport {
             inner: CommonTransportInner { inner: transport },
@@ -900,10 +1552,242 @@ impl Transport for CommonTransport {
     }
 }
 
+fn dns_error(error: dns::DnsErr<io::Error>) -> io::Error {
+    match error {
+        dns::DnsErr::Underlying(error) => error,
+        error => io::Error::new(io::ErrorKind::Other, error),
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::UnicastPayload;
+    use super::{new_service, ControlMessage, Libp2pNetwork, UnicastPayload};
+    use crate::config::{NetworkConfig, TransportSecurity};
+    use crate::replication::ReplicationEvent;
+    use crate::{Network, NetworkName};
+    use futures::channel::mpsc;
+    use futures::StreamExt;
+    use std::net::{TcpListener, TcpStream};
+    use std::time::Duration;
     use stegos_crypto::pbc;
+    use tokio::time::{delay_for, timeout};
+
+    /// Returns a port which is free at the moment.
+    fn free_port() -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        listener.local_addr().expect("local address").port()
+    }
+
+    async fn start_node(
+        port: u16,
+        seed_port: Option<u16>,
+        transport_security: TransportSecurity,
+    ) -> (
+        Network,
+        pbc::PublicKey,
+        mpsc::UnboundedReceiver<Vec<u8>>,
+        mpsc::UnboundedReceiver<ReplicationEvent>,
+    ) {
+        start_node_with(port, seed_port, transport_security, false).await
+    }
+
+    /// Starts a node subscribed to the "test" topic, `legacy` nodes emulate the previous release.
+    async fn start_node_with(
+        port: u16,
+        seed_port: Option<u16>,
+        transport_security: TransportSecurity,
+        legacy: bool,
+    ) -> (
+        Network,
+        pbc::PublicKey,
+        mpsc::UnboundedReceiver<Vec<u8>>,
+        mpsc::UnboundedReceiver<ReplicationEvent>,
+    ) {
+        let mut config = NetworkConfig::default();
+        config.endpoint = format!("127.0.0.1:{}", port);
+        config.seed_nodes = seed_port
+            .into_iter()
+            .map(|port| format!("127.0.0.1:{}", port))
+            .collect();
+        config.readiness_threshold = 1;
+        config.transport_security = transport_security;
+        let (skey, pkey) = pbc::make_random_keys();
+        let (service, control_tx, nat_status, _peer_id, replication_rx) =
+            new_service(&config, NetworkName::Devnet, skey, pkey, None).expect("network started");
+        if legacy {
+            // Processed before any connection is made.
+            control_tx
+                .unbounded_send(ControlMessage::DisableNodeRecords)
+                .unwrap();
+        }
+        let network: Network = Box::new(Libp2pNetwork {
+            control_tx,
+            nat_status,
+        });
+        // Subscriptions are announced to peers when they connect.
+        let rx = network.subscribe("test").unwrap();
+        tokio::spawn(service);
+        // The listener is bound when the service is polled,
+        // wait for it before other nodes dial this one.
+        while TcpStream::connect(("127.0.0.1", port)).is_err() {
+            delay_for(Duration::from_millis(10)).await;
+        }
+        (network, pkey, rx, replication_rx)
+    }
+
+    /// Check that two peers complete the gatekeeper handshake and exchange pubsub messages.
+    async fn pubsub(security1: TransportSecurity, security2: TransportSecurity) {
+        let (port1, port2) = (free_port(), free_port());
+        let (_net1, _pkey1, mut rx1, _replication_rx1) = start_node(port1, None, security1).await;
+        let (net2, _pkey2, _rx2, _replication_rx2) =
+            start_node(port2, Some(port1), security2).await;
+
+        // Both floodsub and gossipsub are enabled only after the gatekeeper handshake,
+        // subscriptions are propagated asynchronously - retry until delivered.
+        let data = vec![1, 2, 3];
+        let deliver = async {
+            loop {
+                net2.publish("test", data.clone()).unwrap();
+                if let Ok(msg) = timeout(Duration::from_millis(500), rx1.next()).await {
+                    return msg;
+                }
+            }
+        };
+        let msg = timeout(Duration::from_secs(60), deliver)
+            .await
+            .expect("message delivered");
+        assert_eq!(msg, Some(data));
+    }
+
+    #[tokio::test]
+    async fn secio_to_noise() {
+        pubsub(TransportSecurity::NoiseOrSecio, TransportSecurity::Secio).await;
+    }
+
+    #[tokio::test]
+    async fn noise_to_secio() {
+        pubsub(TransportSecurity::Secio, TransportSecurity::NoiseOrSecio).await;
+    }
+
+    #[tokio::test]
+    async fn noise_to_noise() {
+        pubsub(TransportSecurity::NoiseOrSecio, TransportSecurity::Noise).await;
+    }
+
+    /// Check topic delivery and unicast between nodes which are not connected directly.
+    #[tokio::test]
+    async fn multi_node() {
+        let port = free_port();
+        let security = TransportSecurity::NoiseOrSecio;
+        let (_seed, _seed_pkey, mut seed_rx, _replication_rx0) =
+            start_node(port, None, security).await;
+        let (net1, pkey1, mut rx1, _replication_rx1) =
+            start_node(free_port(), Some(port), security).await;
+        let (net2, pkey2, _rx2, _replication_rx2) =
+            start_node(free_port(), Some(port), security).await;
+        let mut unicast_rx1 = net1.subscribe_unicast("test").unwrap();
+        let mut unicast_rx2 = net2.subscribe_unicast("test").unwrap();
+
+        // Topic delivery, publish until the mesh is formed.
+        // Gossip messages are content-addressed, so every attempt is unique.
+        let publish = async {
+            for attempt in 0u64.. {
+                net2.publish("test", attempt.to_be_bytes().to_vec())
+                    .unwrap();
+                delay_for(Duration::from_millis(500)).await;
+            }
+        };
+        let deliver = async {
+            tokio::select! {
+                _ = publish => unreachable!(),
+                msgs = async { (seed_rx.next().await, rx1.next().await) } => msgs,
+            }
+        };
+        let (msg0, msg1) = timeout(Duration::from_secs(60), deliver)
+            .await
+            .expect("message delivered");
+        assert!(msg0.is_some());
+        assert!(msg1.is_some());
+
+        // Unicast, the nodes are found through the DHT.
+        let data = vec![1, 2, 3];
+        let deliver = async {
+            loop {
+                net1.send(pkey2, "test", data.clone()).unwrap();
+                if let Ok(msg) = timeout(Duration::from_millis(500), unicast_rx2.next()).await {
+                    return msg;
+                }
+            }
+        };
+        let msg = timeout(Duration::from_secs(60), deliver)
+            .await
+            .expect("unicast delivered")
+            .unwrap();
+        assert_eq!(msg.from, pkey1);
+        assert_eq!(msg.data, data);
+
+        net2.send(pkey1, "test", data.clone()).unwrap();
+        let msg = timeout(Duration::from_secs(10), unicast_rx1.next())
+            .await
+            .expect("reply delivered")
+            .unwrap();
+        assert_eq!(msg.from, pkey2);
+        assert_eq!(msg.data, data);
+    }
+
+    /// Check topic delivery and unicast between this release and a node
+    /// which has no node record, like the previous one.
+    #[cfg(feature = "old_protos")]
+    #[tokio::test]
+    async fn mixed_releases() {
+        let port = free_port();
+        let security = TransportSecurity::NoiseOrSecio;
+        let (old, old_pkey, mut old_rx, _replication_rx0) =
+            start_node_with(port, None, security, true).await;
+        let (new, new_pkey, mut new_rx, _replication_rx1) =
+            start_node(free_port(), Some(port), security).await;
+        let mut old_unicast_rx = old.subscribe_unicast("test").unwrap();
+        let mut new_unicast_rx = new.subscribe_unicast("test").unwrap();
+
+        for (from, to_rx) in vec![(&old, &mut new_rx), (&new, &mut old_rx)] {
+            let deliver = async {
+                for attempt in 0u64.. {
+                    from.publish("test", attempt.to_be_bytes().to_vec())
+                        .unwrap();
+                    if let Ok(msg) = timeout(Duration::from_millis(500), to_rx.next()).await {
+                        return msg;
+                    }
+                }
+                unreachable!()
+            };
+            let msg = timeout(Duration::from_secs(60), deliver)
+                .await
+                .expect("message delivered");
+            assert!(msg.is_some());
+        }
+
+        // The record of the old node is not found, the message is routed by old_protos.
+        let data = vec![1, 2, 3];
+        for (from, from_pkey, to_pkey, to_rx) in vec![
+            (&new, new_pkey, old_pkey, &mut old_unicast_rx),
+            (&old, old_pkey, new_pkey, &mut new_unicast_rx),
+        ] {
+            let deliver = async {
+                loop {
+                    from.send(to_pkey, "test", data.clone()).unwrap();
+                    if let Ok(msg) = timeout(Duration::from_secs(1), to_rx.next()).await {
+                        return msg;
+                    }
+                }
+            };
+            let msg = timeout(Duration::from_secs(120), deliver)
+                .await
+                .expect("unicast delivered")
+                .unwrap();
+            assert_eq!(msg.from, from_pkey);
+            assert_eq!(msg.data, data);
+        }
+    }
 
     #[test]
     fn encode_decode() {
@@ -0,0 +1,328 @@
+//
+// MIT License
+//
+// Copyright (c) 2018-2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! SOCKS5 (RFC 1928) outbound transport.
+
+use futures::future::BoxFuture;
+use futures::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use libp2p_core::multiaddr::{Multiaddr, Protocol};
+use libp2p_core::{transport::TransportError, Transport};
+use std::io;
+use std::net::IpAddr;
+
+const SOCKS_VERSION: u8 = 5;
+const NO_AUTHENTICATION: u8 = 0;
+const CMD_CONNECT: u8 = 1;
+const ATYP_IPV4: u8 = 1;
+const ATYP_DOMAIN: u8 = 3;
+const ATYP_IPV6: u8 = 4;
+
+/// Destination of a CONNECT request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Target {
+    Ip(IpAddr, u16),
+    Domain(String, u16),
+}
+
+impl Target {
+    /// Extracts the destination from `/ip4|ip6|dns4|dns6/.../tcp/...` addresses.
+    fn from_multiaddr(addr: &Multiaddr) -> Option<Target> {
+        let mut iter = addr.iter();
+        let host = iter.next()?;
+        let port = match iter.next()? {
+            Protocol::Tcp(port) => port,
+            _ => return None,
+        };
+        if iter.next().is_some() {
+            return None;
+        }
+        match host {
+            Protocol::Ip4(ip) => Some(Target::Ip(ip.into(), port)),
+            Protocol::Ip6(ip) => Some(Target::Ip(ip.into(), port)),
+            Protocol::Dns4(name) | Protocol::Dns6(name) => {
+                Some(Target::Domain(name.to_string(), port))
+            }
+            _ => None,
+        }
+    }
+
+    /// Encodes ATYP, DST.ADDR and DST.PORT fields of a request.
+    fn encode(&self, buf: &mut Vec<u8>) -> io::Result<()> {
+        let port = match self {
+            Target::Ip(IpAddr::V4(ip), port) => {
+                buf.push(ATYP_IPV4);
+                buf.extend_from_slice(&ip.octets());
+                port
+            }
+            Target::Ip(IpAddr::V6(ip), port) => {
+                buf.push(ATYP_IPV6);
+                buf.extend_from_slice(&ip.octets());
+                port
+            }
+            Target::Domain(name, port) => {
+                if name.len() > 255 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "SOCKS5 domain name is too long",
+                    ));
+                }
+                buf.push(ATYP_DOMAIN);
+                buf.push(name.len() as u8);
+                buf.extend_from_slice(name.as_bytes());
+                port
+            }
+        };
+        buf.extend_from_slice(&port.to_be_bytes());
+        Ok(())
+    }
+}
+
+/// Human-readable REP field of a reply.
+fn reply_error(code: u8) -> &'static str {
+    match code {
+        1 => "general SOCKS server failure",
+        2 => "connection not allowed by ruleset",
+        3 => "network unreachable",
+        4 => "host unreachable",
+        5 => "connection refused",
+        6 => "TTL expired",
+        7 => "command not supported",
+        8 => "address type not supported",
+        _ => "unknown error",
+    }
+}
+
+fn protocol_error(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Performs the client side of SOCKS5 handshake over an established connection to the proxy.
+async fn connect<S>(stream: &mut S, target: &Target) -> io::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    stream
+        .write_all(&[SOCKS_VERSION, 1, NO_AUTHENTICATION])
+        .await?;
+    let mut reply = [0u8; 2];
+    stream.read_exact(&mut reply).await?;
+    if reply[0] != SOCKS_VERSION {
+        return Err(protocol_error("invalid SOCKS version"));
+    }
+    if reply[1] != NO_AUTHENTICATION {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "SOCKS5 proxy requires authentication",
+        ));
+    }
+
+    let mut request = vec![SOCKS_VERSION, CMD_CONNECT, 0];
+    target.encode(&mut request)?;
+    stream.write_all(&request).await?;
+
+    let mut reply = [0u8; 4];
+    stream.read_exact(&mut reply).await?;
+    if reply[0] != SOCKS_VERSION {
+        return Err(protocol_error("invalid SOCKS version"));
+    }
+    if reply[1] != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::ConnectionRefused,
+            format!("SOCKS5 proxy: {}", reply_error(reply[1])),
+        ));
+    }
+    // Skip BND.ADDR and BND.PORT.
+    let len = match reply[3] {
+        ATYP_IPV4 => 4,
+        ATYP_IPV6 => 16,
+        ATYP_DOMAIN => {
+            let mut len = [0u8; 1];
+            stream.read_exact(&mut len).await?;
+            len[0] as usize
+        }
+        _ => return Err(protocol_error("invalid SOCKS5 address type")),
+    };
+    let mut bound = vec![0u8; len + 2];
+    stream.read_exact(&mut bound).await?;
+    Ok(())
+}
+
+/// Transport which dials all outbound connections through a SOCKS5 proxy.
+///
+/// Names in `/dns4` and `/dns6` addresses are passed to the proxy as is,
+/// wrap the transport into `DnsConfig` to resolve them locally.
+/// Listening is delegated to the inner transport.
+#[derive(Debug, Clone)]
+pub struct Socks5Transport<T> {
+    inner: T,
+    proxy: Multiaddr,
+}
+
+impl<T> Socks5Transport<T> {
+    /// Creates a new transport which reaches `proxy` using `inner`.
+    pub fn new(inner: T, proxy: Multiaddr) -> Self {
+        Socks5Transport { inner, proxy }
+    }
+}
+
+impl<T> Transport for Socks5Transport<T>
+where
+    T: Transport<Error = io::Error>,
+    T::Dial: Send + 'static,
+    T::Output: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    type Output = T::Output;
+    type Error = io::Error;
+    type Listener = T::Listener;
+    type ListenerUpgrade = T::ListenerUpgrade;
+    type Dial = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+
+    fn listen_on(self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+        self.inner.listen_on(addr)
+    }
+
+    fn dial(self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+        let target = match Target::from_multiaddr(&addr) {
+            Some(target) => target,
+            None => return Err(TransportError::MultiaddrNotSupported(addr)),
+        };
+        let dial = match self.inner.dial(self.proxy) {
+            Ok(dial) => dial,
+            Err(TransportError::MultiaddrNotSupported(proxy)) => {
+                let error = format!("Unsupported SOCKS5 proxy address: {}", proxy);
+                let error = io::Error::new(io::ErrorKind::InvalidInput, error);
+                return Err(TransportError::Other(error));
+            }
+            Err(e) => return Err(e),
+        };
+        Ok(Box::pin(async move {
+            let mut stream = dial.await?;
+            connect(&mut stream, &target).await?;
+            Ok(stream)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_std::net::TcpListener;
+    use futures::future;
+    use libp2p_tcp as tcp;
+    use std::net::SocketAddr;
+
+    #[test]
+    fn target() {
+        let target = |addr: &str| Target::from_multiaddr(&addr.parse().unwrap());
+        assert_eq!(
+            target("/ip4/10.0.0.1/tcp/10203"),
+            Some(Target::Ip("10.0.0.1".parse().unwrap(), 10203))
+        );
+        assert_eq!(
+            target("/ip6/::1/tcp/10203"),
+            Some(Target::Ip("::1".parse().unwrap(), 10203))
+        );
+        assert_eq!(
+            target("/dns4/node.stegos.com/tcp/10203"),
+            Some(Target::Domain("node.stegos.com".to_string(), 10203))
+        );
+        assert_eq!(target("/ip4/10.0.0.1/udp/10203"), None);
+        assert_eq!(target("/ip4/10.0.0.1"), None);
+        assert_eq!(target("/ip4/10.0.0.1/tcp/10203/ws"), None);
+    }
+
+    /// Minimal SOCKS5 proxy which checks the request, replies with `rep` and echoes data back.
+    async fn proxy(listener: TcpListener, expected: Vec<u8>, rep: u8) {
+        let (mut stream, _addr) = listener.accept().await.unwrap();
+        let mut greeting = [0u8; 3];
+        stream.read_exact(&mut greeting).await.unwrap();
+        assert_eq!(greeting, [SOCKS_VERSION, 1, NO_AUTHENTICATION]);
+        stream
+            .write_all(&[SOCKS_VERSION, NO_AUTHENTICATION])
+            .await
+            .unwrap();
+
+        let mut request = vec![0u8; expected.len()];
+        stream.read_exact(&mut request).await.unwrap();
+        assert_eq!(request, expected);
+        let reply = [SOCKS_VERSION, rep, 0, ATYP_IPV4, 127, 0, 0, 1, 0, 80];
+        stream.write_all(&reply).await.unwrap();
+        if rep != 0 {
+            return;
+        }
+
+        let mut buf = [0u8; 4];
+        stream.read_exact(&mut buf).await.unwrap();
+        stream.write_all(&buf).await.unwrap();
+    }
+
+    fn dial(target: &str, expected: Vec<u8>, rep: u8) -> io::Result<()> {
+        async_std::task::block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let proxy_addr: SocketAddr = listener.local_addr().unwrap();
+            let proxy_addr = crate::utils::socket_to_multi_addr(&proxy_addr);
+            let transport = Socks5Transport::new(tcp::TcpConfig::new(), proxy_addr);
+            let client = async {
+                let mut stream = transport.dial(target.parse().unwrap()).unwrap().await?;
+                stream.write_all(b"ping").await?;
+                let mut buf = [0u8; 4];
+                stream.read_exact(&mut buf).await?;
+                assert_eq!(&buf, b"ping");
+                Ok(())
+            };
+            let (result, ()) = future::join(client, proxy(listener, expected, rep)).await;
+            result
+        })
+    }
+
+    #[test]
+    fn connect_ip() {
+        let expected = vec![5, 1, 0, ATYP_IPV4, 10, 0, 0, 1, 0x27, 0xdb];
+        dial("/ip4/10.0.0.1/tcp/10203", expected, 0).unwrap();
+    }
+
+    #[test]
+    fn connect_domain() {
+        let mut expected = vec![5, 1, 0, ATYP_DOMAIN, 15];
+        expected.extend_from_slice(b"node.stegos.com");
+        expected.extend_from_slice(&[0x27, 0xdb]);
+        dial("/dns4/node.stegos.com/tcp/10203", expected, 0).unwrap();
+    }
+
+    #[test]
+    fn connect_refused() {
+        let expected = vec![5, 1, 0, ATYP_IPV4, 10, 0, 0, 1, 0x27, 0xdb];
+        let error = dial("/ip4/10.0.0.1/tcp/10203", expected, 5).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::ConnectionRefused);
+    }
+
+    #[test]
+    fn unsupported_address() {
+        let proxy = "/ip4/127.0.0.1/tcp/9050".parse().unwrap();
+        let transport = Socks5Transport::new(tcp::TcpConfig::new(), proxy);
+        match transport.dial("/ip4/10.0.0.1/udp/10203".parse().unwrap()) {
+            Err(TransportError::MultiaddrNotSupported(_)) => {}
+            _ => panic!("unexpected result"),
+        }
+    }
+}
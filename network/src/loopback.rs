@@ -0,0 +1,672 @@
+//
+// MIT License
+//
+// Copyright (c) 2018-2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! In-process implementation of `NetworkProvider`.
+//!
+//! `LoopbackHub` connects any number of in-process peers without real sockets.
+//! It is intended for integration tests of node, wallet, txpool and replication.
+
+use crate::libp2p_network::{NETWORK_READY_TOKEN, NETWORK_STATUS_TOPIC};
+use crate::replication::{ReplicationEvent, ReplicationVersion};
+use crate::utils;
+use crate::{
+    MessageValidation, Misbehavior, NatStatus, Network, NetworkProvider, NetworkResponse, NodeInfo,
+    UnicastMessage,
+};
+use failure::{format_err, Error};
+use futures::channel::{mpsc, oneshot};
+use libp2p_core::identity;
+use libp2p_core::multiaddr::{Multiaddr, Protocol};
+use libp2p_core::PeerId;
+use log::*;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use stegos_crypto::pbc;
+use tokio::time;
+
+/// Size of buffers used for replication streams.
+const REPLICATION_BUFFER_SIZE: usize = 10;
+
+/// Fault injection settings for `LoopbackHub`.
+#[derive(Debug, Clone)]
+pub struct LoopbackConfig {
+    /// Delay applied to every pubsub and unicast message.
+    pub latency: Duration,
+    /// Probability in range [0.0, 1.0] to drop a pubsub or unicast message.
+    pub loss: f64,
+}
+
+impl Default for LoopbackConfig {
+    fn default() -> Self {
+        LoopbackConfig {
+            latency: Duration::from_secs(0),
+            loss: 0.0,
+        }
+    }
+}
+
+/// A peer registered in the hub.
+struct LoopbackPeer {
+    peer_id: PeerId,
+    multiaddr: Multiaddr,
+    topics: HashMap<String, Vec<mpsc::UnboundedSender<Vec<u8>>>>,
+    unicast: HashMap<String, Vec<mpsc::UnboundedSender<UnicastMessage>>>,
+    replication_tx: mpsc::UnboundedSender<ReplicationEvent>,
+}
+
+struct HubState {
+    config: LoopbackConfig,
+    peers: HashMap<pbc::PublicKey, LoopbackPeer>,
+    next_addr: u64,
+}
+
+/// Shared in-process switch between `LoopbackNetwork` peers.
+#[derive(Clone)]
+pub struct LoopbackHub {
+    state: Arc<Mutex<HubState>>,
+}
+
+impl fmt::Debug for LoopbackHub {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "LoopbackHub")
+    }
+}
+
+impl LoopbackHub {
+    /// Creates a new hub with the given fault injection settings.
+    pub fn new(config: LoopbackConfig) -> Self {
+        let state = HubState {
+            config,
+            peers: HashMap::new(),
+            next_addr: 1,
+        };
+        LoopbackHub {
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// Changes the fault injection settings for all subsequent messages.
+    pub fn set_config(&self, config: LoopbackConfig) {
+        self.state.lock().unwrap().config = config;
+    }
+
+    /// Attaches a new peer to the hub.
+    /// Returns the same triple as `Libp2pNetwork::new()`, except the service future.
+    pub fn connect(
+        &self,
+        network_skey: pbc::SecretKey,
+        network_pkey: pbc::PublicKey,
+    ) -> Result<(Network, PeerId, mpsc::UnboundedReceiver<ReplicationEvent>), Error> {
+        let keypair = utils::ed25519_from_pbc(&network_skey);
+        let peer_id = identity::Keypair::Ed25519(keypair).public().into_peer_id();
+        let (replication_tx, replication_rx) = mpsc::unbounded();
+
+        let mut state = self.state.lock().unwrap();
+        if state.peers.contains_key(&network_pkey) {
+            return Err(format_err!(
+                "Peer is already connected: pkey={}",
+                network_pkey
+            ));
+        }
+        let multiaddr = Multiaddr::empty().with(Protocol::Memory(state.next_addr));
+        state.next_addr += 1;
+        debug!(target: "stegos_network::loopback",
+               "Connected: pkey={}, peer_id={}, multiaddr={}", network_pkey, peer_id, multiaddr);
+
+        // Register everybody with everybody, like libp2p does on a new connection.
+        for other in state.peers.values() {
+            register(&other.replication_tx, &peer_id, &multiaddr);
+            register(&replication_tx, &other.peer_id, &other.multiaddr);
+        }
+
+        let peer = LoopbackPeer {
+            peer_id: peer_id.clone(),
+            multiaddr,
+            topics: HashMap::new(),
+            unicast: HashMap::new(),
+            replication_tx,
+        };
+        state.peers.insert(network_pkey, peer);
+        drop(state);
+
+        let network = LoopbackNetwork {
+            hub: self.clone(),
+            pkey: Arc::new(Mutex::new(network_pkey)),
+        };
+        Ok((Box::new(network), peer_id, replication_rx))
+    }
+
+    /// Detaches a peer from the hub, simulating a network partition of this node.
+    pub fn disconnect(&self, network_pkey: &pbc::PublicKey) {
+        let mut state = self.state.lock().unwrap();
+        let peer = match state.peers.remove(network_pkey) {
+            Some(peer) => peer,
+            None => return,
+        };
+        debug!(target: "stegos_network::loopback", "Disconnected: pkey={}, peer_id={}", network_pkey, peer.peer_id);
+        for other in state.peers.values() {
+            unregister(&other.replication_tx, &peer.peer_id, &peer.multiaddr);
+            unregister(&peer.replication_tx, &other.peer_id, &other.multiaddr);
+        }
+    }
+
+    /// Returns the list of attached peers.
+    pub fn peers(&self) -> Vec<pbc::PublicKey> {
+        self.state.lock().unwrap().peers.keys().cloned().collect()
+    }
+
+    fn subscribe(
+        &self,
+        pkey: &pbc::PublicKey,
+        topic: &str,
+    ) -> Result<mpsc::UnboundedReceiver<Vec<u8>>, Error> {
+        let (tx, rx) = mpsc::unbounded();
+        if topic == NETWORK_STATUS_TOPIC {
+            // Loopback network is always ready.
+            tx.unbounded_send(NETWORK_READY_TOKEN.to_vec())?;
+            return Ok(rx);
+        }
+        let mut state = self.state.lock().unwrap();
+        let peer = state.peer_mut(pkey)?;
+        peer.topics
+            .entry(topic.to_string())
+            .or_insert_with(Vec::new)
+            .push(tx);
+        Ok(rx)
+    }
+
+    fn publish(&self, pkey: &pbc::PublicKey, topic: &str, data: Vec<u8>) -> Result<(), Error> {
+        let mut state = self.state.lock().unwrap();
+        state.peer_mut(pkey)?;
+        debug!(target: "stegos_network::loopback",
+               "Sending broadcast message: from={}, topic={}, size={}", pkey, topic, data.len());
+        let config = state.config.clone();
+        for (other_pkey, other) in state.peers.iter_mut() {
+            // Pubsub never delivers own messages.
+            if other_pkey == pkey {
+                continue;
+            }
+            if let Some(consumers) = other.topics.get_mut(topic) {
+                consumers.retain(|c| !c.is_closed());
+                for consumer in consumers.iter() {
+                    deliver(&config, consumer.clone(), data.clone());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn subscribe_unicast(
+        &self,
+        pkey: &pbc::PublicKey,
+        protocol_id: &str,
+    ) -> Result<mpsc::UnboundedReceiver<UnicastMessage>, Error> {
+        let (tx, rx) = mpsc::unbounded();
+        let mut state = self.state.lock().unwrap();
+        let peer = state.peer_mut(pkey)?;
+        peer.unicast
+            .entry(protocol_id.to_string())
+            .or_insert_with(Vec::new)
+            .push(tx);
+        Ok(rx)
+    }
+
+    fn send(
+        &self,
+        from: &pbc::PublicKey,
+        to: &pbc::PublicKey,
+        protocol_id: &str,
+        data: Vec<u8>,
+    ) -> Result<(), Error> {
+        let mut state = self.state.lock().unwrap();
+        state.peer_mut(from)?;
+        debug!(target: "stegos_network::loopback",
+               "Sending unicast message: to={}, from={}, protocol={}, size={}", to, from, protocol_id, data.len());
+        let config = state.config.clone();
+        let peer = match state.peers.get_mut(to) {
+            Some(peer) => peer,
+            None => {
+                // Unreachable destinations are silently dropped, as with libp2p.
+                debug!(target: "stegos_network::loopback", "Unknown unicast destination: to={}", to);
+                return Ok(());
+            }
+        };
+        if let Some(consumers) = peer.unicast.get_mut(protocol_id) {
+            consumers.retain(|c| !c.is_closed());
+            let msg = UnicastMessage { from: *from, data };
+            for consumer in consumers.iter() {
+                if from == to {
+                    // Local delivery is not subject to fault injection.
+                    let _ = consumer.unbounded_send(msg.clone());
+                } else {
+                    deliver(&config, consumer.clone(), msg.clone());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn replication_connect(&self, pkey: &pbc::PublicKey, peer_id: PeerId) -> Result<(), Error> {
+        let state = self.state.lock().unwrap();
+        let me = state.peer(pkey)?;
+        let upstream = match state.peers.values().find(|p| p.peer_id == peer_id) {
+            Some(upstream) => upstream,
+            None => {
+                let error = io::Error::new(io::ErrorKind::NotFound, "peer is not connected");
+                let event = ReplicationEvent::ConnectionFailed { peer_id, error };
+                me.replication_tx.unbounded_send(event)?;
+                return Ok(());
+            }
+        };
+        debug!(target: "stegos_network::loopback", "Replication connect: from={}, to={}", me.peer_id, peer_id);
+        let (down_tx, up_rx) = mpsc::channel(REPLICATION_BUFFER_SIZE);
+        let (up_tx, down_rx) = mpsc::channel(REPLICATION_BUFFER_SIZE);
+        let accepted = ReplicationEvent::Accepted {
+            peer_id: me.peer_id.clone(),
+            tx: up_tx,
+            rx: up_rx,
+        };
+        upstream.replication_tx.unbounded_send(accepted)?;
+        let connected = ReplicationEvent::Connected {
+            peer_id,
+            tx: down_tx,
+            rx: down_rx,
+        };
+        me.replication_tx.unbounded_send(connected)?;
+        Ok(())
+    }
+
+    fn replication_disconnect(&self, pkey: &pbc::PublicKey, peer_id: PeerId) -> Result<(), Error> {
+        // Streams are closed by dropping the channels on the replication side,
+        // the hub doesn't keep any state for them.
+        let state = self.state.lock().unwrap();
+        let me = state.peer(pkey)?;
+        debug!(target: "stegos_network::loopback", "Replication disconnect: from={}, to={}", me.peer_id, peer_id);
+        Ok(())
+    }
+
+    fn connected_nodes(&self, pkey: &pbc::PublicKey) -> Result<Vec<NodeInfo>, Error> {
+        let state = self.state.lock().unwrap();
+        state.peer(pkey)?;
+        let nodes = state
+            .peers
+            .iter()
+            .filter(|(other_pkey, _)| *other_pkey != pkey)
+            .map(|(other_pkey, other)| {
+                NodeInfo::new(&other.peer_id, *other_pkey, vec![other.multiaddr.clone()])
+            })
+            .collect();
+        Ok(nodes)
+    }
+
+    fn change_network_keys(
+        &self,
+        old_pkey: &pbc::PublicKey,
+        new_pkey: pbc::PublicKey,
+    ) -> Result<(), Error> {
+        let mut state = self.state.lock().unwrap();
+        if state.peers.contains_key(&new_pkey) {
+            return Err(format_err!("Peer is already connected: pkey={}", new_pkey));
+        }
+        let peer = state
+            .peers
+            .remove(old_pkey)
+            .ok_or_else(|| format_err!("Peer is not connected: pkey={}", old_pkey))?;
+        state.peers.insert(new_pkey, peer);
+        Ok(())
+    }
+}
+
+impl HubState {
+    fn peer(&self, pkey: &pbc::PublicKey) -> Result<&LoopbackPeer, Error> {
+        self.peers
+            .get(pkey)
+            .ok_or_else(|| format_err!("Peer is not connected: pkey={}", pkey))
+    }
+
+    fn peer_mut(&mut self, pkey: &pbc::PublicKey) -> Result<&mut LoopbackPeer, Error> {
+        self.peers
+            .get_mut(pkey)
+            .ok_or_else(|| format_err!("Peer is not connected: pkey={}", pkey))
+    }
+}
+
+fn register(tx: &mpsc::UnboundedSender<ReplicationEvent>, peer_id: &PeerId, multiaddr: &Multiaddr) {
+    let events = vec![
+        ReplicationEvent::Registered {
+            peer_id: peer_id.clone(),
+            multiaddr: multiaddr.clone(),
+        },
+        ReplicationEvent::ResolvedVersion {
+            peer_id: peer_id.clone(),
+            version: ReplicationVersion::latest(),
+        },
+    ];
+    for event in events {
+        let _ = tx.unbounded_send(event);
+    }
+}
+
+fn unregister(
+    tx: &mpsc::UnboundedSender<ReplicationEvent>,
+    peer_id: &PeerId,
+    multiaddr: &Multiaddr,
+) {
+    let events = vec![
+        ReplicationEvent::Unregistered {
+            peer_id: peer_id.clone(),
+            multiaddr: multiaddr.clone(),
+        },
+        ReplicationEvent::Disconnected {
+            peer_id: peer_id.clone(),
+        },
+    ];
+    for event in events {
+        let _ = tx.unbounded_send(event);
+    }
+}
+
+/// Delivers a message, applying loss and latency from the config.
+/// A non-zero latency requires a running tokio runtime.
+fn deliver<T: Send + 'static>(config: &LoopbackConfig, tx: mpsc::UnboundedSender<T>, msg: T) {
+    if config.loss > 0.0 && rand::random::<f64>() < config.loss {
+        trace!(target: "stegos_network::loopback", "Message dropped");
+        return;
+    }
+    if config.latency == Duration::from_secs(0) {
+        let _ = tx.unbounded_send(msg);
+        return;
+    }
+    let latency = config.latency;
+    tokio::spawn(async move {
+        time::delay_for(latency).await;
+        let _ = tx.unbounded_send(msg);
+    });
+}
+
+/// `NetworkProvider` attached to a `LoopbackHub`.
+#[derive(Clone)]
+pub struct LoopbackNetwork {
+    hub: LoopbackHub,
+    pkey: Arc<Mutex<pbc::PublicKey>>,
+}
+
+impl fmt::Debug for LoopbackNetwork {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "LoopbackNetwork({})", self.pkey())
+    }
+}
+
+impl LoopbackNetwork {
+    fn pkey(&self) -> pbc::PublicKey {
+        *self.pkey.lock().unwrap()
+    }
+}
+
+impl NetworkProvider for LoopbackNetwork {
+    fn subscribe(&self, topic: &str) -> Result<mpsc::UnboundedReceiver<Vec<u8>>, Error> {
+        self.hub.subscribe(&self.pkey(), topic)
+    }
+
+    fn subscribe_validated(&self, topic: &str) -> Result<mpsc::UnboundedReceiver<Vec<u8>>, Error> {
+        // Loopback network doesn't forward messages.
+        self.hub.subscribe(&self.pkey(), topic)
+    }
+
+    fn subscribe_priority(&self, topic: &str) -> Result<mpsc::UnboundedReceiver<Vec<u8>>, Error> {
+        // Loopback network has no bandwidth limits.
+        self.hub.subscribe(&self.pkey(), topic)
+    }
+
+    fn validate_message(
+        &self,
+        _topic: &str,
+        _data: &[u8],
+        _validation: MessageValidation,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn publish(&self, topic: &str, data: Vec<u8>) -> Result<(), Error> {
+        self.hub.publish(&self.pkey(), topic, data)
+    }
+
+    fn subscribe_unicast(
+        &self,
+        protocol_id: &str,
+    ) -> Result<mpsc::UnboundedReceiver<UnicastMessage>, Error> {
+        self.hub.subscribe_unicast(&self.pkey(), protocol_id)
+    }
+
+    fn send(&self, to: pbc::PublicKey, protocol_id: &str, data: Vec<u8>) -> Result<(), Error> {
+        self.hub.send(&self.pkey(), &to, protocol_id, data)
+    }
+
+    fn replication_connect(&self, peer_id: PeerId) -> Result<(), Error> {
+        self.hub.replication_connect(&self.pkey(), peer_id)
+    }
+
+    fn replication_disconnect(&self, peer_id: PeerId) -> Result<(), Error> {
+        self.hub.replication_disconnect(&self.pkey(), peer_id)
+    }
+
+    fn list_connected_nodes(&self) -> Result<oneshot::Receiver<NetworkResponse>, Error> {
+        let nodes = self.hub.connected_nodes(&self.pkey())?;
+        let (tx, rx) = oneshot::channel::<NetworkResponse>();
+        let _ = tx.send(NetworkResponse::ConnectedNodes { nodes });
+        Ok(rx)
+    }
+
+    fn report_misbehavior(
+        &self,
+        _topic: &str,
+        _data: &[u8],
+        _misbehavior: Misbehavior,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn peer_scores(&self) -> Result<oneshot::Receiver<NetworkResponse>, Error> {
+        let (tx, rx) = oneshot::channel::<NetworkResponse>();
+        let _ = tx.send(NetworkResponse::PeerScores { peers: Vec::new() });
+        Ok(rx)
+    }
+
+    fn clear_peer_scores(&self, _peer_id: Option<PeerId>) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn traffic_stats(&self) -> Result<oneshot::Receiver<NetworkResponse>, Error> {
+        let (tx, rx) = oneshot::channel::<NetworkResponse>();
+        let _ = tx.send(NetworkResponse::TrafficStats {
+            topics: Vec::new(),
+            peers: Vec::new(),
+        });
+        Ok(rx)
+    }
+
+    fn nat_status(&self) -> NatStatus {
+        NatStatus::default()
+    }
+
+    fn box_clone(&self) -> Network {
+        Box::new((*self).clone())
+    }
+
+    fn change_network_keys(
+        &self,
+        new_pkey: pbc::PublicKey,
+        _new_skey: pbc::SecretKey,
+    ) -> Result<(), Error> {
+        let mut pkey = self.pkey.lock().unwrap();
+        self.hub.change_network_keys(&pkey, new_pkey)?;
+        *pkey = new_pkey;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use futures::{SinkExt, StreamExt};
+
+    fn new_peer(
+        hub: &LoopbackHub,
+    ) -> (
+        Network,
+        pbc::PublicKey,
+        PeerId,
+        mpsc::UnboundedReceiver<ReplicationEvent>,
+    ) {
+        let (skey, pkey) = pbc::make_random_keys();
+        let (network, peer_id, replication_rx) = hub.connect(skey, pkey).unwrap();
+        (network, pkey, peer_id, replication_rx)
+    }
+
+    #[test]
+    fn pubsub() {
+        let hub = LoopbackHub::new(Default::default());
+        let (net1, _, _, _) = new_peer(&hub);
+        let (net2, _, _, _) = new_peer(&hub);
+        let (net3, _, _, _) = new_peer(&hub);
+        let mut rx1 = net1.subscribe("topic").unwrap();
+        let mut rx2 = net2.subscribe("topic").unwrap();
+        let mut rx3 = net3.subscribe("other").unwrap();
+
+        net1.publish("topic", vec![1, 2, 3]).unwrap();
+        assert_eq!(block_on(rx2.next()).unwrap(), vec![1, 2, 3]);
+        // Own messages and other topics are not delivered.
+        assert!(rx1.try_next().is_err());
+        assert!(rx3.try_next().is_err());
+
+        let mut status = net1.subscribe(NETWORK_STATUS_TOPIC).unwrap();
+        assert_eq!(
+            block_on(status.next()).unwrap(),
+            NETWORK_READY_TOKEN.to_vec()
+        );
+    }
+
+    #[test]
+    fn unicast() {
+        let hub = LoopbackHub::new(Default::default());
+        let (net1, pkey1, _, _) = new_peer(&hub);
+        let (net2, pkey2, _, _) = new_peer(&hub);
+        let mut rx2 = net2.subscribe_unicast("proto").unwrap();
+
+        net1.send(pkey2, "proto", vec![4, 5]).unwrap();
+        let msg = block_on(rx2.next()).unwrap();
+        assert_eq!(msg.from, pkey1);
+        assert_eq!(msg.data, vec![4, 5]);
+
+        // Unknown destinations are dropped.
+        let (_skey, pkey3) = pbc::make_random_keys();
+        net1.send(pkey3, "proto", vec![6]).unwrap();
+
+        let nodes = block_on(net1.list_connected_nodes().unwrap()).unwrap();
+        match nodes {
+            NetworkResponse::ConnectedNodes { nodes } => assert_eq!(nodes.len(), 1),
+            _ => panic!("Invalid response"),
+        }
+    }
+
+    #[test]
+    fn loss() {
+        let config = LoopbackConfig {
+            loss: 1.0,
+            ..Default::default()
+        };
+        let hub = LoopbackHub::new(config);
+        let (net1, _, _, _) = new_peer(&hub);
+        let (net2, _, _, _) = new_peer(&hub);
+        let mut rx2 = net2.subscribe("topic").unwrap();
+        net1.publish("topic", vec![1]).unwrap();
+        assert!(rx2.try_next().is_err());
+    }
+
+    #[tokio::test]
+    async fn latency() {
+        let config = LoopbackConfig {
+            latency: Duration::from_millis(50),
+            ..Default::default()
+        };
+        let hub = LoopbackHub::new(config);
+        let (net1, _, _, _) = new_peer(&hub);
+        let (net2, _, _, _) = new_peer(&hub);
+        let mut rx2 = net2.subscribe("topic").unwrap();
+        net1.publish("topic", vec![1]).unwrap();
+        assert!(rx2.try_next().is_err());
+        assert_eq!(rx2.next().await.unwrap(), vec![1]);
+    }
+
+    #[test]
+    fn replication() {
+        let hub = LoopbackHub::new(Default::default());
+        let (net1, _, peer_id1, mut events1) = new_peer(&hub);
+        let (_net2, pkey2, peer_id2, mut events2) = new_peer(&hub);
+
+        match block_on(events1.next()).unwrap() {
+            ReplicationEvent::Registered { peer_id, .. } => assert_eq!(peer_id, peer_id2),
+            e => panic!("unexpected event: {:?}", e),
+        }
+        match block_on(events1.next()).unwrap() {
+            ReplicationEvent::ResolvedVersion { peer_id, .. } => assert_eq!(peer_id, peer_id2),
+            e => panic!("unexpected event: {:?}", e),
+        }
+        block_on(events2.next()).unwrap();
+        block_on(events2.next()).unwrap();
+
+        net1.replication_connect(peer_id2.clone()).unwrap();
+        let (mut down_tx, mut down_rx) = match block_on(events1.next()).unwrap() {
+            ReplicationEvent::Connected { peer_id, tx, rx } => {
+                assert_eq!(peer_id, peer_id2);
+                (tx, rx)
+            }
+            e => panic!("unexpected event: {:?}", e),
+        };
+        let (mut up_tx, mut up_rx) = match block_on(events2.next()).unwrap() {
+            ReplicationEvent::Accepted { peer_id, tx, rx } => {
+                assert_eq!(peer_id, peer_id1);
+                (tx, rx)
+            }
+            e => panic!("unexpected event: {:?}", e),
+        };
+        block_on(down_tx.send(vec![1])).unwrap();
+        assert_eq!(block_on(up_rx.next()).unwrap(), vec![1]);
+        block_on(up_tx.send(vec![2])).unwrap();
+        assert_eq!(block_on(down_rx.next()).unwrap(), vec![2]);
+
+        hub.disconnect(&pkey2);
+        match block_on(events1.next()).unwrap() {
+            ReplicationEvent::Unregistered { peer_id, .. } => assert_eq!(peer_id, peer_id2),
+            e => panic!("unexpected event: {:?}", e),
+        }
+        match block_on(events1.next()).unwrap() {
+            ReplicationEvent::Disconnected { peer_id } => assert_eq!(peer_id, peer_id2),
+            e => panic!("unexpected event: {:?}", e),
+        }
+    }
+}
@@ -0,0 +1,351 @@
+//
+// MIT License
+//
+// Copyright (c) 2018-2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! NAT traversal and advertised address detection.
+//!
+//! Peers report the address they see this node at by Identify (and NCP with `old_protos`).
+//! Reachability is checked by the back-connect of the gatekeeper handshake:
+//! a peer announces a back-connect and dials our listening port on the observed IP.
+//! Optionally, the listening port is mapped on the gateway by UPnP or NAT-PMP
+//! (requires `port_mapping` feature).
+//!
+//! Unless `advertised_endpoint` is set in the config, an address is advertised
+//! in the node record only after the node has been found reachable.
+
+#[cfg(feature = "port_mapping")]
+mod port_mapping;
+
+use crate::config::NetworkConfig;
+use crate::utils::socket_to_multi_addr;
+use crate::{Multiaddr, PeerId};
+use futures::channel::mpsc;
+use futures::stream::StreamExt;
+use libp2p_core::multiaddr::Protocol;
+use log::*;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+pub use stegos_blockchain::api::Reachability;
+
+/// How long to wait for a back-connect announced by a peer.
+pub(crate) const PROBE_TIMEOUT: Duration = Duration::from_secs(30);
+/// Number of missed back-connects in a row to consider the node unreachable.
+const MAX_FAILED_PROBES: u32 = 3;
+
+/// Reachability status of the node.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NatStatus {
+    pub reachability: Reachability,
+    /// Address advertised to peers.
+    pub advertised_address: Option<Multiaddr>,
+    /// Addresses of the node reported by connected peers, most reported first.
+    pub observed_addresses: Vec<Multiaddr>,
+    /// External address mapped on the gateway.
+    pub mapped_address: Option<Multiaddr>,
+}
+
+/// The last reported status, shared with `Libp2pNetwork`.
+pub(crate) type SharedNatStatus = Arc<Mutex<NatStatus>>;
+
+/// Tracks observed addresses and reachability of the node.
+pub(crate) struct Nat {
+    /// Listening port, None if inbound connections are disabled.
+    port: Option<u16>,
+    /// Address from the config, advertised unconditionally.
+    manual: Option<Multiaddr>,
+    /// Our IP reported by each connected peer.
+    observed: HashMap<PeerId, IpAddr>,
+    /// External address mapped on the gateway.
+    mapped: Option<Multiaddr>,
+    /// Updates of the mapped address.
+    mapped_rx: Option<mpsc::UnboundedReceiver<Option<Multiaddr>>>,
+    /// Back-connects announced by peers, by deadline.
+    probes: HashMap<PeerId, Instant>,
+    /// Number of missed back-connects since the last successful one.
+    failed_probes: u32,
+    reachability: Reachability,
+    /// Last reported status.
+    status: NatStatus,
+    shared: SharedNatStatus,
+}
+
+impl Nat {
+    pub(crate) fn new(config: &NetworkConfig) -> Self {
+        let port = if config.listen && config.endpoint != "" {
+            let endpoint = SocketAddr::from_str(&config.endpoint).expect("Invalid endpoint");
+            Some(endpoint.port())
+        } else {
+            None
+        };
+        let manual = if config.advertised_endpoint != "" {
+            let endpoint = SocketAddr::from_str(&config.advertised_endpoint)
+                .expect("Invalid advertised_endpoint");
+            Some(socket_to_multi_addr(&endpoint))
+        } else {
+            None
+        };
+        let reachability = if port.is_some() {
+            Reachability::Unknown
+        } else {
+            Reachability::Private
+        };
+        let mapped_rx = match port {
+            Some(port) if config.port_mapping => Self::start_port_mapping(port),
+            _ => None,
+        };
+        let mut nat = Nat {
+            port,
+            manual,
+            observed: HashMap::new(),
+            mapped: None,
+            mapped_rx,
+            probes: HashMap::new(),
+            failed_probes: 0,
+            reachability,
+            status: NatStatus::default(),
+            shared: Arc::new(Mutex::new(NatStatus::default())),
+        };
+        nat.status = nat.current_status();
+        *nat.shared.lock().unwrap() = nat.status.clone();
+        nat
+    }
+
+    /// Returns the handle to the last reported status.
+    pub(crate) fn shared_status(&self) -> SharedNatStatus {
+        self.shared.clone()
+    }
+
+    #[cfg(feature = "port_mapping")]
+    fn start_port_mapping(port: u16) -> Option<mpsc::UnboundedReceiver<Option<Multiaddr>>> {
+        Some(port_mapping::spawn(port))
+    }
+
+    #[cfg(not(feature = "port_mapping"))]
+    fn start_port_mapping(_port: u16) -> Option<mpsc::UnboundedReceiver<Option<Multiaddr>>> {
+        warn!(target: "stegos_network::nat", "network.port_mapping is ignored: compiled without port_mapping feature");
+        None
+    }
+
+    /// Registers the address of this node reported by a peer.
+    pub(crate) fn observed(&mut self, peer_id: PeerId, address: &Multiaddr) {
+        let ip = match address.iter().next() {
+            Some(Protocol::Ip4(ip)) => IpAddr::V4(ip),
+            Some(Protocol::Ip6(ip)) => IpAddr::V6(ip),
+            _ => return,
+        };
+        if ip.is_loopback() || ip.is_unspecified() {
+            return;
+        }
+        debug!(target: "stegos_network::nat", "observed address: peer_id={}, ip={}", peer_id, ip);
+        self.observed.insert(peer_id, ip);
+    }
+
+    /// Forgets about a disconnected peer.
+    pub(crate) fn remove_peer(&mut self, peer_id: &PeerId) {
+        self.observed.remove(peer_id);
+        self.probes.remove(peer_id);
+    }
+
+    /// A peer announced that it is going to connect back to us.
+    pub(crate) fn probe_started(&mut self, peer_id: PeerId, now: Instant) {
+        if self.port.is_some() {
+            self.probes.insert(peer_id, now + PROBE_TIMEOUT);
+        }
+    }
+
+    /// A peer has connected back to us.
+    pub(crate) fn probe_succeeded(&mut self, peer_id: &PeerId) {
+        self.probes.remove(peer_id);
+        if self.port.is_some() {
+            self.failed_probes = 0;
+            self.reachability = Reachability::Public;
+        }
+    }
+
+    /// Counts back-connects which haven't arrived in time.
+    pub(crate) fn expire_probes(&mut self, now: Instant) {
+        let before = self.probes.len();
+        self.probes.retain(|_, deadline| *deadline > now);
+        self.failed_probes += (before - self.probes.len()) as u32;
+        if self.failed_probes >= MAX_FAILED_PROBES {
+            self.reachability = Reachability::Private;
+        }
+    }
+
+    /// Observed addresses with the listening port, most reported first.
+    fn observed_addresses(&self) -> Vec<Multiaddr> {
+        let mut counts: HashMap<IpAddr, usize> = HashMap::new();
+        for ip in self.observed.values() {
+            *counts.entry(*ip).or_insert(0) += 1;
+        }
+        let mut counts: Vec<(IpAddr, usize)> = counts.into_iter().collect();
+        counts.sort_by(|(ip1, count1), (ip2, count2)| count2.cmp(count1).then(ip1.cmp(ip2)));
+        counts
+            .into_iter()
+            .map(|(ip, _)| match self.port {
+                Some(port) => socket_to_multi_addr(&SocketAddr::new(ip, port)),
+                None => ip.into(),
+            })
+            .collect()
+    }
+
+    fn current_status(&self) -> NatStatus {
+        let observed_addresses = self.observed_addresses();
+        let advertised_address = if self.manual.is_some() {
+            self.manual.clone()
+        } else if self.reachability == Reachability::Public {
+            self.mapped
+                .clone()
+                .or_else(|| observed_addresses.first().cloned())
+        } else {
+            None
+        };
+        NatStatus {
+            reachability: self.reachability,
+            advertised_address,
+            observed_addresses,
+            mapped_address: self.mapped.clone(),
+        }
+    }
+
+    /// Returns the new status, if it has changed since the last call.
+    pub(crate) fn poll(&mut self, cx: &mut Context, now: Instant) -> Poll<NatStatus> {
+        if let Some(mapped_rx) = &mut self.mapped_rx {
+            while let Poll::Ready(Some(mapped)) = mapped_rx.poll_next_unpin(cx) {
+                self.mapped = mapped;
+            }
+        }
+        self.expire_probes(now);
+
+        let status = self.current_status();
+        if status == self.status {
+            return Poll::Pending;
+        }
+        if status.reachability != self.status.reachability
+            || status.advertised_address != self.status.advertised_address
+        {
+            info!(target: "stegos_network::nat", "Reachability changed: reachability={:?}, advertised_address={:?}",
+                  status.reachability, status.advertised_address);
+        }
+        self.status = status.clone();
+        *self.shared.lock().unwrap() = status.clone();
+        Poll::Ready(status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> NetworkConfig {
+        NetworkConfig {
+            endpoint: "0.0.0.0:10203".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn observed_addresses() {
+        let mut nat = Nat::new(&config());
+        let (peer1, peer2, peer3) = (PeerId::random(), PeerId::random(), PeerId::random());
+        nat.observed(peer1.clone(), &"/ip4/1.2.3.4/tcp/55555".parse().unwrap());
+        nat.observed(peer2.clone(), &"/ip4/5.6.7.8/tcp/44444".parse().unwrap());
+        nat.observed(peer3.clone(), &"/ip4/5.6.7.8/tcp/33333".parse().unwrap());
+        nat.observed(peer3.clone(), &"/ip4/127.0.0.1/tcp/33333".parse().unwrap());
+        let expected: Vec<Multiaddr> = vec![
+            "/ip4/5.6.7.8/tcp/10203".parse().unwrap(),
+            "/ip4/1.2.3.4/tcp/10203".parse().unwrap(),
+        ];
+        let status = nat.current_status();
+        assert_eq!(status.observed_addresses, expected);
+        assert_eq!(status.reachability, Reachability::Unknown);
+        // Not advertised until confirmed by a back-connect.
+        assert_eq!(status.advertised_address, None);
+
+        nat.probe_started(peer1.clone(), Instant::now());
+        nat.probe_succeeded(&peer1);
+        let status = nat.current_status();
+        assert_eq!(status.reachability, Reachability::Public);
+        assert_eq!(status.advertised_address, Some(expected[0].clone()));
+
+        nat.remove_peer(&peer2);
+        nat.remove_peer(&peer3);
+        let status = nat.current_status();
+        assert_eq!(status.advertised_address, Some(expected[1].clone()));
+    }
+
+    #[test]
+    fn probes() {
+        let mut nat = Nat::new(&config());
+        let now = Instant::now();
+        for _ in 0..MAX_FAILED_PROBES {
+            nat.probe_started(PeerId::random(), now);
+        }
+        let peer_id = PeerId::random();
+        nat.probe_started(peer_id.clone(), now + PROBE_TIMEOUT);
+        nat.expire_probes(now + PROBE_TIMEOUT);
+        assert_eq!(nat.reachability, Reachability::Private);
+
+        nat.probe_succeeded(&peer_id);
+        assert_eq!(nat.reachability, Reachability::Public);
+        assert_eq!(nat.failed_probes, 0);
+    }
+
+    #[test]
+    fn manual() {
+        let config = NetworkConfig {
+            advertised_endpoint: "1.2.3.4:10203".to_string(),
+            ..config()
+        };
+        let nat = Nat::new(&config);
+        let status = nat.current_status();
+        assert_eq!(status.reachability, Reachability::Unknown);
+        assert_eq!(
+            status.advertised_address,
+            Some("/ip4/1.2.3.4/tcp/10203".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn not_listening() {
+        let config = NetworkConfig {
+            listen: false,
+            ..config()
+        };
+        let mut nat = Nat::new(&config);
+        nat.observed(PeerId::random(), &"/ip4/1.2.3.4/tcp/55555".parse().unwrap());
+        nat.probe_started(PeerId::random(), Instant::now());
+        assert!(nat.probes.is_empty());
+        let status = nat.current_status();
+        assert_eq!(status.reachability, Reachability::Private);
+        assert_eq!(status.advertised_address, None);
+        assert_eq!(
+            status.observed_addresses,
+            vec!["/ip4/1.2.3.4".parse::<Multiaddr>().unwrap()]
+        );
+    }
+}
@@ -0,0 +1,143 @@
+//
+// MIT License
+//
+// Copyright (c) 2018-2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Port mapping on the gateway by UPnP IGD or NAT-PMP.
+
+use crate::utils::socket_to_multi_addr;
+use crate::Multiaddr;
+use failure::{format_err, Error};
+use futures::channel::mpsc;
+use log::*;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Lifetime of a mapping, renewed after a half of it.
+const LEASE_DURATION: Duration = Duration::from_secs(60 * 60);
+/// Delay between attempts if the gateway doesn't support mapping.
+const RETRY_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// How long to wait for NAT-PMP response.
+const NATPMP_TIMEOUT: Duration = Duration::from_secs(10);
+/// Description of UPnP mapping.
+const DESCRIPTION: &str = "stegos";
+
+/// Starts a thread which keeps `port` mapped on the gateway.
+/// The external address is sent after every attempt, None if mapping has failed.
+pub(super) fn spawn(port: u16) -> mpsc::UnboundedReceiver<Option<Multiaddr>> {
+    let (tx, rx) = mpsc::unbounded();
+    thread::Builder::new()
+        .name("port-mapping".to_string())
+        .spawn(move || loop {
+            let (address, delay) = match map_port(port) {
+                Ok(address) => {
+                    debug!(target: "stegos_network::nat", "mapped port on the gateway: port={}, address={}", port, address);
+                    let address = socket_to_multi_addr(&SocketAddr::V4(address));
+                    (Some(address), LEASE_DURATION / 2)
+                }
+                Err(e) => {
+                    warn!(target: "stegos_network::nat", "Failed to map port on the gateway: port={}, error={}", port, e);
+                    (None, RETRY_INTERVAL)
+                }
+            };
+            if tx.unbounded_send(address).is_err() {
+                // Network has been stopped.
+                break;
+            }
+            thread::sleep(delay);
+        })
+        .expect("failed to start port mapping thread");
+    rx
+}
+
+fn map_port(port: u16) -> Result<SocketAddrV4, Error> {
+    match map_port_upnp(port) {
+        Ok(address) => Ok(address),
+        Err(e) => {
+            debug!(target: "stegos_network::nat", "UPnP is not available: {}", e);
+            map_port_natpmp(port)
+        }
+    }
+}
+
+fn map_port_upnp(port: u16) -> Result<SocketAddrV4, Error> {
+    let gateway = igd::search_gateway(Default::default())?;
+    let local_ip = local_ip(*gateway.addr.ip())?;
+    let external_ip = gateway.get_external_ip()?;
+    gateway.add_port(
+        igd::PortMappingProtocol::TCP,
+        port,
+        SocketAddrV4::new(local_ip, port),
+        LEASE_DURATION.as_secs() as u32,
+        DESCRIPTION,
+    )?;
+    Ok(SocketAddrV4::new(external_ip, port))
+}
+
+fn map_port_natpmp(port: u16) -> Result<SocketAddrV4, Error> {
+    let mut natpmp = natpmp::Natpmp::new().map_err(natpmp_error)?;
+    natpmp.send_public_address_request().map_err(natpmp_error)?;
+    let external_ip = match natpmp_response(&mut natpmp)? {
+        natpmp::Response::Gateway(response) => *response.public_address(),
+        _ => return Err(format_err!("NAT-PMP: unexpected response")),
+    };
+    natpmp
+        .send_port_mapping_request(
+            natpmp::Protocol::TCP,
+            port,
+            port,
+            LEASE_DURATION.as_secs() as u32,
+        )
+        .map_err(natpmp_error)?;
+    let external_port = match natpmp_response(&mut natpmp)? {
+        natpmp::Response::TCP(response) => response.public_port(),
+        _ => return Err(format_err!("NAT-PMP: unexpected response")),
+    };
+    Ok(SocketAddrV4::new(external_ip, external_port))
+}
+
+fn natpmp_response(natpmp: &mut natpmp::Natpmp) -> Result<natpmp::Response, Error> {
+    let deadline = Instant::now() + NATPMP_TIMEOUT;
+    loop {
+        match natpmp.read_response_or_retry() {
+            Ok(response) => return Ok(response),
+            Err(natpmp::Error::NATPMP_TRYAGAIN) if Instant::now() < deadline => {
+                thread::sleep(Duration::from_millis(100));
+            }
+            Err(e) => return Err(natpmp_error(e)),
+        }
+    }
+}
+
+fn natpmp_error(error: natpmp::Error) -> Error {
+    format_err!("NAT-PMP: {:?}", error)
+}
+
+/// Returns the local address used to reach the gateway.
+fn local_ip(gateway: Ipv4Addr) -> Result<Ipv4Addr, Error> {
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    socket.connect((gateway, 1))?;
+    match socket.local_addr()?.ip() {
+        IpAddr::V4(ip) => Ok(ip),
+        ip => Err(format_err!("Unexpected local address: {}", ip)),
+    }
+}
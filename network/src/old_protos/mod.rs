@@ -21,9 +21,13 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
+//! Protocols of the previous releases: floodsub, NCP and the forked Kademlia.
+//!
+//! Enabled by the `old_protos` feature to keep talking to nodes which haven't been
+//! upgraded yet. Will be removed in the next release.
+
 #![allow(unused)]
 
-pub mod delivery;
 pub mod discovery;
 pub mod kad;
 pub mod ncp;
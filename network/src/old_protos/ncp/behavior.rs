@@ -24,7 +24,7 @@
 use futures::prelude::*;
 use futures::task::{Context, Poll};
 use libp2p_core::connection::ConnectionId;
-use libp2p_core::{multiaddr::Protocol, Multiaddr, PeerId};
+use libp2p_core::{multiaddr::Protocol, ConnectedPoint, Multiaddr, PeerId};
 use libp2p_swarm::{
     protocols_handler::ProtocolsHandler, NetworkBehaviour, NetworkBehaviourAction, NotifyHandler,
     PollParameters,
@@ -32,13 +32,14 @@ use libp2p_swarm::{
 use log::*;
 use lru_time_cache::LruCache;
 use rand::{thread_rng, Rng};
-use serde_derive::{Deserialize, Serialize};
 use smallvec::SmallVec;
-use std::{collections::VecDeque, net::Ipv4Addr};
+use std::collections::{HashMap, VecDeque};
+use std::net::Ipv4Addr;
 use stegos_crypto::pbc;
 use tokio::time::{self, Delay, Duration, Instant};
 
 use crate::config::NetworkConfig;
+use crate::dht::NodeInfo;
 use crate::ncp::handler::NcpHandler;
 use crate::ncp::protocol::{GetPeersResponse, NcpMessage, PeerInfo};
 use crate::utils::{socket_to_multi_addr, ExpiringQueue};
@@ -67,6 +68,8 @@ pub struct Ncp {
     out_events: VecDeque<NcpOutEvent>,
     /// List of connected peers (including disabled)
     connected_peers: ExpiringQueue<PeerId, Instant>,
+    /// Addresses of connected peers as seen by us
+    remote_addresses: HashMap<PeerId, Multiaddr>,
     /// Known peers
     known_peers: LruCache<Vec<u8>, (pbc::PublicKey, SmallVec<[Multiaddr; 16]>)>,
     /// Maximum connections allowd
@@ -81,17 +84,6 @@ pub struct Ncp {
     seed_nodes: Vec<Multiaddr>,
 }
 
-/// Node Info struct for passing to API
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct NodeInfo {
-    /// Libp2p PeerId of the node
-    peer_id: String,
-    ///Network Public Key of the node
-    network_pkey: pbc::PublicKey,
-    /// Known addresses of the node
-    addresses: Vec<Multiaddr>,
-}
-
 impl Ncp {
     /// Creates a NetworkBehaviour for NCP.
     pub fn new(config: &NetworkConfig, network_pkey: pbc::PublicKey) -> Self {
@@ -124,6 +116,7 @@ impl Ncp {
             events: VecDeque::new(),
             out_events: VecDeque::new(),
             connected_peers: ExpiringQueue::new(IDLE_TIMEOUT),
+            remote_addresses: HashMap::new(),
             known_peers:
                 LruCache::<Vec<u8>, (pbc::PublicKey, SmallVec<[Multiaddr; 16]>)>::with_capacity(
                     KNOWN_PEERS_TABLE_SIZE,
@@ -148,6 +141,19 @@ impl Ncp {
         }
     }
 
+    /// Changes the advertised address and notifies connected peers.
+    pub fn set_advertised_endpoint(&mut self, endpoint: Option<Multiaddr>) {
+        if self.advertised_endpoint == endpoint {
+            return;
+        }
+        debug!(target: "stegos_network::ncp", "Public Network endpoint: {:?}", endpoint);
+        self.advertised_endpoint = endpoint;
+        for p in self.connected_peers.keys() {
+            self.events
+                .push_back(NcpEvent::SendPeers { peer_id: p.clone() });
+        }
+    }
+
     // Terminate connection to peer
     pub fn terminate(&mut self, peer_id: PeerId) {
         debug!(target: "stegos_network::ncp", "terminating connection with peer: peer_id={}", peer_id);
@@ -158,11 +164,7 @@ impl Ncp {
         let mut nodes: Vec<NodeInfo> = vec![];
         for p in self.connected_peers.keys() {
             if let Some(info) = self.known_peers.peek(p.as_bytes()) {
-                nodes.push(NodeInfo {
-                    peer_id: p.to_base58(),
-                    network_pkey: info.0,
-                    addresses: info.1.to_vec(),
-                });
+                nodes.push(NodeInfo::new(p, info.0, info.1.to_vec()));
             }
         }
         nodes
@@ -197,9 +199,23 @@ impl NetworkBehaviour for Ncp {
         self.connected_peers.insert(id.clone(), Instant::now());
     }
 
+    fn inject_connection_established(
+        &mut self,
+        id: &PeerId,
+        _: &ConnectionId,
+        endpoint: &ConnectedPoint,
+    ) {
+        let address = match endpoint {
+            ConnectedPoint::Dialer { address } => address,
+            ConnectedPoint::Listener { send_back_addr, .. } => send_back_addr,
+        };
+        self.remote_addresses.insert(id.clone(), address.clone());
+    }
+
     fn inject_disconnected(&mut self, id: &PeerId) {
         debug!(target: "stegos_network::ncp", "peer disconnected: peer_id={}", id.to_base58());
         self.connected_peers.remove(id);
+        self.remote_addresses.remove(id);
         self.known_peers.remove(id.as_bytes());
         self.out_events.push_back(NcpOutEvent::Disconnected {
             peer_id: id.clone(),
@@ -325,6 +341,12 @@ impl NetworkBehaviour for Ncp {
             match event {
                 NcpEvent::StorePeers { from, message } => {
                     debug!(target: "stegos_network::ncp", "received peers: from_peer={}", from.to_base58());
+                    if let Some(address) = message.observed_address {
+                        self.out_events.push_back(NcpOutEvent::ObservedAddress {
+                            peer_id: from,
+                            address,
+                        });
+                    }
                     for peer in message.peers.into_iter() {
                         if peer.peer_id != *poll_parameters.local_peer_id() {
                             let id = peer.peer_id.clone();
@@ -370,7 +392,10 @@ impl NetworkBehaviour for Ncp {
                 }
                 NcpEvent::SendPeers { peer_id } => {
                     debug!(target: "stegos_network::ncp", "sending peers info: to_peer={}", peer_id.to_base58());
-                    let mut response = GetPeersResponse { peers: vec![] };
+                    let mut response = GetPeersResponse {
+                        peers: vec![],
+                        observed_address: self.remote_addresses.get(&peer_id).cloned(),
+                    };
                     let mut connected: Vec<PeerId> = self.connected_peers.keys().cloned().collect();
                     for peer in connected.drain(..) {
                         if peer == peer_id {
@@ -486,6 +511,11 @@ pub enum NcpOutEvent {
     Disconnected {
         peer_id: PeerId,
     },
+    /// Peer reported the address it sees us at.
+    ObservedAddress {
+        peer_id: PeerId,
+        address: Multiaddr,
+    },
 }
 
 /// Event passed to protocol handler
@@ -131,6 +131,9 @@ impl Encoder for NcpCodec {
                     }
                     msg.mut_peers().push(peer_info);
                 }
+                if let Some(addr) = response.observed_address {
+                    msg.set_observed_addr(addr.to_vec());
+                }
 
                 msg
             }
@@ -183,7 +186,15 @@ impl Decoder for NcpCodec {
             ncp_proto::Message_MessageType::GET_PEERS_REQ => Ok(Some(NcpMessage::GetPeersRequest)),
 
             ncp_proto::Message_MessageType::GET_PEERS_RES => {
-                let mut response = GetPeersResponse { peers: vec![] };
+                let observed_address = if !message.get_observed_addr().is_empty() {
+                    Multiaddr::try_from(message.get_observed_addr().to_vec()).ok()
+                } else {
+                    None
+                };
+                let mut response = GetPeersResponse {
+                    peers: vec![],
+                    observed_address,
+                };
                 for peer in message.get_peers().iter() {
                     let peer_id =
                         PeerId::from_bytes(peer.get_peer_id().to_vec()).map_err(|_| {
@@ -239,6 +250,8 @@ pub struct PeerInfo {
 #[derive(Debug, Clone, PartialEq)]
 pub struct GetPeersResponse {
     pub peers: Vec<PeerInfo>,
+    /// Address of the receiver as seen by the sender.
+    pub observed_address: Option<Multiaddr>,
 }
 
 impl PeerInfo {
@@ -23,6 +23,9 @@ use super::metrics;
 use super::protocol::{
     FloodsubMessage, FloodsubRpc, FloodsubSubscription, FloodsubSubscriptionAction,
 };
+use crate::bandwidth::SharedBandwidth;
+use crate::traffic::{self, Direction};
+use crate::utils::message_key;
 
 use futures::prelude::*;
 use futures::task::{Context, Poll};
@@ -43,6 +46,8 @@ use update_rate::{RateCounter, RollingRateCounter};
 const PUBSUB_SAMPLES: u64 = 100;
 const METRICS_UPDATE_INTERVAL: Duration = Duration::from_secs(5);
 const LRU_EXPIRE_TIME: Duration = Duration::from_secs(60); // 1 minute to allow transaction retransmit
+const TRAFFIC_LABEL: &str = "floodsub";
+const PENDING_VALIDATION_CAPACITY: usize = 16384;
 
 /// Network behaviour that automatically identifies nodes periodically, and returns information
 /// about them.
@@ -75,11 +80,20 @@ pub struct Floodsub {
 
     /// Do we relay (disabled on edge nodes)
     relaying: bool,
+
+    /// Bandwidth limits
+    bandwidth: SharedBandwidth,
+
+    /// Topics which are forwarded only after validation by the user.
+    validated_topics: HashSet<String>,
+
+    /// Messages waiting for validation, by (topic, message_key).
+    pending_validation: LruCache<(String, u64), (FloodsubMessage, PeerId)>,
 }
 
 impl Floodsub {
     /// Creates a `Floodsub`.
-    pub fn new(relaying: bool) -> Self {
+    pub fn new(relaying: bool, bandwidth: SharedBandwidth) -> Self {
         Floodsub {
             events: VecDeque::new(),
             connected_peers: HashSet::new(),
@@ -90,6 +104,12 @@ impl Floodsub {
             incoming_rates: HashMap::new(),
             metrics_update_delay: tokio::time::delay_for(METRICS_UPDATE_INTERVAL),
             relaying,
+            bandwidth,
+            validated_topics: HashSet::new(),
+            pending_validation: LruCache::with_expiry_duration_and_capacity(
+                LRU_EXPIRE_TIME,
+                PENDING_VALIDATION_CAPACITY,
+            ),
         }
     }
 }
@@ -122,6 +142,83 @@ impl Floodsub {
         true
     }
 
+    /// Forward messages of the topic only after `report_message_validation_result()`.
+    pub fn enable_validation(&mut self, topic: &str) {
+        self.validated_topics.insert(topic.to_string());
+    }
+
+    /// Report the result of validation of a message received on a validated topic.
+    ///
+    /// Accepted messages are forwarded to other peers, the rest are dropped.
+    /// Returns false if the message is not waiting for validation.
+    pub fn report_message_validation_result(
+        &mut self,
+        topic: &str,
+        message_key: u64,
+        accept: bool,
+    ) -> bool {
+        let (message, propagation_source) = match self
+            .pending_validation
+            .remove(&(topic.to_string(), message_key))
+        {
+            Some(pending) => pending,
+            None => return false,
+        };
+        if accept {
+            let mut rpcs_to_dispatch: Vec<(PeerId, FloodsubRpc)> = Vec::new();
+            self.forward_message(&message, &propagation_source, &mut rpcs_to_dispatch);
+            for (peer_id, rpc) in rpcs_to_dispatch {
+                self.events
+                    .push_back(NetworkBehaviourAction::NotifyHandler {
+                        peer_id,
+                        handler: NotifyHandler::Any,
+                        event: FloodsubSendEvent::Publish(rpc),
+                    });
+            }
+        }
+        true
+    }
+
+    /// Propagate the message to everyone else who is subscribed to the topic.
+    fn forward_message(
+        &self,
+        message: &FloodsubMessage,
+        propagation_source: &PeerId,
+        rpcs_to_dispatch: &mut Vec<(PeerId, FloodsubRpc)>,
+    ) {
+        for (peer_id, subscr_topics) in self.unlocked_remotes.iter() {
+            if peer_id == propagation_source {
+                continue;
+            }
+
+            if !subscr_topics.iter().any(|t| t == &message.topic) {
+                continue;
+            }
+
+            if !self.bandwidth.lock().unwrap().admit(
+                Some(peer_id),
+                Direction::Outgoing,
+                &message.topic,
+                message.data.len(),
+            ) {
+                debug!(target: "stegos_network::pubsub", "upload limit exceeded, not forwarding message: peer_id={}, topic={}", peer_id, message.topic);
+                continue;
+            }
+
+            if let Some(pos) = rpcs_to_dispatch.iter().position(|(p, _)| p == peer_id) {
+                rpcs_to_dispatch[pos].1.messages.push(message.clone());
+            } else {
+                rpcs_to_dispatch.push((
+                    peer_id.clone(),
+                    FloodsubRpc {
+                        subscriptions: Vec::new(),
+                        messages: vec![message.clone()],
+                    },
+                ));
+            }
+        }
+    }
+
     /// Publishes a message to the network.
     ///
     /// > **Note**: Doesn't do anything if we're not subscribed to the topic.
@@ -143,6 +240,12 @@ impl Floodsub {
             }
 
             trace!(target: "stegos_network::pubsub", "sending message to peer: peer_id={}", peer_id);
+            // Own messages are never dropped.
+            self.bandwidth.lock().unwrap().charge(
+                Some(peer_id),
+                Direction::Outgoing,
+                message.data.len(),
+            );
             self.events
                 .push_back(NetworkBehaviourAction::NotifyHandler {
                     peer_id: peer_id.clone(),
@@ -298,6 +401,22 @@ impl NetworkBehaviour for Floodsub {
                 let mut rpcs_to_dispatch: Vec<(PeerId, FloodsubRpc)> = Vec::new();
 
                 for message in event.messages {
+                    traffic::record(
+                        TRAFFIC_LABEL,
+                        Some(&message.topic),
+                        Some(&propagation_source),
+                        Direction::Incoming,
+                        message.data.len(),
+                    );
+                    if !self.bandwidth.lock().unwrap().admit(
+                        Some(&propagation_source),
+                        Direction::Incoming,
+                        &message.topic,
+                        message.data.len(),
+                    ) {
+                        debug!(target: "stegos_network::pubsub", "download limit exceeded, dropping message: peer_id={}, topic={}", propagation_source, message.topic);
+                        continue;
+                    }
                     // Use `self.received` to skip the messages that we have already received in the past.
                     // Note that this can false positive.
                     if self.received.contains_key(&message.digest()) {
@@ -323,28 +442,16 @@ impl NetworkBehaviour for Floodsub {
                         return;
                     }
 
-                    // Propagate the message to everyone else who is subscribed to any of the topics.
-                    for (peer_id, subscr_topics) in self.unlocked_remotes.iter() {
-                        if peer_id == &propagation_source {
-                            continue;
-                        }
-
-                        if !subscr_topics.iter().any(|t| t == &message.topic) {
-                            continue;
-                        }
-
-                        if let Some(pos) = rpcs_to_dispatch.iter().position(|(p, _)| p == peer_id) {
-                            rpcs_to_dispatch[pos].1.messages.push(message.clone());
-                        } else {
-                            rpcs_to_dispatch.push((
-                                peer_id.clone(),
-                                FloodsubRpc {
-                                    subscriptions: Vec::new(),
-                                    messages: vec![message.clone()],
-                                },
-                            ));
-                        }
+                    // Wait for validation before forwarding.
+                    if self.validated_topics.contains(&message.topic) {
+                        let key = (message.topic.clone(), message_key(&message.data));
+                        self.pending_validation
+                            .insert(key, (message, propagation_source.clone()));
+                        continue;
                     }
+
+                    // Propagate the message to everyone else who is subscribed to any of the topics.
+                    self.forward_message(&message, &propagation_source, &mut rpcs_to_dispatch);
                 }
 
                 for (peer_id, rpc) in rpcs_to_dispatch {
@@ -380,6 +487,22 @@ impl NetworkBehaviour for Floodsub {
         }
 
         if let Some(event) = self.events.pop_front() {
+            if let NetworkBehaviourAction::NotifyHandler {
+                peer_id,
+                event: FloodsubSendEvent::Publish(rpc),
+                ..
+            } = &event
+            {
+                for message in &rpc.messages {
+                    traffic::record(
+                        TRAFFIC_LABEL,
+                        Some(&message.topic),
+                        Some(peer_id),
+                        Direction::Outgoing,
+                        message.data.len(),
+                    );
+                }
+            }
             return Poll::Ready(event);
         }
 
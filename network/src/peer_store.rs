@@ -0,0 +1,328 @@
+//
+// MIT License
+//
+// Copyright (c) 2018-2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Persistent store of known peers.
+
+use crate::libp2p_network::{Multiaddr, PeerId};
+use failure::{format_err, Error};
+use log::*;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use stegos_crypto::pbc;
+
+/// Maximal number of peers kept in the store.
+const MAX_PEERS: usize = 1024;
+/// Peers which haven't been seen for so long are forgotten.
+const PEER_EXPIRATION: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Information about a known peer.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PeerRecord {
+    /// Network public key of the node.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node_id: Option<pbc::PublicKey>,
+    /// Known addresses of the peer.
+    pub addresses: Vec<String>,
+    /// Last time when the peer was discovered or connected (UNIX timestamp, secs).
+    pub last_seen: u64,
+    /// Number of successful connections.
+    pub successes: u32,
+    /// Number of failed dial attempts.
+    pub failures: u32,
+}
+
+impl PeerRecord {
+    fn new(now: u64) -> Self {
+        PeerRecord {
+            node_id: None,
+            addresses: Vec::new(),
+            last_seen: now,
+            successes: 0,
+            failures: 0,
+        }
+    }
+
+    /// Rank used to choose peers to dial, the better peers go first.
+    fn rank(&self) -> (i64, u64) {
+        (self.successes as i64 - self.failures as i64, self.last_seen)
+    }
+}
+
+/// Known peers, saved to disk between restarts.
+pub(crate) struct PeerStore {
+    /// Path to the file, None if the store is not persisted.
+    path: Option<PathBuf>,
+    peers: HashMap<PeerId, PeerRecord>,
+    /// The store has been changed since the last save.
+    dirty: bool,
+}
+
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+impl PeerStore {
+    /// Load the store from file, starts with an empty store on errors.
+    pub fn open(path: Option<PathBuf>) -> Self {
+        let mut store = PeerStore {
+            path,
+            peers: HashMap::new(),
+            dirty: false,
+        };
+        if let Some(path) = &store.path {
+            if path.exists() {
+                match Self::load(path) {
+                    Ok(peers) => {
+                        info!(target: "stegos_network::peer_store",
+                            "Loaded known peers: path={:?}, peers={}",
+                            path,
+                            peers.len()
+                        );
+                        store.peers = peers;
+                    }
+                    Err(e) => {
+                        warn!(target: "stegos_network::peer_store",
+                            "Failed to load known peers: path={:?}, error={}",
+                            path,
+                            e
+                        );
+                    }
+                }
+            }
+        }
+        store.prune(unix_now());
+        store
+    }
+
+    fn load(path: &PathBuf) -> Result<HashMap<PeerId, PeerRecord>, Error> {
+        let data = fs::read(path)?;
+        let records: HashMap<String, PeerRecord> = serde_json::from_slice(&data)?;
+        let mut peers = HashMap::with_capacity(records.len());
+        for (peer_id, record) in records {
+            let peer_id = PeerId::from_str(&peer_id)
+                .map_err(|_| format_err!("Invalid peer_id: {}", peer_id))?;
+            peers.insert(peer_id, record);
+        }
+        Ok(peers)
+    }
+
+    /// Save the store to file, if it has been changed.
+    pub fn save(&mut self) -> Result<(), Error> {
+        let path = match &self.path {
+            Some(path) if self.dirty => path,
+            _ => return Ok(()),
+        };
+        let records: HashMap<String, &PeerRecord> = self
+            .peers
+            .iter()
+            .map(|(peer_id, record)| (peer_id.to_base58(), record))
+            .collect();
+        let data = serde_json::to_vec_pretty(&records)?;
+        // Write to a temporary file first to don't lose the store on crash.
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, data)?;
+        fs::rename(&tmp_path, path)?;
+        debug!(target: "stegos_network::peer_store",
+            "Saved known peers: path={:?}, peers={}",
+            path,
+            self.peers.len()
+        );
+        self.dirty = false;
+        Ok(())
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.peers.len()
+    }
+
+    #[cfg(test)]
+    pub fn get(&self, peer_id: &PeerId) -> Option<&PeerRecord> {
+        self.peers.get(peer_id)
+    }
+
+    /// Remember a peer discovered by the network.
+    pub fn discovered(
+        &mut self,
+        peer_id: PeerId,
+        node_id: pbc::PublicKey,
+        addresses: &[Multiaddr],
+    ) {
+        let now = unix_now();
+        let record = self
+            .peers
+            .entry(peer_id)
+            .or_insert_with(|| PeerRecord::new(now));
+        record.node_id = Some(node_id);
+        for address in addresses {
+            let address = address.to_string();
+            if !record.addresses.contains(&address) {
+                record.addresses.push(address);
+            }
+        }
+        record.last_seen = now;
+        self.dirty = true;
+        if self.peers.len() > MAX_PEERS {
+            self.prune(now);
+        }
+    }
+
+    /// Record a successful connection to the peer.
+    pub fn connected(&mut self, peer_id: &PeerId) {
+        if let Some(record) = self.peers.get_mut(peer_id) {
+            record.successes = record.successes.saturating_add(1);
+            record.last_seen = unix_now();
+            self.dirty = true;
+        }
+    }
+
+    /// Record a failed dial attempt to the peer.
+    pub fn dial_failed(&mut self, peer_id: &PeerId) {
+        if let Some(record) = self.peers.get_mut(peer_id) {
+            record.failures = record.failures.saturating_add(1);
+            self.dirty = true;
+        }
+    }
+
+    /// Returns up to `limit` best peers with their addresses.
+    pub fn best_peers(&self, limit: usize) -> Vec<(PeerId, &PeerRecord, Vec<Multiaddr>)> {
+        let mut peers: Vec<(&PeerId, &PeerRecord)> = self
+            .peers
+            .iter()
+            .filter(|(_, record)| !record.addresses.is_empty())
+            .collect();
+        peers.sort_by(|(_, a), (_, b)| b.rank().cmp(&a.rank()));
+        peers
+            .into_iter()
+            .take(limit)
+            .map(|(peer_id, record)| {
+                let addresses = record
+                    .addresses
+                    .iter()
+                    .filter_map(|a| a.parse::<Multiaddr>().ok())
+                    .collect();
+                (peer_id.clone(), record, addresses)
+            })
+            .collect()
+    }
+
+    /// Forget expired peers and the worst peers above the limit.
+    fn prune(&mut self, now: u64) {
+        let len = self.peers.len();
+        let expiration = PEER_EXPIRATION.as_secs();
+        self.peers
+            .retain(|_, record| record.last_seen + expiration > now);
+        if self.peers.len() > MAX_PEERS {
+            let mut ranks: Vec<(PeerId, (i64, u64))> = self
+                .peers
+                .iter()
+                .map(|(peer_id, record)| (peer_id.clone(), record.rank()))
+                .collect();
+            ranks.sort_by(|(_, a), (_, b)| b.cmp(a));
+            for (peer_id, _) in ranks.into_iter().skip(MAX_PEERS) {
+                self.peers.remove(&peer_id);
+            }
+        }
+        if self.peers.len() != len {
+            self.dirty = true;
+        }
+    }
+}
+
+impl Drop for PeerStore {
+    fn drop(&mut self) {
+        if let Err(e) = self.save() {
+            error!(target: "stegos_network::peer_store", "Failed to save known peers: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path() -> PathBuf {
+        let mut path = std::env::temp_dir();
+        path.push(format!(
+            "stegos-peers-{}.json",
+            PeerId::random().to_base58()
+        ));
+        path
+    }
+
+    #[test]
+    fn persistence() {
+        let path = temp_path();
+        let (_, node_id1) = pbc::make_random_keys();
+        let (_, node_id2) = pbc::make_random_keys();
+        let peer_id1 = PeerId::random();
+        let peer_id2 = PeerId::random();
+        let addr1: Multiaddr = "/ip4/10.0.0.1/tcp/10203".parse().unwrap();
+        let addr2: Multiaddr = "/ip4/10.0.0.2/tcp/10203".parse().unwrap();
+
+        {
+            let mut store = PeerStore::open(Some(path.clone()));
+            assert_eq!(store.len(), 0);
+            store.discovered(peer_id1.clone(), node_id1, &[addr1.clone()]);
+            store.discovered(peer_id1.clone(), node_id1, &[addr1.clone()]);
+            store.discovered(peer_id2.clone(), node_id2, &[addr2.clone()]);
+            store.connected(&peer_id2);
+            store.dial_failed(&peer_id1);
+            // Unknown peers are ignored.
+            store.connected(&PeerId::random());
+            assert_eq!(store.len(), 2);
+            // Saved on drop.
+        }
+
+        let store = PeerStore::open(Some(path.clone()));
+        assert_eq!(store.len(), 2);
+        let record1 = store.get(&peer_id1).unwrap();
+        assert_eq!(record1.node_id, Some(node_id1));
+        assert_eq!(record1.addresses, vec![addr1.to_string()]);
+        assert_eq!(record1.failures, 1);
+        assert_eq!(record1.successes, 0);
+        let best = store.best_peers(1);
+        assert_eq!(best.len(), 1);
+        assert_eq!(best[0].0, peer_id2);
+        assert_eq!(best[0].2, vec![addr2]);
+        drop(store);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn prune() {
+        let mut store = PeerStore::open(None);
+        let (_, node_id) = pbc::make_random_keys();
+        let peer_id = PeerId::random();
+        store.discovered(peer_id, node_id, &[]);
+        store.prune(unix_now() + PEER_EXPIRATION.as_secs());
+        assert_eq!(store.len(), 0);
+    }
+}
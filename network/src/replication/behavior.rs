@@ -19,19 +19,24 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
-use super::handler::{HandlerInEvent, HandlerOutEvent, ReplicationHandler};
+use super::handler::{HandlerInEvent, HandlerOutEvent, ReplicationHandlerProto};
 use super::protocol::ReplicationVersion;
+use crate::bandwidth::SharedBandwidth;
+use crate::traffic;
 use futures::channel::mpsc;
 use futures::task::{Context, Poll};
 use libp2p_core::connection::ConnectionId;
 use libp2p_core::{ConnectedPoint, Multiaddr, PeerId};
 use libp2p_swarm::{
-    protocols_handler::ProtocolsHandler, NetworkBehaviour, NetworkBehaviourAction, NotifyHandler,
-    PollParameters,
+    protocols_handler::{IntoProtocolsHandler, ProtocolsHandler},
+    NetworkBehaviour, NetworkBehaviourAction, NotifyHandler, PollParameters,
 };
 use log::*;
 use std::collections::VecDeque;
 
+// Label for traffic accounting
+const TRAFFIC_LABEL: &str = "replication";
+
 /// Replication event.
 #[derive(Debug)]
 pub enum ReplicationEvent {
@@ -67,17 +72,19 @@ pub enum ReplicationEvent {
 }
 
 /// Replication protocol.
-#[derive(Default)]
 pub struct Replication {
     /// Events that need to be yielded to the outside when polling.
     events: VecDeque<NetworkBehaviourAction<HandlerInEvent, ReplicationEvent>>,
+    /// Bandwidth limits shared with handlers.
+    bandwidth: SharedBandwidth,
 }
 
 impl Replication {
     /// Creates a `Replication`.
-    pub fn new() -> Self {
+    pub fn new(bandwidth: SharedBandwidth) -> Self {
         Replication {
             events: VecDeque::new(),
+            bandwidth,
         }
     }
 
@@ -103,11 +110,11 @@ impl Replication {
 }
 
 impl NetworkBehaviour for Replication {
-    type ProtocolsHandler = ReplicationHandler;
+    type ProtocolsHandler = ReplicationHandlerProto;
     type OutEvent = ReplicationEvent;
 
     fn new_handler(&mut self) -> Self::ProtocolsHandler {
-        ReplicationHandler::new()
+        ReplicationHandlerProto::new(self.bandwidth.clone())
     }
 
     fn addresses_of_peer(&mut self, _peer_id: &PeerId) -> Vec<Multiaddr> {
@@ -177,6 +184,10 @@ impl NetworkBehaviour for Replication {
                 ReplicationEvent::ConnectionFailed { peer_id, error }
             }
             HandlerOutEvent::Accepted { tx, rx } => ReplicationEvent::Accepted { peer_id, tx, rx },
+            HandlerOutEvent::Traffic { counters } => {
+                traffic::record_counters(TRAFFIC_LABEL, Some(""), Some(&peer_id), counters);
+                return;
+            }
         };
         let event = NetworkBehaviourAction::GenerateEvent(event);
         self.events.push_back(event);
@@ -188,10 +199,10 @@ impl NetworkBehaviour for Replication {
         _: &mut impl PollParameters,
     ) -> Poll<
         NetworkBehaviourAction<
-            <Self::ProtocolsHandler as ProtocolsHandler>::InEvent,
+            <<Self::ProtocolsHandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::InEvent,
             Self::OutEvent,
         >,
-    > {
+>{
         if let Some(event) = self.events.pop_front() {
             trace!("Generated event: {:?}", event);
             return Poll::Ready(event);
@@ -20,13 +20,17 @@
 // SOFTWARE.
 
 use super::protocol::{ReplicationCodec, ReplicationConfig, ReplicationVersion};
+use crate::bandwidth::{SharedBandwidth, Throttled};
+use crate::traffic::{Direction, TrafficCounters, TrafficMeter};
 
 use futures::prelude::*;
 use futures::sink::SinkExt;
 use futures::task::{Context, Poll};
 use libp2p_core::upgrade::{InboundUpgrade, OutboundUpgrade};
+use libp2p_core::{ConnectedPoint, PeerId};
 use libp2p_swarm::protocols_handler::{
-    KeepAlive, ProtocolsHandler, ProtocolsHandlerEvent, ProtocolsHandlerUpgrErr, SubstreamProtocol,
+    IntoProtocolsHandler, KeepAlive, ProtocolsHandler, ProtocolsHandlerEvent,
+    ProtocolsHandlerUpgrErr, SubstreamProtocol,
 };
 use libp2p_swarm::NegotiatedSubstream;
 
@@ -35,6 +39,7 @@ use std::fmt;
 use std::io;
 use std::io::ErrorKind;
 use std::pin::Pin;
+use std::sync::Arc;
 
 use derivative::Derivative;
 use futures::channel::mpsc;
@@ -68,6 +73,9 @@ pub enum HandlerOutEvent {
         tx: mpsc::Sender<Vec<u8>>,
         rx: mpsc::Receiver<Vec<u8>>,
     },
+    Traffic {
+        counters: TrafficCounters,
+    },
 }
 
 /// State of an active substream, opened either by us or by the remote.
@@ -106,6 +114,9 @@ impl SubstreamState {
     fn poll_unpin(
         &mut self,
         cx: &mut Context,
+        meter: &Arc<TrafficMeter>,
+        peer_id: &PeerId,
+        bandwidth: &SharedBandwidth,
     ) -> Poll<(mpsc::Sender<Vec<u8>>, mpsc::Receiver<Vec<u8>>)> {
         match self {
             SubstreamState::Registered
@@ -119,11 +130,39 @@ impl SubstreamState {
                 };
 
                 let (net_tx, net_rx) = protocol.split();
+                let net_rx = net_rx.inspect({
+                    let meter = meter.clone();
+                    move |r| {
+                        if let Ok(data) = r {
+                            meter.add(Direction::Incoming, data.len());
+                        }
+                    }
+                });
+                let net_rx = Throttled::new(
+                    net_rx,
+                    bandwidth.clone(),
+                    peer_id.clone(),
+                    Direction::Incoming,
+                    |r| r.as_ref().map(|data| data.len()).unwrap_or(0),
+                );
                 let (node_tx, rx) = mpsc::channel::<Vec<u8>>(INPUT_BUFFER_SIZE);
                 let (tx, node_rx) = mpsc::channel::<Vec<u8>>(OUTPUT_BUFFER_SIZE);
                 let node_tx =
                     node_tx.sink_map_err(|_e| io::Error::new(ErrorKind::Other, "forward error"));
-                let node_rx = node_rx.map(Ok);
+                let node_rx = Throttled::new(
+                    node_rx,
+                    bandwidth.clone(),
+                    peer_id.clone(),
+                    Direction::Outgoing,
+                    |data| data.len(),
+                );
+                let node_rx = node_rx.map({
+                    let meter = meter.clone();
+                    move |data| {
+                        meter.add(Direction::Outgoing, data.len());
+                        Ok(data)
+                    }
+                });
                 let rx_forward = Box::pin(net_rx.forward(node_tx));
                 let tx_forward = Box::pin(node_rx.forward(net_tx));
 
@@ -180,23 +219,49 @@ pub struct ReplicationHandler {
     upstream: SubstreamState,
     downstream: SubstreamState,
     resolved_version: Option<ReplicationVersion>,
+    /// Traffic of both substreams, reported to the behaviour.
+    meter: Arc<TrafficMeter>,
+    /// Remote peer.
+    peer_id: PeerId,
+    /// Bandwidth limits.
+    bandwidth: SharedBandwidth,
 }
 
 impl ReplicationHandler {
     /// Builds a new `ReplicationHandler`.
-    pub fn new() -> Self {
+    pub fn new(peer_id: PeerId, bandwidth: SharedBandwidth) -> Self {
         ReplicationHandler {
             config: ReplicationConfig::new(),
             upstream: SubstreamState::Registered,
             downstream: SubstreamState::Registered,
             resolved_version: None,
+            meter: Arc::new(TrafficMeter::default()),
+            peer_id,
+            bandwidth,
         }
     }
 }
 
-impl Default for ReplicationHandler {
-    fn default() -> Self {
-        Self::new()
+/// Prototype of `ReplicationHandler`, the handler is built when the remote peer is known.
+pub struct ReplicationHandlerProto {
+    bandwidth: SharedBandwidth,
+}
+
+impl ReplicationHandlerProto {
+    pub fn new(bandwidth: SharedBandwidth) -> Self {
+        ReplicationHandlerProto { bandwidth }
+    }
+}
+
+impl IntoProtocolsHandler for ReplicationHandlerProto {
+    type Handler = ReplicationHandler;
+
+    fn into_handler(self, remote_peer_id: &PeerId, _: &ConnectedPoint) -> Self::Handler {
+        ReplicationHandler::new(remote_peer_id.clone(), self.bandwidth)
+    }
+
+    fn inbound_protocol(&self) -> <Self::Handler as ProtocolsHandler>::InboundProtocol {
+        ReplicationConfig::new()
     }
 }
 
@@ -328,7 +393,10 @@ impl ProtocolsHandler for ReplicationHandler {
             ));
         }
 
-        if let Poll::Ready(r) = self.upstream.poll_unpin(cx) {
+        if let Poll::Ready(r) =
+            self.upstream
+                .poll_unpin(cx, &self.meter, &self.peer_id, &self.bandwidth)
+        {
             let (tx, rx) = r;
             return Poll::Ready(ProtocolsHandlerEvent::Custom(HandlerOutEvent::Connected {
                 rx,
@@ -336,7 +404,10 @@ impl ProtocolsHandler for ReplicationHandler {
             }));
         }
 
-        if let Poll::Ready(r) = self.downstream.poll_unpin(cx) {
+        if let Poll::Ready(r) =
+            self.downstream
+                .poll_unpin(cx, &self.meter, &self.peer_id, &self.bandwidth)
+        {
             let (tx, rx) = r;
             return Poll::Ready(ProtocolsHandlerEvent::Custom(HandlerOutEvent::Accepted {
                 rx,
@@ -344,6 +415,12 @@ impl ProtocolsHandler for ReplicationHandler {
             }));
         }
 
+        if let Some(counters) = self.meter.take() {
+            return Poll::Ready(ProtocolsHandlerEvent::Custom(HandlerOutEvent::Traffic {
+                counters,
+            }));
+        }
+
         Poll::Pending
     }
 }
@@ -0,0 +1,371 @@
+//
+// MIT License
+//
+// Copyright (c) 2018-2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Peer reputation.
+//!
+//! Every peer starts with zero score. Misbehavior lowers the score, the score
+//! slowly recovers back to zero over time. Peers which drop below the threshold
+//! are banned for a backoff period, which doubles on every subsequent ban.
+
+use crate::PeerId;
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Score points recovered by peer per minute.
+const SCORE_RECOVERY_PER_MINUTE: i64 = 10;
+/// Maximal multiplier of ban duration for repeated offenders.
+const MAX_BAN_BACKOFF: u32 = 64;
+/// Interval used to count gossip messages.
+const GOSSIP_RATE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Kind of misbehavior reported about a peer.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Misbehavior {
+    /// Block failed validation.
+    InvalidBlock,
+    /// Transaction failed validation.
+    InvalidTransaction,
+    /// Consensus message failed validation.
+    InvalidConsensusMessage,
+    /// Too many gossip messages.
+    GossipSpam,
+}
+
+impl Misbehavior {
+    /// Score points subtracted for this misbehavior.
+    pub fn penalty(self) -> i64 {
+        match self {
+            Misbehavior::InvalidBlock => 50,
+            Misbehavior::InvalidTransaction => 10,
+            Misbehavior::InvalidConsensusMessage => 25,
+            Misbehavior::GossipSpam => 20,
+        }
+    }
+}
+
+/// Reputation of a peer, returned by API.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
+pub struct PeerScoreInfo {
+    pub peer_id: String,
+    /// Current score, always <= 0.
+    pub score: i64,
+    /// Number of seconds until the ban expires, None if peer is not banned.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub banned_secs: Option<u64>,
+    /// Number of times this peer has been banned.
+    pub bans: u32,
+}
+
+#[derive(Debug, Clone)]
+struct PeerScore {
+    score: i64,
+    updated: Instant,
+    banned_until: Option<Instant>,
+    bans: u32,
+    window_start: Instant,
+    window_messages: u32,
+}
+
+impl PeerScore {
+    fn new(now: Instant) -> Self {
+        PeerScore {
+            score: 0,
+            updated: now,
+            banned_until: None,
+            bans: 0,
+            window_start: now,
+            window_messages: 0,
+        }
+    }
+
+    /// Recover score for the time passed since the last update.
+    fn recover(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.updated);
+        let recovered = (elapsed.as_secs() as i64) * SCORE_RECOVERY_PER_MINUTE / 60;
+        if recovered > 0 {
+            self.score = std::cmp::min(self.score + recovered, 0);
+            self.updated = now;
+        }
+    }
+}
+
+/// Scores of known peers.
+pub(crate) struct Reputation {
+    /// Peer is banned when score drops to -threshold.
+    threshold: i64,
+    /// Duration of the first ban.
+    ban_duration: Duration,
+    /// Maximal number of gossip messages per second, 0 means unlimited.
+    gossip_rate_limit: u32,
+    peers: HashMap<PeerId, PeerScore>,
+}
+
+impl Reputation {
+    pub fn new(threshold: i64, ban_duration: Duration, gossip_rate_limit: u32) -> Self {
+        Reputation {
+            threshold,
+            ban_duration,
+            gossip_rate_limit,
+            peers: HashMap::new(),
+        }
+    }
+
+    /// Lower peer's score.
+    /// Returns the duration of the ban if peer has just been banned.
+    pub fn report(
+        &mut self,
+        peer_id: &PeerId,
+        misbehavior: Misbehavior,
+        now: Instant,
+    ) -> Option<Duration> {
+        let threshold = self.threshold;
+        let ban_duration = self.ban_duration;
+        let peer = self
+            .peers
+            .entry(peer_id.clone())
+            .or_insert_with(|| PeerScore::new(now));
+        if peer.banned_until.map_or(false, |until| until > now) {
+            return None;
+        }
+        peer.recover(now);
+        peer.score -= misbehavior.penalty();
+        if peer.score > -threshold {
+            return None;
+        }
+        peer.bans += 1;
+        let backoff = std::cmp::min(1u32 << std::cmp::min(peer.bans - 1, 31), MAX_BAN_BACKOFF);
+        let duration = ban_duration * backoff;
+        peer.banned_until = Some(now + duration);
+        peer.score = 0;
+        Some(duration)
+    }
+
+    /// Count a gossip message received from peer.
+    /// Returns false if the peer has exceeded the rate limit in the current interval.
+    pub fn record_message(&mut self, peer_id: &PeerId, now: Instant) -> bool {
+        if self.gossip_rate_limit == 0 {
+            return true;
+        }
+        let peer = self
+            .peers
+            .entry(peer_id.clone())
+            .or_insert_with(|| PeerScore::new(now));
+        if now.duration_since(peer.window_start) >= GOSSIP_RATE_INTERVAL {
+            peer.window_start = now;
+            peer.window_messages = 0;
+        }
+        peer.window_messages += 1;
+        // Report only once per interval.
+        peer.window_messages != self.gossip_rate_limit + 1
+    }
+
+    /// Returns true if peer is banned.
+    #[cfg(test)]
+    pub fn is_banned(&self, peer_id: &PeerId, now: Instant) -> bool {
+        self.peers
+            .get(peer_id)
+            .and_then(|p| p.banned_until)
+            .map_or(false, |until| until > now)
+    }
+
+    /// Finish the ban of peer.
+    pub fn unban(&mut self, peer_id: &PeerId, now: Instant) {
+        if let Some(peer) = self.peers.get_mut(peer_id) {
+            // Keep the end of the ban to forget the peer later.
+            peer.banned_until = peer.banned_until.map(|until| std::cmp::min(until, now));
+        }
+    }
+
+    /// Forget peers which have fully recovered.
+    /// Banned peers are remembered for the longest ban after the end of the last ban.
+    pub fn prune(&mut self, now: Instant) {
+        let retention = self.ban_duration * MAX_BAN_BACKOFF;
+        self.peers.retain(|_peer_id, peer| {
+            peer.recover(now);
+            if peer.score < 0 {
+                return true;
+            }
+            match peer.banned_until {
+                Some(until) => until + retention > now,
+                None => now.duration_since(peer.window_start) < GOSSIP_RATE_INTERVAL,
+            }
+        });
+    }
+
+    /// Returns scores of all known peers with non-zero score or bans.
+    pub fn scores(&mut self, now: Instant) -> Vec<PeerScoreInfo> {
+        let mut scores = Vec::new();
+        for (peer_id, peer) in self.peers.iter_mut() {
+            peer.recover(now);
+            let banned_secs = peer
+                .banned_until
+                .filter(|until| *until > now)
+                .map(|until| until.duration_since(now).as_secs());
+            if peer.score == 0 && peer.bans == 0 {
+                continue;
+            }
+            scores.push(PeerScoreInfo {
+                peer_id: peer_id.to_base58(),
+                score: peer.score,
+                banned_secs,
+                bans: peer.bans,
+            });
+        }
+        scores.sort_by(|a, b| a.score.cmp(&b.score).then(a.peer_id.cmp(&b.peer_id)));
+        scores
+    }
+
+    /// Forget scores and bans of one or all peers.
+    /// Returns the list of peers which were banned.
+    pub fn clear(&mut self, peer_id: Option<&PeerId>, now: Instant) -> Vec<PeerId> {
+        let removed: Vec<(PeerId, PeerScore)> = match peer_id {
+            Some(peer_id) => self
+                .peers
+                .remove(peer_id)
+                .map(|p| (peer_id.clone(), p))
+                .into_iter()
+                .collect(),
+            None => self.peers.drain().collect(),
+        };
+        removed
+            .into_iter()
+            .filter(|(_, p)| p.banned_until.map_or(false, |until| until > now))
+            .map(|(peer_id, _)| peer_id)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ban_and_recover() {
+        let mut reputation = Reputation::new(100, Duration::from_secs(60), 0);
+        let peer_id = PeerId::random();
+        let now = Instant::now();
+
+        assert_eq!(
+            reputation.report(&peer_id, Misbehavior::InvalidBlock, now),
+            None
+        );
+        let scores = reputation.scores(now);
+        assert_eq!(scores.len(), 1);
+        assert_eq!(scores[0].score, -50);
+        assert_eq!(scores[0].banned_secs, None);
+
+        // Score recovers over time.
+        let now = now + Duration::from_secs(60);
+        assert_eq!(reputation.scores(now)[0].score, -40);
+
+        let now = now + Duration::from_secs(1);
+        assert_eq!(
+            reputation.report(&peer_id, Misbehavior::InvalidBlock, now),
+            None
+        );
+        assert_eq!(
+            reputation.report(&peer_id, Misbehavior::InvalidTransaction, now),
+            Some(Duration::from_secs(60))
+        );
+        assert!(reputation.is_banned(&peer_id, now));
+        let scores = reputation.scores(now);
+        assert_eq!(scores[0].score, 0);
+        assert_eq!(scores[0].banned_secs, Some(60));
+        assert_eq!(scores[0].bans, 1);
+
+        // Reports are ignored during the ban.
+        assert_eq!(
+            reputation.report(&peer_id, Misbehavior::InvalidBlock, now),
+            None
+        );
+        assert_eq!(reputation.scores(now)[0].score, 0);
+
+        // Ban duration doubles for repeated offenders.
+        let now = now + Duration::from_secs(61);
+        assert!(!reputation.is_banned(&peer_id, now));
+        reputation.report(&peer_id, Misbehavior::InvalidBlock, now);
+        assert_eq!(
+            reputation.report(&peer_id, Misbehavior::InvalidBlock, now),
+            Some(Duration::from_secs(120))
+        );
+
+        let other_peer_id = PeerId::random();
+        reputation.report(&other_peer_id, Misbehavior::GossipSpam, now);
+        assert_eq!(reputation.scores(now).len(), 2);
+        assert_eq!(reputation.clear(Some(&peer_id), now), vec![peer_id.clone()]);
+        assert!(!reputation.is_banned(&peer_id, now));
+        assert_eq!(reputation.scores(now).len(), 1);
+        assert!(reputation.clear(None, now).is_empty());
+        assert!(reputation.scores(now).is_empty());
+    }
+
+    #[test]
+    fn gossip_rate_limit() {
+        let mut reputation = Reputation::new(100, Duration::from_secs(60), 3);
+        let peer_id = PeerId::random();
+        let now = Instant::now();
+        for _ in 0..3 {
+            assert!(reputation.record_message(&peer_id, now));
+        }
+        assert!(!reputation.record_message(&peer_id, now));
+        // Only the first excess message is reported.
+        assert!(reputation.record_message(&peer_id, now));
+        let now = now + GOSSIP_RATE_INTERVAL;
+        assert!(reputation.record_message(&peer_id, now));
+    }
+
+    #[test]
+    fn prune() {
+        let ban_duration = Duration::from_secs(60);
+        let mut reputation = Reputation::new(100, ban_duration, 3);
+        let quiet_peer_id = PeerId::random();
+        let bad_peer_id = PeerId::random();
+        let now = Instant::now();
+        assert!(reputation.record_message(&quiet_peer_id, now));
+        reputation.report(&bad_peer_id, Misbehavior::InvalidBlock, now);
+        assert!(reputation
+            .report(&bad_peer_id, Misbehavior::InvalidBlock, now)
+            .is_some());
+        reputation.prune(now);
+        assert_eq!(reputation.peers.len(), 2);
+
+        // Quiet peers are forgotten after the rate interval.
+        let now = now + GOSSIP_RATE_INTERVAL;
+        reputation.prune(now);
+        assert_eq!(reputation.peers.len(), 1);
+
+        // Offenders are remembered after the ban.
+        let now = now + ban_duration;
+        reputation.unban(&bad_peer_id, now);
+        assert!(!reputation.is_banned(&bad_peer_id, now));
+        reputation.prune(now);
+        assert_eq!(reputation.scores(now)[0].bans, 1);
+
+        let now = now + ban_duration * MAX_BAN_BACKOFF;
+        reputation.prune(now);
+        assert!(reputation.peers.is_empty());
+    }
+}
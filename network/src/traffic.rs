@@ -0,0 +1,414 @@
+//
+// MIT License
+//
+// Copyright (c) 2018-2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Traffic accounting.
+//!
+//! Counts bytes and messages sent and received per protocol and topic,
+//! and per remote peer. Counters are exported to Prometheus and can be
+//! requested through `NetworkProvider::traffic_stats()`.
+//!
+//! Protocols without topics are accounted with an empty topic.
+//! Only topics used by this node are accounted by name, the rest are
+//! accounted as `other`, so remote peers can't create unbounded labels.
+//! Unicast messages are accounted per hop without a topic,
+//! and per protocol id on the origin and destination nodes.
+
+use crate::PeerId;
+use lazy_static::lazy_static;
+use prometheus::*;
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Maximal number of peers to keep counters for.
+const MAX_PEERS: usize = 1024;
+/// Label of topics which aren't used by this node.
+const OTHER_TOPIC: &str = "other";
+
+lazy_static! {
+    static ref TOPIC_TRAFFIC: IntCounterVec = register_int_counter_vec!(
+        "stegos_network_topic_traffic",
+        "Bytes per protocol, topic and direction",
+        &["protocol", "topic", "direction"]
+    )
+    .unwrap();
+    static ref TOPIC_MESSAGES: IntCounterVec = register_int_counter_vec!(
+        "stegos_network_topic_messages",
+        "Messages per protocol, topic and direction",
+        &["protocol", "topic", "direction"]
+    )
+    .unwrap();
+    static ref PEER_TRAFFIC: IntCounterVec = register_int_counter_vec!(
+        "stegos_network_peer_traffic",
+        "Bytes per peer and direction",
+        &["peer", "direction"]
+    )
+    .unwrap();
+    static ref PEER_MESSAGES: IntCounterVec = register_int_counter_vec!(
+        "stegos_network_peer_messages",
+        "Messages per peer and direction",
+        &["peer", "direction"]
+    )
+    .unwrap();
+    static ref TRAFFIC: Mutex<Traffic> = Mutex::new(Traffic::new());
+}
+
+/// Direction of traffic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Direction {
+    Incoming,
+    Outgoing,
+}
+
+impl Direction {
+    fn label(self) -> &'static str {
+        match self {
+            Direction::Incoming => "incoming",
+            Direction::Outgoing => "outgoing",
+        }
+    }
+}
+
+/// Byte and message counters.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, JsonSchema)]
+pub struct TrafficCounters {
+    pub incoming_bytes: u64,
+    pub incoming_messages: u64,
+    pub outgoing_bytes: u64,
+    pub outgoing_messages: u64,
+}
+
+impl TrafficCounters {
+    fn add(&mut self, direction: Direction, bytes: u64, messages: u64) {
+        match direction {
+            Direction::Incoming => {
+                self.incoming_bytes += bytes;
+                self.incoming_messages += messages;
+            }
+            Direction::Outgoing => {
+                self.outgoing_bytes += bytes;
+                self.outgoing_messages += messages;
+            }
+        }
+    }
+
+    /// Total number of bytes in both directions.
+    pub fn total_bytes(&self) -> u64 {
+        self.incoming_bytes + self.outgoing_bytes
+    }
+}
+
+/// Traffic of a topic, returned by API.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
+pub struct TopicTraffic {
+    pub protocol: String,
+    pub topic: String,
+    #[serde(flatten)]
+    pub counters: TrafficCounters,
+}
+
+/// Traffic of a peer, returned by API.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
+pub struct PeerTraffic {
+    pub peer_id: String,
+    #[serde(flatten)]
+    pub counters: TrafficCounters,
+}
+
+/// Counters shared with a protocol handler, which doesn't know the remote peer.
+/// The owning behaviour takes the values and accounts them with `record()`.
+#[derive(Debug, Default)]
+pub(crate) struct TrafficMeter {
+    incoming_bytes: AtomicU64,
+    incoming_messages: AtomicU64,
+    outgoing_bytes: AtomicU64,
+    outgoing_messages: AtomicU64,
+}
+
+impl TrafficMeter {
+    pub fn add(&self, direction: Direction, bytes: usize) {
+        let (bytes_counter, messages_counter) = match direction {
+            Direction::Incoming => (&self.incoming_bytes, &self.incoming_messages),
+            Direction::Outgoing => (&self.outgoing_bytes, &self.outgoing_messages),
+        };
+        bytes_counter.fetch_add(bytes as u64, Ordering::Relaxed);
+        messages_counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Take accumulated counters, returns None if nothing has been accounted.
+    pub fn take(&self) -> Option<TrafficCounters> {
+        let counters = TrafficCounters {
+            incoming_bytes: self.incoming_bytes.swap(0, Ordering::Relaxed),
+            incoming_messages: self.incoming_messages.swap(0, Ordering::Relaxed),
+            outgoing_bytes: self.outgoing_bytes.swap(0, Ordering::Relaxed),
+            outgoing_messages: self.outgoing_messages.swap(0, Ordering::Relaxed),
+        };
+        if counters == TrafficCounters::default() {
+            return None;
+        }
+        Some(counters)
+    }
+}
+
+struct PeerEntry {
+    counters: TrafficCounters,
+    last_active: Instant,
+}
+
+struct Traffic {
+    topics: HashMap<(&'static str, String), TrafficCounters>,
+    peers: HashMap<PeerId, PeerEntry>,
+    /// Topics accounted by name.
+    known_topics: HashSet<String>,
+}
+
+impl Traffic {
+    fn new() -> Self {
+        Traffic {
+            topics: HashMap::new(),
+            peers: HashMap::new(),
+            known_topics: HashSet::new(),
+        }
+    }
+
+    fn record(
+        &mut self,
+        protocol: &'static str,
+        topic: Option<&str>,
+        peer_id: Option<&PeerId>,
+        direction: Direction,
+        bytes: u64,
+        messages: u64,
+    ) {
+        if let Some(topic) = topic {
+            let topic = if topic.is_empty() || self.known_topics.contains(topic) {
+                topic
+            } else {
+                OTHER_TOPIC
+            };
+            let labels = [protocol, topic, direction.label()];
+            TOPIC_TRAFFIC
+                .with_label_values(&labels)
+                .inc_by(bytes as i64);
+            TOPIC_MESSAGES
+                .with_label_values(&labels)
+                .inc_by(messages as i64);
+            self.topics
+                .entry((protocol, topic.to_string()))
+                .or_default()
+                .add(direction, bytes, messages);
+        }
+        if let Some(peer_id) = peer_id {
+            let peer = peer_id.to_base58();
+            let labels = [peer.as_str(), direction.label()];
+            PEER_TRAFFIC.with_label_values(&labels).inc_by(bytes as i64);
+            PEER_MESSAGES
+                .with_label_values(&labels)
+                .inc_by(messages as i64);
+            if !self.peers.contains_key(peer_id) && self.peers.len() >= MAX_PEERS {
+                self.evict();
+            }
+            let entry = self
+                .peers
+                .entry(peer_id.clone())
+                .or_insert_with(|| PeerEntry {
+                    counters: TrafficCounters::default(),
+                    last_active: Instant::now(),
+                });
+            entry.counters.add(direction, bytes, messages);
+            entry.last_active = Instant::now();
+        }
+    }
+
+    /// Forget the least recently active peer.
+    fn evict(&mut self) {
+        let peer_id = match self
+            .peers
+            .iter()
+            .min_by_key(|(_, entry)| entry.last_active)
+            .map(|(peer_id, _)| peer_id.clone())
+        {
+            Some(peer_id) => peer_id,
+            None => return,
+        };
+        self.peers.remove(&peer_id);
+        let peer = peer_id.to_base58();
+        for direction in &[Direction::Incoming, Direction::Outgoing] {
+            let labels = [peer.as_str(), direction.label()];
+            PEER_TRAFFIC.remove_label_values(&labels).ok();
+            PEER_MESSAGES.remove_label_values(&labels).ok();
+        }
+    }
+
+    /// Returns counters of topics and peers, ordered by total bytes.
+    fn stats(&self) -> (Vec<TopicTraffic>, Vec<PeerTraffic>) {
+        let mut topics: Vec<TopicTraffic> = self
+            .topics
+            .iter()
+            .map(|((protocol, topic), counters)| TopicTraffic {
+                protocol: (*protocol).to_string(),
+                topic: topic.clone(),
+                counters: *counters,
+            })
+            .collect();
+        topics.sort_by(|a, b| b.counters.total_bytes().cmp(&a.counters.total_bytes()));
+        let mut peers: Vec<PeerTraffic> = self
+            .peers
+            .iter()
+            .map(|(peer_id, entry)| PeerTraffic {
+                peer_id: peer_id.to_base58(),
+                counters: entry.counters,
+            })
+            .collect();
+        peers.sort_by(|a, b| b.counters.total_bytes().cmp(&a.counters.total_bytes()));
+        (topics, peers)
+    }
+}
+
+/// Account traffic of the topic or unicast protocol id by name.
+pub(crate) fn register_topic(topic: &str) {
+    let mut traffic = TRAFFIC.lock().unwrap();
+    if !traffic.known_topics.contains(topic) {
+        traffic.known_topics.insert(topic.to_string());
+    }
+}
+
+/// Account a single message.
+pub(crate) fn record(
+    protocol: &'static str,
+    topic: Option<&str>,
+    peer_id: Option<&PeerId>,
+    direction: Direction,
+    bytes: usize,
+) {
+    TRAFFIC
+        .lock()
+        .unwrap()
+        .record(protocol, topic, peer_id, direction, bytes as u64, 1);
+}
+
+/// Account counters taken from a `TrafficMeter`.
+pub(crate) fn record_counters(
+    protocol: &'static str,
+    topic: Option<&str>,
+    peer_id: Option<&PeerId>,
+    counters: TrafficCounters,
+) {
+    let mut traffic = TRAFFIC.lock().unwrap();
+    traffic.record(
+        protocol,
+        topic,
+        peer_id,
+        Direction::Incoming,
+        counters.incoming_bytes,
+        counters.incoming_messages,
+    );
+    traffic.record(
+        protocol,
+        topic,
+        peer_id,
+        Direction::Outgoing,
+        counters.outgoing_bytes,
+        counters.outgoing_messages,
+    );
+}
+
+/// Returns counters of topics and peers, ordered by total bytes.
+pub(crate) fn stats() -> (Vec<TopicTraffic>, Vec<PeerTraffic>) {
+    TRAFFIC.lock().unwrap().stats()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accounting() {
+        let mut traffic = Traffic::new();
+        traffic.known_topics.insert("tx".to_string());
+        let peer1 = PeerId::random();
+        let peer2 = PeerId::random();
+
+        traffic.record(
+            "gossipsub",
+            Some("tx"),
+            Some(&peer1),
+            Direction::Incoming,
+            100,
+            1,
+        );
+        traffic.record(
+            "gossipsub",
+            Some("tx"),
+            Some(&peer2),
+            Direction::Outgoing,
+            50,
+            1,
+        );
+        traffic.record("delivery", None, Some(&peer2), Direction::Outgoing, 500, 2);
+        for topic in &["spam1", "spam2"] {
+            traffic.record("floodsub", Some(topic), None, Direction::Incoming, 10, 1);
+        }
+
+        let (topics, peers) = traffic.stats();
+        assert_eq!(topics.len(), 2);
+        assert_eq!(topics[1].protocol, "floodsub");
+        assert_eq!(topics[1].topic, OTHER_TOPIC);
+        assert_eq!(topics[1].counters.incoming_messages, 2);
+        assert_eq!(topics[0].protocol, "gossipsub");
+        assert_eq!(topics[0].topic, "tx");
+        assert_eq!(
+            topics[0].counters,
+            TrafficCounters {
+                incoming_bytes: 100,
+                incoming_messages: 1,
+                outgoing_bytes: 50,
+                outgoing_messages: 1,
+            }
+        );
+        assert_eq!(peers.len(), 2);
+        assert_eq!(peers[0].peer_id, peer2.to_base58());
+        assert_eq!(peers[0].counters.outgoing_bytes, 550);
+        assert_eq!(peers[0].counters.outgoing_messages, 3);
+        assert_eq!(peers[1].peer_id, peer1.to_base58());
+        assert_eq!(peers[1].counters.incoming_bytes, 100);
+    }
+
+    #[test]
+    fn meter() {
+        let meter = TrafficMeter::default();
+        assert_eq!(meter.take(), None);
+        meter.add(Direction::Incoming, 10);
+        meter.add(Direction::Incoming, 20);
+        meter.add(Direction::Outgoing, 5);
+        let counters = meter.take().unwrap();
+        assert_eq!(counters.incoming_bytes, 30);
+        assert_eq!(counters.incoming_messages, 2);
+        assert_eq!(counters.outgoing_bytes, 5);
+        assert_eq!(counters.outgoing_messages, 1);
+        assert_eq!(meter.take(), None);
+    }
+}
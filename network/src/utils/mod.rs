@@ -102,6 +102,15 @@ pub async fn resolve_seed_nodes(
     Ok(seed_nodes)
 }
 
+/// Content-addressed key of a broadcast message.
+pub fn message_key(data: &[u8]) -> u64 {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+    let mut s = DefaultHasher::new();
+    Hash::hash(data, &mut s);
+    s.finish()
+}
+
 pub fn ed25519_from_pbc(source: &pbc::SecretKey) -> ed25519::Keypair {
     let mut raw = source.to_bytes();
     let secret = ed25519::SecretKey::from_bytes(&mut raw)
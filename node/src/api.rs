@@ -22,22 +22,24 @@
 // SOFTWARE.
 
 use futures::channel::mpsc;
+use schemars::JsonSchema;
 use serde_derive::{Deserialize, Serialize};
 use std::collections::HashMap;
 use stegos_blockchain::api::StatusInfo;
 use stegos_blockchain::{
-    ElectionInfo, EpochInfo, EscrowInfo, MacroBlock, MicroBlock, Output, Timestamp, Transaction,
-    TransactionStatus, ValidatorKeyInfo,
+    ChainParameter, ChainParametersInfo, ElectionInfo, ElectionProof, EpochInfo, EscrowInfo,
+    MacroBlock, MicroBlock, Output, OutputProof, Timestamp, Transaction, TransactionStatus,
+    ValidatorEpochStats, ValidatorKeyInfo,
 };
 use stegos_crypto::hash::Hash;
-use stegos_crypto::scc;
 use stegos_crypto::utils::{
     deserialize_protobuf_array_from_hex, deserialize_protobuf_from_hex,
     serialize_protobuf_array_to_hex, serialize_protobuf_to_hex,
 };
+use stegos_crypto::{pbc, scc};
 use stegos_replication::api::*;
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "output_type")]
 #[serde(rename_all = "snake_case")]
 pub enum OutputType {
@@ -45,7 +47,7 @@ pub enum OutputType {
     Payment { comment: String },
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct NewOutputInfo {
     #[serde(flatten)]
     pub output_type: OutputType,
@@ -55,7 +57,7 @@ pub struct NewOutputInfo {
 ///
 /// RPC requests.
 ///
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "type")]
 #[serde(rename_all = "snake_case")]
 pub enum NodeRequest {
@@ -67,6 +69,7 @@ pub enum NodeRequest {
     BroadcastTransaction {
         #[serde(serialize_with = "serialize_protobuf_to_hex")]
         #[serde(deserialize_with = "deserialize_protobuf_from_hex")]
+        #[schemars(with = "String")]
         data: Transaction,
     },
     /// Get full output corresponding to output id.
@@ -84,6 +87,7 @@ pub enum NodeRequest {
         #[serde(default)]
         #[serde(serialize_with = "serialize_protobuf_array_to_hex")]
         #[serde(deserialize_with = "deserialize_protobuf_array_from_hex")]
+        #[schemars(with = "Vec<String>")]
         unspent_list: Vec<Output>,
     },
     ValidateCertificate {
@@ -92,6 +96,10 @@ pub enum NodeRequest {
         recipient: scc::PublicKey,
         rvalue: scc::Fr,
     },
+    /// Get unspent output with a Merkle proof against `outputs_range_hash` of the block.
+    OutputProof {
+        output_hash: Hash,
+    },
     EnableRestaking {},
     DisableRestaking {},
     ChangeUpstream {},
@@ -105,16 +113,39 @@ pub enum NodeRequest {
         epoch: u64,
         offset: u32,
     },
+    /// Get per-epoch statistics of validator for epochs in [from_epoch, to_epoch].
+    ValidatorStats {
+        validator: pbc::PublicKey,
+        from_epoch: u64,
+        to_epoch: u64,
+    },
+    /// Get inputs of the leader election, which can be replayed by
+    /// `stegos_blockchain::election::verify_election_proof()`.
+    ElectionProof {
+        epoch: u64,
+        offset: u32,
+        view_change: u32,
+    },
+    /// Get current chain parameters and scheduled changes.
+    ChainParameters {},
+    /// Vote for a change of chain parameter, as a validator.
+    /// The vote is re-cast every epoch until the change is accepted or becomes too late.
+    VoteChainParameter {
+        parameter: ChainParameter,
+        activation_epoch: u64,
+    },
     SubscribeChain {
         epoch: u64,
         offset: u32,
     },
+    /// Get JSON Schema of API requests.
+    ApiSchema {},
 }
 
 ///
 /// RPC responses.
 ///
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "type")]
 #[serde(rename_all = "snake_case")]
 pub enum NodeResponse {
@@ -128,12 +159,14 @@ pub enum NodeResponse {
     OutputsList {
         #[serde(serialize_with = "serialize_protobuf_array_to_hex")]
         #[serde(deserialize_with = "deserialize_protobuf_array_from_hex")]
+        #[schemars(with = "Vec<String>")]
         utxos: Vec<Output>,
     },
     CreateRawTransaction {
         txouts: Vec<Hash>,
         #[serde(serialize_with = "serialize_protobuf_to_hex")]
         #[serde(deserialize_with = "deserialize_protobuf_from_hex")]
+        #[schemars(with = "String")]
         data: Transaction,
     },
     BroadcastTransaction {
@@ -147,6 +180,7 @@ pub enum NodeResponse {
         timestamp: Timestamp,
         amount: i64,
     },
+    OutputProof(OutputProof),
     RestakingEnabled,
     RestakingDisabled,
     UpstreamChanged,
@@ -165,19 +199,31 @@ pub enum NodeResponse {
     },
     MacroBlockInfo(ExtendedMacroBlock),
     MicroBlockInfo(MicroBlock),
+    ValidatorStats {
+        validator: pbc::PublicKey,
+        stats: Vec<ValidatorEpochStats>,
+    },
+    ElectionProof(ElectionProof),
+    ChainParameters(ChainParametersInfo),
+    ChainParameterVoted {
+        epoch: u64,
+    },
     SubscribedChain {
         current_epoch: u64,
         current_offset: u32,
         #[serde(skip)]
         rx: Option<mpsc::Receiver<ChainNotification>>, // Option is needed for serde.
     },
+    ApiSchema {
+        schema: serde_json::Value,
+    },
     Error {
         error: String,
     },
 }
 
 /// Status notifications.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "type")]
 #[serde(rename_all = "snake_case")]
 pub enum StatusNotification {
@@ -191,7 +237,7 @@ impl From<StatusInfo> for StatusNotification {
 }
 
 /// Blockchain notifications.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "type")]
 #[serde(rename_all = "snake_case")]
 pub enum ChainNotification {
@@ -201,7 +247,7 @@ pub enum ChainNotification {
 }
 
 /// A macro block with extra information.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct ExtendedMacroBlock {
     /// Committed macro block.
     #[serde(flatten)]
@@ -223,7 +269,7 @@ impl ExtendedMacroBlock {
 }
 
 /// Information about reverted micro block.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct RevertedMicroBlock {
     #[serde(flatten)]
     pub block: MicroBlock,
@@ -243,7 +289,7 @@ impl RevertedMicroBlock {
 }
 
 /// PA micro block with extra information.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct ExtendedMicroBlock {
     #[serde(flatten)]
     pub block: MicroBlock,
@@ -24,7 +24,8 @@
 // SOFTWARE.
 
 use failure::Fail;
-use stegos_blockchain::{BlockError, BlockchainError, StorageError};
+use stegos_blockchain::{BlockError, BlockchainError, StorageError, TransactionError};
+use stegos_consensus::ConsensusError;
 use stegos_crypto::hash::Hash;
 
 #[derive(Debug, Fail, PartialEq, Eq)]
@@ -90,3 +91,146 @@ impl From<StorageError> for ForkError {
 }
 
 pub type ForkResult = Result<(), ForkError>;
+
+/// Find the block validation error in the chain of wrappers.
+fn find_block_error(e: &failure::Error) -> Option<&BlockError> {
+    if let Some(e) = e.downcast_ref::<BlockError>() {
+        return Some(e);
+    }
+    if let Some(BlockchainError::BlockError(e)) = e.downcast_ref::<BlockchainError>() {
+        return Some(e);
+    }
+    match e.downcast_ref::<ForkError>() {
+        Some(ForkError::Error(e)) => find_block_error(e),
+        _ => None,
+    }
+}
+
+/// Returns true if the block received from the network is certainly invalid,
+/// i.e. can't be explained by a fork or by an outdated state of this node.
+pub(crate) fn is_invalid_block(e: &failure::Error) -> bool {
+    let e = match find_block_error(e) {
+        Some(e) => e,
+        None => return false,
+    };
+    match e {
+        // The block contradicts itself.
+        BlockError::InvalidMacroBlockInputsHash(..)
+        | BlockError::InvalidMacroBlockOutputsHash(..)
+        | BlockError::InvalidMacroBlockCanariesHash(..)
+        | BlockError::InvalidMacroBlockInputsLen(..)
+        | BlockError::InvalidMacroBlockOutputsLen(..)
+        | BlockError::InvalidMacroBlockVotesHash(..)
+        | BlockError::InvalidMacroBlockVotesLen(..)
+        | BlockError::InvalidMacroBlockValidatorsHash(..)
+        | BlockError::InvalidMacroBlockValidatorsLen(..)
+        | BlockError::InvalidMicroBlockTransactionsHash(..)
+        | BlockError::InvalidMicroBlockInputsHash(..)
+        | BlockError::InvalidMicroBlockOutputsHash(..)
+        | BlockError::InvalidMicroBlockCanariesHash(..)
+        | BlockError::InvalidMicroBlockSlashingProof(..)
+        | BlockError::InvalidMicroBlockTransactionsLen(..)
+        | BlockError::InvalidMicroBlockInputsLen(..)
+        | BlockError::InvalidMicroBlockOutputsLen(..)
+        | BlockError::InvalidMicroBlockCanariesLen(..)
+        | BlockError::InvalidMicroBlockFee(..)
+        | BlockError::InvalidMacroBlockFee(..)
+        | BlockError::InvalidBlockBalance(..)
+        | BlockError::CoinbaseMustBeFirst(..)
+        | BlockError::DuplicateBlockInput(..)
+        | BlockError::DuplicateBlockOutput(..)
+        | BlockError::MoreThanOneSignatureAtPropose(..)
+        | BlockError::TooBigActivitymap(..) => true,
+        // Signatures and randomness can't be forged.
+        BlockError::InvalidLeaderSignature(..)
+        | BlockError::InvalidBlockSignature(..)
+        | BlockError::InvalidViewChangeProof(..)
+        | BlockError::IncorrectRandom(..)
+        | BlockError::InvalidVDFProof(..) => true,
+        // The block belongs to another fork or the node is out of sync.
+        BlockError::InvalidMicroBlockPreviousHash(..)
+        | BlockError::InvalidMacroBlockPreviousHash(..)
+        | BlockError::MicroBlockHashCollision(..)
+        | BlockError::MacroBlockHashCollision(..)
+        | BlockError::OutOfOrderMacroBlock(..)
+        | BlockError::OutOfOrderMicroBlock(..)
+        | BlockError::MissingBlockInput(..)
+        | BlockError::OutputHashCollision(..)
+        | BlockError::LeaderIsNotValidator(..)
+        | BlockError::DifferentPublicKey(..)
+        | BlockError::InvalidViewChange(..)
+        | BlockError::NoProofWasFound(..)
+        | BlockError::ElectionResultForPastEpoch(..)
+        | BlockError::ElectionResultForFutureBlock(..)
+        | BlockError::InvalidBlockProposal(..)
+        | BlockError::InvalidBlockEpoch(..)
+        | BlockError::OutOfSyncViewChange(..) => false,
+        // Depends on the local clock.
+        BlockError::OutdatedBlock(..) | BlockError::OutOfSyncTimestamp(..) => false,
+        // Depends on the chain configuration or the version of software.
+        BlockError::InvalidBlockVersion(..)
+        | BlockError::UnexpectedVDFComplexity(..)
+        | BlockError::InvalidMacroBlockReward(..)
+        | BlockError::InvalidMicroBlockReward(..) => false,
+        // Depends on the activity of validators seen by this node.
+        BlockError::NoServiceAwardTx(..)
+        | BlockError::AwardMoreThanOneWinner(..)
+        | BlockError::AwardDifferentWinner(..)
+        | BlockError::AwardDifferentOutputType(..)
+        | BlockError::AwardDifferentReward(..) => false,
+    }
+}
+
+/// Returns true if the transaction received from the network is certainly invalid,
+/// i.e. can't be explained by an outdated state of this node.
+pub(crate) fn is_invalid_transaction(e: &failure::Error) -> bool {
+    let tx_error = match e.downcast_ref::<BlockchainError>() {
+        Some(BlockchainError::TransactionError(e)) => Some(e),
+        _ => e.downcast_ref::<TransactionError>(),
+    };
+    if let Some(tx_error) = tx_error {
+        return match tx_error {
+            TransactionError::InvalidSignature(..)
+            | TransactionError::InvalidMonetaryBalance(..)
+            | TransactionError::NegativeFee(..)
+            | TransactionError::NegativeReward(..)
+            | TransactionError::NoInputs(..)
+            | TransactionError::DuplicateInput(..)
+            | TransactionError::DuplicateOutput(..)
+            | TransactionError::ReceivedInvalidTransaction(..)
+            | TransactionError::NonPaymentOutputInCoinbase(..)
+            | TransactionError::InvalidRestakingInput(..)
+            | TransactionError::RestakingValidatorKeyMismatch(..)
+            | TransactionError::MixedRestakingOwners(..)
+            | TransactionError::NoRestakingTxins(..)
+            | TransactionError::InvalidRestakingOutput(..)
+            | TransactionError::MixedTxoutValidators(..)
+            | TransactionError::UnexpectedTxType
+            | TransactionError::ImbalancedRestaking(..) => true,
+            // Input can be spent by a block, which hasn't been seen by the sender yet.
+            TransactionError::MissingInput(..)
+            // Output can be created by a block, which hasn't been seen by the sender yet.
+            | TransactionError::OutputHashCollision(..)
+            // Depends on the state of the chain.
+            | TransactionError::SlashingError(..) => false,
+        };
+    }
+    match e.downcast_ref::<NodeTransactionError>() {
+        Some(NodeTransactionError::InvalidType(..))
+        | Some(NodeTransactionError::TooManyInputs(..))
+        | Some(NodeTransactionError::TooManyOutputs(..)) => true,
+        _ => false,
+    }
+}
+
+/// Returns true if the consensus message received from the network is certainly invalid.
+pub(crate) fn is_invalid_consensus_message(e: &failure::Error) -> bool {
+    match e.downcast_ref::<ConsensusError>() {
+        Some(ConsensusError::InvalidMessageSignature)
+        | Some(ConsensusError::InvalidRequestSignature(..)) => true,
+        Some(ConsensusError::InvalidPropose(e)) => is_invalid_block(e),
+        // The leader depends on view changes, which can be unknown to this node.
+        Some(ConsensusError::ProposalFromNonLeader(..)) => false,
+        _ => false,
+    }
+}
@@ -44,6 +44,7 @@ use rand::{self, Rng};
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use std::collections::{HashMap, HashSet};
 use std::time::{Duration, Instant};
+use stegos_blockchain::api::StatusInfo;
 use stegos_blockchain::Timestamp;
 use stegos_blockchain::*;
 use stegos_consensus::optimistic::{
@@ -54,6 +55,7 @@ use stegos_crypto::hash::Hash;
 use stegos_crypto::scc::Fr;
 use stegos_crypto::vdf::VDF;
 use stegos_crypto::{pbc, scc};
+use stegos_network::{MessageValidation, Misbehavior, Network};
 use stegos_serialization::traits::ProtoConvert;
 pub use stegos_txpool::MAX_PARTICIPANTS;
 use Validation::*;
@@ -76,6 +78,8 @@ pub(crate) const VIEW_CHANGE_DIRECT: &str = "view_changes_direct";
 pub(crate) const SEALED_BLOCK_TOPIC: &str = "block";
 /// Unicast topic for loading blocks.
 pub(crate) const CHAIN_LOADER_TOPIC: &str = "chain-loader";
+/// Topic for governance votes.
+pub(crate) const GOVERNANCE_TOPIC: &str = "governance";
 
 //
 // Logging utils.
@@ -126,6 +130,7 @@ pub enum NodeIncomingEvent {
         from: pbc::PublicKey,
         data: Vec<u8>,
     },
+    ParameterVote(Vec<u8>),
     CheckSyncTimer,
     MacroBlockProposeTimer,
     MacroBlockViewChangeTimer,
@@ -172,6 +177,16 @@ pub enum NodeOutgoingEvent {
         epoch: u64,
         offset: u32,
     },
+    ReportMisbehavior {
+        topic: String,
+        data: Vec<u8>,
+        misbehavior: Misbehavior,
+    },
+    ValidateMessage {
+        topic: String,
+        data: Vec<u8>,
+        validation: MessageValidation,
+    },
 }
 
 #[derive(Debug)]
@@ -212,6 +227,8 @@ pub struct NodeState {
     pub(crate) network_pkey: pbc::PublicKey,
     /// Network secret key.
     network_skey: pbc::SecretKey,
+    /// Network, used to query reachability of the node.
+    network: Network,
 
     /// Memory pool of pending transactions.
     mempool: Mempool,
@@ -231,6 +248,12 @@ pub struct NodeState {
     /// Automatic re-staking status.
     is_restaking_enabled: bool,
 
+    /// Governance votes for the current epoch, received from validators.
+    parameter_votes: HashMap<Hash, ParameterVote>,
+
+    /// Parameter changes voted by this node, re-cast every epoch until accepted.
+    local_parameter_votes: Vec<(ChainParameter, u64)>,
+
     pub(crate) outgoing: Vec<NodeOutgoingEvent>,
 }
 
@@ -241,6 +264,7 @@ impl NodeState {
         chain: Blockchain,
         network_skey: pbc::SecretKey,
         network_pkey: pbc::PublicKey,
+        network: Network,
         chain_name: String,
     ) -> Result<Self, Error> {
         let mempool = Mempool::new();
@@ -255,6 +279,8 @@ impl NodeState {
 
         let restaking_offset = 0; // will be updated on init().
         let is_restaking_enabled = true;
+        let parameter_votes = HashMap::new();
+        let local_parameter_votes = Vec::new();
 
         let state = NodeState {
             cfg,
@@ -262,12 +288,15 @@ impl NodeState {
             chain,
             network_skey,
             network_pkey,
+            network,
             mempool,
             validation,
             last_block_clock,
             cheating_proofs,
             restaking_offset,
             is_restaking_enabled,
+            parameter_votes,
+            local_parameter_votes,
             outgoing: Vec::new(),
         };
         state.update_stake_balance();
@@ -310,6 +339,111 @@ impl NodeState {
         Ok(())
     }
 
+    /// Handle a governance vote received from the network.
+    fn handle_parameter_vote(&mut self, vote: ParameterVote) -> Result<(), Error> {
+        let vote_hash = Hash::digest(&vote);
+        if self.parameter_votes.contains_key(&vote_hash) {
+            return Ok(());
+        }
+        self.chain.validate_parameter_vote(&vote)?;
+        let count = self
+            .parameter_votes
+            .values()
+            .filter(|v| v.pkey == vote.pkey)
+            .count();
+        if count >= MAX_VOTES_PER_VALIDATOR {
+            return Err(GovernanceError::TooManyVotes(vote.pkey).into());
+        }
+        sdebug!(
+            self,
+            "Received a governance vote: validator={}, parameter={:?}, activation_epoch={}",
+            vote.pkey,
+            vote.parameter,
+            vote.activation_epoch
+        );
+        self.parameter_votes.insert(vote_hash, vote);
+        Ok(())
+    }
+
+    /// Handle NodeRequest::VoteChainParameter.
+    fn handle_vote_chain_parameter(
+        &mut self,
+        parameter: ChainParameter,
+        activation_epoch: u64,
+    ) -> Result<(), Error> {
+        let vote = ParameterVote::new(
+            self.chain.epoch(),
+            parameter,
+            activation_epoch,
+            &self.network_skey,
+            &self.network_pkey,
+        );
+        self.chain.validate_parameter_vote(&vote)?;
+        if !self
+            .local_parameter_votes
+            .contains(&(parameter, activation_epoch))
+        {
+            if self.local_parameter_votes.len() >= MAX_VOTES_PER_VALIDATOR {
+                return Err(GovernanceError::TooManyVotes(self.network_pkey).into());
+            }
+            self.local_parameter_votes
+                .push((parameter, activation_epoch));
+        }
+        self.send_parameter_vote(vote)
+    }
+
+    /// Re-cast governance votes of this node for the new epoch.
+    fn cast_parameter_votes(&mut self) -> Result<(), Error> {
+        let epoch = self.chain.epoch();
+        let min_activation_epoch = epoch + PARAMETER_CHANGE_DELAY;
+        let pending = self.chain.chain_parameters().pending;
+        // Drop votes which were accepted or can't be accepted anymore.
+        self.local_parameter_votes
+            .retain(|(parameter, activation_epoch)| {
+                *activation_epoch >= min_activation_epoch
+                    && !pending
+                        .iter()
+                        .any(|c| c.epoch == *activation_epoch && c.parameter == *parameter)
+            });
+        if self.local_parameter_votes.is_empty()
+            || !self
+                .chain
+                .validators_at_epoch_start()
+                .iter()
+                .any(|(pkey, _)| *pkey == self.network_pkey)
+        {
+            return Ok(());
+        }
+        for (parameter, activation_epoch) in self.local_parameter_votes.clone() {
+            let vote = ParameterVote::new(
+                epoch,
+                parameter,
+                activation_epoch,
+                &self.network_skey,
+                &self.network_pkey,
+            );
+            self.send_parameter_vote(vote)?;
+        }
+        Ok(())
+    }
+
+    /// Send a governance vote to the network.
+    fn send_parameter_vote(&mut self, vote: ParameterVote) -> Result<(), Error> {
+        let data = vote.into_buffer()?;
+        self.outgoing.push(NodeOutgoingEvent::Publish {
+            topic: GOVERNANCE_TOPIC.to_string(),
+            data,
+        });
+        sinfo!(
+            self,
+            "Sent a governance vote to the network: parameter={:?}, activation_epoch={}",
+            vote.parameter,
+            vote.activation_epoch
+        );
+        self.parameter_votes.insert(Hash::digest(&vote), vote);
+        Ok(())
+    }
+
     /// Handle incoming transactions received from network.
     fn handle_transaction(&mut self, tx: Transaction) -> Result<(), Error> {
         let tx_hash = Hash::digest(&tx);
@@ -919,6 +1053,11 @@ impl NodeState {
         let block_timestamp = block.header.timestamp;
         let epoch = block.header.epoch;
         let was_synchronized = self.chain.is_synchronized();
+        // Prevotes observed by our consensus, if we are a validator.
+        let prevoters = match &self.validation {
+            MacroBlockValidator { consensus, .. } => Some(consensus.prevoters()),
+            _ => None,
+        };
 
         // Validate signature.
         check_multi_signature(
@@ -937,6 +1076,9 @@ impl NodeState {
         assert_eq!(0, self.chain.offset());
 
         let (inputs, outputs) = self.chain.push_macro_block(block.clone(), timestamp)?;
+        if let Some(prevoters) = prevoters {
+            self.chain.record_prevotes(epoch, &prevoters)?;
+        }
 
         // Remove conflict transactions from the mempool.
         self.mempool.prune(inputs.iter(), outputs.keys());
@@ -963,6 +1105,11 @@ impl NodeState {
             old_epoch_info,
         };
         self.cheating_proofs.clear();
+        // Governance votes are valid only within one epoch.
+        self.parameter_votes.clear();
+        if self.chain.is_synchronized() {
+            self.cast_parameter_votes()?;
+        }
         self.on_facilitator_changed();
         self.on_block_added(block_timestamp, notification.into(), was_synchronized);
 
@@ -1135,8 +1282,17 @@ impl NodeState {
         }
     }
 
+    /// Returns the chain status with reachability of the node.
+    pub(crate) fn status(&self) -> StatusInfo {
+        let mut status = self.chain.status();
+        let nat = self.network.nat_status();
+        status.reachability = nat.reachability;
+        status.advertised_address = nat.advertised_address.map(|a| a.to_string());
+        status
+    }
+
     fn on_status_changed(&mut self) {
-        let msg = self.chain.status();
+        let msg = self.status();
         metrics::SYNCHRONIZED.set(if msg.is_synchronized { 1 } else { 0 });
         self.outgoing
             .push(NodeOutgoingEvent::StatusNotification(msg.into()));
@@ -1472,6 +1628,7 @@ impl NodeState {
                 block_hash,
                 &block_proposal.header,
                 &block_proposal.transactions,
+                &block_proposal.parameter_votes,
             );
             let duration = Instant::now().duration_since(start_clock);
             let duration = (duration.as_secs() as f64) + (duration.subsec_nanos() as f64) * 1e-9;
@@ -1557,12 +1714,14 @@ impl NodeState {
             .account_by_network_key(&self.network_pkey)
             .expect("Staked");
 
+        let parameter_votes: Vec<ParameterVote> = self.parameter_votes.values().cloned().collect();
         let (block, transactions) = self.chain.create_macro_block(
             consensus.round(),
             &recipient_pkey,
             &self.network_skey,
             self.network_pkey.clone(),
             timestamp,
+            parameter_votes,
         );
         let block_hash = Hash::digest(&block);
 
@@ -1570,6 +1729,7 @@ impl NodeState {
         let block_proposal = MacroBlockProposal {
             header: block.header.clone(),
             transactions,
+            parameter_votes: block.parameter_votes.clone(),
         };
 
         let duration = Instant::now().duration_since(start_clock);
@@ -1894,10 +2054,20 @@ impl NodeState {
         // Commit the block.
         match std::mem::replace(&mut self.validation, Validation::MacroBlockAuditor) {
             MacroBlockValidator { consensus, .. } => {
+                let prevoters = consensus.prevoters();
                 let macro_block = consensus.commit();
                 let macro_block2 = macro_block.clone();
+                let epoch = macro_block.header.epoch;
                 self.apply_macro_block(macro_block)
                     .expect("block is validated before");
+                if let Err(e) = self.chain.record_prevotes(epoch, &prevoters) {
+                    serror!(
+                        self,
+                        "Failed to save prevotes: epoch={}, error={}",
+                        epoch,
+                        e
+                    );
+                }
                 self.send_block(Block::MacroBlock(macro_block2))
                     .expect("failed to send sealed micro block");
             }
@@ -1994,6 +2164,38 @@ impl NodeState {
     // Event Handling
     /////////////////////////////////////////////////////////////////////////////////////////////////
 
+    /// Lower the score of the peer which has published an invalid message.
+    fn report_misbehavior(&mut self, topic: &str, data: Vec<u8>, misbehavior: Misbehavior) {
+        swarn!(
+            self,
+            "Invalid message from the network: topic={}, misbehavior={:?}",
+            topic,
+            misbehavior
+        );
+        self.outgoing.push(NodeOutgoingEvent::ReportMisbehavior {
+            topic: topic.to_string(),
+            data,
+            misbehavior,
+        });
+    }
+
+    /// Report the result of validation of a broadcast message, only valid messages are forwarded.
+    fn validate_message(&mut self, topic: &str, data: Vec<u8>, validation: MessageValidation) {
+        if let MessageValidation::Reject(misbehavior) = validation {
+            swarn!(
+                self,
+                "Invalid message from the network: topic={}, misbehavior={:?}",
+                topic,
+                misbehavior
+            );
+        }
+        self.outgoing.push(NodeOutgoingEvent::ValidateMessage {
+            topic: topic.to_string(),
+            data,
+            validation,
+        });
+    }
+
     fn handle_event(&mut self, event: NodeIncomingEvent) {
         strace!(self, "Handle event = {:?}", event);
         let result: Result<(), Error> = match event {
@@ -2107,6 +2309,17 @@ impl NodeState {
                             error: format!("Invalid UTXO type: {}", output_hash),
                         },
                     },
+                    NodeRequest::OutputProof { output_hash } => {
+                        match self.chain.output_proof(&output_hash) {
+                            Ok(Some(proof)) => NodeResponse::OutputProof(proof),
+                            Ok(None) => NodeResponse::Error {
+                                error: format!("Missing UTXO: {}", output_hash),
+                            },
+                            Err(e) => NodeResponse::Error {
+                                error: format!("{}", e),
+                            },
+                        }
+                    }
                     NodeRequest::EnableRestaking {} => {
                         if self.is_restaking_enabled {
                             NodeResponse::Error {
@@ -2130,7 +2343,7 @@ impl NodeState {
                         }
                     }
                     NodeRequest::StatusInfo {} => {
-                        let status = self.chain.status();
+                        let status = self.status();
                         NodeResponse::StatusInfo(status)
                     }
                     NodeRequest::ValidatorsInfo {} => {
@@ -2169,6 +2382,40 @@ impl NodeState {
                             },
                         }
                     }
+                    NodeRequest::ValidatorStats {
+                        validator,
+                        from_epoch,
+                        to_epoch,
+                    } => match self.chain.validator_stats(&validator, from_epoch, to_epoch) {
+                        Ok(stats) => NodeResponse::ValidatorStats { validator, stats },
+                        Err(e) => NodeResponse::Error {
+                            error: format!("{}", e),
+                        },
+                    },
+                    NodeRequest::ElectionProof {
+                        epoch,
+                        offset,
+                        view_change,
+                    } => match self.chain.election_proof(epoch, offset, view_change) {
+                        Ok(proof) => NodeResponse::ElectionProof(proof),
+                        Err(e) => NodeResponse::Error {
+                            error: format!("{}", e),
+                        },
+                    },
+                    NodeRequest::ChainParameters {} => {
+                        NodeResponse::ChainParameters(self.chain.chain_parameters())
+                    }
+                    NodeRequest::VoteChainParameter {
+                        parameter,
+                        activation_epoch,
+                    } => match self.handle_vote_chain_parameter(parameter, activation_epoch) {
+                        Ok(()) => NodeResponse::ChainParameterVoted {
+                            epoch: self.chain.epoch(),
+                        },
+                        Err(e) => NodeResponse::Error {
+                            error: format!("{}", e),
+                        },
+                    },
                     NodeRequest::ReplicationInfo {} => {
                         unreachable!("Must be handled by NodeService");
                     }
@@ -2181,22 +2428,67 @@ impl NodeState {
                     NodeRequest::SubscribeChain { .. } => {
                         unreachable!("Must be handled by NodeService");
                     }
+                    NodeRequest::ApiSchema {} => NodeResponse::Error {
+                        error: "Must be handled by API server".to_string(),
+                    },
                 };
                 strace!(self, "<= {:?}", response);
                 tx.send(response).ok(); // ignore errors.
                 Ok(())
             }
-            NodeIncomingEvent::Transaction(msg) => {
-                match Transaction::from_buffer(&msg).and_then(|msg| self.handle_transaction(msg)) {
-                    Ok(()) => Ok(()),
-                    Err(e) => match e.downcast_ref::<NodeTransactionError>() {
-                        Some(NodeTransactionError::NotSynchronized(_)) => Ok(()),
-                        _ => Err(e),
-                    },
+            NodeIncomingEvent::Transaction(msg) => match Transaction::from_buffer(&msg) {
+                Ok(tx) => {
+                    let result = self.handle_transaction(tx);
+                    let validation = match &result {
+                        Ok(()) => MessageValidation::Accept,
+                        Err(e) if is_invalid_transaction(e) => {
+                            MessageValidation::Reject(Misbehavior::InvalidTransaction)
+                        }
+                        Err(e) => match e.downcast_ref::<NodeTransactionError>() {
+                            // Already validated.
+                            Some(NodeTransactionError::AlreadyExists(_)) => {
+                                MessageValidation::Accept
+                            }
+                            _ => MessageValidation::Ignore,
+                        },
+                    };
+                    self.validate_message(TX_TOPIC, msg, validation);
+                    match result {
+                        Err(e) => match e.downcast_ref::<NodeTransactionError>() {
+                            Some(NodeTransactionError::NotSynchronized(_)) => Ok(()),
+                            _ => Err(e),
+                        },
+                        Ok(()) => Ok(()),
+                    }
                 }
-            }
-            NodeIncomingEvent::Consensus(msg) => ConsensusMessage::from_buffer(&msg)
-                .and_then(|msg| self.handle_consensus_message(msg)),
+                Err(e) => {
+                    let validation = MessageValidation::Reject(Misbehavior::InvalidTransaction);
+                    self.validate_message(TX_TOPIC, msg, validation);
+                    Err(e)
+                }
+            },
+            NodeIncomingEvent::Consensus(msg) => match ConsensusMessage::from_buffer(&msg) {
+                Ok(consensus_msg) => {
+                    let result = self.handle_consensus_message(consensus_msg);
+                    match &result {
+                        Err(e) if is_invalid_consensus_message(e) => self.report_misbehavior(
+                            CONSENSUS_TOPIC,
+                            msg,
+                            Misbehavior::InvalidConsensusMessage,
+                        ),
+                        _ => {}
+                    }
+                    result
+                }
+                Err(e) => {
+                    self.report_misbehavior(
+                        CONSENSUS_TOPIC,
+                        msg,
+                        Misbehavior::InvalidConsensusMessage,
+                    );
+                    Err(e)
+                }
+            },
             NodeIncomingEvent::ViewChangeMessage(msg) => ViewChangeMessage::from_buffer(&msg)
                 .and_then(|msg| self.handle_view_change_message(msg)),
             NodeIncomingEvent::ViewChangeProof(msg) => AddressedViewChangeProof::from_buffer(&msg)
@@ -2207,9 +2499,25 @@ impl NodeState {
                 SealedViewChangeProof::from_buffer(&data)
                     .and_then(|proof| self.handle_view_change_direct(proof, from))
             }
-            NodeIncomingEvent::Block(msg) => {
-                Block::from_buffer(&msg).and_then(|msg| self.handle_block(msg))
-            }
+            NodeIncomingEvent::Block(msg) => match Block::from_buffer(&msg) {
+                Ok(block) => {
+                    let result = self.handle_block(block);
+                    let validation = match &result {
+                        Ok(()) => MessageValidation::Accept,
+                        Err(e) if is_invalid_block(e) => {
+                            MessageValidation::Reject(Misbehavior::InvalidBlock)
+                        }
+                        Err(_) => MessageValidation::Ignore,
+                    };
+                    self.validate_message(SEALED_BLOCK_TOPIC, msg, validation);
+                    result
+                }
+                Err(e) => {
+                    let validation = MessageValidation::Reject(Misbehavior::InvalidBlock);
+                    self.validate_message(SEALED_BLOCK_TOPIC, msg, validation);
+                    Err(e)
+                }
+            },
             NodeIncomingEvent::DecodedBlock(msg) => {
                 let result = self.handle_block(msg);
                 if let Err(error) = &result {
@@ -2218,6 +2526,9 @@ impl NodeState {
                 }
                 result
             }
+            NodeIncomingEvent::ParameterVote(msg) => {
+                ParameterVote::from_buffer(&msg).and_then(|vote| self.handle_parameter_vote(vote))
+            }
             NodeIncomingEvent::CheckSyncTimer => {
                 if !self.chain.is_synchronized() {
                     self.on_status_changed();
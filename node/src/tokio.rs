@@ -25,8 +25,8 @@ use super::api::*;
 use super::protos::{ChainLoaderMessage, RequestBlocks, ResponseBlocks};
 use super::{NodeIncomingEvent, NodeOutgoingEvent, NodeRequest, NodeResponse, NodeState};
 use crate::{
-    NodeConfig, CHAIN_LOADER_TOPIC, CONSENSUS_TOPIC, SEALED_BLOCK_TOPIC, TX_TOPIC,
-    VIEW_CHANGE_DIRECT, VIEW_CHANGE_PROOFS_TOPIC, VIEW_CHANGE_TOPIC,
+    NodeConfig, CHAIN_LOADER_TOPIC, CONSENSUS_TOPIC, GOVERNANCE_TOPIC, SEALED_BLOCK_TOPIC,
+    TX_TOPIC, VIEW_CHANGE_DIRECT, VIEW_CHANGE_PROOFS_TOPIC, VIEW_CHANGE_TOPIC,
 };
 use failure::{format_err, Error};
 use futures::channel::{mpsc, oneshot};
@@ -198,7 +198,14 @@ impl NodeService {
         peer_id: PeerId,
         replication_rx: mpsc::UnboundedReceiver<ReplicationEvent>,
     ) -> Result<(Self, Node), Error> {
-        let state = NodeState::new(cfg, chain, network_skey, network_pkey, chain_name)?;
+        let state = NodeState::new(
+            cfg,
+            chain,
+            network_skey,
+            network_pkey,
+            network.clone(),
+            chain_name,
+        )?;
         let (outbox, inbox) = mpsc::unbounded();
 
         let status_subscribers = Vec::new();
@@ -210,23 +217,23 @@ impl NodeService {
 
         // Transaction Requests
         let transaction_rx = network
-            .subscribe(&TX_TOPIC)?
+            .subscribe_validated(&TX_TOPIC)?
             .map(|m| NodeIncomingEvent::Transaction(m));
         streams.push(transaction_rx.boxed());
 
         // Consensus Requests
         let consensus_rx = network
-            .subscribe(&CONSENSUS_TOPIC)?
+            .subscribe_priority(&CONSENSUS_TOPIC)?
             .map(|m| NodeIncomingEvent::Consensus(m));
         streams.push(consensus_rx.boxed());
 
         let view_change_rx = network
-            .subscribe(&VIEW_CHANGE_TOPIC)?
+            .subscribe_priority(&VIEW_CHANGE_TOPIC)?
             .map(|m| NodeIncomingEvent::ViewChangeMessage(m));
         streams.push(view_change_rx.boxed());
 
         let view_change_proofs_rx = network
-            .subscribe(&VIEW_CHANGE_PROOFS_TOPIC)?
+            .subscribe_priority(&VIEW_CHANGE_PROOFS_TOPIC)?
             .map(|m| NodeIncomingEvent::ViewChangeProof(m));
         streams.push(view_change_proofs_rx.boxed());
 
@@ -240,10 +247,16 @@ impl NodeService {
 
         // Sealed blocks broadcast topic.
         let block_rx = network
-            .subscribe(&SEALED_BLOCK_TOPIC)?
+            .subscribe_validated(&SEALED_BLOCK_TOPIC)?
             .map(|m| NodeIncomingEvent::Block(m));
         streams.push(block_rx.boxed());
 
+        // Governance votes.
+        let parameter_vote_rx = network
+            .subscribe(&GOVERNANCE_TOPIC)?
+            .map(|m| NodeIncomingEvent::ParameterVote(m));
+        streams.push(parameter_vote_rx.boxed());
+
         // Chain loader messages.
         let requests_rx = network.subscribe_unicast(CHAIN_LOADER_TOPIC)?.map(|m| {
             NodeIncomingEvent::ChainLoaderMessage {
@@ -554,7 +567,7 @@ impl NodeService {
                                 NodeRequest::SubscribeStatus {} => {
                                     let response = match Self::handle_subscription_to_status(&mut status_subscribers) {
                                         Ok(rx) => {
-                                            let status = state.chain.status();
+                                            let status = state.status();
                                             NodeResponse::SubscribedStatus {
                                                 status,
                                                 rx: Some(rx),
@@ -644,6 +657,16 @@ impl NodeService {
                         //
                         network.send(dest, &topic, data)
                     }
+                    NodeOutgoingEvent::ReportMisbehavior {
+                        topic,
+                        data,
+                        misbehavior,
+                    } => network.report_misbehavior(&topic, &data, misbehavior),
+                    NodeOutgoingEvent::ValidateMessage {
+                        topic,
+                        data,
+                        validation,
+                    } => network.validate_message(&topic, &data, validation),
                     NodeOutgoingEvent::MacroBlockProposeTimer(duration) => {
                         macro_block_propose_timer.set(time::delay_for(duration).fuse());
                         micro_block_propose_timer.set(Fuse::terminated());
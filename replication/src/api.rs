@@ -21,10 +21,11 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
+use schemars::JsonSchema;
 use serde_derive::{Deserialize, Serialize};
 use std::time::Duration;
 
-#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "state")]
 #[serde(rename_all = "snake_case")]
 pub enum PeerInfo {
@@ -38,6 +39,7 @@ pub enum PeerInfo {
         peer_id: String,
         multiaddr: Vec<String>,
         #[serde(with = "humantime_serde")]
+        #[schemars(with = "String")]
         idle: Duration,
     },
     Connecting {
@@ -46,6 +48,7 @@ pub enum PeerInfo {
         peer_id: String,
         multiaddr: Vec<String>,
         #[serde(with = "humantime_serde")]
+        #[schemars(with = "String")]
         idle: Duration,
     },
     Connected {
@@ -54,6 +57,7 @@ pub enum PeerInfo {
         peer_id: String,
         multiaddr: Vec<String>,
         #[serde(with = "humantime_serde")]
+        #[schemars(with = "String")]
         idle: Duration,
     },
     Accepted {
@@ -62,6 +66,7 @@ pub enum PeerInfo {
         peer_id: String,
         multiaddr: Vec<String>,
         #[serde(with = "humantime_serde")]
+        #[schemars(with = "String")]
         idle: Duration,
     },
     Receiving {
@@ -70,6 +75,7 @@ pub enum PeerInfo {
         peer_id: String,
         multiaddr: Vec<String>,
         #[serde(with = "humantime_serde")]
+        #[schemars(with = "String")]
         idle: Duration,
         epoch: u64,
         offset: u32,
@@ -82,6 +88,7 @@ pub enum PeerInfo {
         peer_id: String,
         multiaddr: Vec<String>,
         #[serde(with = "humantime_serde")]
+        #[schemars(with = "String")]
         idle: Duration,
         epoch: u64,
         offset: u32,
@@ -94,12 +101,13 @@ pub enum PeerInfo {
         peer_id: String,
         multiaddr: Vec<String>,
         #[serde(with = "humantime_serde")]
+        #[schemars(with = "String")]
         idle: Duration,
         error: String,
     },
 }
 
-#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct ReplicationInfo {
     pub peers: Vec<PeerInfo>,
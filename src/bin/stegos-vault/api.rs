@@ -2,12 +2,13 @@ use crate::error::Error as VaultError;
 use failure::{bail, Error};
 use futures::channel::mpsc;
 use futures::{Stream, StreamExt};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use stegos_api::{server::api::RawResponse, ResponseKind};
+use stegos_api::{server::api::RawResponse, InnerResponses, ResponseKind};
 use stegos_crypto::hash::Hash;
 use stegos_crypto::scc;
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "type")]
 #[serde(rename_all = "snake_case")]
 pub enum VaultRequest {
@@ -117,7 +118,13 @@ impl VaultResponse {
                 return Ok(Box::new(
                     rx.take()
                         .expect("Stream exist")
-                        .map(|i| ResponseKind::Raw(serde_json::to_value(i).unwrap()))
+                        .map(|i| match serde_json::to_value(i) {
+                            Ok(value) => ResponseKind::Raw(value),
+                            Err(e) => {
+                                let error = e.to_string();
+                                ResponseKind::Inner(InnerResponses::InternalError { error })
+                            }
+                        })
                         .map(RawResponse),
                 ))
             }
@@ -179,3 +186,21 @@ pub enum VaultNotification {
         code: u64,
     },
 }
+
+#[cfg(test)]
+mod tests {
+    use super::VaultRequest;
+    use schemars::schema_for;
+    use std::path::Path;
+    use stegos_api::schema::check_snapshot;
+
+    /// Compares the schema with `api/schema/vault.json`, see `stegos_api::schema::api_schema`.
+    #[test]
+    fn schema() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("api")
+            .join("schema")
+            .join("vault.json");
+        check_snapshot(&schema_for!(VaultRequest), &path);
+    }
+}
@@ -31,7 +31,9 @@ use std::fs;
 use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::str::FromStr;
+use std::sync::Arc;
 use stegos_api::load_or_create_api_token;
+use stegos_api::server::limits::{ApiLimits, Limiter};
 use stegos_api::server::spawn_server;
 use stegos_blockchain::{chain_to_prefix, initialize_chain};
 use stegos_crypto::hash::Hash;
@@ -244,12 +246,15 @@ async fn run() -> Result<(), Error> {
     let api = Vault::spawn(cfg.clone(), Hash::digest(&genesis));
     let api_token = load_or_create_api_token(&api_token_file)?;
     let join = spawn_server(
-        cfg.general.api_endpoint,
-        api_token,
+        vec![cfg.general.api_endpoint],
+        None,
+        api_token.into(),
         vec![Box::new(api)],
         None,
         version,
         cfg.general.chain,
+        None,
+        Arc::new(Limiter::new(ApiLimits::default())),
     )
     .await?;
     join.await?;
@@ -17,17 +17,16 @@ use std::str::FromStr;
 use futures::select;
 use futures::stream::SelectAll;
 use stegos_api::load_api_token;
-use stegos_api::WebSocketClient;
-use stegos_api::{InnerResponses, Request, RequestKind, Response, ResponseKind};
+use stegos_api::{RequestKind, ResponseKind};
 use stegos_blockchain::api::StatusInfo;
 use stegos_blockchain::Timestamp;
+use stegos_client::{Client, Notifications};
 use stegos_crypto::hash::Hash;
 use stegos_crypto::{pbc, scc};
 use stegos_keychain::keyfile::{
     load_account_pkey, load_account_skey, write_account_pkey, write_account_skey,
 };
 use stegos_node::api::ChainNotification;
-use stegos_node::{NodeRequest, NodeResponse};
 use stegos_wallet::{
     accounts::UnsealedAccountService,
     api::{AccountNotification, AccountRequest, AccountResponse},
@@ -151,7 +150,9 @@ struct VaultService {
     server: mpsc::UnboundedReceiver<AsyncRequest>,
 
     // Websocket client to online node.
-    client: WebSocketClient,
+    client: Client,
+    // Chain notifications, replaced on resubscribe.
+    chain: SelectAll<Notifications<ChainNotification>>,
 
     cfg: VaultConfig,
     password: String,
@@ -181,14 +182,14 @@ impl VaultService {
         let created_accounts = HashMap::new();
         let uri = format!("ws://{}", cfg.node_address);
         let api_token = load_api_token(&cfg.node_token_path).map_err(Error::from)?;
-        let client = match WebSocketClient::new(uri, api_token).await {
+        let client = match Client::new(uri, api_token).await {
             Ok(client) => client,
             Err(e) => {
                 error!(
                     "Cannot connect to online node, check if {} address is available.",
                     cfg.node_address
                 );
-                return Err(e);
+                return Err(e.into());
             }
         };
 
@@ -239,6 +240,7 @@ impl VaultService {
             server,
             cfg,
             client,
+            chain: SelectAll::new(),
             password,
             handle,
             users_list,
@@ -253,37 +255,33 @@ impl VaultService {
         Ok(vault_service)
     }
 
-    async fn subscribe_to_online_node(client: &mut WebSocketClient, epoch: u64) {
+    /// Subscribes to chain notifications since the current epoch.
+    async fn subscribe_to_online_node(&mut self) {
+        let epoch = self.handle.status.epoch;
         info!(
             "Requesting history from online node since epoch = {}",
             epoch
         );
-        let request_kind =
-            RequestKind::NodeRequest(NodeRequest::SubscribeChain { epoch, offset: 0 });
-        let request = Request {
-            kind: request_kind,
-            id: 0,
-        };
-        let response = client.request(request).await.unwrap();
-        match response.kind {
-            ResponseKind::NodeResponse(NodeResponse::SubscribedChain { .. }) => {
+        match self.client.node().subscribe_chain(epoch, 0).await {
+            Ok(notifications) => {
                 info!("Successfully subscribed to online node chain notifications");
+                self.chain = SelectAll::new();
+                self.chain.push(notifications);
             }
-            _ => {
+            Err(e) => {
                 error!(
-                    "Received wrong response for notification request = {:?}",
-                    response
+                    "Failed to subscribe to online node chain notifications = {}",
+                    e
                 );
             }
         }
     }
 
     async fn run(mut self) {
-        Self::subscribe_to_online_node(&mut self.client, self.handle.status.epoch).await;
+        self.subscribe_to_online_node().await;
         let mut interval =
             tokio::time::interval_at(Instant::now() + RESUBSCRIBE_INTERVAL, RESUBSCRIBE_INTERVAL);
         loop {
-            let notifications = self.client.notification();
             select! {
                 res = self.server.next() => {
                     let (sender, request) = res.unwrap();
@@ -294,8 +292,8 @@ impl VaultService {
                     };
                     sender.send(response).unwrap();
                 },
-                node_notification = notifications.fuse() => {
-                    self.handle_client(node_notification.expect("Online node should never gone.")).await;
+                chain_notification = self.chain.select_next_some() => {
+                    self.handle_chain_notification(chain_notification).await;
                 },
                 account_notification = self.account_subscribtion.next() => {
                     let account_notification = account_notification.expect("Inner account node should never gone.");
@@ -304,7 +302,7 @@ impl VaultService {
                 },
                 tick = interval.tick().fuse() => {
                     debug!("Timeout while receiving for notification from node, resubscribing.");
-                    Self::subscribe_to_online_node(&mut self.client, self.handle.status.epoch).await;
+                    self.subscribe_to_online_node().await;
                 }
             }
 
@@ -408,16 +406,9 @@ impl VaultService {
                 }
             };
 
-            let request = NodeRequest::BroadcastTransaction { data: data.clone() };
-            let raw_request = Request {
-                id: 0,
-                kind: RequestKind::NodeRequest(request),
-            };
-
             debug!("Broadcasting transaction trough online node.");
-            let response = self.client.request(raw_request).await.unwrap();
-            match response.kind {
-                ResponseKind::NodeResponse(NodeResponse::BroadcastTransaction { .. }) => {
+            match self.client.node().broadcast_transaction(data.clone()).await {
+                Ok(_) => {
                     debug!("Successfully broadcasted transaction. Added to pending list.");
                     let update = UserBalanceUpdated {
                         public_key,
@@ -436,53 +427,40 @@ impl VaultService {
                         self.push_user_updates(epoch, notification);
                     }
                 }
-                _ => {
-                    trace!(
-                        "Received wrong response for broadcast request = {:?}",
-                        response
-                    );
+                Err(e) => {
+                    error!("Failed to broadcast transaction = {}", e);
                 }
             }
         }
         Ok(())
     }
 
-    async fn handle_client(&mut self, response: Response) {
-        trace!("Received new notification = {:?}", response);
-        match response.kind {
-            ResponseKind::ChainNotification(node_chain) => {
-                if let ChainNotification::MacroBlockCommitted(block) = node_chain {
-                    info!(
-                        "Received new macro_block epoch={}, processing",
-                        block.block.header.epoch
-                    );
-                    let validators = block.epoch_info.into_stakers_group();
-                    if let Err(e) = self
-                        .process_deposit(block.block.header.epoch, block.block.outputs.clone())
-                        .await
-                    {
-                        error!("Failed to process deposit = {}", e)
-                    }
-                    let outputs = block.block.outputs.clone();
-                    let light_block = block.block.into_light_macro_block(validators);
-                    let event = stegos_wallet::ReplicationOutEvent::FullBlock {
-                        block: light_block.into(),
-                        outputs,
-                    };
-
-                    self.handle
-                        .chain_tx
-                        .send(event)
-                        .await
-                        .expect("Account should read blocks.");
-                }
+    async fn handle_chain_notification(&mut self, notification: ChainNotification) {
+        trace!("Received new notification = {:?}", notification);
+        if let ChainNotification::MacroBlockCommitted(block) = notification {
+            info!(
+                "Received new macro_block epoch={}, processing",
+                block.block.header.epoch
+            );
+            let validators = block.epoch_info.into_stakers_group();
+            if let Err(e) = self
+                .process_deposit(block.block.header.epoch, block.block.outputs.clone())
+                .await
+            {
+                error!("Failed to process deposit = {}", e)
             }
+            let outputs = block.block.outputs.clone();
+            let light_block = block.block.into_light_macro_block(validators);
+            let event = stegos_wallet::ReplicationOutEvent::FullBlock {
+                block: light_block.into(),
+                outputs,
+            };
 
-            ResponseKind::Inner(InnerResponses::Reconnect) => {
-                info!("Connection to node was recovered, trying to resubscribe.");
-                Self::subscribe_to_online_node(&mut self.client, self.handle.status.epoch).await;
-            }
-            _ => {}
+            self.handle
+                .chain_tx
+                .send(event)
+                .await
+                .expect("Account should read blocks.");
         }
     }
 
@@ -544,29 +522,18 @@ impl VaultService {
             AccountResponse::RawTransactionCreated { data: tx } => {
                 let outputs_hashes: Vec<_> = tx.txouts().iter().map(Hash::digest).collect();
 
-                let request = NodeRequest::BroadcastTransaction { data: tx };
-                let raw_request = Request {
-                    id: 0,
-                    kind: RequestKind::NodeRequest(request),
-                };
-
                 debug!("Broadcasting transaction trough online node.");
-                let response = self.client.request(raw_request).await.unwrap();
-                match response.kind {
-                    ResponseKind::NodeResponse(NodeResponse::BroadcastTransaction {
-                        hash, ..
-                    }) => {
+                match self.client.node().broadcast_transaction(tx).await {
+                    Ok((hash, _status)) => {
                         debug!(
                             "Successfully broadcasted withdraw transaction, tx_hash = {}",
                             hash
                         );
                         Ok(VaultResponse::WithdrawCreated { outputs_hashes })
                     }
-                    _ => Err(format_err!(
-                        "Received wrong response for broadcast request = {:?}",
-                        response
-                    )
-                    .into()),
+                    Err(e) => {
+                        Err(format_err!("Failed to broadcast withdraw transaction = {}", e).into())
+                    }
                 }
             }
             response => return Err(VaultError::UnexpectedResponse(format!("{:?}", response))),
@@ -903,6 +870,7 @@ impl VaultService {
                 stegos_network::NetworkName::from_str("dev").unwrap(),
                 network_skey.clone(),
                 network_pkey.clone(),
+                None,
             )
             .await?;
 
@@ -912,6 +880,8 @@ impl VaultService {
         let (chain_tx, chain_notifications) = mpsc::channel(2);
         let subscribers: Vec<mpsc::UnboundedSender<AccountNotification>> = Vec::new();
         let (outbox, events) = mpsc::unbounded::<AccountEvent>();
+        // Account messages are not relayed by the vault.
+        let (outgoing_messages, _) = mpsc::unbounded();
 
         let mut unsealed = UnsealedAccountService::new(
             account_database_dir,
@@ -925,6 +895,7 @@ impl VaultService {
             cfg.chain_cfg.clone(),
             max_inputs_in_tx,
             subscribers,
+            outgoing_messages,
             events,
             chain_notifications,
         );
@@ -946,6 +917,8 @@ impl VaultService {
                 last_macro_block_hash: Hash::zero(),
                 last_macro_block_timestamp: Timestamp::now(),
                 local_timestamp: Timestamp::now(),
+                reachability: Default::default(),
+                advertised_address: None,
             },
             chain_tx,
         };
@@ -23,6 +23,7 @@ use failure::{format_err, Error};
 use futures::future::Fuse;
 use futures::prelude::*;
 use futures::select;
+use futures::stream::SelectAll;
 use lazy_static::*;
 use log::{debug, trace};
 use regex::Regex;
@@ -32,12 +33,12 @@ use serde::ser::Serialize;
 use std::fmt;
 use std::io::stdin;
 use std::path::PathBuf;
-use std::pin::Pin;
 use std::str::FromStr;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use stegos_api::*;
 use stegos_blockchain::{chain_to_prefix, Timestamp};
+use stegos_client::{is_subscribe, Client, ClientConfig, ClientError, Notifications};
 use stegos_crypto::hash::Hash;
 use stegos_crypto::{pbc, scc};
 
@@ -58,6 +59,8 @@ lazy_static! {
     static ref PAY_ARGUMENTS_RE: Regex = Regex::new(r"^(\s+(?P<public>(/public)))?(\s+(?P<snowball>(/snowball)))?(\s+(?P<comment>[^/]+?))?(\s+(?P<fee>(/fee\s[0-9_]{1,25})))?(\s+(?P<certificate>(/certificate)))?$").unwrap();
     /// Regex to parse "msg" command.
     static ref MSG_COMMAND_RE: Regex = Regex::new(r"^\s*(?P<recipient>[0-9a-f]+)\s+(?P<msg>.+)$").unwrap();
+    /// Regex to parse "dm" command.
+    static ref DM_COMMAND_RE: Regex = Regex::new(r"^\s*(?P<recipient>[0-9A-Za-z]+)\s+(?P<msg>.+)$").unwrap();
     /// Regex to parse "stake/unstake" command.
     static ref STAKE_COMMAND_RE: Regex = Regex::new(r"^\s*(?P<amount>[0-9_]{1,25})$").unwrap();
     /// Regex to parse "publish" command.
@@ -150,7 +153,9 @@ impl FromStr for Formatter {
 /// Console (stdin) service.
 pub struct ConsoleService {
     /// API client.
-    client: WebSocketClient,
+    client: Client,
+    /// Notifications of subscriptions.
+    notifications: SelectAll<Notifications<serde_json::Value>>,
     /// Current Account Id.
     account_id: Arc<Mutex<AccountId>>,
     /// A channel to receive message from stdin thread.
@@ -170,12 +175,17 @@ impl ConsoleService {
         version: String,
         uri: String,
         api_token: ApiToken,
+        tls: ClientTlsConfig,
         history_file: PathBuf,
         formatter: Formatter,
         raw: bool,
         subscribed: bool,
     ) -> Result<(), Error> {
-        let mut client = WebSocketClient::new(uri, api_token).await?;
+        let config = ClientConfig {
+            tls,
+            ..Default::default()
+        };
+        let client = Client::with_config(uri, api_token, config).await?;
         let account_id = Arc::new(Mutex::new("1".to_string()));
 
         if let Some(chain) = &chain {
@@ -183,7 +193,7 @@ impl ConsoleService {
             stegos_crypto::set_network_prefix(chain_to_prefix(&chain))
                 .expect("Network prefix not initialised.");
         } else {
-            Self::try_chain_name_resolve(&mut client).await;
+            Self::try_chain_name_resolve(&client).await;
         }
         let (reader, raw) = if atty::is(atty::Stream::Stdin) {
             println!("{} {}", name, version);
@@ -199,6 +209,7 @@ impl ConsoleService {
         };
         let service = ConsoleService {
             client,
+            notifications: SelectAll::new(),
             account_id,
             reader,
             formatter,
@@ -211,21 +222,15 @@ impl ConsoleService {
     async fn run(mut self) -> Result<(), Error> {
         let mut first_time = true;
         loop {
-            let mut notification_orig = self.client.notification();
-
-            let notification = unsafe { Pin::new_unchecked(&mut notification_orig) };
-            let mut notification = notification.fuse();
             let mut reader = self.reader.next().fuse();
             if !first_time {
                 reader = Fuse::terminated();
             }
             select! {
-                item = notification => {
-                    drop(notification_orig);
-                    self.on_notification(item.unwrap());
+                notification = self.notifications.select_next_some() => {
+                    self.on_notification(notification);
                 },
                 input = reader => {
-                    drop(notification_orig);
                     match input {
                         Some(Ok(line)) => {
                             self.on_input(&line).await?;
@@ -245,6 +250,7 @@ impl ConsoleService {
                         }
                     }
                 }
+                complete => break,
             }
         }
         Ok(())
@@ -340,6 +346,7 @@ impl ConsoleService {
         );
         eprintln!("validate certificate UTXO SENDER_ADDRESS RECIPIENT_ADDRESS RVALUE - check that payment certificate is valid");
         eprintln!("msg ADDRESS MESSAGE - send a message via blockchain");
+        eprintln!("dm ADDRESS MESSAGE - send an encrypted message to an account");
         eprintln!("stake remote - stake money to remote node, network key should be located near account key.");
         eprintln!("stake AMOUNT - stake money");
         eprintln!("stake all - stake all available money");
@@ -353,6 +360,7 @@ impl ConsoleService {
         eprintln!("show balance - print balance");
         eprintln!("show utxo - print unspent outputs");
         eprintln!("show history [STARTING DATE] - print history since date");
+        eprintln!("show inbox [STARTING DATE] - print received messages since date");
         eprintln!("show election - show consensus state");
         eprintln!("show escrow - print escrow");
         eprintln!("show replication - show replication status");
@@ -367,6 +375,9 @@ impl ConsoleService {
         eprintln!("net publish TOPIC MESSAGE - publish a network message via floodsub");
         eprintln!("net send NETWORK_ADDRESS TOPIC MESSAGE - send a network message via unicast");
         eprintln!("net peers - show connected peers");
+        eprintln!("net scores - show reputation of peers");
+        eprintln!("net scores clear [PEER_ID] - forget scores and lift bans of one or all peers");
+        eprintln!("net traffic - show traffic per topic and per peer");
         eprintln!();
     }
 
@@ -434,6 +445,13 @@ impl ConsoleService {
         eprintln!();
     }
 
+    fn help_dm() {
+        eprintln!("Usage: dm ADDRESS MESSAGE");
+        eprintln!(" - ADDRESS recipient's address");
+        eprintln!(" - MESSAGE some message");
+        eprintln!();
+    }
+
     fn help_use() {
         eprintln!("Usage: use ACCOUNT_ID");
         eprintln!();
@@ -453,15 +471,26 @@ impl ConsoleService {
         eprintln!();
     }
 
+    /// Sends a request, subscriptions are added to notifications.
+    async fn send_request(&mut self, request: RequestKind) -> Result<(), Error> {
+        let response = if is_subscribe(&request) {
+            match self.client.subscribe(request).await {
+                Ok((response, notifications)) => {
+                    self.notifications.push(notifications);
+                    Ok(response)
+                }
+                Err(e) => Err(e),
+            }
+        } else {
+            self.client.request(request).await
+        };
+        self.on_response(response)
+    }
+
     async fn send_network_request(&mut self, request: NetworkRequest) -> Result<(), Error> {
         self.print(&request);
-        let request = Request {
-            kind: RequestKind::NetworkRequest(request),
-            id: 0,
-        };
-        let response = self.client.request(request).await?;
-        self.on_response(response);
-        Ok(())
+        self.send_request(RequestKind::NetworkRequest(request))
+            .await
     }
 
     async fn send_wallet_control_request(
@@ -481,13 +510,8 @@ impl ConsoleService {
             }
         }
         let request = WalletRequest::WalletControlRequest(request);
-        let request = Request {
-            kind: RequestKind::WalletsRequest(request),
-            id: 0,
-        };
-        let response = self.client.request(request).await?;
-        self.on_response(response);
-        Ok(())
+        self.send_request(RequestKind::WalletsRequest(request))
+            .await
     }
 
     async fn send_account_request(&mut self, request: AccountRequest) -> Result<(), Error> {
@@ -510,37 +534,35 @@ impl ConsoleService {
             account_id,
             request,
         };
-
-        let request = Request {
-            kind: RequestKind::WalletsRequest(request),
-            id: 0,
-        };
-        let response = self.client.request(request).await?;
-        self.on_response(response);
-        Ok(())
+        self.send_request(RequestKind::WalletsRequest(request))
+            .await
     }
 
     async fn send_raw_request(&mut self, request: serde_json::Value) -> Result<(), Error> {
         trace!("Received raw request ={:?}", request);
         self.print(&request);
-        let request = Request {
-            kind: RequestKind::Raw(request),
-            id: 0,
+        // Requests of external APIs don't have a schema.
+        let subscribe = request
+            .get("type")
+            .and_then(|t| t.as_str())
+            .map_or(false, |t| t.starts_with("subscribe"));
+        let response = if subscribe {
+            match self.client.subscribe_raw(request).await {
+                Ok((response, notifications)) => {
+                    self.notifications.push(notifications);
+                    Ok(response)
+                }
+                Err(e) => Err(e),
+            }
+        } else {
+            self.client.raw(request).await
         };
-        let response = self.client.request(request).await?;
-        self.on_response(response);
-        Ok(())
+        self.on_response(response)
     }
 
     async fn send_node_request(&mut self, request: NodeRequest) -> Result<(), Error> {
         self.print(&request);
-        let request = Request {
-            kind: RequestKind::NodeRequest(request),
-            id: 0,
-        };
-        let response = self.client.request(request).await?;
-        self.on_response(response);
-        Ok(())
+        self.send_request(RequestKind::NodeRequest(request)).await
     }
 
     /// Called when line is typed on standard input.
@@ -619,6 +641,21 @@ impl ConsoleService {
         } else if msg.starts_with("net peers") {
             self.send_network_request(NetworkRequest::ConnectedNodesRequest {})
                 .await?
+        } else if msg.starts_with("net scores clear") {
+            let peer_id = msg["net scores clear".len()..].trim();
+            let peer_id = if peer_id.is_empty() {
+                None
+            } else {
+                Some(peer_id.to_string())
+            };
+            self.send_network_request(NetworkRequest::ClearPeerScores { peer_id })
+                .await?
+        } else if msg.starts_with("net scores") {
+            self.send_network_request(NetworkRequest::PeerScores {})
+                .await?
+        } else if msg.starts_with("net traffic") {
+            self.send_network_request(NetworkRequest::TrafficStats {})
+                .await?
         } else if msg.starts_with("pay ") {
             let caps = match PAY_COMMAND_RE.captures(&msg[4..]) {
                 Some(c) => c,
@@ -819,6 +856,27 @@ impl ConsoleService {
                 raw: false,
             };
             self.send_account_request(request).await?
+        } else if msg.starts_with("dm ") {
+            let caps = match DM_COMMAND_RE.captures(&msg[3..]) {
+                Some(c) => c,
+                None => {
+                    Self::help_dm();
+                    return Ok(true);
+                }
+            };
+
+            let recipient = caps.name("recipient").unwrap().as_str();
+            let recipient = match scc::PublicKey::from_str(recipient) {
+                Ok(r) => r,
+                Err(e) => {
+                    eprintln!("Invalid account public key '{}': {}", recipient, e);
+                    Self::help_dm();
+                    return Ok(true);
+                }
+            };
+            let text = caps.name("msg").unwrap().as_str().to_string();
+            let request = AccountRequest::SendMessage { recipient, text };
+            self.send_account_request(request).await?
         } else if msg.starts_with("stake all") {
             let payment_fee = PAYMENT_FEE;
             let request = AccountRequest::StakeAll { payment_fee };
@@ -947,6 +1005,18 @@ impl ConsoleService {
                 limit: CONSOLE_HISTORY_LIMIT,
             };
             self.send_account_request(request).await?
+        } else if msg.starts_with("show inbox") {
+            let arg = &msg[10..];
+            let starting_from = if arg.is_empty() {
+                Timestamp::now() - Duration::from_secs(86400)
+            } else {
+                parse_past_datetime(arg)?
+            };
+            let request = AccountRequest::InboxInfo {
+                starting_from,
+                limit: CONSOLE_HISTORY_LIMIT,
+            };
+            self.send_account_request(request).await?
         } else if msg == "show recovery" {
             let request = AccountRequest::GetRecovery {};
             self.send_account_request(request).await?
@@ -1083,36 +1153,28 @@ impl ConsoleService {
             }
         }
     }
-    fn on_notification(&mut self, notification: Response) {
+    fn on_notification(&mut self, notification: serde_json::Value) {
         self.print(&notification);
     }
 
-    fn on_response(&mut self, response: Response) {
-        match &response.kind {
-            ResponseKind::NodeResponse(_)
-            | ResponseKind::WalletResponse(_)
-            | ResponseKind::NetworkResponse(_) => {
-                self.print(&response);
-            }
-            _ => {
-                self.print(&response);
+    /// Prints the response, stops on connection errors.
+    fn on_response<T: Serialize>(&mut self, response: Result<T, ClientError>) -> Result<(), Error> {
+        match response {
+            Ok(response) => self.print(&response),
+            Err(e @ ClientError::Closed) | Err(e @ ClientError::Connection(_)) => {
+                return Err(e.into())
             }
+            Err(e) => eprintln!("{}", e),
         }
+        Ok(())
     }
 
-    async fn try_chain_name_resolve(client: &mut WebSocketClient) {
-        let request = Request {
-            kind: RequestKind::NetworkRequest(NetworkRequest::ChainName {}),
-            id: 0,
-        };
-        let response = client.request(request).await.unwrap();
-        let chain = match response {
-            Response {
-                kind: ResponseKind::NetworkResponse(NetworkResponse::ChainName { name }),
-                ..
-            } => name,
-            response => panic!("Wrong reponse to chain name request = {:?}", response),
-        };
+    async fn try_chain_name_resolve(client: &Client) {
+        let chain = client
+            .network()
+            .chain_name()
+            .await
+            .expect("Failed to resolve chain name");
         debug!("Initialising cli for chain = {}", chain);
         stegos_crypto::set_network_prefix(chain_to_prefix(&chain))
             .expect("Network prefix not initialised.");
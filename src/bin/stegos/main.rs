@@ -26,9 +26,9 @@ use clap;
 use clap::{App, Arg};
 use console::ConsoleService;
 use std::fs;
-use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::str::FromStr;
+use stegos_api::{ClientTlsConfig, UNIX_ENDPOINT_PREFIX};
 
 #[tokio::main]
 async fn main() {
@@ -60,15 +60,59 @@ async fn main() {
                 .long("api-endpoint")
                 .env("STEGOS_API_ENDPOINT")
                 .value_name("ENDPOINT")
-                .help("API ENDPOINT, e.g. 127.0.0.1:3145")
+                .help("API ENDPOINT (host:port or unix:PATH), e.g. 127.0.0.1:3145")
                 .takes_value(true)
                 .default_value(&default_endpoint)
-                .validator(|uri| {
-                    SocketAddr::from_str(&uri)
+                .validator(|uri| match uri.rfind(':') {
+                    _ if uri.starts_with(UNIX_ENDPOINT_PREFIX) => Ok(()),
+                    Some(sep) if sep > 0 => uri[sep + 1..]
+                        .parse::<u16>()
                         .map(|_| ())
-                        .map_err(|e| format!("{}", e))
+                        .map_err(|e| format!("{}", e)),
+                    _ => Err("Missing port".to_string()),
                 }),
         )
+        .arg(
+            Arg::with_name("tls")
+                .long("tls")
+                .help("Connect using TLS (wss://)")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("tls-ca")
+                .long("tls-ca")
+                .env("STEGOS_TLS_CA")
+                .value_name("FILE")
+                .help("Trust only CA certificates from this PEM file")
+                .requires("tls")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("tls-cert")
+                .long("tls-cert")
+                .env("STEGOS_TLS_CERT")
+                .value_name("FILE")
+                .help("Client certificate (PEM)")
+                .requires_all(&["tls", "tls-key"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("tls-key")
+                .long("tls-key")
+                .env("STEGOS_TLS_KEY")
+                .value_name("FILE")
+                .help("Client private key (PEM)")
+                .requires_all(&["tls", "tls-cert"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("tls-server-name")
+                .long("tls-server-name")
+                .value_name("NAME")
+                .help("Name in the server certificate, if it differs from API ENDPOINT")
+                .requires("tls")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("api-token-file")
                 .short("t")
@@ -159,7 +203,23 @@ async fn main() {
     } else {
         data_dir.join("api.token")
     };
-    let uri = format!("ws://{}", args.value_of("api-endpoint").unwrap());
+    let tls = if args.is_present("tls") {
+        Some(ClientTlsConfig {
+            ca: args.value_of("tls-ca").map(PathBuf::from),
+            certificate: args.value_of("tls-cert").map(PathBuf::from),
+            private_key: args.value_of("tls-key").map(PathBuf::from),
+            server_name: args.value_of("tls-server-name").map(String::from),
+        })
+    } else {
+        None
+    };
+    let endpoint = args.value_of("api-endpoint").unwrap();
+    let uri = if endpoint.starts_with(UNIX_ENDPOINT_PREFIX) {
+        endpoint.to_string()
+    } else {
+        let scheme = if tls.is_some() { "wss" } else { "ws" };
+        format!("{}://{}", scheme, endpoint)
+    };
     let api_token = match stegos_api::load_api_token(&api_token_file) {
         Ok(r) => r,
         Err(e) => {
@@ -174,6 +234,7 @@ async fn main() {
         version,
         uri,
         api_token,
+        tls.unwrap_or_default(),
         history_file,
         formatter,
         raw,
@@ -29,6 +29,9 @@ use std::io::ErrorKind;
 use std::io::Read;
 use std::path::{Path, PathBuf};
 use std::result::Result;
+use stegos_api::server::limits::ApiLimits;
+use stegos_api::server::webhooks::WebhookConfig;
+use stegos_api::ServerTlsConfig;
 use stegos_blockchain::ConsistencyCheck;
 use stegos_network::NetworkConfig;
 use stegos_node::NodeConfig;
@@ -52,6 +55,8 @@ pub struct Config {
     pub node: NodeConfig,
     /// Network configuration.
     pub network: NetworkConfig,
+    /// Webhooks for wallet and chain events.
+    pub webhooks: Vec<WebhookConfig>,
 }
 
 /// Default values for global configuration.
@@ -61,6 +66,7 @@ impl Default for Config {
             general: Default::default(),
             node: Default::default(),
             network: Default::default(),
+            webhooks: Vec::new(),
         }
     }
 }
@@ -81,6 +87,20 @@ pub struct GeneralConfig {
     pub prometheus_endpoint: String,
     /// WebSocket API endpoint,
     pub api_endpoint: String,
+    /// JSON-RPC over HTTP endpoint, disabled if empty.
+    /// Uses `api_tls`, only loopback addresses are allowed without TLS.
+    pub jsonrpc_endpoint: String,
+    /// Unix domain socket for WebSocket API, disabled if empty.
+    pub api_unix_socket: String,
+    /// gRPC endpoint, disabled if empty. Requires the `grpc` feature.
+    /// Uses `api_tls`, only loopback addresses are allowed without TLS.
+    pub grpc_endpoint: String,
+    /// TLS for WebSocket API, disabled if not set.
+    pub api_tls: Option<ServerTlsConfig>,
+    /// The number of notifications kept to resume API subscriptions, disabled if zero.
+    pub api_journal_capacity: usize,
+    /// Limits of WebSocket API, JSON-RPC and gRPC servers.
+    pub api_limits: ApiLimits,
 }
 
 impl Default for GeneralConfig {
@@ -100,6 +120,12 @@ impl Default for GeneralConfig {
             log_config: PathBuf::new(),
             prometheus_endpoint: "".to_string(),
             api_endpoint: "127.0.0.1:3145".to_string(),
+            jsonrpc_endpoint: "".to_string(),
+            api_unix_socket: "".to_string(),
+            grpc_endpoint: "".to_string(),
+            api_tls: None,
+            api_journal_capacity: 10000,
+            api_limits: Default::default(),
         }
     }
 }
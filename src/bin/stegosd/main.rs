@@ -22,7 +22,7 @@
 mod config;
 
 use crate::config::GeneralConfig;
-use clap::{self, App, Arg, ArgMatches};
+use clap::{self, App, AppSettings, Arg, ArgMatches, SubCommand};
 use dirs;
 use failure::{format_err, Error};
 use futures::StreamExt;
@@ -43,8 +43,17 @@ use prometheus::{self, Encoder};
 use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::Arc;
 use std::{fs, process};
-use stegos_api::{load_or_create_api_token, server::spawn_server};
+#[cfg(feature = "grpc")]
+use stegos_api::server::grpc::spawn_grpc_server;
+use stegos_api::server::{
+    api::ApiHandler, journal::Journal, jsonrpc::spawn_jsonrpc_server, limits::Limiter,
+    spawn_server, webhooks::spawn_webhooks,
+};
+use stegos_api::{
+    load_or_create_api_token, ApiTokens, TokenScope, TokenStore, UNIX_ENDPOINT_PREFIX,
+};
 use stegos_blockchain::{
     chain_to_prefix, initialize_chain, Blockchain, ConsistencyCheck, Timestamp,
 };
@@ -58,6 +67,14 @@ use stegos_wallet::WalletService;
 
 /// The default file name for configuration
 const STEGOSD_TOML: &'static str = "stegosd.toml";
+/// The file name for named API tokens.
+const API_TOKENS_JSON: &'static str = "api_tokens.json";
+/// The file name for the audit log of privileged API calls.
+const API_AUDIT_LOG: &'static str = "api_audit.log";
+/// The file name for the replay journal of API notifications.
+const API_JOURNAL: &'static str = "api_journal.jsonl";
+/// The file name for undelivered webhook events.
+const WEBHOOKS_OUTBOX: &'static str = "webhooks_outbox.json";
 /// The default file name for logger configuration.
 const STEGOSD_LOG4RS_TOML: &'static str = "stegosd-log4rs.toml";
 /// The default file name for the log file.
@@ -296,6 +313,21 @@ fn load_configuration(args: &ArgMatches<'_>) -> Result<config::Config, Error> {
         })?;
     }
 
+    if cfg.network.socks5_proxy != "" {
+        SocketAddr::from_str(&cfg.network.socks5_proxy).map_err(|e| {
+            format_err!(
+                "Invalid network.socks5_proxy '{}': {}",
+                cfg.network.socks5_proxy,
+                e
+            )
+        })?;
+    }
+    if !cfg.network.listen && cfg.network.advertised_endpoint != "" {
+        return Err(format_err!(
+            "network.advertised_endpoint can't be used with network.listen = false"
+        ));
+    }
+
     // Use default SRV record for the chain
     if cfg.general.chain != "dev" && cfg.network.seed_pool == "" {
         cfg.network.seed_pool =
@@ -336,6 +368,42 @@ fn load_configuration(args: &ArgMatches<'_>) -> Result<config::Config, Error> {
         })?;
     }
 
+    // Override global.api_unix_socket via command-line or environment.
+    if let Some(api_unix_socket) = args.value_of("api-unix-socket") {
+        cfg.general.api_unix_socket = api_unix_socket.to_string();
+    }
+
+    // Override global.jsonrpc_endpoint via command-line or environment.
+    if let Some(jsonrpc_endpoint) = args.value_of("jsonrpc-endpoint") {
+        cfg.general.jsonrpc_endpoint = jsonrpc_endpoint.to_string();
+    } else if cfg.general.jsonrpc_endpoint != "" {
+        SocketAddr::from_str(&cfg.general.jsonrpc_endpoint).map_err(|e| {
+            format_err!(
+                "Invalid jsonrpc_endpoint '{}': {}",
+                &cfg.general.jsonrpc_endpoint,
+                e
+            )
+        })?;
+    }
+
+    // Override global.grpc_endpoint via command-line or environment.
+    if let Some(grpc_endpoint) = args.value_of("grpc-endpoint") {
+        cfg.general.grpc_endpoint = grpc_endpoint.to_string();
+    } else if cfg.general.grpc_endpoint != "" {
+        SocketAddr::from_str(&cfg.general.grpc_endpoint).map_err(|e| {
+            format_err!(
+                "Invalid grpc_endpoint '{}': {}",
+                &cfg.general.grpc_endpoint,
+                e
+            )
+        })?;
+    }
+    if cfg!(not(feature = "grpc")) && cfg.general.grpc_endpoint != "" {
+        return Err(format_err!(
+            "Can't use grpc_endpoint: stegosd was built without the grpc feature"
+        ));
+    }
+
     // Disable [node] sections.
     if cfg.general.chain == "mainnet" && cfg.node != Default::default() {
         return Err(format_err!(
@@ -351,6 +419,41 @@ fn load_configuration(args: &ArgMatches<'_>) -> Result<config::Config, Error> {
     Ok(cfg)
 }
 
+/// Manage named API tokens.
+fn api_token_command(root_dir: &Path, args: &ArgMatches<'_>) -> Result<(), Error> {
+    if !root_dir.exists() {
+        fs::create_dir_all(root_dir)
+            .map_err(|e| format_err!("Failed to create {:?}: {}", root_dir, e))?
+    }
+    let mut store = TokenStore::open(&root_dir.join(API_TOKENS_JSON))?;
+    match args.subcommand() {
+        ("create", Some(args)) => {
+            let name = args.value_of("name").unwrap();
+            let scopes = args
+                .values_of("scope")
+                .unwrap()
+                .map(TokenScope::from_str)
+                .collect::<Result<Vec<_>, _>>()?;
+            let token = store.create(name, scopes)?;
+            store.save()?;
+            println!("{}", token.to_base64());
+        }
+        ("revoke", Some(args)) => {
+            let name = args.value_of("name").unwrap();
+            store.revoke(name)?;
+            store.save()?;
+        }
+        ("list", Some(_)) => {
+            for info in store.tokens() {
+                let scopes: Vec<String> = info.scopes.iter().map(ToString::to_string).collect();
+                println!("{}\t{}", info.name, scopes.join(","));
+            }
+        }
+        _ => unreachable!(),
+    }
+    Ok(())
+}
+
 async fn run() -> Result<(), Error> {
     let name = "Stegos Node";
     let version = format!(
@@ -440,6 +543,40 @@ async fn run() -> Result<(), Error> {
                 })
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("api-unix-socket")
+                .long("api-unix-socket")
+                .env("STEGOS_API_UNIX_SOCKET")
+                .value_name("PATH")
+                .help("WebSocket API Unix domain socket, e.g. /run/stegos/stegosd.sock")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("grpc-endpoint")
+                .long("grpc-endpoint")
+                .env("STEGOS_GRPC_ENDPOINT")
+                .value_name("ENDPOINT")
+                .help("gRPC endpoint (ip:port), e.g. 127.0.0.1:3165")
+                .validator(|uri| {
+                    SocketAddr::from_str(&uri)
+                        .map(|_| ())
+                        .map_err(|e| format!("{}", e))
+                })
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("jsonrpc-endpoint")
+                .long("jsonrpc-endpoint")
+                .env("STEGOS_JSONRPC_ENDPOINT")
+                .value_name("ENDPOINT")
+                .help("JSON-RPC over HTTP endpoint (ip:port), e.g. 127.0.0.1:3155")
+                .validator(|uri| {
+                    SocketAddr::from_str(&uri)
+                        .map(|_| ())
+                        .map_err(|e| format!("{}", e))
+                })
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("prometheus-endpoint")
                 .short("p")
@@ -485,11 +622,46 @@ async fn run() -> Result<(), Error> {
                 .help("Start the light node.")
                 .long("light"),
         )
+        .subcommand(
+            SubCommand::with_name("api-token")
+                .about("Manage named API tokens")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("create")
+                        .about("Create a new token and print it")
+                        .arg(Arg::with_name("name").required(true))
+                        .arg(
+                            Arg::with_name("scope")
+                                .short("s")
+                                .long("scope")
+                                .value_name("SCOPE")
+                                .help("admin, chain, network, wallet or account:ID")
+                                .multiple(true)
+                                .number_of_values(1)
+                                .required(true)
+                                .validator(|scope| {
+                                    TokenScope::from_str(&scope)
+                                        .map(|_| ())
+                                        .map_err(|e| format!("{}", e))
+                                }),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("revoke")
+                        .about("Revoke a token")
+                        .arg(Arg::with_name("name").required(true)),
+                )
+                .subcommand(SubCommand::with_name("list").about("List tokens")),
+        )
         .get_matches();
 
     // Parse configuration
     let cfg = load_configuration(&args)?;
 
+    if let Some(args) = args.subcommand_matches("api-token") {
+        return api_token_command(&cfg.general.data_dir, args);
+    }
+
     // Initialize logger
     let _log = load_logger_configuration(&args, &cfg.general.data_dir, &cfg.general.log_config)?;
     // Print welcome message
@@ -539,6 +711,7 @@ async fn run() -> Result<(), Error> {
         NetworkName::from_str(&cfg.general.chain).expect("Valid network name."),
         network_skey.clone(),
         network_pkey.clone(),
+        Some(data_dir.join("peers.json")),
     )
     .await?;
 
@@ -623,20 +796,96 @@ async fn run() -> Result<(), Error> {
         (None, Some(wallet))
     };
 
-    // Start WebSocket API server.
+    // Start webhooks.
+    if !cfg.webhooks.is_empty() {
+        let api_token = load_or_create_api_token(&root_dir.join("api.token"))?;
+        let apis: Vec<Box<dyn ApiHandler>> = vec![Box::new(node.clone()), Box::new(wallet.clone())];
+        spawn_webhooks(
+            cfg.webhooks.clone(),
+            &data_dir.join(WEBHOOKS_OUTBOX),
+            api_token,
+            apis,
+        )?;
+    }
+
+    // Start WebSocket API, JSON-RPC and gRPC servers.
+    let mut api_endpoints = Vec::new();
     if cfg.general.api_endpoint != "" {
+        api_endpoints.push(cfg.general.api_endpoint.clone());
+    }
+    if cfg.general.api_unix_socket != "" {
+        api_endpoints.push(format!(
+            "{}{}",
+            UNIX_ENDPOINT_PREFIX, cfg.general.api_unix_socket
+        ));
+    }
+    #[cfg(feature = "grpc")]
+    let mut _grpc_server = None;
+    if !api_endpoints.is_empty()
+        || cfg.general.jsonrpc_endpoint != ""
+        || cfg.general.grpc_endpoint != ""
+    {
         let token_file = root_dir.join("api.token");
         let api_token = load_or_create_api_token(&token_file)?;
-        spawn_server(
-            cfg.general.api_endpoint,
-            api_token,
-            // wallet,
-            vec![Box::new(node), Box::new(wallet)],
-            network.clone().into(),
-            version,
-            cfg.general.chain,
-        )
-        .await?;
+        let token_store = TokenStore::open(&root_dir.join(API_TOKENS_JSON))?;
+        let tokens = ApiTokens::new(
+            Some(api_token),
+            Some(token_store),
+            Some(root_dir.join(API_AUDIT_LOG)),
+        );
+        let apis: Vec<Box<dyn ApiHandler>> = vec![Box::new(node), Box::new(wallet)];
+        // Limits are shared by all API servers.
+        let limiter = Arc::new(Limiter::new(cfg.general.api_limits.clone()));
+        #[cfg(feature = "grpc")]
+        {
+            if cfg.general.grpc_endpoint != "" {
+                let server = spawn_grpc_server(
+                    cfg.general.grpc_endpoint.clone(),
+                    cfg.general.api_tls.clone(),
+                    tokens.clone(),
+                    apis.iter().map(|api| api.cloned()).collect(),
+                    network.clone().into(),
+                    version.clone(),
+                    cfg.general.chain.clone(),
+                    limiter.clone(),
+                )?;
+                // The server is stopped when dropped.
+                _grpc_server = Some(server);
+            }
+        }
+        if cfg.general.jsonrpc_endpoint != "" {
+            spawn_jsonrpc_server(
+                cfg.general.jsonrpc_endpoint,
+                cfg.general.api_tls.clone(),
+                tokens.clone(),
+                apis.iter().map(|api| api.cloned()).collect(),
+                network.clone().into(),
+                version.clone(),
+                cfg.general.chain.clone(),
+                limiter.clone(),
+            )
+            .await?;
+        }
+        if !api_endpoints.is_empty() {
+            let journal = if cfg.general.api_journal_capacity > 0 {
+                let path = data_dir.join(API_JOURNAL);
+                Some(Journal::open(&path, cfg.general.api_journal_capacity)?)
+            } else {
+                None
+            };
+            spawn_server(
+                api_endpoints,
+                cfg.general.api_tls,
+                tokens,
+                apis,
+                network.clone().into(),
+                version,
+                cfg.general.chain,
+                journal,
+                limiter,
+            )
+            .await?;
+        }
     }
 
     // Start main event loop
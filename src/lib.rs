@@ -39,8 +39,14 @@ use log4rs::Handle as LogHandle;
 use std::fs;
 use std::path::PathBuf;
 use std::str::FromStr;
-use std::sync::Mutex;
-use stegos_api::{server::spawn_server, ApiToken};
+use std::sync::{Arc, Mutex};
+use stegos_api::{
+    server::{
+        limits::{ApiLimits, Limiter},
+        spawn_server,
+    },
+    ApiToken,
+};
 use stegos_blockchain::{chain_to_prefix, initialize_chain};
 use stegos_crypto::hash::Hash;
 use stegos_keychain::keyfile::load_network_keys;
@@ -181,6 +187,7 @@ async fn init(
             NetworkName::from_str(&chain_name).unwrap(),
             network_skey.clone(),
             network_pkey.clone(),
+            Some(data_dir.join("peers.json")),
         )
         .await?;
 
@@ -208,12 +215,15 @@ async fn init(
         // Start WebSocket API server.
         let api_token = ApiToken::from_base64(&api_token)?;
         spawn_server(
-            api_endpoint.clone(),
-            api_token,
+            vec![api_endpoint.clone()],
+            None,
+            api_token.into(),
             vec![Box::new(wallet.clone())],
             network.clone().into(),
             version,
             chain_name.clone(),
+            None,
+            Arc::new(Limiter::new(ApiLimits::default())),
         )
         .await?;
 
@@ -23,6 +23,7 @@
 
 use super::unsealed::{UnsealedAccountResult, UnsealedAccountService};
 use crate::api::*;
+use crate::messaging::MessagingPacket;
 use crate::ReplicationOutEvent;
 use crate::{Account, AccountEvent};
 use futures::channel::mpsc;
@@ -62,6 +63,8 @@ pub struct SealedAccountService {
     // Api subscribers
     //
     subscribers: Vec<mpsc::UnboundedSender<AccountNotification>>,
+    /// Messages and fetch requests to be relayed by the wallet service.
+    outgoing_messages: mpsc::UnboundedSender<MessagingPacket>,
     /// Incoming events.
     events: mpsc::UnboundedReceiver<AccountEvent>,
     /// Incoming blocks.
@@ -78,6 +81,7 @@ impl SealedAccountService {
         genesis_hash: Hash,
         chain_cfg: ChainConfig,
         max_inputs_in_tx: usize,
+        outgoing_messages: mpsc::UnboundedSender<MessagingPacket>,
         chain_notifications: mpsc::Receiver<ReplicationOutEvent>,
     ) -> Result<(Self, Account), KeyError> {
         let account_pkey_file = account_dir.join("account.pkey");
@@ -95,6 +99,7 @@ impl SealedAccountService {
             chain_cfg,
             max_inputs_in_tx,
             subscribers,
+            outgoing_messages,
             events,
             chain_notifications,
         );
@@ -113,6 +118,7 @@ impl SealedAccountService {
         chain_cfg: ChainConfig,
         max_inputs_in_tx: usize,
         subscribers: Vec<mpsc::UnboundedSender<AccountNotification>>,
+        outgoing_messages: mpsc::UnboundedSender<MessagingPacket>,
         events: mpsc::UnboundedReceiver<AccountEvent>,
         chain_notifications: mpsc::Receiver<ReplicationOutEvent>,
     ) -> Self {
@@ -127,6 +133,7 @@ impl SealedAccountService {
             max_inputs_in_tx,
             network,
             subscribers,
+            outgoing_messages,
             events,
             chain_notifications,
         };
@@ -192,6 +199,9 @@ impl SealedAccountService {
                 AccountEvent::Subscribe { tx } => {
                     self.subscribers.push(tx);
                 }
+                AccountEvent::Message { .. } => {
+                    // Delivered again on unseal.
+                }
             }
         }
     }
@@ -222,6 +232,7 @@ impl SealedAccountService {
                 sealed.chain_cfg,
                 sealed.max_inputs_in_tx,
                 sealed.subscribers,
+                sealed.outgoing_messages,
                 sealed.events,
                 sealed.chain_notifications,
             );
@@ -249,6 +260,7 @@ impl SealedAccountService {
                         unsealed.database.cfg().clone(),
                         unsealed.max_inputs_in_tx,
                         unsealed.subscribers,
+                        unsealed.outgoing_messages,
                         unsealed.events,
                         unsealed.chain_notifications,
                     );
@@ -25,6 +25,7 @@ use crate::metrics;
 
 use crate::api::*;
 use crate::error::WalletError;
+use crate::messaging::{EncryptedMessage, FetchMessages, MessagingPacket, MESSAGE_TTL};
 use crate::storage::*;
 use crate::transaction::*;
 
@@ -32,7 +33,6 @@ use crate::AccountEvent;
 
 use crate::CanaryProcessed;
 use crate::ReplicationOutEvent;
-use bit_vec::BitVec;
 use failure::{format_err, Error};
 use futures::channel::{mpsc, oneshot};
 use futures::prelude::*;
@@ -117,6 +117,8 @@ pub struct UnsealedAccountService {
     //
     /// Triggered when state has changed.
     pub(super) subscribers: Vec<mpsc::UnboundedSender<AccountNotification>>,
+    /// Messages and fetch requests to be relayed by the wallet service.
+    pub(super) outgoing_messages: mpsc::UnboundedSender<MessagingPacket>,
 
     //
     // Events source
@@ -146,6 +148,7 @@ impl UnsealedAccountService {
         chain_cfg: ChainConfig,
         max_inputs_in_tx: usize,
         subscribers: Vec<mpsc::UnboundedSender<AccountNotification>>,
+        outgoing_messages: mpsc::UnboundedSender<MessagingPacket>,
         events: mpsc::UnboundedReceiver<AccountEvent>,
         chain_notifications: mpsc::Receiver<ReplicationOutEvent>,
     ) -> Self {
@@ -178,12 +181,15 @@ impl UnsealedAccountService {
             max_inputs_in_tx,
             network,
             subscribers,
+            outgoing_messages,
             events,
             chain_notifications,
             transaction_rx,
         };
         service.notify(AccountNotification::Unsealed);
+        service.notify_chain_config();
         service.notify_status();
+        service.fetch_messages();
         service
     }
 
@@ -303,6 +309,86 @@ impl UnsealedAccountService {
             .collect()
     }
 
+    fn get_inbox(&self, starting_from: Timestamp, limit: u64) -> Vec<MessageInfo> {
+        self.database
+            .iter_inbox(starting_from, limit)
+            .map(|(_timestamp, entry)| entry.to_info())
+            .collect()
+    }
+
+    /// Send a message to another account.
+    fn send_message(&mut self, recipient: &scc::PublicKey, text: String) -> Result<Hash, Error> {
+        let message = EncryptedMessage::new(
+            &self.account_skey,
+            &self.account_pkey,
+            recipient,
+            text,
+            Timestamp::now(),
+        )?;
+        let message_hash = Hash::digest(&message);
+        info!(
+            "Sending a message: message={}, recipient={}",
+            message_hash, recipient
+        );
+        // Relaying is done by the wallet service.
+        self.outgoing_messages
+            .unbounded_send(MessagingPacket::Message(message))
+            .ok();
+        Ok(message_hash)
+    }
+
+    /// Ask relays for messages received while the account was offline.
+    fn fetch_messages(&mut self) {
+        let now = Timestamp::now();
+        let fetch = FetchMessages::new(
+            &self.account_skey,
+            &self.account_pkey,
+            self.network_pkey,
+            now - MESSAGE_TTL,
+            now,
+        );
+        self.outgoing_messages
+            .unbounded_send(MessagingPacket::Fetch(fetch))
+            .ok();
+    }
+
+    /// Called when a message for this account has been received.
+    fn on_message(&mut self, message: EncryptedMessage) {
+        let message_hash = Hash::digest(&message);
+        let content = match message.decrypt(&self.account_skey) {
+            Ok(content) => content,
+            Err(e) => {
+                warn!("Invalid message: message={}, error={}", message_hash, e);
+                return;
+            }
+        };
+        match self
+            .database
+            .push_message(message_hash, content.clone(), Timestamp::now())
+        {
+            Ok(true) => {}
+            Ok(false) => return, // Already in the inbox.
+            Err(e) => {
+                error!(
+                    "Failed to save message: message={}, error={}",
+                    message_hash, e
+                );
+                return;
+            }
+        }
+        info!(
+            "Received a message: message={}, sender={}",
+            message_hash, content.sender
+        );
+        let info = MessageInfo {
+            message_hash,
+            sender: content.sender,
+            timestamp: content.timestamp,
+            text: content.text,
+        };
+        self.notify(AccountNotification::MessageReceived(info));
+    }
+
     fn stake_all(&mut self, payment_fee: i64) -> Result<TransactionInfo, Error> {
         let mut payment_amount: i64 = 0;
         let mut outputs: Vec<_> = self.database.available_payment_outputs().collect();
@@ -555,15 +641,12 @@ impl UnsealedAccountService {
 
     fn apply_light_micro_block(
         &mut self,
-        header: MicroBlockHeader,
-        sig: pbc::Signature,
-        input_hashes: Vec<Hash>,
-        output_hashes: Vec<Hash>,
-        canaries: Vec<Canary>,
-        outputs: Vec<Output>, // TODO: split validate and apply, to validate before applying.
+        block: LightMicroBlock,
+        outputs: Vec<Output>,
     ) -> Result<(), Error> {
+        let header = &block.header;
         if header.epoch < self.database.epoch() || header.offset < self.database.offset() {
-            let block_hash = Hash::digest(&header);
+            let block_hash = Hash::digest(header);
             debug!(
                 "Skip an outdated micro block: block={}, epoch={}, offset={}, our_epoch={}, our_offset={}",
                 block_hash,
@@ -574,7 +657,7 @@ impl UnsealedAccountService {
             );
             return Ok(());
         } else if header.epoch > self.database.epoch() || header.offset > self.database.offset() {
-            let block_hash = Hash::digest(&header);
+            let block_hash = Hash::digest(header);
             let err = format!("A micro block from the future: block={}, block_epoch={}, block_offset={}, our_epoch={}, our_offset={}",
                 block_hash,
                 header.epoch,
@@ -591,20 +674,13 @@ impl UnsealedAccountService {
         //
         assert_eq!(header.epoch, self.database.epoch());
         assert_eq!(header.offset, self.database.offset());
-        self.database.validate_light_micro_block(
-            &header,
-            &sig,
-            &input_hashes,
-            &output_hashes,
-            &canaries,
-        )?;
+        self.database.validate_light_micro_block(&block)?;
 
         //
         // Register block.
         //
         let transaction_statuses = self.database.apply_light_micro_block(
-            header,
-            input_hashes.iter(),
+            &block,
             outputs.iter(),
             &self.account_pkey,
             &self.account_skey,
@@ -621,17 +697,12 @@ impl UnsealedAccountService {
 
     fn apply_light_macro_block(
         &mut self,
-        header: MacroBlockHeader,
-        multisig: pbc::Signature,
-        multisigmap: BitVec,
-        input_hashes: Vec<Hash>,
-        outputs_hashes: Vec<Hash>,
-        canaries: Vec<Canary>,
-        outputs: Vec<Output>, // TODO: split validate and apply methods.
-        validators: StakersGroup,
+        block: LightMacroBlock,
+        outputs: Vec<Output>,
     ) -> Result<(), Error> {
+        let header = &block.header;
         if header.epoch < self.database.epoch() {
-            let block_hash = Hash::digest(&header);
+            let block_hash = Hash::digest(header);
             debug!(
                 "Skip an outdated macro block: block={}, block_epoch={}, our_epoch={}",
                 block_hash,
@@ -640,7 +711,7 @@ impl UnsealedAccountService {
             );
             return Ok(());
         } else if header.epoch > self.database.epoch() {
-            let block_hash = Hash::digest(&header);
+            let block_hash = Hash::digest(header);
             let err = format!(
                 "A macro block from the future: block={}, block_epoch={}, our_epoch={}",
                 block_hash,
@@ -655,24 +726,15 @@ impl UnsealedAccountService {
         // Validate block.
         //
         assert_eq!(header.epoch, self.database.epoch());
-        self.database.validate_macro_block(
-            &header,
-            &multisig,
-            &multisigmap,
-            &input_hashes,
-            &outputs_hashes,
-            &canaries,
-            &validators,
-        )?;
+        let cfg = self.database.cfg().clone();
+        self.database.validate_macro_block(&block)?;
 
         //
         // Register block
         //
         let transaction_statuses = self.database.apply_light_macro_block(
-            header,
-            input_hashes.iter(),
+            &block,
             outputs.iter(),
-            validators,
             &self.account_pkey,
             &self.account_skey,
         );
@@ -684,6 +746,9 @@ impl UnsealedAccountService {
         if transaction_statuses.len() > 0 || outputs.len() > 0 {
             self.notify_balance_changed(self.database.balance());
         }
+        if *self.database.cfg() != cfg {
+            self.notify_chain_config();
+        }
 
         self.notify_status();
         Ok(())
@@ -841,6 +906,11 @@ impl UnsealedAccountService {
         self.notify(AccountNotification::BalanceChanged(balance));
     }
 
+    fn notify_chain_config(&mut self) {
+        let cfg = self.database.cfg().clone();
+        self.notify(AccountNotification::ChainConfigChanged(cfg));
+    }
+
     fn notify_status(&mut self) {
         let status = self.database.status();
         self.notify(AccountNotification::StatusChanged(status));
@@ -1019,12 +1089,27 @@ impl UnsealedAccountService {
                                                 error: format!("Snowball was deprecated"),
                                         }
                                     }
+                                    AccountRequest::SendMessage { recipient, text } => {
+                                        match self.send_message(&recipient, text) {
+                                            Ok(message_hash) => AccountResponse::MessageSent { message_hash },
+                                            Err(e) => AccountResponse::Error {
+                                                error: format!("{}", e),
+                                            },
+                                        }
+                                    }
+                                    AccountRequest::InboxInfo {
+                                        starting_from,
+                                        limit,
+                                    } => AccountResponse::InboxInfo {
+                                        messages: self.get_inbox(starting_from, limit),
+                                    },
                                 };
                                 tx.send(response).ok(); // ignore errors.
                             }
                             AccountEvent::Subscribe { tx } => {
                                 self.subscribers.push(tx);
                             }
+                            AccountEvent::Message { message } => self.on_message(message),
                         }
                     }
                 },
@@ -1052,30 +1137,14 @@ impl UnsealedAccountService {
                             let r = match block {
                                 LightBlock::LightMacroBlock(block) => {
                                     debug!("Got a macro block: epoch={}, inputs={:?}", block.header.epoch, block.input_hashes);
-                                    self.apply_light_macro_block(
-                                        block.header,
-                                        block.multisig,
-                                        block.multisigmap,
-                                        block.input_hashes,
-                                        block.output_hashes,
-                                        block.canaries,
-                                        outputs,
-                                        block.validators,
-                                    )
+                                    self.apply_light_macro_block(block, outputs)
                                 }
                                 LightBlock::LightMicroBlock(block) => {
                                     debug!(
                                         "Got a micro block: epoch={}, offset={}, inputs={:?}",
                                         block.header.epoch, block.header.offset, block.input_hashes
                                     );
-                                    self.apply_light_micro_block(
-                                        block.header,
-                                        block.sig,
-                                        block.input_hashes,
-                                        block.output_hashes,
-                                        block.canaries,
-                                        outputs,
-                                    )
+                                    self.apply_light_micro_block(block, outputs)
                                 }
                             };
                             if let Err(e) = r {
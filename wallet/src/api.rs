@@ -23,9 +23,11 @@
 
 // pub use crate::snowball::State as SnowballStatus;
 use futures::channel::mpsc;
+use schemars::JsonSchema;
 use serde_derive::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use stegos_blockchain::api::StatusInfo;
+use stegos_blockchain::ChainConfig;
 pub use stegos_blockchain::PaymentPayloadData;
 pub use stegos_blockchain::StakeInfo;
 use stegos_blockchain::Timestamp;
@@ -40,7 +42,7 @@ pub use stegos_replication::api::*;
 
 pub type AccountId = String;
 
-#[derive(Eq, PartialEq, Serialize, Deserialize, Clone, Debug)]
+#[derive(Eq, PartialEq, Serialize, Deserialize, Clone, Debug, JsonSchema)]
 #[serde(tag = "type")]
 #[serde(rename_all = "snake_case")]
 pub enum LogEntryInfo {
@@ -56,7 +58,7 @@ pub enum LogEntryInfo {
     },
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 #[serde(tag = "output_type")]
 pub enum OutputInfo {
@@ -75,7 +77,7 @@ impl OutputInfo {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct PaymentInfo {
     pub output_hash: Hash,
     pub amount: i64,
@@ -89,7 +91,7 @@ pub struct PaymentInfo {
     pub is_change: bool,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct PublicPaymentInfo {
     pub output_hash: Hash,
     pub amount: i64,
@@ -98,10 +100,19 @@ pub struct PublicPaymentInfo {
     pub recipient: scc::PublicKey,
 }
 
+/// A message received from another account.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct MessageInfo {
+    pub message_hash: Hash,
+    pub sender: scc::PublicKey,
+    pub timestamp: Timestamp,
+    pub text: String,
+}
+
 ///
 /// Information about balance.
 ///
-#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize, Default, JsonSchema)]
 pub struct Balance {
     /// Available funds plus funds that are being held.
     pub current: i64,
@@ -112,7 +123,7 @@ pub struct Balance {
 ///
 /// Account balance per each UTXO type.
 ///
-#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize, Default, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct AccountBalance {
     /// PaymentUTXO.
@@ -131,7 +142,7 @@ pub struct AccountBalance {
 }
 
 /// Recovery information.
-#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize, Default, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct AccountRecovery {
     /// 24-word recovery phrase.
@@ -141,13 +152,16 @@ pub struct AccountRecovery {
 ///
 /// Out-of-band notifications.
 ///
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "type")]
 #[serde(rename_all = "snake_case")]
 pub enum AccountNotification {
     StatusChanged(StatusInfo),
     #[serde(skip)]
     UpstreamError(String),
+    /// Chain parameters of the account's epoch, changed by governance.
+    #[serde(skip)]
+    ChainConfigChanged(ChainConfig),
     Unsealed,
     Sealed,
     BalanceChanged(AccountBalance),
@@ -163,9 +177,10 @@ pub enum AccountNotification {
     SpentPublic(PublicPaymentInfo),
     Staked(StakeInfo),
     Unstaked(StakeInfo),
+    MessageReceived(MessageInfo),
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct WalletNotification {
     pub account_id: AccountId,
@@ -176,7 +191,7 @@ pub struct WalletNotification {
 ///
 /// RPC requests.
 ///
-#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "type")]
 #[serde(rename_all = "snake_case")]
 pub enum AccountRequest {
@@ -242,9 +257,17 @@ pub enum AccountRequest {
         new_password: String,
     },
     GetRecovery {},
+    SendMessage {
+        recipient: scc::PublicKey,
+        text: String,
+    },
+    InboxInfo {
+        starting_from: Timestamp,
+        limit: u64,
+    },
 }
 
-#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 #[serde(tag = "type")]
 pub enum WalletControlRequest {
@@ -265,7 +288,7 @@ pub enum WalletControlRequest {
     SubscribeWalletUpdates {},
 }
 
-#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 #[serde(untagged)]
 pub enum WalletRequest {
@@ -277,7 +300,7 @@ pub enum WalletRequest {
     },
 }
 
-#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct TransactionInfo {
     pub tx_hash: Hash,
     pub fee: i64,
@@ -292,7 +315,7 @@ pub struct TransactionInfo {
 ///
 /// RPC responses.
 ///
-#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "type")]
 #[serde(rename_all = "snake_case")]
 pub enum AccountResponse {
@@ -304,6 +327,7 @@ pub enum AccountResponse {
     RawTransactionCreated {
         #[serde(serialize_with = "serialize_protobuf_to_hex")]
         #[serde(deserialize_with = "deserialize_protobuf_from_hex")]
+        #[schemars(with = "String")]
         data: Transaction,
     },
     BalanceInfo(AccountBalance),
@@ -318,12 +342,18 @@ pub enum AccountResponse {
     },
     PasswordChanged,
     Recovery(AccountRecovery),
+    MessageSent {
+        message_hash: Hash,
+    },
+    InboxInfo {
+        messages: Vec<MessageInfo>,
+    },
     Error {
         error: String,
     },
 }
 
-#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct AccountInfo {
     pub account_pkey: scc::PublicKey,
@@ -333,7 +363,7 @@ pub struct AccountInfo {
     pub status: StatusInfo,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 #[serde(tag = "type")]
 pub enum WalletControlResponse {
@@ -357,7 +387,7 @@ pub enum WalletControlResponse {
     },
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 #[serde(untagged)]
 pub enum WalletResponse {
@@ -53,4 +53,10 @@ pub enum WalletError {
     IncorrectTXINType,
     #[fail(display = "Snowball is busy")]
     SnowballBusy,
+    #[fail(display = "Message is too long: size={}, max={}", _0, _1)]
+    MessageTooLong(usize, usize),
+    #[fail(display = "Invalid message")]
+    InvalidMessage,
+    #[fail(display = "Invalid or replayed fetch request")]
+    InvalidFetchRequest,
 }
@@ -26,6 +26,7 @@ pub mod accounts;
 pub mod api;
 mod change;
 mod error;
+mod messaging;
 mod metrics;
 mod protos;
 pub mod recovery;
@@ -34,6 +35,8 @@ mod transaction;
 use self::accounts::*;
 
 use self::error::WalletError;
+use self::messaging::MESSAGES_TOPIC;
+use self::messaging::{EncryptedMessage, FetchMessages, MessageStore, MessagingPacket};
 use self::recovery::recovery_to_account_skey;
 use api::*;
 use failure::{format_err, Error};
@@ -52,9 +55,10 @@ use stegos_blockchain::*;
 use stegos_crypto::hash::Hash;
 use stegos_crypto::{pbc, scc};
 use stegos_keychain::keyfile::{load_account_pkey, write_account_pkey, write_account_skey};
-use stegos_network::{Network, PeerId, ReplicationEvent};
+use stegos_network::{Network, PeerId, ReplicationEvent, UnicastMessage};
 use stegos_replication::api::PeerInfo;
 use stegos_replication::{OutputsInfo, Replication, ReplicationRow};
+use stegos_serialization::traits::ProtoConvert;
 use tokio::time::{Duration, Instant};
 
 use futures::stream::SelectAll;
@@ -84,6 +88,11 @@ pub enum AccountEvent {
         request: AccountRequest,
         tx: oneshot::Sender<AccountResponse>,
     },
+    //
+    // Internal events.
+    //
+    /// A message addressed to this account.
+    Message { message: EncryptedMessage },
 }
 
 /// This could be used for non PaymentTx.
@@ -134,6 +143,8 @@ pub struct AccountHandle {
     pub account: Account,
     /// Current status,
     pub status: StatusInfo,
+    /// Chain parameters of the current epoch.
+    pub chain_cfg: ChainConfig,
     /// True if unsealed.
     pub unsealed: bool,
     /// A channel to send blocks,
@@ -156,6 +167,16 @@ pub struct WalletService {
 
     events: mpsc::UnboundedReceiver<(WalletRequest, oneshot::Sender<WalletResponse>)>,
     replication: ReplicationBlockCollector,
+
+    /// Messages stored for offline recipients.
+    messages: MessageStore,
+    /// Account messages and fetch requests from the network.
+    messages_rx: mpsc::UnboundedReceiver<Vec<u8>>,
+    /// Replies to fetch requests.
+    messages_unicast_rx: mpsc::UnboundedReceiver<UnicastMessage>,
+    /// Messages and fetch requests created by local accounts.
+    outgoing_messages: mpsc::UnboundedSender<MessagingPacket>,
+    outgoing_messages_rx: mpsc::UnboundedReceiver<MessagingPacket>,
 }
 
 impl WalletService {
@@ -174,10 +195,16 @@ impl WalletService {
         let subscribers = Vec::new();
         let account_notifications = SelectAll::new();
         let light = true;
-        let replication = ReplicationBlockCollector::new(
-            chain_cfg.clone(),
-            Replication::new(peer_id, network.clone(), light, replication_rx),
-        );
+        let replication = ReplicationBlockCollector::new(Replication::new(
+            peer_id,
+            network.clone(),
+            light,
+            replication_rx,
+        ));
+        let messages = MessageStore::new();
+        let messages_rx = network.subscribe(&MESSAGES_TOPIC)?;
+        let messages_unicast_rx = network.subscribe_unicast(&MESSAGES_TOPIC)?;
+        let (outgoing_messages, outgoing_messages_rx) = mpsc::unbounded();
         let mut service = WalletService {
             accounts_dir: accounts_dir.to_path_buf(),
             network_skey,
@@ -191,6 +218,11 @@ impl WalletService {
             account_notifications,
             events,
             replication,
+            messages,
+            messages_rx,
+            messages_unicast_rx,
+            outgoing_messages,
+            outgoing_messages_rx,
         };
 
         info!("Scanning directory {:?} for accounts", accounts_dir);
@@ -262,6 +294,7 @@ impl WalletService {
             self.genesis_hash.clone(),
             self.chain_cfg.clone(),
             self.max_inputs_in_tx,
+            self.outgoing_messages.clone(),
             chain_rx,
         )?;
         let account_id_clone = account_id.to_string();
@@ -284,7 +317,10 @@ impl WalletService {
                 last_macro_block_hash: Hash::zero(),
                 last_macro_block_timestamp: Timestamp::now(),
                 local_timestamp: Timestamp::now(),
+                reachability: Default::default(),
+                advertised_address: None,
             },
+            chain_cfg: self.chain_cfg.clone(),
             unsealed: false,
             chain_tx,
         };
@@ -491,6 +527,91 @@ impl WalletService {
         );
     }
 
+    fn on_messaging_packet(&mut self, data: Vec<u8>, from: Option<pbc::PublicKey>) {
+        let packet = match MessagingPacket::from_buffer(&data) {
+            Ok(packet) => packet,
+            Err(e) => {
+                warn!("Invalid messaging packet: error={}", e);
+                return;
+            }
+        };
+        match (packet, from) {
+            (MessagingPacket::Message(message), _) => self.on_message(message),
+            (MessagingPacket::Fetch(fetch), None) => self.on_fetch_messages(fetch),
+            (MessagingPacket::Fetch(_fetch), Some(from)) => {
+                warn!("Unexpected fetch request by unicast: from={}", from);
+            }
+        }
+    }
+
+    /// Store a message and deliver it to the local recipient, if any.
+    fn on_message(&mut self, message: EncryptedMessage) {
+        if !self.messages.insert(message.clone(), Timestamp::now()) {
+            return; // Already seen, expired or too large.
+        }
+        for handle in self.accounts.values() {
+            if handle.unsealed && handle.account_pkey == message.recipient {
+                let event = AccountEvent::Message {
+                    message: message.clone(),
+                };
+                handle.account.outbox.unbounded_send(event).ok();
+            }
+        }
+    }
+
+    /// Send stored messages to the node which asked for them.
+    fn on_fetch_messages(&mut self, fetch: FetchMessages) {
+        if fetch.reply_to == self.network_pkey {
+            return;
+        }
+        let now = Timestamp::now();
+        self.messages.prune(now);
+        let messages = match self.messages.serve(&fetch, now) {
+            Ok(messages) => messages,
+            Err(e) => {
+                debug!(
+                    "Ignored fetch request: recipient={}, error={}",
+                    fetch.recipient, e
+                );
+                return;
+            }
+        };
+        for message in messages {
+            let data = MessagingPacket::Message(message)
+                .into_buffer()
+                .expect("serialization never fails");
+            if let Err(e) = self.network.send(fetch.reply_to, &MESSAGES_TOPIC, data) {
+                error!("Failed to send message: error={}", e);
+            }
+        }
+    }
+
+    /// Relay a message or a fetch request created by a local account.
+    fn on_outgoing_packet(&mut self, packet: MessagingPacket) {
+        let data = packet.into_buffer().expect("serialization never fails");
+        if let Err(e) = self.network.publish(&MESSAGES_TOPIC, data) {
+            error!("Failed to publish messaging packet: error={}", e);
+        }
+        if let MessagingPacket::Message(message) = packet {
+            self.on_message(message);
+        }
+    }
+
+    /// Deliver messages received while the account was sealed.
+    fn deliver_stored_messages(&mut self, account_pkey: scc::PublicKey) {
+        self.messages.prune(Timestamp::now());
+        if let Some(handle) = self
+            .accounts
+            .values()
+            .find(|handle| handle.account_pkey == account_pkey)
+        {
+            for message in self.messages.fetch(&account_pkey, Timestamp::UNIX_EPOCH) {
+                let event = AccountEvent::Message { message };
+                handle.account.outbox.unbounded_send(event).ok();
+            }
+        }
+    }
+
     pub async fn start(mut self) {
         // Timer to prevent network death.
         // let dead_timer = tokio::time::interval_at(Duration::from_secs(3));
@@ -540,6 +661,8 @@ impl WalletService {
                                     debug!("Account unsealed: account_id={}", account_id);
                                     handle.unsealed = true;
                                     self.replication.change_upstream(false);
+                                    let account_pkey = handle.account_pkey;
+                                    self.deliver_stored_messages(account_pkey);
                                 }
 
                                 AccountNotification::Sealed => {
@@ -550,6 +673,15 @@ impl WalletService {
                                     debug!("Upstream error: {}", e);
                                     self.replication.change_upstream(false);
                                 }
+                                AccountNotification::ChainConfigChanged(cfg) => {
+                                    debug!(
+                                        "Chain parameters changed: account_id={}, micro_blocks_in_epoch={}",
+                                        account_id, cfg.micro_blocks_in_epoch
+                                    );
+                                    handle.chain_cfg = cfg.clone();
+                                    // Not sent to clients.
+                                    continue;
+                                }
                                 _ => {}
                             }
 
@@ -564,6 +696,21 @@ impl WalletService {
                         }
                     }
                 }
+                data = self.messages_rx.next() => {
+                    if let Some(data) = data {
+                        self.on_messaging_packet(data, None);
+                    }
+                }
+                msg = self.messages_unicast_rx.next() => {
+                    if let Some(msg) = msg {
+                        self.on_messaging_packet(msg.data, Some(msg.from));
+                    }
+                }
+                packet = self.outgoing_messages_rx.next() => {
+                    if let Some(packet) = packet {
+                        self.on_outgoing_packet(packet);
+                    }
+                }
                 event = self.replication.select(&mut self.accounts).fuse() => {
                     trace!("Return replication event = {:?}", event);
 
@@ -701,11 +848,29 @@ impl<B> BlockState<B> {
     }
 }
 
+/// Returns the oldest position of unsealed accounts and chain parameters of its epoch.
+fn replication_start(
+    accounts: &HashMap<AccountId, AccountHandle>,
+) -> Option<(u64, u32, &ChainConfig)> {
+    let mut start: Option<(u64, u32, &ChainConfig)> = None;
+    for handle in accounts.values() {
+        if !handle.unsealed {
+            continue;
+        }
+        let (current_epoch, current_offset, _) =
+            start.unwrap_or((std::u64::MAX, std::u32::MAX, &handle.chain_cfg));
+        if handle.status.epoch <= current_epoch {
+            let offset = std::cmp::min(handle.status.offset, current_offset);
+            start = Some((handle.status.epoch, offset, &handle.chain_cfg));
+        }
+    }
+    start
+}
+
 pub struct ReplicationBlockCollector {
     replication: Replication,
     pending_blocks: VecDeque<BlockState<LightMacroBlock>>,
     micro_blocks: VecDeque<BlockState<LightMicroBlock>>,
-    chain_cfg: ChainConfig,
 
     replication_responses: stream::FuturesUnordered<
         Box<dyn Future<Output = Result<BlockEvent, oneshot::Canceled>> + Unpin + Send>,
@@ -715,12 +880,11 @@ pub struct ReplicationBlockCollector {
 }
 
 impl ReplicationBlockCollector {
-    fn new(chain_cfg: ChainConfig, replication: Replication) -> Self {
+    fn new(replication: Replication) -> Self {
         Self {
             replication,
             pending_blocks: VecDeque::new(),
             micro_blocks: VecDeque::new(),
-            chain_cfg,
             replication_responses: stream::FuturesUnordered::new(),
             timer: tokio::time::interval(Duration::from_secs(2)),
         }
@@ -1186,28 +1350,15 @@ impl ReplicationBlockCollector {
         // Replication
         loop {
             // Sic: check that all accounts are ready before polling the replication.
-            let mut current_epoch = std::u64::MAX;
-            let mut current_offset = std::u32::MAX;
-            let mut unsealed = false;
-            for (_account_id, handle) in accounts.iter() {
-                if !handle.unsealed {
-                    continue;
-                }
-                unsealed = true;
-                if handle.status.epoch <= current_epoch {
-                    current_epoch = handle.status.epoch;
-                    if handle.status.offset <= current_offset {
-                        current_offset = handle.status.offset;
+            let (mut current_epoch, mut current_offset, micro_blocks_in_epoch) =
+                match replication_start(accounts) {
+                    Some((epoch, offset, cfg)) => (epoch, offset, cfg.micro_blocks_in_epoch),
+                    None => {
+                        // If no unsealed accounts, wait for external events.
+                        future::pending().await
                     }
-                }
-            }
-            if !unsealed {
-                // If no unsealed accounts, wait for external events.
-                let future = future::pending();
-                let () = future.await;
-            }
+                };
 
-            let micro_blocks_in_epoch = self.chain_cfg.micro_blocks_in_epoch;
             let block_reader = DummyBlockReady {};
             if let Some(epoch) = self.last_full_epoch() {
                 let mut epoch = epoch;
@@ -1334,3 +1485,53 @@ impl Wallet {
         rx
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account_handle(epoch: u64, micro_blocks_in_epoch: u32, unsealed: bool) -> AccountHandle {
+        let mut chain_cfg = ChainConfig::default();
+        chain_cfg.micro_blocks_in_epoch = micro_blocks_in_epoch;
+        let status = StatusInfo {
+            epoch,
+            ..Default::default()
+        };
+        AccountHandle {
+            account_pkey: scc::make_random_keys().1,
+            account: Account {
+                outbox: mpsc::unbounded().0,
+            },
+            status,
+            chain_cfg,
+            unsealed,
+            chain_tx: mpsc::channel(1).0,
+        }
+    }
+
+    #[test]
+    fn replication_follows_chain_parameters() {
+        let mut accounts = HashMap::new();
+        assert!(replication_start(&accounts).is_none());
+
+        // Sealed accounts are not replicated.
+        accounts.insert("1".to_string(), account_handle(1, 10, false));
+        assert!(replication_start(&accounts).is_none());
+
+        // Parameters of the oldest account are used.
+        accounts.insert("2".to_string(), account_handle(5, 20, true));
+        accounts.insert("3".to_string(), account_handle(3, 30, true));
+        let (epoch, offset, cfg) = replication_start(&accounts).unwrap();
+        assert_eq!((epoch, offset), (3, 0));
+        assert_eq!(cfg.micro_blocks_in_epoch, 30);
+
+        // Changed by governance.
+        accounts
+            .get_mut("3")
+            .unwrap()
+            .chain_cfg
+            .micro_blocks_in_epoch = 40;
+        let (_epoch, _offset, cfg) = replication_start(&accounts).unwrap();
+        assert_eq!(cfg.micro_blocks_in_epoch, 40);
+    }
+}
@@ -0,0 +1,486 @@
+//
+// MIT License
+//
+// Copyright (c) 2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Account-to-account messaging.
+
+use crate::api::MessageInfo;
+use crate::error::WalletError;
+use failure::Error;
+use std::collections::{HashMap, HashSet, VecDeque};
+use stegos_blockchain::Timestamp;
+use stegos_crypto::hash::{Hash, Hashable, Hasher};
+use stegos_crypto::pbc;
+use stegos_crypto::scc::{self, EncryptedPayload};
+use stegos_serialization::traits::ProtoConvert;
+use tokio::time::Duration;
+
+/// Topic used for account messages and fetch requests.
+/// Replies to fetch requests are sent by unicast with the same protocol id.
+pub const MESSAGES_TOPIC: &'static str = "account_messages";
+
+/// Maximal size of the message text in bytes.
+pub const MAX_MESSAGE_SIZE: usize = 16 * 1024;
+/// Maximal size of the encrypted content accepted by relays.
+const MAX_PAYLOAD_SIZE: usize = MAX_MESSAGE_SIZE + 1024;
+/// How long relays keep messages for offline recipients.
+pub(crate) const MESSAGE_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+/// Allowed clock difference between the sender and relays.
+const MAX_CLOCK_SKEW: Duration = Duration::from_secs(5 * 60);
+/// Maximal number of messages stored for a single recipient.
+/// New messages are rejected until the old ones expire.
+const MAX_MESSAGES_PER_RECIPIENT: usize = 100;
+/// Maximal number of messages stored by a relay.
+const MAX_STORED_MESSAGES: usize = 10_000;
+
+///
+/// A message encrypted for the recipient's account key.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncryptedMessage {
+    /// Recipient, kept in clear for routing.
+    pub recipient: scc::PublicKey,
+    /// Creation time, used by relays to expire messages.
+    pub timestamp: Timestamp,
+    /// Encrypted `MessageContent`.
+    pub payload: EncryptedPayload,
+}
+
+///
+/// Plaintext of `EncryptedMessage`.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct MessageContent {
+    pub sender: scc::PublicKey,
+    pub timestamp: Timestamp,
+    pub text: String,
+    /// Signature of `signing_hash()` by the sender's account key.
+    pub signature: scc::SchnorrSig,
+}
+
+///
+/// Request for messages stored by relays.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchMessages {
+    pub recipient: scc::PublicKey,
+    pub since: Timestamp,
+    /// Network key of the requesting node.
+    pub reply_to: pbc::PublicKey,
+    /// Creation time, relays serve only fresh requests.
+    pub timestamp: Timestamp,
+    /// Signature of `signing_hash()` by the recipient's account key.
+    pub signature: scc::SchnorrSig,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MessagingPacket {
+    Message(EncryptedMessage),
+    Fetch(FetchMessages),
+}
+
+///
+/// A message saved in the account inbox.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct InboxEntry {
+    pub message_hash: Hash,
+    pub content: MessageContent,
+}
+
+fn signing_hash(
+    recipient: &scc::PublicKey,
+    timestamp: Timestamp,
+    sender: &scc::PublicKey,
+    text: &str,
+) -> Hash {
+    let mut hasher = Hasher::new();
+    "account_message".hash(&mut hasher);
+    recipient.hash(&mut hasher);
+    timestamp.hash(&mut hasher);
+    sender.hash(&mut hasher);
+    text.hash(&mut hasher);
+    hasher.result()
+}
+
+impl EncryptedMessage {
+    /// Sign `text` by the sender's key and encrypt it for `recipient`.
+    pub(crate) fn new(
+        sender_skey: &scc::SecretKey,
+        sender_pkey: &scc::PublicKey,
+        recipient: &scc::PublicKey,
+        text: String,
+        timestamp: Timestamp,
+    ) -> Result<Self, Error> {
+        if text.len() > MAX_MESSAGE_SIZE {
+            return Err(WalletError::MessageTooLong(text.len(), MAX_MESSAGE_SIZE).into());
+        }
+        let hash = signing_hash(recipient, timestamp, sender_pkey, &text);
+        let signature = scc::sign_hash(&hash, sender_skey);
+        let content = MessageContent {
+            sender: *sender_pkey,
+            timestamp,
+            text,
+            signature,
+        };
+        let data = content.into_buffer()?;
+        let (ag, ctxt, _rvalue) = scc::aes_encrypt(&data, recipient)?;
+        let message = EncryptedMessage {
+            recipient: *recipient,
+            timestamp,
+            payload: EncryptedPayload { ag, ctxt },
+        };
+        Ok(message)
+    }
+
+    /// Decrypt the message and check the sender's signature.
+    pub(crate) fn decrypt(&self, recipient_skey: &scc::SecretKey) -> Result<MessageContent, Error> {
+        let data = scc::aes_decrypt(self.payload.ag, &self.payload.ctxt, recipient_skey)?;
+        let content = MessageContent::from_buffer(&data)?;
+        if content.timestamp != self.timestamp {
+            return Err(WalletError::InvalidMessage.into());
+        }
+        let hash = signing_hash(
+            &self.recipient,
+            content.timestamp,
+            &content.sender,
+            &content.text,
+        );
+        scc::validate_sig(&hash, &content.signature, &content.sender)?;
+        Ok(content)
+    }
+
+    /// Returns true if relays should accept this message at `now`.
+    fn is_acceptable(&self, now: Timestamp) -> bool {
+        self.timestamp + MESSAGE_TTL >= now
+            && self.timestamp <= now + MAX_CLOCK_SKEW
+            && self.payload.ctxt.len() <= MAX_PAYLOAD_SIZE
+    }
+}
+
+impl FetchMessages {
+    /// Create a request signed by the recipient's key.
+    pub(crate) fn new(
+        recipient_skey: &scc::SecretKey,
+        recipient_pkey: &scc::PublicKey,
+        reply_to: pbc::PublicKey,
+        since: Timestamp,
+        timestamp: Timestamp,
+    ) -> Self {
+        let mut fetch = FetchMessages {
+            recipient: *recipient_pkey,
+            since,
+            reply_to,
+            timestamp,
+            signature: scc::SchnorrSig::new(),
+        };
+        fetch.signature = scc::sign_hash(&fetch.signing_hash(), recipient_skey);
+        fetch
+    }
+
+    fn signing_hash(&self) -> Hash {
+        let mut hasher = Hasher::new();
+        "fetch_messages".hash(&mut hasher);
+        self.recipient.hash(&mut hasher);
+        self.since.hash(&mut hasher);
+        self.reply_to.hash(&mut hasher);
+        self.timestamp.hash(&mut hasher);
+        hasher.result()
+    }
+}
+
+impl InboxEntry {
+    pub fn to_info(&self) -> MessageInfo {
+        MessageInfo {
+            message_hash: self.message_hash,
+            sender: self.content.sender,
+            timestamp: self.content.timestamp,
+            text: self.content.text.clone(),
+        }
+    }
+}
+
+impl Hashable for EncryptedMessage {
+    fn hash(&self, state: &mut Hasher) {
+        self.recipient.hash(state);
+        self.timestamp.hash(state);
+        self.payload.ag.hash(state);
+        self.payload.ctxt.hash(state);
+    }
+}
+
+impl Hashable for MessageContent {
+    fn hash(&self, state: &mut Hasher) {
+        self.sender.hash(state);
+        self.timestamp.hash(state);
+        self.text.hash(state);
+        self.signature.hash(state);
+    }
+}
+
+///
+/// Store-and-forward queue of messages for offline recipients.
+///
+pub(crate) struct MessageStore {
+    /// Stored messages per recipient, in order of arrival.
+    messages: HashMap<scc::PublicKey, VecDeque<(Hash, EncryptedMessage)>>,
+    /// Hashes of all stored messages.
+    known: HashSet<Hash>,
+    /// Timestamp of the last served fetch request per recipient.
+    last_fetch: HashMap<scc::PublicKey, Timestamp>,
+}
+
+impl MessageStore {
+    pub fn new() -> Self {
+        MessageStore {
+            messages: HashMap::new(),
+            known: HashSet::new(),
+            last_fetch: HashMap::new(),
+        }
+    }
+
+    /// Returns the number of stored messages.
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.known.len()
+    }
+
+    /// Store a message.
+    /// Returns false if the message is already known, expired, too large
+    /// or the recipient's queue is full.
+    pub fn insert(&mut self, message: EncryptedMessage, now: Timestamp) -> bool {
+        let message_hash = Hash::digest(&message);
+        if self.known.contains(&message_hash) || !message.is_acceptable(now) {
+            return false;
+        }
+        if self.known.len() >= MAX_STORED_MESSAGES {
+            self.prune(now);
+            if self.known.len() >= MAX_STORED_MESSAGES {
+                return false;
+            }
+        }
+        let queue = self
+            .messages
+            .entry(message.recipient.clone())
+            .or_insert_with(VecDeque::new);
+        if queue.len() >= MAX_MESSAGES_PER_RECIPIENT {
+            return false;
+        }
+        queue.push_back((message_hash, message));
+        self.known.insert(message_hash);
+        true
+    }
+
+    /// Returns stored messages for `recipient` created at or after `since`.
+    pub fn fetch(&self, recipient: &scc::PublicKey, since: Timestamp) -> Vec<EncryptedMessage> {
+        match self.messages.get(recipient) {
+            Some(queue) => queue
+                .iter()
+                .filter(|(_, message)| message.timestamp >= since)
+                .map(|(_, message)| message.clone())
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Returns stored messages asked by a fetch request.
+    /// Each request is served once and only if it is fresh and signed by the recipient,
+    /// so replies can't be directed to a node which hasn't asked for them.
+    pub fn serve(
+        &mut self,
+        fetch: &FetchMessages,
+        now: Timestamp,
+    ) -> Result<Vec<EncryptedMessage>, Error> {
+        if fetch.timestamp + MAX_CLOCK_SKEW < now || fetch.timestamp > now + MAX_CLOCK_SKEW {
+            return Err(WalletError::InvalidFetchRequest.into());
+        }
+        if let Some(last_fetch) = self.last_fetch.get(&fetch.recipient) {
+            if fetch.timestamp <= *last_fetch {
+                return Err(WalletError::InvalidFetchRequest.into());
+            }
+        }
+        scc::validate_sig(&fetch.signing_hash(), &fetch.signature, &fetch.recipient)?;
+        self.last_fetch
+            .insert(fetch.recipient.clone(), fetch.timestamp);
+        Ok(self.fetch(&fetch.recipient, fetch.since))
+    }
+
+    /// Remove expired messages.
+    pub fn prune(&mut self, now: Timestamp) {
+        // Stale requests are rejected by timestamp.
+        self.last_fetch
+            .retain(|_recipient, timestamp| *timestamp + MAX_CLOCK_SKEW >= now);
+        let known = &mut self.known;
+        self.messages.retain(|_recipient, queue| {
+            queue.retain(|(message_hash, message)| {
+                if message.timestamp + MESSAGE_TTL >= now {
+                    true
+                } else {
+                    known.remove(message_hash);
+                    false
+                }
+            });
+            !queue.is_empty()
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encrypt_decrypt() {
+        let (sender_skey, sender_pkey) = scc::make_random_keys();
+        let (recipient_skey, recipient_pkey) = scc::make_random_keys();
+        let (other_skey, _other_pkey) = scc::make_random_keys();
+        let timestamp = Timestamp::now();
+        let text = "hello".to_string();
+
+        let message = EncryptedMessage::new(
+            &sender_skey,
+            &sender_pkey,
+            &recipient_pkey,
+            text.clone(),
+            timestamp,
+        )
+        .unwrap();
+        assert_eq!(message.recipient, recipient_pkey);
+        let content = message.decrypt(&recipient_skey).unwrap();
+        assert_eq!(content.sender, sender_pkey);
+        assert_eq!(content.timestamp, timestamp);
+        assert_eq!(content.text, text);
+
+        // Wrong key.
+        assert!(message.decrypt(&other_skey).is_err());
+
+        // Timestamp changed by a relay.
+        let mut message2 = message;
+        message2.timestamp += Duration::from_millis(1);
+        assert!(message2.decrypt(&recipient_skey).is_err());
+
+        // Too long.
+        let text = "x".repeat(MAX_MESSAGE_SIZE + 1);
+        let e = EncryptedMessage::new(&sender_skey, &sender_pkey, &recipient_pkey, text, timestamp)
+            .unwrap_err();
+        assert_eq!(
+            e.downcast::<WalletError>().unwrap(),
+            WalletError::MessageTooLong(MAX_MESSAGE_SIZE + 1, MAX_MESSAGE_SIZE)
+        );
+    }
+
+    #[test]
+    fn store() {
+        let (sender_skey, sender_pkey) = scc::make_random_keys();
+        let (_recipient_skey, recipient_pkey) = scc::make_random_keys();
+        let (_other_skey, other_pkey) = scc::make_random_keys();
+        let now = Timestamp::now();
+        let message = |text: &str, timestamp: Timestamp| {
+            EncryptedMessage::new(
+                &sender_skey,
+                &sender_pkey,
+                &recipient_pkey,
+                text.to_string(),
+                timestamp,
+            )
+            .unwrap()
+        };
+
+        let mut store = MessageStore::new();
+        let old = message("old", now - Duration::from_secs(60));
+        let new = message("new", now);
+        assert!(store.insert(old.clone(), now));
+        assert!(store.insert(new.clone(), now));
+        assert!(!store.insert(new.clone(), now));
+        assert_eq!(store.len(), 2);
+
+        assert_eq!(
+            store.fetch(&recipient_pkey, Timestamp::UNIX_EPOCH),
+            vec![old.clone(), new.clone()]
+        );
+        assert_eq!(store.fetch(&recipient_pkey, now), vec![new.clone()]);
+        assert!(store.fetch(&other_pkey, Timestamp::UNIX_EPOCH).is_empty());
+
+        // Expired and future messages are rejected.
+        let expired = message("expired", now - MESSAGE_TTL - Duration::from_secs(1));
+        assert!(!store.insert(expired, now));
+        let future = message("future", now + MAX_CLOCK_SKEW + Duration::from_secs(1));
+        assert!(!store.insert(future, now));
+
+        // Messages expire.
+        store.prune(old.timestamp + MESSAGE_TTL + Duration::from_millis(1));
+        assert_eq!(
+            store.fetch(&recipient_pkey, Timestamp::UNIX_EPOCH),
+            vec![new.clone()]
+        );
+        assert_eq!(store.len(), 1);
+
+        // New messages are rejected when the recipient's queue is full.
+        for i in 1..MAX_MESSAGES_PER_RECIPIENT {
+            assert!(store.insert(message(&i.to_string(), now), now));
+        }
+        assert_eq!(store.len(), MAX_MESSAGES_PER_RECIPIENT);
+        assert!(!store.insert(message("overflow", now), now));
+        assert_eq!(store.fetch(&recipient_pkey, Timestamp::UNIX_EPOCH)[0], new);
+    }
+
+    #[test]
+    fn serve() {
+        let (sender_skey, sender_pkey) = scc::make_random_keys();
+        let (recipient_skey, recipient_pkey) = scc::make_random_keys();
+        let (other_skey, other_pkey) = scc::make_random_keys();
+        let (_network_skey, network_pkey) = pbc::make_random_keys();
+        let now = Timestamp::now();
+        let message = EncryptedMessage::new(
+            &sender_skey,
+            &sender_pkey,
+            &recipient_pkey,
+            "hello".to_string(),
+            now,
+        )
+        .unwrap();
+        let mut store = MessageStore::new();
+        assert!(store.insert(message.clone(), now));
+        let since = now - MESSAGE_TTL;
+
+        // Signed by someone else.
+        let mut fetch = FetchMessages::new(&other_skey, &other_pkey, network_pkey, since, now);
+        fetch.recipient = recipient_pkey;
+        assert!(store.serve(&fetch, now).is_err());
+
+        // Stale.
+        let stale = now - MAX_CLOCK_SKEW - Duration::from_secs(1);
+        let fetch =
+            FetchMessages::new(&recipient_skey, &recipient_pkey, network_pkey, since, stale);
+        assert!(store.serve(&fetch, now).is_err());
+
+        // Served once.
+        let fetch = FetchMessages::new(&recipient_skey, &recipient_pkey, network_pkey, since, now);
+        assert_eq!(store.serve(&fetch, now).unwrap(), vec![message.clone()]);
+        assert!(store.serve(&fetch, now).is_err());
+
+        let later = now + Duration::from_secs(1);
+        let fetch =
+            FetchMessages::new(&recipient_skey, &recipient_pkey, network_pkey, since, later);
+        assert_eq!(store.serve(&fetch, later).unwrap(), vec![message]);
+    }
+}
@@ -30,13 +30,18 @@ use stegos_serialization::traits::*;
 use stegos_blockchain::protos::*;
 use stegos_crypto::protos::*;
 include!(concat!(env!("OUT_DIR"), "/protos/mod.rs"));
+use super::messaging::{
+    EncryptedMessage, FetchMessages, InboxEntry, MessageContent, MessagingPacket,
+};
 use super::storage::{LogEntry, OutputValue, PaymentValue, TransactionValue};
 use crate::storage::{PublicPaymentValue, StakeValue};
 use stegos_blockchain::TransactionStatus;
 use stegos_blockchain::{
     PaymentOutput, PaymentPayloadData, PaymentTransaction, PublicPaymentOutput, StakeOutput,
 };
-use stegos_crypto::scc::{Fr, PublicKey};
+use stegos_crypto::hash::Hash;
+use stegos_crypto::pbc;
+use stegos_crypto::scc::{EncryptedPayload, Fr, Pt, PublicKey, SchnorrSig};
 
 // -----------------------------------------------------------
 
@@ -299,6 +304,134 @@ impl ProtoConvert for TransactionValue {
     }
 }
 
+impl ProtoConvert for EncryptedMessage {
+    type Proto = messages::EncryptedMessage;
+    fn into_proto(&self) -> Self::Proto {
+        let mut msg = messages::EncryptedMessage::new();
+        msg.set_recipient(self.recipient.into_proto());
+        msg.set_timestamp(self.timestamp.into());
+        msg.set_ag(self.payload.ag.into_proto());
+        msg.set_ctxt(self.payload.ctxt.clone());
+        msg
+    }
+
+    fn from_proto(proto: &Self::Proto) -> Result<Self, Error> {
+        let recipient = PublicKey::from_proto(proto.get_recipient())?;
+        let timestamp = proto.get_timestamp().into();
+        let ag = Pt::from_proto(proto.get_ag())?;
+        let ctxt = proto.get_ctxt().to_vec();
+        let payload = EncryptedPayload { ag, ctxt };
+        Ok(EncryptedMessage {
+            recipient,
+            timestamp,
+            payload,
+        })
+    }
+}
+
+impl ProtoConvert for MessageContent {
+    type Proto = messages::MessageContent;
+    fn into_proto(&self) -> Self::Proto {
+        let mut msg = messages::MessageContent::new();
+        msg.set_sender(self.sender.into_proto());
+        msg.set_timestamp(self.timestamp.into());
+        msg.set_text(self.text.clone());
+        msg.set_signature(self.signature.into_proto());
+        msg
+    }
+
+    fn from_proto(proto: &Self::Proto) -> Result<Self, Error> {
+        let sender = PublicKey::from_proto(proto.get_sender())?;
+        let timestamp = proto.get_timestamp().into();
+        let text = proto.get_text().to_string();
+        let signature = SchnorrSig::from_proto(proto.get_signature())?;
+        Ok(MessageContent {
+            sender,
+            timestamp,
+            text,
+            signature,
+        })
+    }
+}
+
+impl ProtoConvert for FetchMessages {
+    type Proto = messages::FetchMessages;
+    fn into_proto(&self) -> Self::Proto {
+        let mut msg = messages::FetchMessages::new();
+        msg.set_recipient(self.recipient.into_proto());
+        msg.set_since(self.since.into());
+        msg.set_reply_to(self.reply_to.into_proto());
+        msg.set_timestamp(self.timestamp.into());
+        msg.set_signature(self.signature.into_proto());
+        msg
+    }
+
+    fn from_proto(proto: &Self::Proto) -> Result<Self, Error> {
+        let recipient = PublicKey::from_proto(proto.get_recipient())?;
+        let since = proto.get_since().into();
+        let reply_to = pbc::PublicKey::from_proto(proto.get_reply_to())?;
+        let timestamp = proto.get_timestamp().into();
+        let signature = SchnorrSig::from_proto(proto.get_signature())?;
+        Ok(FetchMessages {
+            recipient,
+            since,
+            reply_to,
+            timestamp,
+            signature,
+        })
+    }
+}
+
+impl ProtoConvert for MessagingPacket {
+    type Proto = messages::MessagingPacket;
+    fn into_proto(&self) -> Self::Proto {
+        let mut msg = messages::MessagingPacket::new();
+        match self {
+            MessagingPacket::Message(message) => msg.set_message(message.into_proto()),
+            MessagingPacket::Fetch(fetch) => msg.set_fetch(fetch.into_proto()),
+        }
+        msg
+    }
+
+    fn from_proto(proto: &Self::Proto) -> Result<Self, Error> {
+        let packet = match proto.enum_value {
+            Some(messages::MessagingPacket_oneof_enum_value::message(ref msg)) => {
+                MessagingPacket::Message(EncryptedMessage::from_proto(msg)?)
+            }
+            Some(messages::MessagingPacket_oneof_enum_value::fetch(ref msg)) => {
+                MessagingPacket::Fetch(FetchMessages::from_proto(msg)?)
+            }
+            None => {
+                return Err(ProtoError::MissingField(
+                    "enum_value".to_string(),
+                    "enum_value".to_string(),
+                )
+                .into());
+            }
+        };
+        Ok(packet)
+    }
+}
+
+impl ProtoConvert for InboxEntry {
+    type Proto = messages::InboxEntry;
+    fn into_proto(&self) -> Self::Proto {
+        let mut msg = messages::InboxEntry::new();
+        msg.set_message_hash(self.message_hash.into_proto());
+        msg.set_content(self.content.into_proto());
+        msg
+    }
+
+    fn from_proto(proto: &Self::Proto) -> Result<Self, Error> {
+        let message_hash = Hash::from_proto(proto.get_message_hash())?;
+        let content = MessageContent::from_proto(proto.get_content())?;
+        Ok(InboxEntry {
+            message_hash,
+            content,
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -373,4 +506,35 @@ mod tests {
         };
         roundtrip(&request);
     }
+
+    #[test]
+    fn messages() {
+        let (sender_skey, sender_pkey) = stegos_crypto::scc::make_random_keys();
+        let (recipient_skey, recipient_pkey) = stegos_crypto::scc::make_random_keys();
+        let (_network_skey, network_pkey) = pbc::make_random_keys();
+        let timestamp = stegos_blockchain::Timestamp::now();
+        let message = EncryptedMessage::new(
+            &sender_skey,
+            &sender_pkey,
+            &recipient_pkey,
+            "hello".to_string(),
+            timestamp,
+        )
+        .unwrap();
+        roundtrip(&message);
+
+        let packet = MessagingPacket::Message(message);
+        let packet2 = MessagingPacket::from_buffer(&packet.into_buffer().unwrap()).unwrap();
+        assert_eq!(packet, packet2);
+
+        let packet = MessagingPacket::Fetch(FetchMessages::new(
+            &recipient_skey,
+            &recipient_pkey,
+            network_pkey,
+            timestamp,
+            timestamp,
+        ));
+        let packet2 = MessagingPacket::from_buffer(&packet.into_buffer().unwrap()).unwrap();
+        assert_eq!(packet, packet2);
+    }
 }
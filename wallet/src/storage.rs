@@ -22,7 +22,7 @@
 // SOFTWARE.
 
 use crate::api::*;
-use bit_vec::BitVec;
+use crate::messaging::{InboxEntry, MessageContent};
 use byteorder::{BigEndian, ByteOrder};
 use failure::{bail, Error};
 use log::*;
@@ -44,11 +44,15 @@ use tokio::time::{Duration, Instant};
 const HISTORY: &'static str = "history";
 const UNSPENT: &'static str = "unspent";
 const META: &'static str = "meta";
-const COLON_FAMILIES: &[&'static str] = &[HISTORY, UNSPENT, META];
+const MESSAGES: &'static str = "messages";
+const COLON_FAMILIES: &[&'static str] = &[HISTORY, UNSPENT, META, MESSAGES];
 
 // Keys in meta cf
 const EPOCH_KEY: &[u8; 5] = b"epoch";
 
+/// Maximal number of messages in the inbox, the oldest ones are removed first.
+const MAX_INBOX_MESSAGES: usize = 1000;
+
 /// A special offset used to tore Macro Blocks on the disk.
 const MACRO_BLOCK_OFFSET: u32 = u32::max_value();
 
@@ -77,8 +81,6 @@ pub struct LightDatabase {
     genesis_hash: Hash,
     /// Copy of the last macro block hash.
     last_macro_block_hash: Hash,
-    /// Copy of the last macro block random.
-    last_macro_block_random: Hash,
     /// Copy of the last macro block timestamp.
     last_macro_block_timestamp: Timestamp,
     /// Validators on the start of the epoch.
@@ -87,6 +89,8 @@ pub struct LightDatabase {
     facilitator_pkey: pbc::PublicKey,
     /// Micro blocks for the current epoch.
     micro_blocks: Vec<MicroBlockHeader>,
+    /// Verifier of blocks from the upstream.
+    verifier: LightVerifier,
 
     /// In-memory index of all UTXOs.
     utxos: OutputByHashMap,
@@ -109,6 +113,8 @@ pub struct LightDatabase {
     outputs: HashMap<Hash, Hash>,
     /// Transactions that was created in current epoch.
     epoch_transactions: HashSet<Hash>,
+    /// Index of all messages in the inbox.
+    inbox: HashSet<Hash>,
 }
 
 impl LightDatabase {
@@ -124,14 +130,14 @@ impl LightDatabase {
         let mut log = LightDatabase {
             database,
             epoch: 0,
-            cfg,
+            cfg: cfg.clone(),
             genesis_hash,
             last_macro_block_hash: Hash::digest("genesis"),
-            last_macro_block_random: Hash::digest("genesis"),
             last_macro_block_timestamp: Timestamp::now(),
             validators: vec![],
             facilitator_pkey: pbc::PublicKey::dum(),
             micro_blocks: Vec::new(),
+            verifier: LightVerifier::new(genesis_hash, cfg),
             created_txs: HashMap::new(),
             locked_inputs: HashMap::new(),
             pending_txs: HashSet::new(),
@@ -142,8 +148,10 @@ impl LightDatabase {
             known_changes: HashSet::new(),
             utxos: MultiVersionedMap::new(),
             current_epoch_balance_changed: false,
+            inbox: HashSet::new(),
         };
         log.recover_state();
+        log.recover_inbox();
         log
     }
 
@@ -172,6 +180,8 @@ impl LightDatabase {
             last_macro_block_hash: self.last_macro_block_hash,
             last_macro_block_timestamp: self.last_macro_block_timestamp,
             local_timestamp: Timestamp::now(),
+            reachability: Default::default(),
+            advertised_address: None,
         }
     }
 
@@ -215,15 +225,6 @@ impl LightDatabase {
         }
     }
 
-    /// Returns the last random.
-    pub fn last_block_random(&self) -> Hash {
-        if let Some(header) = self.micro_blocks.last() {
-            header.random.rand
-        } else {
-            self.last_macro_block_random
-        }
-    }
-
     /// Returns the last block timestamp.
     pub fn last_block_timestamp(&self) -> Timestamp {
         if let Some(header) = self.micro_blocks.last() {
@@ -337,10 +338,12 @@ impl LightDatabase {
         self.epoch = epoch_info.header.epoch + 1;
         assert!(self.micro_blocks.is_empty());
         self.last_macro_block_hash = Hash::digest(&epoch_info.header);
-        self.last_macro_block_random = epoch_info.header.random.rand;
         self.last_macro_block_timestamp = epoch_info.header.timestamp;
         self.facilitator_pkey = epoch_info.facilitator;
-        self.validators = epoch_info.validators;
+        self.validators = epoch_info.validators.clone();
+        self.verifier =
+            LightVerifier::from_epoch_info(self.genesis_hash, self.cfg.clone(), &epoch_info);
+        self.cfg = self.verifier.cfg().clone();
         let lsn = LSN(epoch_info.header.epoch, MACRO_BLOCK_OFFSET);
         let cf_unspent = self
             .database
@@ -397,6 +400,15 @@ impl LightDatabase {
         );
     }
 
+    fn recover_inbox(&mut self) {
+        let inbox: Vec<Hash> = self
+            .iter_inbox(Timestamp::UNIX_EPOCH, u64::max_value())
+            .map(|(_timestamp, entry)| entry.message_hash)
+            .collect();
+        self.inbox.extend(inbox);
+        debug!("Recovered inbox: messages={}", self.inbox.len());
+    }
+
     pub fn iter_unspent<'a>(&'a self) -> impl Iterator<Item = (Hash, OutputValue)> + 'a {
         // TODO: remove cloned().
         self.utxos.iter().map(|(k, v)| (k.clone(), v.clone()))
@@ -611,291 +623,20 @@ impl LightDatabase {
     ///
     /// Validates the light macro block.
     ///
-    pub fn validate_macro_block(
-        &mut self,
-        header: &MacroBlockHeader,
-        multisig: &pbc::Signature,
-        multisigmap: &BitVec,
-        input_hashes: &[Hash],
-        output_hashes: &[Hash],
-        canaries: &[Canary],
-        validators: &StakersGroup,
-    ) -> Result<(), Error> {
-        let block_hash = Hash::digest(header);
-
-        // Check genesis.
-        if self.epoch == 0 && block_hash != self.genesis_hash {
-            return Err(BlockchainError::IncompatibleGenesis(self.genesis_hash, block_hash).into());
-        }
-
-        // Check block version.
-        if header.version != VERSION {
-            return Err(BlockError::InvalidBlockVersion(
-                header.epoch,
-                block_hash,
-                header.version,
-                VERSION,
-            )
-            .into());
-        }
-
-        // Check epoch.
-        if header.epoch != self.epoch {
-            return Err(
-                BlockError::OutOfOrderMacroBlock(block_hash, header.epoch, self.epoch).into(),
-            );
-        }
-
-        // Check previous hash.
-        if self.last_macro_block_hash != header.previous {
-            return Err(BlockError::InvalidMacroBlockPreviousHash(
-                header.epoch,
-                block_hash,
-                header.previous,
-                self.last_macro_block_hash,
-            )
-            .into());
-        }
-
-        //
-        // Validate multi-signature.
-        //
-        if header.epoch > 0 {
-            check_multi_signature(
-                &block_hash,
-                multisig,
-                &multisigmap,
-                &self.validators,
-                self.cfg.max_slot_count,
-            )
-            .map_err(|e| BlockError::InvalidBlockSignature(e, header.epoch, block_hash))?;
-        }
-
-        // Check VRF.
-        let seed = mix(self.last_macro_block_random.clone(), header.view_change);
-        if !pbc::validate_VRF_source(&header.random, &header.pkey, &seed).is_ok() {
-            return Err(BlockError::IncorrectRandom(header.epoch, block_hash).into());
-        }
-
-        //
-        // Validate inputs.
-        //
-        if header.inputs_len as usize != input_hashes.len() {
-            return Err(BlockError::InvalidMacroBlockInputsLen(
-                header.epoch,
-                block_hash,
-                header.inputs_len as usize,
-                input_hashes.len(),
-            )
-            .into());
-        }
-        let inputs_range_hash = Merkle::root_hash_from_array(&input_hashes);
-        if header.inputs_range_hash != inputs_range_hash {
-            return Err(BlockError::InvalidMacroBlockInputsHash(
-                header.epoch,
-                block_hash,
-                inputs_range_hash,
-                header.inputs_range_hash,
-            )
-            .into());
-        }
-
-        //
-        // Validate outputs.
-        //
-        if header.outputs_len as usize != output_hashes.len() {
-            return Err(BlockError::InvalidMacroBlockInputsLen(
-                header.epoch,
-                block_hash,
-                header.outputs_len as usize,
-                output_hashes.len(),
-            )
-            .into());
-        }
-        let outputs_range_hash = Merkle::root_hash_from_array(&output_hashes);
-        if header.outputs_range_hash != outputs_range_hash {
-            return Err(BlockError::InvalidMacroBlockOutputsHash(
-                header.epoch,
-                block_hash,
-                outputs_range_hash,
-                header.outputs_range_hash,
-            )
-            .into());
-        }
-
-        //
-        // Validate canaries.
-        //
-        let canary_hashes: Vec<Hash> = canaries.iter().map(Hash::digest).collect();
-        let canaries_range_hash = Merkle::root_hash_from_array(&canary_hashes);
-        if header.canaries_range_hash != canaries_range_hash {
-            return Err(BlockError::InvalidMacroBlockCanariesHash(
-                header.epoch,
-                block_hash,
-                canaries_range_hash,
-                header.canaries_range_hash,
-            )
-            .into());
-        }
-
-        //
-        // Validate validators.
-        //
-        let validators_len = validators.len();
-        if header.validators_len as usize != validators_len {
-            panic!(
-                "Invalid validators_len: expected={}, got={}",
-                validators_len, header.validators_len,
-            );
-        }
-        let validators_range_hash = Merkle::root_hash_from_array(validators);
-        if header.validators_range_hash != validators_range_hash {
-            panic!(
-                "Invalid validators_range_hash: expected={}, got={}",
-                validators_range_hash, header.validators_range_hash
-            );
-        }
-
+    /// The block must be applied after successful validation.
+    ///
+    pub fn validate_macro_block(&self, block: &LightMacroBlock) -> Result<(), Error> {
+        self.verifier.verify_light_macro_block(block)?;
         Ok(())
     }
 
     ///
     /// Validate the light micro block.
     ///
-    pub fn validate_light_micro_block(
-        &mut self,
-        header: &MicroBlockHeader,
-        sig: &pbc::Signature,
-        input_hashes: &[Hash],
-        output_hashes: &[Hash],
-        canaries: &[Canary],
-    ) -> Result<(), Error> {
-        let block_hash = Hash::digest(header);
-
-        // Check block version.
-        if header.version != VERSION {
-            return Err(BlockError::InvalidBlockVersion(
-                header.epoch,
-                block_hash,
-                header.version,
-                VERSION,
-            )
-            .into());
-        }
-
-        // Check epoch and offset.
-        if header.epoch != self.epoch() || header.offset != self.offset() {
-            return Err(BlockError::OutOfOrderMicroBlock(
-                block_hash,
-                header.epoch,
-                header.offset,
-                self.epoch(),
-                self.offset(),
-            )
-            .into());
-        }
-
-        // Check the block order.
-        if self.offset() >= self.cfg.micro_blocks_in_epoch {
-            return Err(
-                BlockchainError::ExpectedMacroBlock(self.epoch, self.offset(), block_hash).into(),
-            );
-        }
-
-        // Check previous hash.
-        if self.last_block_hash() != header.previous {
-            return Err(BlockError::InvalidMicroBlockPreviousHash(
-                header.epoch,
-                header.offset,
-                block_hash,
-                header.previous,
-                self.last_block_hash(),
-            )
-            .into());
-        }
-
-        // Check signature.
-        let last_random = self.last_block_random();
-        let leader = election::select_leader(&self.validators, &last_random, header.view_change);
-        if leader != header.pkey {
-            return Err(BlockError::DifferentPublicKey(leader, header.pkey).into());
-        }
-        if let Err(_e) = pbc::check_hash(&block_hash, sig, &leader) {
-            return Err(BlockError::InvalidLeaderSignature(header.epoch, block_hash).into());
-        }
-
-        // Check VRF.
-        let seed = mix(last_random.clone(), header.view_change);
-        if !pbc::validate_VRF_source(&header.random, &header.pkey, &seed).is_ok() {
-            return Err(BlockError::IncorrectRandom(header.epoch, block_hash).into());
-        }
-
-        //
-        // Validate inputs.
-        //
-        if header.inputs_len as usize != input_hashes.len() {
-            return Err(BlockError::InvalidMicroBlockInputsLen(
-                header.epoch,
-                header.offset,
-                block_hash,
-                header.inputs_len as usize,
-                input_hashes.len(),
-            )
-            .into());
-        }
-        let inputs_range_hash = Merkle::root_hash_from_array(&input_hashes);
-        if header.inputs_range_hash != inputs_range_hash {
-            return Err(BlockError::InvalidMicroBlockInputsHash(
-                header.epoch,
-                header.offset,
-                block_hash,
-                inputs_range_hash,
-                header.inputs_range_hash,
-            )
-            .into());
-        }
-
-        //
-        // Validate outputs.
-        //
-        if header.outputs_len as usize != output_hashes.len() {
-            return Err(BlockError::InvalidMicroBlockInputsLen(
-                header.epoch,
-                header.offset,
-                block_hash,
-                header.outputs_len as usize,
-                output_hashes.len(),
-            )
-            .into());
-        }
-        let outputs_range_hash = Merkle::root_hash_from_array(&output_hashes);
-        if header.outputs_range_hash != outputs_range_hash {
-            return Err(BlockError::InvalidMicroBlockOutputsHash(
-                header.epoch,
-                header.offset,
-                block_hash,
-                outputs_range_hash,
-                header.outputs_range_hash,
-            )
-            .into());
-        }
-
-        //
-        // Validate canaries.
-        //
-        let canary_hashes: Vec<Hash> = canaries.iter().map(Hash::digest).collect();
-        let canaries_range_hash = Merkle::root_hash_from_array(&canary_hashes);
-        if header.canaries_range_hash != canaries_range_hash {
-            return Err(BlockError::InvalidMicroBlockCanariesHash(
-                header.epoch,
-                header.offset,
-                block_hash,
-                canaries_range_hash,
-                header.canaries_range_hash,
-            )
-            .into());
-        }
-
+    /// The block must be applied after successful validation.
+    ///
+    pub fn validate_light_micro_block(&self, block: &LightMicroBlock) -> Result<(), Error> {
+        self.verifier.verify_light_micro_block(block)?;
         Ok(())
     }
 
@@ -904,19 +645,19 @@ impl LightDatabase {
     ///
     /// Inputs && outputs are automatically filtered out by account_pkey/account_skey.
     ///
-    pub fn apply_light_macro_block<'a, InputsIter, OutputsIter>(
+    pub fn apply_light_macro_block<'a, OutputsIter>(
         &mut self,
-        header: MacroBlockHeader,
-        inputs_iter: InputsIter,
+        block: &'a LightMacroBlock,
         outputs_iter: OutputsIter,
-        validators: StakersGroup,
         account_pkey: &scc::PublicKey,
         account_skey: &scc::SecretKey,
     ) -> HashMap<Hash, TransactionStatus>
     where
-        InputsIter: Iterator<Item = &'a Hash>,
         OutputsIter: Iterator<Item = &'a Output>,
     {
+        let header = block.header.clone();
+        let inputs_iter = block.input_hashes.iter();
+        let validators = block.validators.clone();
         assert_eq!(self.epoch, header.epoch, "block order");
         let epoch = header.epoch;
 
@@ -953,9 +694,10 @@ impl LightDatabase {
         self.epoch += 1;
         self.micro_blocks.clear();
         self.last_macro_block_hash = block_hash;
-        self.last_macro_block_random = header.random.rand;
         self.last_macro_block_timestamp = header.timestamp;
         self.validators = validators;
+        self.verifier.commit_light_macro_block(block);
+        self.cfg = self.verifier.cfg().clone();
         self.current_epoch_balance_changed = false;
 
         let unspent = self.database.cf_handle(UNSPENT).expect("cf created");
@@ -965,6 +707,7 @@ impl LightDatabase {
             header,
             validators: self.validators.clone(),
             facilitator: self.facilitator_pkey.clone(),
+            parameter_changes: self.verifier.parameter_changes().to_vec(),
         };
         batch
             .put_cf(
@@ -1011,18 +754,18 @@ impl LightDatabase {
     ///
     /// Inputs && outputs are automatically filtered out by account_pkey/account_skey.
     ///
-    pub fn apply_light_micro_block<'a, InputsIter, OutputsIter>(
+    pub fn apply_light_micro_block<'a, OutputsIter>(
         &mut self,
-        header: MicroBlockHeader,
-        inputs_iter: InputsIter,
+        block: &'a LightMicroBlock,
         outputs_iter: OutputsIter,
         account_pkey: &scc::PublicKey,
         account_skey: &scc::SecretKey,
     ) -> HashMap<Hash, TransactionStatus>
     where
-        InputsIter: Iterator<Item = &'a Hash>,
         OutputsIter: Iterator<Item = &'a Output>,
     {
+        let header = block.header.clone();
+        let inputs_iter = block.input_hashes.iter();
         assert_eq!(header.version, VERSION);
         assert_eq!(self.epoch, header.epoch);
         assert_eq!(self.offset(), header.offset);
@@ -1047,6 +790,7 @@ impl LightDatabase {
             my_inputs,
             my_outputs,
         );
+        self.verifier.commit_light_micro_block(block);
         self.micro_blocks.push(header);
 
         info!(
@@ -1430,6 +1174,70 @@ impl LightDatabase {
         Self::iter_range_inner(&self.database, starting_from, limit)
     }
 
+    /// Save a message received at `received_at` to the inbox.
+    /// Returns false if the message is already in the inbox.
+    pub(crate) fn push_message(
+        &mut self,
+        message_hash: Hash,
+        content: MessageContent,
+        received_at: Timestamp,
+    ) -> Result<bool, Error> {
+        if self.inbox.contains(&message_hash) {
+            return Ok(false);
+        }
+        let messages_cf = self.database.cf_handle(MESSAGES).expect("cf created");
+        let entry = InboxEntry {
+            message_hash,
+            content,
+        };
+        let data = entry.into_buffer().expect("couldn't serialize message.");
+
+        let mut batch = WriteBatch::default();
+        // The hash makes keys unique for messages received at the same time.
+        let mut key = Self::bytes_from_timestamp(received_at).to_vec();
+        key.extend_from_slice(message_hash.base_vector());
+        batch.put_cf(messages_cf, &key, &data)?;
+        let mut removed = Vec::new();
+        if self.inbox.len() >= MAX_INBOX_MESSAGES {
+            let excess = self.inbox.len() + 1 - MAX_INBOX_MESSAGES;
+            for (key, value) in self
+                .database
+                .iterator_cf(messages_cf, IteratorMode::Start)?
+                .take(excess)
+            {
+                let entry = InboxEntry::from_buffer(&*value)?;
+                batch.delete_cf(messages_cf, &key)?;
+                removed.push(entry.message_hash);
+            }
+        }
+        self.database.write(batch)?;
+        for message_hash in removed {
+            self.inbox.remove(&message_hash);
+        }
+        self.inbox.insert(message_hash);
+        Ok(true)
+    }
+
+    /// List inbox messages received starting from `starting_from`, limited by `limit`.
+    pub(crate) fn iter_inbox<'a>(
+        &'a self,
+        starting_from: Timestamp,
+        limit: u64,
+    ) -> impl Iterator<Item = (Timestamp, InboxEntry)> + 'a {
+        let messages_cf = self.database.cf_handle(MESSAGES).expect("cf created");
+        let key = Self::bytes_from_timestamp(starting_from);
+        let mode = IteratorMode::From(&key, Direction::Forward);
+        self.database
+            .iterator_cf(messages_cf, mode)
+            .expect("cannot open cf")
+            .map(|(k, v)| {
+                let k = Self::timestamp_from_bytes(&k[..8]).expect("parsable time");
+                let v = InboxEntry::from_buffer(&*v).expect("couldn't deserialize message.");
+                (k, v)
+            })
+            .take(limit as usize)
+    }
+
     //
     // Internal api.
     //
@@ -1757,7 +1565,9 @@ impl Hashable for StakeValue {
 #[cfg(test)]
 mod test {
     use super::*;
-    use stegos_blockchain::test::fake_genesis;
+    use stegos_blockchain::test::{
+        create_fake_macro_block_with_votes, create_fake_micro_block, fake_genesis,
+    };
     use stegos_crypto::scc;
     use tempdir::TempDir;
 
@@ -1867,6 +1677,61 @@ mod test {
         }
     }
 
+    #[test]
+    fn inbox() {
+        let _ = simple_logger::init();
+
+        let temp_dir = TempDir::new("account").expect("couldn't create temp dir");
+        let mut db = LightDatabase::testing(temp_dir.path());
+        let (skey, pkey) = scc::make_random_keys();
+        let timestamp = Timestamp::UNIX_EPOCH + Duration::from_millis(5);
+        let content = |text: &str| MessageContent {
+            sender: pkey,
+            // Set by the sender, not used for ordering.
+            timestamp: Timestamp::UNIX_EPOCH,
+            text: text.to_string(),
+            signature: scc::sign_hash(&Hash::digest(text), &skey),
+        };
+        let push = |db: &mut LightDatabase, text: &str, received_at: Timestamp| {
+            db.push_message(Hash::digest(text), content(text), received_at)
+                .unwrap()
+        };
+        let later = timestamp + Duration::from_millis(1);
+        assert!(push(&mut db, "b", later));
+        assert!(push(&mut db, "a", timestamp));
+        assert!(push(&mut db, "c", later));
+        assert!(!push(&mut db, "a", timestamp));
+
+        let inbox: Vec<_> = db.iter_inbox(Timestamp::UNIX_EPOCH, 10).collect();
+        assert_eq!(inbox.len(), 3);
+        assert_eq!(inbox[0].0, timestamp);
+        assert_eq!(inbox[0].1.content.text, "a");
+        assert_eq!(inbox[1].0, later);
+        assert_eq!(inbox[2].0, later);
+        assert_eq!(db.iter_inbox(later, 10).count(), 2);
+
+        // Deduplication survives restart.
+        drop(db);
+        let mut db = LightDatabase::testing(temp_dir.path());
+        assert!(!push(&mut db, "b", later));
+        assert_eq!(db.iter_inbox(Timestamp::UNIX_EPOCH, 10).count(), 3);
+
+        // The oldest messages are removed.
+        for i in 3..=MAX_INBOX_MESSAGES {
+            assert!(push(
+                &mut db,
+                &i.to_string(),
+                later + Duration::from_millis(1)
+            ));
+        }
+        let inbox: Vec<_> = db
+            .iter_inbox(Timestamp::UNIX_EPOCH, u64::max_value())
+            .collect();
+        assert_eq!(inbox.len(), MAX_INBOX_MESSAGES);
+        assert_eq!(inbox[0].0, later);
+        assert!(push(&mut db, "a", later));
+    }
+
     #[test]
     fn basic() {
         simple_logger::init_with_level(log::Level::Debug).unwrap_or_default();
@@ -1893,4 +1758,88 @@ mod test {
         // TODO: check
         drop(db);
     }
+
+    #[test]
+    fn governance() {
+        const NUM_NODES: usize = 3;
+        let mut cfg: ChainConfig = Default::default();
+        cfg.micro_blocks_in_epoch = 1;
+        let mut timestamp = Timestamp::now();
+        let (keychains, genesis) = fake_genesis(
+            cfg.min_stake_amount,
+            (NUM_NODES as i64) * cfg.min_stake_amount + 100,
+            cfg.max_slot_count,
+            NUM_NODES,
+            timestamp,
+            None,
+        );
+        let chain_dir = TempDir::new("test").unwrap();
+        let mut chain = Blockchain::new(
+            cfg.clone(),
+            chain_dir.path(),
+            ConsistencyCheck::None,
+            genesis.clone(),
+            timestamp,
+        )
+        .expect("Failed to create blockchain");
+
+        // Validators vote in the first epoch, the changes are applied at the third one.
+        let parameters = [
+            ChainParameter::StakeEpochs(5),
+            ChainParameter::MicroBlocksInEpoch(2),
+        ];
+        let votes: Vec<ParameterVote> = keychains
+            .iter()
+            .flat_map(|k| {
+                parameters
+                    .iter()
+                    .map(move |p| ParameterVote::new(1, *p, 3, &k.network_skey, &k.network_pkey))
+            })
+            .collect();
+        for votes in vec![votes, Vec::new()] {
+            for _offset in 0..chain.cfg().micro_blocks_in_epoch {
+                timestamp += Duration::from_millis(1);
+                let (block, _input_hashes, _output_hashes) =
+                    create_fake_micro_block(&chain, &keychains, timestamp);
+                chain
+                    .push_micro_block(block, timestamp)
+                    .expect("Invalid block");
+            }
+            timestamp += Duration::from_millis(1);
+            let (block, _extra_transactions) =
+                create_fake_macro_block_with_votes(&chain, &keychains, timestamp, votes);
+            while chain.offset() > 0 {
+                chain.pop_micro_block().expect("Should be ok");
+            }
+            chain
+                .push_macro_block(block, timestamp)
+                .expect("Invalid block");
+        }
+        assert_eq!(chain.epoch(), 3);
+        assert_eq!(chain.cfg().micro_blocks_in_epoch, 2);
+
+        // The account follows the chain parameters.
+        let (account_skey, account_pkey) = scc::make_random_keys();
+        let genesis_hash = Hash::digest(&genesis);
+        let account_dir = TempDir::new("account").unwrap();
+        let mut db = LightDatabase::open(account_dir.path(), genesis_hash, cfg.clone());
+        for block in chain.light_blocks_starting(0, 0) {
+            let block = match block {
+                LightBlock::LightMacroBlock(block) => block,
+                LightBlock::LightMicroBlock(_) => panic!("Expected macro block"),
+            };
+            db.validate_macro_block(&block).expect("valid block");
+            db.apply_light_macro_block(&block, std::iter::empty(), &account_pkey, &account_skey);
+        }
+        assert_eq!(db.epoch(), 3);
+        assert_eq!(db.cfg().stake_epochs, 5);
+        assert_eq!(db.cfg().micro_blocks_in_epoch, 2);
+
+        // Recovery.
+        drop(db);
+        let db = LightDatabase::open(account_dir.path(), genesis_hash, cfg);
+        assert_eq!(db.epoch(), 3);
+        assert_eq!(db.cfg().stake_epochs, 5);
+        assert_eq!(db.cfg().micro_blocks_in_epoch, 2);
+    }
 }